@@ -0,0 +1,40 @@
+//! Recursive Fibonacci with the tree-walking interpreter and the bytecode VM.
+//!
+//! Run with `cargo bench --bench fib`.
+
+use std::time::{Duration, Instant};
+
+use zope::ast::Parser;
+use zope::interp::{run_program, Value};
+use zope::resolve::resolve_program;
+use zope::vm::{compile_program, run_bytecode};
+
+const SOURCE: &str = "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }\nfib(25)";
+const RUNS: u32 = 5;
+
+fn time(name: &str, mut run: impl FnMut() -> Value) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = run();
+        best = best.min(start.elapsed());
+        assert_eq!(value, Value::Number(75025.0));
+    }
+    println!("{:<12} {:>10.2?}", name, best);
+    best
+}
+
+fn main() {
+    let program = Parser::new(SOURCE).parse_program().unwrap();
+    let resolution = resolve_program(&program);
+    let bytecode = compile_program(&program, &resolution).unwrap();
+
+    let interp = time("interpreter", || {
+        run_program(&program, &resolution).unwrap()
+    });
+    let vm = time("vm", || run_bytecode(&bytecode).unwrap());
+    println!(
+        "the vm takes {:.2}x the time of the interpreter",
+        vm.as_secs_f64() / interp.as_secs_f64()
+    );
+}
@@ -0,0 +1,18 @@
+use super::Expr;
+
+/// `target = value`, which writes a `state` binding. The parser only makes
+/// identifier targets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssignExpr {
+    pub target: Box<Expr>,
+    pub value: Box<Expr>,
+}
+
+impl AssignExpr {
+    pub fn new(target: Expr, value: Expr) -> Self {
+        Self {
+            target: Box::new(target),
+            value: Box::new(value),
+        }
+    }
+}
@@ -3,14 +3,14 @@ use super::Expr;
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CallExpr {
     pub called: Box<Expr>,
-    pub input: Box<Expr>,
+    pub args: Vec<Expr>,
 }
 
 impl CallExpr {
-    pub fn new(called: Expr, input: Expr) -> Self {
+    pub fn new(called: Expr, args: Vec<Expr>) -> Self {
         Self {
             called: Box::new(called),
-            input: Box::new(input),
+            args,
         }
     }
 }
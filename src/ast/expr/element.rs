@@ -0,0 +1,111 @@
+use super::Expr;
+use crate::lexer::Position;
+
+/// The name of an element. A capitalized name refers to a component, which
+/// is looked up like an identifier; any other name is an HTML tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    // The name in the opening tag
+    pub pos: Position,
+}
+
+impl Tag {
+    pub fn new(name: String, pos: Position) -> Self {
+        Self { name, pos }
+    }
+
+    pub fn is_component(&self) -> bool {
+        self.name.starts_with(|char: char| char.is_uppercase())
+    }
+}
+
+/// `name={value}` or `name="text"`, or just `name`, which is `true`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Option<Expr>,
+    pub pos: Position,
+}
+
+impl Attribute {
+    pub fn new(name: String, value: Option<Expr>, pos: Position) -> Self {
+        Self { name, value, pos }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChildKind {
+    // With the whitespace around line breaks removed, as in JSX
+    Text(String),
+    // `{value}`
+    Expr(Expr),
+    // A nested element, which is an `ExprKind::Element`
+    Element(Expr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Child {
+    pub kind: ChildKind,
+    // Includes the braces around an expression
+    pub pos: Position,
+}
+
+impl Child {
+    pub fn new(kind: ChildKind, pos: Position) -> Self {
+        Self { kind, pos }
+    }
+
+    /// The expression of the child, unless it is text.
+    pub fn expr(&self) -> Option<&Expr> {
+        match &self.kind {
+            ChildKind::Text(_) => None,
+            ChildKind::Expr(expr) | ChildKind::Element(expr) => Some(expr),
+        }
+    }
+}
+
+/// `<tag attributes>children</tag>`, `<tag attributes />` without children,
+/// or `<>children</>` for a fragment, which has no tag and only groups its
+/// children.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementExpr {
+    pub tag: Option<Tag>,
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Child>,
+}
+
+impl ElementExpr {
+    pub fn new(tag: Option<Tag>, attributes: Vec<Attribute>, children: Vec<Child>) -> Self {
+        Self {
+            tag,
+            attributes,
+            children,
+        }
+    }
+}
+
+/// Applies the JSX rules for whitespace to the text between tags: lines are
+/// trimmed where they meet a line break, and lines left empty are dropped.
+/// Text on a single line is kept as is. `None` if nothing is left.
+pub fn markup_text(raw: &str) -> Option<String> {
+    if !raw.contains('\n') {
+        return Some(raw.to_string()).filter(|text| !text.is_empty());
+    }
+    let lines: Vec<&str> = raw.split('\n').collect();
+    let last = lines.len() - 1;
+    let mut kept = vec![];
+    for (i, line) in lines.into_iter().enumerate() {
+        let mut line = line.trim_end_matches('\r');
+        if i > 0 {
+            line = line.trim_start();
+        }
+        if i < last {
+            line = line.trim_end();
+        }
+        if !line.is_empty() {
+            kept.push(line);
+        }
+    }
+    Some(kept.join(" ")).filter(|text| !text.is_empty())
+}
@@ -0,0 +1,20 @@
+use super::Expr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IfExpr {
+    pub condition: Box<Expr>,
+    // Always a block
+    pub then: Box<Expr>,
+    // A block, or another `if` for `else if`
+    pub otherwise: Option<Box<Expr>>,
+}
+
+impl IfExpr {
+    pub fn new(condition: Expr, then: Expr, otherwise: Option<Expr>) -> Self {
+        Self {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: otherwise.map(Box::new),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use super::{Expr, ExprKind};
+use crate::ast::stmt::Param;
+use crate::ast::ty::TypeExpr;
+
+/// `fn(x) => x + 1`, or `fn(x) { ... }` with a block body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LambdaExpr {
+    pub params: Vec<Param>,
+    pub ret: Option<TypeExpr>,
+    pub body: Box<Expr>,
+}
+
+impl LambdaExpr {
+    pub fn new(params: Vec<Param>, body: Expr) -> Self {
+        Self {
+            params,
+            ret: None,
+            body: Box::new(body),
+        }
+    }
+
+    /// Whether the body is a block rather than an expression after `=>`.
+    pub fn has_block_body(&self) -> bool {
+        matches!(self.body.kind, ExprKind::Block(_))
+    }
+}
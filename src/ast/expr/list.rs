@@ -0,0 +1,46 @@
+use super::Expr;
+use crate::lexer::Position;
+
+/// An item of a list literal, either a single value or `..values` spread
+/// into the list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListItem {
+    pub spread: bool,
+    pub value: Expr,
+    // Includes the `..` of a spread
+    pub pos: Position,
+}
+
+impl ListItem {
+    pub fn single(value: Expr) -> Self {
+        let pos = value.pos;
+        Self {
+            spread: false,
+            value,
+            pos,
+        }
+    }
+
+    pub fn spread(value: Expr, pos: Position) -> Self {
+        Self {
+            spread: true,
+            value,
+            pos,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexExpr {
+    pub indexed: Box<Expr>,
+    pub index: Box<Expr>,
+}
+
+impl IndexExpr {
+    pub fn new(indexed: Expr, index: Expr) -> Self {
+        Self {
+            indexed: Box::new(indexed),
+            index: Box::new(index),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use super::Expr;
+use crate::ast::pattern::Pattern;
+use crate::lexer::Position;
+
+/// `pattern if guard => body`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub pos: Position,
+}
+
+impl MatchArm {
+    pub fn new(pattern: Pattern, body: Expr, pos: Position) -> Self {
+        Self {
+            pattern,
+            guard: None,
+            body,
+            pos,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchExpr {
+    pub scrutinee: Box<Expr>,
+    pub arms: Vec<MatchArm>,
+}
+
+impl MatchExpr {
+    pub fn new(scrutinee: Expr, arms: Vec<MatchArm>) -> Self {
+        Self {
+            scrutinee: Box::new(scrutinee),
+            arms,
+        }
+    }
+}
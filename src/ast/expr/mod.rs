@@ -9,6 +9,26 @@ pub mod call;
 pub use self::call::*;
 pub mod literal;
 pub use self::literal::*;
+pub mod binary;
+pub use self::binary::*;
+pub mod unary;
+pub use self::unary::*;
+pub mod if_expr;
+pub use self::if_expr::*;
+pub mod lambda;
+pub use self::lambda::*;
+pub mod list;
+pub use self::list::*;
+pub mod match_expr;
+pub use self::match_expr::*;
+pub mod record;
+pub use self::record::*;
+pub mod assign;
+pub use self::assign::*;
+pub mod element;
+pub use self::element::*;
+pub mod precedence;
+pub use self::precedence::*;
 
 use crate::ast::stmt::*;
 
@@ -18,6 +38,17 @@ pub enum ExprKind {
     Call(CallExpr),
     Literal(LiteralExpr),
     Block(Vec<Stmt>),
+    Binary(BinaryExpr),
+    Unary(UnaryExpr),
+    If(IfExpr),
+    List(Vec<ListItem>),
+    Index(IndexExpr),
+    Record(RecordExpr),
+    Field(FieldExpr),
+    Lambda(LambdaExpr),
+    Match(MatchExpr),
+    Assign(AssignExpr),
+    Element(ElementExpr),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -0,0 +1,57 @@
+use super::*;
+
+/// How tightly an expression binds, from loosest to tightest. An operand in
+/// a position that requires a higher precedence than its own needs
+/// parentheses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    Lowest,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Sum,
+    Product,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    pub fn of(expr: &Expr) -> Precedence {
+        match &expr.kind {
+            ExprKind::Binary(binary) => binary.op.precedence(),
+            ExprKind::Unary(_) => Precedence::Unary,
+            // The body after `=>` and the value of an assignment take
+            // everything to their right
+            ExprKind::Lambda(lambda) if !lambda.has_block_body() => Precedence::Lowest,
+            ExprKind::Assign(_) => Precedence::Lowest,
+            ExprKind::Call(_) | ExprKind::Index(_) | ExprKind::Field(_) => Precedence::Call,
+            ExprKind::Identifier(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Block(_)
+            | ExprKind::If(_)
+            | ExprKind::List(_)
+            | ExprKind::Record(_)
+            | ExprKind::Lambda(_)
+            | ExprKind::Match(_)
+            | ExprKind::Element(_) => Precedence::Primary,
+        }
+    }
+
+    /// The next tighter level, which is what the right operand of a left
+    /// associative operator needs.
+    pub fn next(self) -> Precedence {
+        match self {
+            Precedence::Lowest => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Sum,
+            Precedence::Sum => Precedence::Product,
+            Precedence::Product => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
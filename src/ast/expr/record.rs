@@ -0,0 +1,62 @@
+use super::{Expr, ExprKind};
+use crate::lexer::Position;
+
+/// `name: value`, or just `name` as shorthand for `name: name`, in which
+/// case `value` is an identifier spanning the name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordField {
+    pub name: String,
+    pub value: Expr,
+    pub pos: Position,
+}
+
+impl RecordField {
+    pub fn new(name: String, value: Expr, pos: Position) -> Self {
+        Self { name, value, pos }
+    }
+
+    pub fn is_shorthand(&self) -> bool {
+        match &self.value.kind {
+            ExprKind::Identifier(identifier) => identifier.ident == self.name,
+            _ => false,
+        }
+    }
+}
+
+/// `{ name: value }`, or `{ ..base, name: value }` to copy `base` with some
+/// of its fields replaced or added.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordExpr {
+    pub base: Option<Box<Expr>>,
+    pub fields: Vec<RecordField>,
+}
+
+impl RecordExpr {
+    pub fn new(base: Option<Expr>, fields: Vec<RecordField>) -> Self {
+        Self {
+            base: base.map(Box::new),
+            fields,
+        }
+    }
+
+    /// Whether shorthand fields can be written as such. A lone `{ name }`
+    /// reads as a block, so it needs to be `{ name: name }`.
+    pub fn allows_shorthand(&self) -> bool {
+        self.base.is_some() || self.fields.len() > 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldExpr {
+    pub record: Box<Expr>,
+    pub field: String,
+}
+
+impl FieldExpr {
+    pub fn new(record: Expr, field: String) -> Self {
+        Self {
+            record: Box::new(record),
+            field,
+        }
+    }
+}
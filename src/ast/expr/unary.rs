@@ -0,0 +1,31 @@
+use super::Expr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl UnaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnaryExpr {
+    pub op: UnaryOp,
+    pub operand: Box<Expr>,
+}
+
+impl UnaryExpr {
+    pub fn new(op: UnaryOp, operand: Expr) -> Self {
+        Self {
+            op,
+            operand: Box::new(operand),
+        }
+    }
+}
@@ -1,12 +1,19 @@
 pub mod expr;
+pub mod pattern;
+pub mod program;
 pub mod stmt;
 #[cfg(test)]
 mod tests;
+pub mod ty;
+pub mod validate;
 
 use std::str::Chars;
 
 pub use self::expr::*;
+pub use self::pattern::*;
+pub use self::program::*;
 pub use self::stmt::*;
+pub use self::ty::*;
 use crate::lexer::*;
 
 macro_rules! expr {
@@ -32,28 +39,596 @@ impl<'a> Parser<'a> {
         Self::from_chars(input.chars())
     }
 
+    // Bumps the next token, skipping spaces in front of it, if `matcher`
+    // accepts it
+    fn eat(lexer: &mut Lexer, matcher: impl Fn(&TokenKind) -> bool) -> Option<Token> {
+        let mut lexer_ = lexer.clone();
+        lexer_.ignore_spaces();
+        let token = lexer_.bump()?;
+        if matcher(&token.kind) {
+            lexer.sync(lexer_);
+            Some(token)
+        } else {
+            None
+        }
+    }
+
+    fn eat_kind(lexer: &mut Lexer, kind: &TokenKind) -> Option<Token> {
+        Self::eat(lexer, |token_kind| token_kind == kind)
+    }
+
+    fn eat_identifier(lexer: &mut Lexer) -> Option<(String, Position)> {
+        let token = Self::eat(lexer, |kind| matches!(kind, TokenKind::Identifier(_)))?;
+        match token.kind {
+            TokenKind::Identifier(identifier) => Some((identifier, token.pos)),
+            _ => None,
+        }
+    }
+
+    // Parses comma separated items up to the `close` bracket, allowing a
+    // trailing comma. The opening bracket must already be bumped.
+    fn parse_list<T>(
+        lexer: &mut Lexer,
+        close: Bracket,
+        item: impl Fn(&mut Lexer) -> Option<T>,
+    ) -> Option<(Vec<T>, Token)> {
+        let close = TokenKind::Bracket(close);
+        let comma = TokenKind::Punctuation(Punctuation::Comma);
+        let mut items = vec![];
+        loop {
+            if let Some(close_bracket) = Self::eat_kind(lexer, &close) {
+                return Some((items, close_bracket));
+            }
+            lexer.ignore_spaces();
+            items.push(item(lexer)?);
+            if Self::eat_kind(lexer, &comma).is_none() {
+                let close_bracket = Self::eat_kind(lexer, &close)?;
+                return Some((items, close_bracket));
+            }
+        }
+    }
+
+    // Two tokens with no space between them, as in `..` or `->`
+    fn eat_pair(lexer_: &mut Lexer, first: &TokenKind, second: &TokenKind) -> Option<Position> {
+        let mut lexer = lexer_.clone();
+        let first = Self::eat_kind(&mut lexer, first)?;
+        let next = lexer.bump()?;
+        if next.kind == *second && next.pos.start == first.pos.end {
+            lexer_.sync(lexer);
+            Some(Position::new(first.pos.start, next.pos.end))
+        } else {
+            None
+        }
+    }
+
+    fn eat_spread(lexer: &mut Lexer) -> Option<Position> {
+        let full_stop = TokenKind::Punctuation(Punctuation::FullStop);
+        Self::eat_pair(lexer, &full_stop, &full_stop)
+    }
+
+    fn parse_list_item(lexer: &mut Lexer) -> Option<ListItem> {
+        match Self::eat_spread(lexer) {
+            Some(spread) => {
+                let value = Self::parse_expr_inner(lexer)?;
+                let pos = Position::new(spread.start, value.pos.end);
+                Some(ListItem::spread(value, pos))
+            }
+            None => Some(ListItem::single(Self::parse_expr_inner(lexer)?)),
+        }
+    }
+
+    // `{` starts a record rather than a block if it is followed by `..`,
+    // `name:` or `name,`. The bracket must already be bumped.
+    fn is_record(lexer: &Lexer) -> bool {
+        let mut lexer = lexer.clone();
+        if Self::eat_spread(&mut lexer).is_some() {
+            return true;
+        }
+        Self::eat_identifier(&mut lexer).is_some()
+            && Self::eat(&mut lexer, |kind| {
+                matches!(
+                    kind,
+                    TokenKind::Punctuation(Punctuation::Colon | Punctuation::Comma)
+                )
+            })
+            .is_some()
+    }
+
+    fn parse_record_field(lexer: &mut Lexer) -> Option<RecordField> {
+        let (name, name_pos) = Self::eat_identifier(lexer)?;
+        if Self::eat_kind(lexer, &TokenKind::Punctuation(Punctuation::Colon)).is_some() {
+            lexer.ignore_spaces();
+            let value = Self::parse_expr_inner(lexer)?;
+            let pos = Position::new(name_pos.start, value.pos.end);
+            Some(RecordField::new(name, value, pos))
+        } else {
+            let value = Expr::new(
+                ExprKind::Identifier(IdentifierExpr::new(name.clone())),
+                name_pos,
+            );
+            Some(RecordField::new(name, value, name_pos))
+        }
+    }
+
+    // The rest of a record after its `{`
+    fn parse_record(lexer: &mut Lexer, start: u32) -> Option<Expr> {
+        let close = TokenKind::Bracket(Bracket::CloseCurly);
+        let mut base = None;
+        if Self::eat_spread(lexer).is_some() {
+            lexer.ignore_spaces();
+            base = Some(Self::parse_expr_inner(lexer)?);
+            if Self::eat_kind(lexer, &TokenKind::Punctuation(Punctuation::Comma)).is_none() {
+                let close_bracket = Self::eat_kind(lexer, &close)?;
+                return Some(Expr::new(
+                    ExprKind::Record(RecordExpr::new(base, vec![])),
+                    Position::new(start, close_bracket.pos.end),
+                ));
+            }
+        }
+        let (fields, close_bracket) =
+            Self::parse_list(lexer, Bracket::CloseCurly, Self::parse_record_field)?;
+        if base.is_none() && fields.is_empty() {
+            return None;
+        }
+        Some(Expr::new(
+            ExprKind::Record(RecordExpr::new(base, fields)),
+            Position::new(start, close_bracket.pos.end),
+        ))
+    }
+
+    // A tag or attribute name, which unlike an identifier can be a keyword,
+    // as in `type`, and go on with digits and dashes, as in `h1` or
+    // `aria-label`
+    fn eat_markup_name(lexer: &mut Lexer) -> Option<(String, Position)> {
+        let token = Self::eat(lexer, |kind| {
+            matches!(kind, TokenKind::Identifier(_) | TokenKind::Keyword(_))
+        })?;
+        let (mut name, mut pos) = match token.kind {
+            TokenKind::Identifier(identifier) => (identifier, token.pos),
+            TokenKind::Keyword(keyword) => (keyword.as_str().to_string(), token.pos),
+            _ => return None,
+        };
+        loop {
+            let mut lexer_ = lexer.clone();
+            let Some(token) = lexer_.bump().filter(|token| token.pos.start == pos.end) else {
+                break;
+            };
+            match token.kind {
+                TokenKind::Identifier(identifier) => name.push_str(&identifier),
+                TokenKind::Keyword(keyword) => name.push_str(keyword.as_str()),
+                TokenKind::NumberChar(number_char) => name.push_str(&number_char.int().to_string()),
+                TokenKind::Operator(Operator::Minus) => name.push('-'),
+                _ => break,
+            }
+            pos.end = token.pos.end;
+            lexer.sync(lexer_);
+        }
+        Some((name, pos))
+    }
+
+    // `name`, `name={value}` or `name="text"` in an opening tag
+    fn parse_attribute(lexer: &mut Lexer) -> Option<Attribute> {
+        let (name, name_pos) = Self::eat_markup_name(lexer)?;
+        if Self::eat_kind(lexer, &TokenKind::Operator(Operator::Equal)).is_none() {
+            return Some(Attribute::new(name, None, name_pos));
+        }
+        lexer.ignore_spaces();
+        let token = lexer.bump()?;
+        let (value, end) = match token.kind {
+            TokenKind::Separation(separation) => (
+                expr!(
+                    Literal,
+                    LiteralExpr::String(separation.separated),
+                    token.pos
+                ),
+                token.pos.end,
+            ),
+            TokenKind::Bracket(Bracket::OpenCurly) => {
+                lexer.ignore_spaces();
+                let value = Self::parse_expr_inner(lexer)?;
+                let close = Self::eat_kind(lexer, &TokenKind::Bracket(Bracket::CloseCurly))?;
+                (value, close.pos.end)
+            }
+            _ => return None,
+        };
+        Some(Attribute::new(
+            name,
+            Some(value),
+            Position::new(name_pos.start, end),
+        ))
+    }
+
+    // The rest of an element after its `<`. Children are read in markup mode,
+    // and the expressions in their braces in code mode again.
+    fn parse_element(lexer: &mut Lexer, start: u32) -> Option<Expr> {
+        let close_angle = TokenKind::Bracket(Bracket::CloseAngle);
+        let slash = TokenKind::Operator(Operator::Slash);
+        let tag = match Self::eat_kind(lexer, &close_angle) {
+            Some(_) => None,
+            None => {
+                let (name, pos) = Self::eat_markup_name(lexer)?;
+                Some(Tag::new(name, pos))
+            }
+        };
+
+        let mut attributes = vec![];
+        if tag.is_some() {
+            loop {
+                if let Some(end) = Self::eat_pair(lexer, &slash, &close_angle) {
+                    let element = ElementExpr::new(tag, attributes, vec![]);
+                    return Some(expr!(Element, element, Position::new(start, end.end)));
+                }
+                if Self::eat_kind(lexer, &close_angle).is_some() {
+                    break;
+                }
+                attributes.push(Self::parse_attribute(lexer)?);
+            }
+        }
+
+        let mut children = vec![];
+        loop {
+            let mut markup = lexer.clone();
+            markup.set_mode(LexerMode::Markup);
+            let token = markup.bump()?;
+            lexer.sync(markup);
+            match token.kind {
+                TokenKind::Text(raw) => {
+                    if let Some(text) = markup_text(&raw) {
+                        children.push(Child::new(ChildKind::Text(text), token.pos));
+                    }
+                }
+                TokenKind::Bracket(Bracket::OpenCurly) => {
+                    lexer.ignore_spaces();
+                    let value = Self::parse_expr_inner(lexer)?;
+                    let close = Self::eat_kind(lexer, &TokenKind::Bracket(Bracket::CloseCurly))?;
+                    let pos = Position::new(token.pos.start, close.pos.end);
+                    children.push(Child::new(ChildKind::Expr(value), pos));
+                }
+                TokenKind::Bracket(Bracket::OpenAngle) => {
+                    if Self::eat_kind(lexer, &slash).is_none() {
+                        let element = Self::parse_element(lexer, token.pos.start)?;
+                        let pos = element.pos;
+                        children.push(Child::new(ChildKind::Element(element), pos));
+                        continue;
+                    }
+                    let closing = match &tag {
+                        Some(_) => Some(Self::eat_markup_name(lexer)?.0),
+                        None => None,
+                    };
+                    if closing.as_ref() != tag.as_ref().map(|tag| &tag.name) {
+                        return None;
+                    }
+                    let end = Self::eat_kind(lexer, &close_angle)?.pos.end;
+                    let element = ElementExpr::new(tag, attributes, children);
+                    return Some(expr!(Element, element, Position::new(start, end)));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn eat_arrow(lexer: &mut Lexer) -> Option<Position> {
+        Self::eat_pair(
+            lexer,
+            &TokenKind::Operator(Operator::Minus),
+            &TokenKind::Bracket(Bracket::CloseAngle),
+        )
+    }
+
+    fn eat_fat_arrow(lexer: &mut Lexer) -> Option<Position> {
+        Self::eat_pair(
+            lexer,
+            &TokenKind::Operator(Operator::Equal),
+            &TokenKind::Bracket(Bracket::CloseAngle),
+        )
+    }
+
+    // `(params) -> Ret` of functions and lambdas
+    fn parse_signature(lexer: &mut Lexer) -> Option<(Vec<Param>, Option<TypeExpr>)> {
+        Self::eat_kind(lexer, &TokenKind::Bracket(Bracket::OpenParen))?;
+        let (params, _) = Self::parse_list(lexer, Bracket::CloseParen, Self::parse_param)?;
+        let ret = match Self::eat_arrow(lexer) {
+            Some(_) => Some(Self::parse_type(lexer)?),
+            None => None,
+        };
+        Some((params, ret))
+    }
+
+    // The rest of a lambda after its `fn`
+    fn parse_lambda(lexer: &mut Lexer, start: u32) -> Option<Expr> {
+        let (params, ret) = Self::parse_signature(lexer)?;
+        let body = match Self::eat_fat_arrow(lexer) {
+            Some(_) => {
+                lexer.ignore_spaces();
+                Self::parse_expr_inner(lexer)?
+            }
+            None => Self::parse_block(lexer)?,
+        };
+        let end = body.pos.end;
+        let mut lambda = LambdaExpr::new(params, body);
+        lambda.ret = ret;
+        Some(Expr::new(
+            ExprKind::Lambda(lambda),
+            Position::new(start, end),
+        ))
+    }
+
+    fn parse_type(lexer_: &mut Lexer) -> Option<TypeExpr> {
+        let mut lexer = lexer_.clone();
+        lexer.ignore_spaces();
+        let token = lexer.bump()?;
+        let ty = match token.kind {
+            TokenKind::Keyword(Keyword::Fn) => {
+                Self::eat_kind(&mut lexer, &TokenKind::Bracket(Bracket::OpenParen))?;
+                let (params, _) =
+                    Self::parse_list(&mut lexer, Bracket::CloseParen, Self::parse_type)?;
+                Self::eat_arrow(&mut lexer)?;
+                let ret = Self::parse_type(&mut lexer)?;
+                let end = ret.pos.end;
+                TypeExpr::new(
+                    TypeExprKind::Fn(FnType::new(params, ret)),
+                    Position::new(token.pos.start, end),
+                )
+            }
+            TokenKind::Identifier(name) => {
+                let open_angle = TokenKind::Bracket(Bracket::OpenAngle);
+                let mut lexer_ = lexer.clone();
+                match Self::eat_kind(&mut lexer_, &open_angle) {
+                    Some(_) => {
+                        let (args, close_angle) =
+                            Self::parse_list(&mut lexer_, Bracket::CloseAngle, Self::parse_type)?;
+                        lexer.sync(lexer_);
+                        TypeExpr::new(
+                            TypeExprKind::Named(NamedType::new(name, args)),
+                            Position::new(token.pos.start, close_angle.pos.end),
+                        )
+                    }
+                    None => {
+                        TypeExpr::new(TypeExprKind::Named(NamedType::new(name, vec![])), token.pos)
+                    }
+                }
+            }
+            _ => return None,
+        };
+        lexer_.sync(lexer);
+        Some(ty)
+    }
+
+    // An optional `: Type`
+    fn parse_annotation(lexer: &mut Lexer) -> Option<Option<TypeExpr>> {
+        match Self::eat_kind(lexer, &TokenKind::Punctuation(Punctuation::Colon)) {
+            Some(_) => Some(Some(Self::parse_type(lexer)?)),
+            None => Some(None),
+        }
+    }
+
+    // The `name: Type = value` part of `let` and `state`
+    fn parse_binding(lexer: &mut Lexer) -> Option<(String, Option<TypeExpr>, Expr)> {
+        let (identifier, _) = Self::eat_identifier(lexer)?;
+        let ty = Self::parse_annotation(lexer)?;
+        Self::eat_kind(lexer, &TokenKind::Operator(Operator::Equal))?;
+        lexer.ignore_spaces();
+        let value_expr = Self::parse_expr_inner(lexer)?;
+        Some((identifier, ty, value_expr))
+    }
+
+    fn parse_param(lexer: &mut Lexer) -> Option<Param> {
+        let (name, pos) = Self::eat_identifier(lexer)?;
+        let mut param = Param::new(name, pos);
+        param.ty = Self::parse_annotation(lexer)?;
+        Some(param)
+    }
+
+    fn parse_variant(lexer: &mut Lexer) -> Option<Variant> {
+        let (name, pos) = Self::eat_identifier(lexer)?;
+        let mut lexer_ = lexer.clone();
+        match Self::eat_kind(&mut lexer_, &TokenKind::Bracket(Bracket::OpenParen)) {
+            Some(_) => {
+                let (fields, close_bracket) =
+                    Self::parse_list(&mut lexer_, Bracket::CloseParen, Self::parse_type)?;
+                lexer.sync(lexer_);
+                let pos = Position::new(pos.start, close_bracket.pos.end);
+                Some(Variant::new(name, fields, pos))
+            }
+            None => Some(Variant::new(name, vec![], pos)),
+        }
+    }
+
+    fn parse_pattern(lexer_: &mut Lexer) -> Option<Pattern> {
+        let mut lexer = lexer_.clone();
+        lexer.ignore_spaces();
+        let pattern = match Self::eat_identifier(&mut lexer) {
+            Some((name, pos)) if name == "_" => Pattern::new(PatternKind::Wildcard, pos),
+            Some((name, pos)) => {
+                let open_paren = TokenKind::Bracket(Bracket::OpenParen);
+                let mut lexer_ = lexer.clone();
+                match Self::eat_kind(&mut lexer_, &open_paren) {
+                    Some(_) => {
+                        let (patterns, close_bracket) = Self::parse_list(
+                            &mut lexer_,
+                            Bracket::CloseParen,
+                            Self::parse_pattern,
+                        )?;
+                        lexer.sync(lexer_);
+                        Pattern::new(
+                            PatternKind::Variant(name, patterns),
+                            Position::new(pos.start, close_bracket.pos.end),
+                        )
+                    }
+                    None => Pattern::new(PatternKind::Identifier(name), pos),
+                }
+            }
+            // Literals are read like their expressions
+            None => match Self::parse_expr_not_call(&mut lexer)? {
+                Expr {
+                    kind: ExprKind::Literal(literal),
+                    pos,
+                } => Pattern::new(PatternKind::Literal(literal), pos),
+                _ => return None,
+            },
+        };
+        lexer_.sync(lexer);
+        Some(pattern)
+    }
+
+    fn parse_match_arm(lexer: &mut Lexer) -> Option<MatchArm> {
+        let pattern = Self::parse_pattern(lexer)?;
+        let guard = match Self::eat_kind(lexer, &TokenKind::Keyword(Keyword::If)) {
+            Some(_) => {
+                lexer.ignore_spaces();
+                Some(Self::parse_expr_inner(lexer)?)
+            }
+            None => None,
+        };
+        Self::eat_fat_arrow(lexer)?;
+        lexer.ignore_spaces();
+        let body = Self::parse_expr_inner(lexer)?;
+        let pos = Position::new(pattern.pos.start, body.pos.end);
+        let mut arm = MatchArm::new(pattern, body, pos);
+        arm.guard = guard;
+        Some(arm)
+    }
+
+    // The rest of a match after its `match`. Arms are separated by commas,
+    // which are optional after a block.
+    fn parse_expr_match(lexer: &mut Lexer, start: u32) -> Option<Expr> {
+        lexer.ignore_spaces();
+        let scrutinee = Self::parse_expr_inner(lexer)?;
+        Self::eat_kind(lexer, &TokenKind::Bracket(Bracket::OpenCurly))?;
+        let close = TokenKind::Bracket(Bracket::CloseCurly);
+        let mut arms = vec![];
+        let close_bracket = loop {
+            if let Some(close_bracket) = Self::eat_kind(lexer, &close) {
+                break close_bracket;
+            }
+            let arm = Self::parse_match_arm(lexer)?;
+            let block = matches!(arm.body.kind, ExprKind::Block(_));
+            arms.push(arm);
+            if Self::eat_kind(lexer, &TokenKind::Punctuation(Punctuation::Comma)).is_none()
+                && !block
+            {
+                break Self::eat_kind(lexer, &close)?;
+            }
+        };
+        Some(Expr::new(
+            ExprKind::Match(MatchExpr::new(scrutinee, arms)),
+            Position::new(start, close_bracket.pos.end),
+        ))
+    }
+
     fn parse_stmt_inner(lexer_: &mut Lexer) -> Option<Stmt> {
         let mut lexer = lexer_.clone();
         let first = lexer.bump()?;
         let stmt_matched = match first.kind {
             TokenKind::Keyword(Keyword::Let) => {
+                let (identifier, ty, value_expr) = Self::parse_binding(&mut lexer)?;
+                let end = value_expr.pos.end;
+                let mut let_stmt = LetStmt::new(identifier, value_expr);
+                let_stmt.ty = ty;
+                Some(Stmt::new(
+                    StmtKind::Let(let_stmt),
+                    Position::new(first.pos.start, end),
+                ))
+            }
+            TokenKind::Keyword(Keyword::State) => {
+                let (identifier, ty, value_expr) = Self::parse_binding(&mut lexer)?;
+                let end = value_expr.pos.end;
+                let mut state_stmt = StateStmt::new(identifier, value_expr);
+                state_stmt.ty = ty;
+                Some(Stmt::new(
+                    StmtKind::State(state_stmt),
+                    Position::new(first.pos.start, end),
+                ))
+            }
+            // Without a name, `fn` starts a lambda expression
+            TokenKind::Keyword(Keyword::Fn)
+                if Self::eat_identifier(&mut lexer.clone()).is_some() =>
+            {
+                let (name, _) = Self::eat_identifier(&mut lexer)?;
+                let (params, ret) = Self::parse_signature(&mut lexer)?;
+                let body = Self::parse_block(&mut lexer)?;
+                let end = body.pos.end;
+                let mut fn_stmt = FnStmt::new(name, params, body);
+                fn_stmt.ret = ret;
+                Some(Stmt::new(
+                    StmtKind::Fn(fn_stmt),
+                    Position::new(first.pos.start, end),
+                ))
+            }
+            TokenKind::Keyword(Keyword::Component) => {
+                let (name, _) = Self::eat_identifier(&mut lexer)?;
+                Self::eat_kind(&mut lexer, &TokenKind::Bracket(Bracket::OpenParen))?;
+                let (props, _) =
+                    Self::parse_list(&mut lexer, Bracket::CloseParen, Self::parse_param)?;
+                let body = Self::parse_block(&mut lexer)?;
+                let end = body.pos.end;
+                Some(Stmt::new(
+                    StmtKind::Component(ComponentStmt::new(name, props, body)),
+                    Position::new(first.pos.start, end),
+                ))
+            }
+            TokenKind::Keyword(Keyword::Type) => {
+                let (name, _) = Self::eat_identifier(&mut lexer)?;
+                let mut params = vec![];
+                if Self::eat_kind(&mut lexer, &TokenKind::Bracket(Bracket::OpenAngle)).is_some() {
+                    let (names, _) =
+                        Self::parse_list(&mut lexer, Bracket::CloseAngle, Self::eat_identifier)?;
+                    params = names.into_iter().map(|(name, _)| name).collect();
+                }
+                Self::eat_kind(&mut lexer, &TokenKind::Operator(Operator::Equal))?;
+                let pipe = TokenKind::Operator(Operator::Pipe);
+                // A leading `|` lines up variants written one per line
+                Self::eat_kind(&mut lexer, &pipe);
+                let mut variants = vec![Self::parse_variant(&mut lexer)?];
+                while Self::eat_kind(&mut lexer, &pipe).is_some() {
+                    variants.push(Self::parse_variant(&mut lexer)?);
+                }
+                let end = variants.last()?.pos.end;
+                let mut type_stmt = TypeStmt::new(name, variants);
+                type_stmt.params = params;
+                Some(Stmt::new(
+                    StmtKind::Type(type_stmt),
+                    Position::new(first.pos.start, end),
+                ))
+            }
+            TokenKind::Keyword(Keyword::Import) => {
+                Self::eat_kind(&mut lexer, &TokenKind::Bracket(Bracket::OpenCurly))?;
+                let (names, _) =
+                    Self::parse_list(&mut lexer, Bracket::CloseCurly, Self::eat_identifier)?;
+                let names = names
+                    .into_iter()
+                    .map(|(name, pos)| ImportName::new(name, pos))
+                    .collect();
+                // `from` is only special here, so it stays an identifier
+                match Self::eat_identifier(&mut lexer)? {
+                    (from, _) if from == "from" => {}
+                    _ => return None,
+                }
+                let path = Self::eat(&mut lexer, |kind| matches!(kind, TokenKind::Separation(_)))?;
+                let TokenKind::Separation(separation) = path.kind else {
+                    return None;
+                };
+                Some(Stmt::new(
+                    StmtKind::Import(ImportStmt::new(names, separation.separated, path.pos)),
+                    Position::new(first.pos.start, path.pos.end),
+                ))
+            }
+            TokenKind::Keyword(Keyword::Export) => {
                 lexer.ignore_spaces();
-                let identifier_token = lexer.bump()?;
-                if let TokenKind::Identifier(identifier) = identifier_token.kind {
-                    lexer.ignore_spaces();
-                    let equal_token = lexer.bump()?;
-                    if let TokenKind::Operator(Operator::Equal) = equal_token.kind {
-                        lexer.ignore_spaces();
-                        let value_expr = Self::parse_expr_inner(&mut lexer)?;
+                let stmt = Self::parse_stmt_inner(&mut lexer)?;
+                match stmt.kind {
+                    StmtKind::Let(_)
+                    | StmtKind::State(_)
+                    | StmtKind::Fn(_)
+                    | StmtKind::Component(_)
+                    | StmtKind::Type(_) => {
+                        let end = stmt.pos.end;
                         Some(Stmt::new(
-                            StmtKind::Let(LetStmt::new(identifier, value_expr)),
-                            Position::new(first.pos.start, 0),
+                            StmtKind::Export(Box::new(stmt)),
+                            Position::new(first.pos.start, end),
                         ))
-                    } else {
-                        None
                     }
-                } else {
-                    None
+                    _ => return None,
                 }
             }
             _ => None,
@@ -65,6 +640,7 @@ impl<'a> Parser<'a> {
             if let Some(semicolon) = semicolon {
                 stmt.pos.end = semicolon.pos.end;
             }
+            lexer_.sync(lexer);
             Some(stmt)
         } else {
             let parsed_expr = Self::parse_expr_inner(lexer_);
@@ -73,7 +649,7 @@ impl<'a> Parser<'a> {
                 let mut end = expr.pos.end;
 
                 // Detect semicolon
-                let semicolon = lexer.ignore_semicolon();
+                let semicolon = lexer_.ignore_semicolon();
                 if let Some(semicolon) = semicolon {
                     end = semicolon.pos.end;
                 }
@@ -86,37 +662,61 @@ impl<'a> Parser<'a> {
 
     pub fn parse_expr_call(lexer_: &mut Lexer) -> Option<Expr> {
         let lexer__ = &mut (lexer_.clone());
-        let called = Self::parse_expr_not_call(lexer__);
-        let lexer = &mut (lexer__.clone());
+        let mut called = Self::parse_expr_not_call(lexer__)?;
+        let start = called.pos.start;
 
-        if let Some(called) = called {
+        // Calls, indexing and field access are postfix, so `f(x)(y)` calls
+        // the result of `f(x)`, `f(x)[0]` indexes it and `f(x).y` reads its
+        // field
+        loop {
+            let lexer = &mut (lexer__.clone());
             let open_bracket = lexer.bump();
             if let Some(open_bracket) = open_bracket {
-                if let TokenKind::Bracket(Bracket::OpenParen) = open_bracket.kind {
-                    let call_input = {
-                        let expr = Self::parse_expr_inner(lexer)?;
-
-                        Some(expr)
-                    };
-
-                    if let Some(call_input) = call_input {
-                        let close_bracket = lexer.bump()?;
-                        if let TokenKind::Bracket(Bracket::CloseParen) = close_bracket.kind {
-                            lexer_.sync(lexer.clone());
-                            let call = CallExpr::new(called.to_owned(), call_input);
-                            return Some(Expr::new(
-                                ExprKind::Call(call),
-                                Position::new(called.pos.start, close_bracket.pos.end),
-                            ));
+                if let TokenKind::Punctuation(Punctuation::FullStop) = open_bracket.kind {
+                    match lexer.bump() {
+                        Some(Token {
+                            kind: TokenKind::Identifier(field),
+                            pos,
+                        }) if pos.start == open_bracket.pos.end => {
+                            lexer__.sync(lexer.clone());
+                            called = Expr::new(
+                                ExprKind::Field(FieldExpr::new(called, field)),
+                                Position::new(start, pos.end),
+                            );
+                            continue;
                         }
+                        _ => break,
                     }
                 }
+                if let TokenKind::Bracket(Bracket::OpenParen) = open_bracket.kind {
+                    let (args, close_bracket) =
+                        Self::parse_list(lexer, Bracket::CloseParen, Self::parse_expr_inner)?;
+                    lexer__.sync(lexer.clone());
+                    let call = CallExpr::new(called, args);
+                    called = Expr::new(
+                        ExprKind::Call(call),
+                        Position::new(start, close_bracket.pos.end),
+                    );
+                    continue;
+                }
+                if let TokenKind::Bracket(Bracket::OpenSquare) = open_bracket.kind {
+                    lexer.ignore_spaces();
+                    let index = Self::parse_expr_inner(lexer)?;
+                    let close_bracket =
+                        Self::eat_kind(lexer, &TokenKind::Bracket(Bracket::CloseSquare))?;
+                    lexer__.sync(lexer.clone());
+                    called = Expr::new(
+                        ExprKind::Index(IndexExpr::new(called, index)),
+                        Position::new(start, close_bracket.pos.end),
+                    );
+                    continue;
+                }
             }
-            lexer_.sync(lexer__.clone());
-            Some(called)
-        } else {
-            None
+            break;
         }
+
+        lexer_.sync(lexer__.clone());
+        Some(called)
     }
 
     fn parse_expr_not_call(lexer_: &mut Lexer) -> Option<Expr> {
@@ -153,10 +753,10 @@ impl<'a> Parser<'a> {
                     }
                 }
                 let numbers: Vec<usize> = number_string.iter().map(|s| s.int()).collect();
+                // A literal too large for a `u32` does not parse
                 let mut number = 0u32;
                 for n in numbers.iter() {
-                    number *= 10;
-                    number += *n as u32;
+                    number = number.checked_mul(10)?.checked_add(*n as u32)?;
                 }
 
                 Some(Expr::new(
@@ -182,18 +782,224 @@ impl<'a> Parser<'a> {
                 Position::new(token.pos.start, token.pos.end)
             )),
 
+            // If
+            TokenKind::Keyword(Keyword::If) => Self::parse_expr_if(&mut lexer, pos.start),
+
+            // Match
+            TokenKind::Keyword(Keyword::Match) => Self::parse_expr_match(&mut lexer, pos.start),
+
+            // Lambda
+            TokenKind::Keyword(Keyword::Fn) => Self::parse_lambda(&mut lexer, pos.start),
+
+            // Record
+            TokenKind::Bracket(Bracket::OpenCurly) if Self::is_record(&lexer) => {
+                Self::parse_record(&mut lexer, pos.start)
+            }
+
+            // Block
+            TokenKind::Bracket(Bracket::OpenCurly) => {
+                let mut stmts = vec![];
+                loop {
+                    lexer.ignore_spaces();
+                    let mut lexer_ = lexer.clone();
+                    let close_bracket = lexer_.bump()?;
+                    if let TokenKind::Bracket(Bracket::CloseCurly) = close_bracket.kind {
+                        lexer.sync(lexer_);
+                        break Some(Expr::new(
+                            ExprKind::Block(stmts),
+                            Position::new(pos.start, close_bracket.pos.end),
+                        ));
+                    }
+                    stmts.push(Self::parse_stmt_inner(&mut lexer)?);
+                }
+            }
+
+            // List
+            TokenKind::Bracket(Bracket::OpenSquare) => {
+                let (items, close_bracket) =
+                    Self::parse_list(&mut lexer, Bracket::CloseSquare, Self::parse_list_item)?;
+                Some(Expr::new(
+                    ExprKind::List(items),
+                    Position::new(pos.start, close_bracket.pos.end),
+                ))
+            }
+
+            // Element
+            TokenKind::Bracket(Bracket::OpenAngle) => Self::parse_element(&mut lexer, pos.start),
+
+            // Parenthesized
+            TokenKind::Bracket(Bracket::OpenParen) => {
+                lexer.ignore_spaces();
+                let inner = Self::parse_expr_inner(&mut lexer)?;
+                lexer.ignore_spaces();
+                let close_bracket = lexer.bump()?;
+                if let TokenKind::Bracket(Bracket::CloseParen) = close_bracket.kind {
+                    // Spans the parens, so that the expressions around it do
+                    Some(Expr::new(
+                        inner.kind,
+                        Position::new(token.pos.start, close_bracket.pos.end),
+                    ))
+                } else {
+                    None
+                }
+            }
+
             _ => None,
         };
 
-        if let Some(_) = result {
+        if result.is_some() {
             lexer_.sync(lexer);
         }
 
         result
     }
 
+    // Reads a binary operator, whose characters are separate tokens that
+    // must not have spaces between them
+    fn parse_binary_op(lexer: &mut Lexer) -> Option<BinaryOp> {
+        let first = lexer.bump()?;
+        let mut lexer_ = lexer.clone();
+        let second = lexer_
+            .bump()
+            .filter(|second| second.pos.start == first.pos.end)
+            .map(|second| second.kind);
+
+        let equal = TokenKind::Operator(Operator::Equal);
+        let (op, double) = match (&first.kind, &second) {
+            (TokenKind::Operator(Operator::Equal), Some(second)) if *second == equal => {
+                (BinaryOp::Eq, true)
+            }
+            (TokenKind::Operator(Operator::Bang), Some(second)) if *second == equal => {
+                (BinaryOp::Ne, true)
+            }
+            (TokenKind::Bracket(Bracket::OpenAngle), Some(second)) if *second == equal => {
+                (BinaryOp::Le, true)
+            }
+            (TokenKind::Bracket(Bracket::CloseAngle), Some(second)) if *second == equal => {
+                (BinaryOp::Ge, true)
+            }
+            (
+                TokenKind::Operator(Operator::Ampersand),
+                Some(TokenKind::Operator(Operator::Ampersand)),
+            ) => (BinaryOp::And, true),
+            (TokenKind::Operator(Operator::Pipe), Some(TokenKind::Operator(Operator::Pipe))) => {
+                (BinaryOp::Or, true)
+            }
+            (TokenKind::Bracket(Bracket::OpenAngle), _) => (BinaryOp::Lt, false),
+            (TokenKind::Bracket(Bracket::CloseAngle), _) => (BinaryOp::Gt, false),
+            (TokenKind::Operator(Operator::Plus), _) => (BinaryOp::Add, false),
+            (TokenKind::Operator(Operator::Minus), _) => (BinaryOp::Sub, false),
+            (TokenKind::Operator(Operator::Star), _) => (BinaryOp::Mul, false),
+            (TokenKind::Operator(Operator::Slash), _) => (BinaryOp::Div, false),
+            (TokenKind::Operator(Operator::Percent), _) => (BinaryOp::Rem, false),
+            _ => return None,
+        };
+
+        if double {
+            lexer.sync(lexer_);
+        }
+        Some(op)
+    }
+
+    // Precedence climbing over left associative operators that bind at
+    // least as tightly as `min`
+    fn parse_expr_binary(lexer_: &mut Lexer, min: Precedence) -> Option<Expr> {
+        let mut lhs = Self::parse_expr_unary(lexer_)?;
+        loop {
+            let mut lexer = lexer_.clone();
+            lexer.ignore_spaces();
+            let op = match Self::parse_binary_op(&mut lexer) {
+                Some(op) if op.precedence() >= min => op,
+                _ => break,
+            };
+            lexer.ignore_spaces();
+            let rhs = Self::parse_expr_binary(&mut lexer, op.precedence().next())?;
+            let pos = Position::new(lhs.pos.start, rhs.pos.end);
+            lhs = Expr::new(ExprKind::Binary(BinaryExpr::new(op, lhs, rhs)), pos);
+            lexer_.sync(lexer);
+        }
+        Some(lhs)
+    }
+
+    fn parse_expr_unary(lexer_: &mut Lexer) -> Option<Expr> {
+        let mut lexer = lexer_.clone();
+        let token = lexer.bump()?;
+        let op = match token.kind {
+            TokenKind::Operator(Operator::Minus) => UnaryOp::Neg,
+            TokenKind::Operator(Operator::Bang) => UnaryOp::Not,
+            _ => return Self::parse_expr_call(lexer_),
+        };
+        let operand = Self::parse_expr_unary(&mut lexer)?;
+        lexer_.sync(lexer);
+        let pos = Position::new(token.pos.start, operand.pos.end);
+        Some(Expr::new(ExprKind::Unary(UnaryExpr::new(op, operand)), pos))
+    }
+
+    // `if condition { ... } else ...`, after the `if`
+    fn parse_expr_if(lexer: &mut Lexer, start: u32) -> Option<Expr> {
+        lexer.ignore_spaces();
+        let condition = Self::parse_expr_inner(lexer)?;
+        let then = Self::parse_block(lexer)?;
+        let mut end = then.pos.end;
+
+        let else_keyword = TokenKind::Keyword(Keyword::Else);
+        let otherwise = if Self::eat_kind(lexer, &else_keyword).is_some() {
+            let otherwise = match Self::eat_kind(lexer, &TokenKind::Keyword(Keyword::If)) {
+                Some(if_keyword) => Self::parse_expr_if(lexer, if_keyword.pos.start)?,
+                None => Self::parse_block(lexer)?,
+            };
+            end = otherwise.pos.end;
+            Some(otherwise)
+        } else {
+            None
+        };
+
+        Some(Expr::new(
+            ExprKind::If(IfExpr::new(condition, then, otherwise)),
+            Position::new(start, end),
+        ))
+    }
+
+    // A block expression, skipping spaces in front of it
+    fn parse_block(lexer_: &mut Lexer) -> Option<Expr> {
+        let mut lexer = lexer_.clone();
+        lexer.ignore_spaces();
+        let block = Self::parse_expr_not_call(&mut lexer)?;
+        if let ExprKind::Block(_) = block.kind {
+            lexer_.sync(lexer);
+            Some(block)
+        } else {
+            None
+        }
+    }
+
+    // `name = value`, where the `=` is not the start of `==` or `=>`
+    fn parse_expr_assign(lexer_: &mut Lexer) -> Option<Expr> {
+        let mut lexer = lexer_.clone();
+        let (name, name_pos) = Self::eat_identifier(&mut lexer)?;
+        let equal = Self::eat_kind(&mut lexer, &TokenKind::Operator(Operator::Equal))?;
+        let next = lexer.clone().bump()?;
+        let joined = next.pos.start == equal.pos.end
+            && matches!(
+                next.kind,
+                TokenKind::Operator(Operator::Equal) | TokenKind::Bracket(Bracket::CloseAngle)
+            );
+        if joined {
+            return None;
+        }
+        lexer.ignore_spaces();
+        let value = Self::parse_expr_inner(&mut lexer)?;
+        lexer_.sync(lexer);
+        let pos = Position::new(name_pos.start, value.pos.end);
+        let target = Expr::new(ExprKind::Identifier(IdentifierExpr::new(name)), name_pos);
+        Some(Expr::new(
+            ExprKind::Assign(AssignExpr::new(target, value)),
+            pos,
+        ))
+    }
+
     fn parse_expr_inner(lexer: &mut Lexer) -> Option<Expr> {
-        Self::parse_expr_call(lexer)
+        Self::parse_expr_assign(lexer).or_else(|| Self::parse_expr_binary(lexer, Precedence::Or))
     }
 
     pub fn parse_expr(&mut self) -> Option<Expr> {
@@ -204,6 +1010,28 @@ impl<'a> Parser<'a> {
         Self::parse_stmt_inner(&mut self.lexer)
     }
 
+    /// Parses statements until the end of the input. Returns `None` if some
+    /// input is left that does not parse, with the lexer stopped in front of it.
+    pub fn parse_program(&mut self) -> Option<Program> {
+        let mut stmts = vec![];
+        loop {
+            self.lexer.ignore_some(|kind| {
+                matches!(
+                    kind,
+                    TokenKind::Space(_)
+                        | TokenKind::Comment(_)
+                        | TokenKind::Punctuation(Punctuation::Semicolon)
+                )
+            });
+            if self.lexer.clone().bump().is_none() {
+                break;
+            }
+            stmts.push(Self::parse_stmt_inner(&mut self.lexer)?);
+        }
+
+        Some(Program::new(stmts))
+    }
+
     pub fn reload_lexer(&mut self, lexer: Lexer<'a>) {
         self.lexer = lexer
     }
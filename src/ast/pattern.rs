@@ -0,0 +1,26 @@
+use crate::ast::expr::LiteralExpr;
+use crate::lexer::Position;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+    // `_`
+    Wildcard,
+    // Either a new binding or a variant without fields, whichever the name
+    // resolves to
+    Identifier(String),
+    Literal(LiteralExpr),
+    // `Name(patterns)`
+    Variant(String, Vec<Pattern>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub pos: Position,
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind, pos: Position) -> Self {
+        Self { kind, pos }
+    }
+}
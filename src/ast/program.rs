@@ -0,0 +1,12 @@
+use crate::ast::stmt::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    pub stmts: Vec<Stmt>,
+}
+
+impl Program {
+    pub fn new(stmts: Vec<Stmt>) -> Program {
+        Self { stmts }
+    }
+}
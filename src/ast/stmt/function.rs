@@ -0,0 +1,58 @@
+use crate::ast::expr::*;
+use crate::ast::ty::*;
+use crate::lexer::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    // The name only, the annotation has its own span
+    pub pos: Position,
+    pub ty: Option<TypeExpr>,
+}
+
+impl Param {
+    pub fn new(name: String, pos: Position) -> Param {
+        Self {
+            name,
+            pos,
+            ty: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FnStmt {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret: Option<TypeExpr>,
+    // Always a block
+    pub body: Expr,
+}
+
+impl FnStmt {
+    pub fn new(name: String, params: Vec<Param>, body: Expr) -> FnStmt {
+        Self {
+            name,
+            params,
+            ret: None,
+            body,
+        }
+    }
+}
+
+/// `component Name(props) { body }`, a function of the attributes of the
+/// elements that use it, each bound to the prop of the same name, that
+/// returns markup. Every instance keeps its own `state`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentStmt {
+    pub name: String,
+    pub props: Vec<Param>,
+    // Always a block
+    pub body: Expr,
+}
+
+impl ComponentStmt {
+    pub fn new(name: String, props: Vec<Param>, body: Expr) -> ComponentStmt {
+        Self { name, props, body }
+    }
+}
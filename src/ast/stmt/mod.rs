@@ -1,15 +1,45 @@
+pub mod function;
+pub use self::function::*;
+pub mod module;
+pub use self::module::*;
+pub mod type_decl;
+pub use self::type_decl::*;
+
 use crate::ast::expr::*;
+use crate::ast::ty::*;
 use crate::lexer::*;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LetStmt {
     pub identifier: String,
+    pub ty: Option<TypeExpr>,
     pub value: Expr,
 }
 
 impl LetStmt {
     pub fn new(identifier: String, value: Expr) -> LetStmt {
-        Self { identifier, value }
+        Self {
+            identifier,
+            ty: None,
+            value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateStmt {
+    pub identifier: String,
+    pub ty: Option<TypeExpr>,
+    pub value: Expr,
+}
+
+impl StateStmt {
+    pub fn new(identifier: String, value: Expr) -> StateStmt {
+        Self {
+            identifier,
+            ty: None,
+            value,
+        }
     }
 }
 
@@ -17,6 +47,14 @@ impl LetStmt {
 pub enum StmtKind {
     Expr(Expr),
     Let(LetStmt),
+    State(StateStmt),
+    Fn(FnStmt),
+    Component(ComponentStmt),
+    Type(TypeStmt),
+    Import(ImportStmt),
+    // `export` in front of a `let`, `state`, `fn`, `component` or `type`
+    // statement
+    Export(Box<Stmt>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -29,4 +67,12 @@ impl Stmt {
     pub fn new(kind: StmtKind, pos: Position) -> Stmt {
         Self { kind, pos }
     }
+
+    /// The statement itself, or the one it exports.
+    pub fn declaration(&self) -> &Stmt {
+        match &self.kind {
+            StmtKind::Export(stmt) => stmt,
+            _ => self,
+        }
+    }
 }
@@ -0,0 +1,33 @@
+use crate::lexer::*;
+
+/// A name in the braces of an import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportName {
+    pub name: String,
+    pub pos: Position,
+}
+
+impl ImportName {
+    pub fn new(name: String, pos: Position) -> ImportName {
+        Self { name, pos }
+    }
+}
+
+/// `import { Name, ... } from "./path"`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportStmt {
+    pub names: Vec<ImportName>,
+    pub path: String,
+    // The path string, quotes included
+    pub path_pos: Position,
+}
+
+impl ImportStmt {
+    pub fn new(names: Vec<ImportName>, path: String, path_pos: Position) -> ImportStmt {
+        Self {
+            names,
+            path,
+            path_pos,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use crate::ast::ty::*;
+use crate::lexer::*;
+
+/// `Name(Field, ...)`, or just `Name` without fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<TypeExpr>,
+    pub pos: Position,
+}
+
+impl Variant {
+    pub fn new(name: String, fields: Vec<TypeExpr>, pos: Position) -> Variant {
+        Self { name, fields, pos }
+    }
+}
+
+/// `type Name<T> = A(T) | B`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeStmt {
+    pub name: String,
+    pub params: Vec<String>,
+    pub variants: Vec<Variant>,
+}
+
+impl TypeStmt {
+    pub fn new(name: String, variants: Vec<Variant>) -> TypeStmt {
+        Self {
+            name,
+            params: vec![],
+            variants,
+        }
+    }
+}
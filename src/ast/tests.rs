@@ -1,5 +1,7 @@
+use super::validate::*;
 use super::*;
 
+#[allow(clippy::assertions_on_constants)]
 fn fail() {
     assert!(false);
 }
@@ -31,6 +33,13 @@ fn parse_literal_expr() {
         parser.parse_expr().unwrap().kind,
         ExprKind::Literal(LiteralExpr::Number(23))
     );
+    parser.reload("4294967295");
+    assert_eq!(
+        parser.parse_expr().unwrap().kind,
+        ExprKind::Literal(LiteralExpr::Number(u32::MAX))
+    );
+    parser.reload("4294967296");
+    assert_eq!(parser.parse_expr(), None);
 
     // Bool
     parser.reload("true false");
@@ -62,7 +71,7 @@ fn parse_call_expr() {
         } else {
             fail()
         }
-        if let ExprKind::Identifier(identifier) = call.input.kind {
+        if let ExprKind::Identifier(identifier) = &call.args[0].kind {
             assert_eq!(identifier.ident, "bar");
         } else {
             fail()
@@ -79,7 +88,7 @@ fn parse_call_expr() {
         } else {
             fail()
         }
-        if let ExprKind::Identifier(identifier) = call.input.kind {
+        if let ExprKind::Identifier(identifier) = &call.args[0].kind {
             assert_eq!(identifier.ident, "input");
         } else {
             fail()
@@ -96,7 +105,7 @@ fn parse_call_expr() {
         } else {
             fail()
         }
-        assert_eq!(call.input.kind, ExprKind::Literal(LiteralExpr::Number(1)));
+        assert_eq!(call.args[0].kind, ExprKind::Literal(LiteralExpr::Number(1)));
     } else {
         fail()
     }
@@ -116,17 +125,610 @@ fn parse_let_stmt() {
                     ExprKind::Call(CallExpr::new(
                         Expr::new(
                             ExprKind::Identifier(IdentifierExpr::new("bar".to_string())),
-                            Position::new(10, 12)
+                            Position::new(10, 13)
                         ),
-                        Expr::new(
+                        vec![Expr::new(
                             ExprKind::Identifier(IdentifierExpr::new("baz".to_string())),
-                            Position::new(14, 16)
-                        )
+                            Position::new(14, 17)
+                        )]
                     )),
-                    Position::new(10, 17)
+                    Position::new(10, 18)
                 )
             )),
-            Position::new(0, 18)
+            Position::new(0, 19)
         )
     );
 }
+
+#[test]
+fn parse_block_expr() {
+    let mut parser = Parser::new("{ let x = foo; x }");
+    let block = parser.parse_expr().unwrap();
+    assert_eq!(block.pos, Position::new(0, 18));
+    if let ExprKind::Block(stmts) = block.kind {
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(
+            stmts[1].kind,
+            StmtKind::Expr(Expr::new(
+                ExprKind::Identifier(IdentifierExpr::new("x".to_string())),
+                Position::new(15, 16)
+            ))
+        );
+    } else {
+        fail()
+    }
+}
+
+#[test]
+fn parse_program() {
+    let mut parser = Parser::new("let a = 1;\n// comment\nfoo(a);\n\n{ bar }\n");
+    let program = parser.parse_program().unwrap();
+    assert_eq!(program.stmts.len(), 3);
+    assert!(matches!(program.stmts[0].kind, StmtKind::Let(_)));
+    assert!(matches!(program.stmts[1].kind, StmtKind::Expr(_)));
+
+    parser.reload("let a = 1; let = 2");
+    assert_eq!(parser.parse_program(), None);
+    assert_eq!(parser.lexer.position(), 11);
+}
+
+#[test]
+fn parse_stmt_spans() {
+    let mut parser = Parser::new("let foo = bar");
+    assert_eq!(parser.parse_stmt().unwrap().pos, Position::new(0, 13));
+
+    parser.reload("foo(bar);");
+    assert_eq!(parser.parse_stmt().unwrap().pos, Position::new(0, 9));
+
+    parser.reload("(foo)(bar)");
+    let call = parser.parse_expr().unwrap();
+    assert_eq!(call.pos, Position::new(0, 10));
+    if let ExprKind::Call(call) = call.kind {
+        assert_eq!(call.called.pos, Position::new(0, 5));
+    } else {
+        fail()
+    }
+}
+
+#[test]
+fn validate_spans() {
+    let stmt = Stmt::new(
+        StmtKind::Expr(Expr::new(
+            ExprKind::Block(vec![
+                Stmt::new(
+                    StmtKind::Expr(Expr::new(
+                        ExprKind::Identifier(IdentifierExpr::new("a".to_string())),
+                        Position::new(2, 5),
+                    )),
+                    Position::new(2, 5),
+                ),
+                Stmt::new(
+                    StmtKind::Expr(Expr::new(
+                        ExprKind::Identifier(IdentifierExpr::new("b".to_string())),
+                        Position::new(4, 12),
+                    )),
+                    Position::new(4, 12),
+                ),
+            ]),
+            Position::new(0, 10),
+        )),
+        Position::new(0, 10),
+    );
+    assert_eq!(
+        validate_stmt(&stmt),
+        vec![
+            SpanError::Escapes {
+                parent: Position::new(0, 10),
+                child: Position::new(4, 12)
+            },
+            SpanError::Overlaps {
+                first: Position::new(2, 5),
+                second: Position::new(4, 12)
+            },
+        ]
+    );
+}
+
+#[test]
+fn validate_corpus_spans() {
+    for (name, source) in crate::corpus::sources() {
+        let program = Parser::new(&source)
+            .parse_program()
+            .unwrap_or_else(|| panic!("{} does not parse", name));
+        assert_eq!(validate_program(&program), vec![], "in {}", name);
+    }
+}
+
+#[test]
+fn parse_binary_precedence() {
+    let mut parser = Parser::new("1 + 2 * 3 - 4 == 3 && !a");
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 24));
+    if let ExprKind::Binary(and) = expr.kind {
+        assert_eq!(and.op, BinaryOp::And);
+        assert!(matches!(and.rhs.kind, ExprKind::Unary(_)));
+        if let ExprKind::Binary(eq) = and.lhs.kind {
+            assert_eq!(eq.op, BinaryOp::Eq);
+            if let ExprKind::Binary(sub) = eq.lhs.kind {
+                assert_eq!(sub.op, BinaryOp::Sub);
+                assert_eq!(sub.lhs.pos, Position::new(0, 9));
+            } else {
+                fail()
+            }
+        } else {
+            fail()
+        }
+    } else {
+        fail()
+    }
+}
+
+#[test]
+fn parse_parenthesized_spans() {
+    let mut parser = Parser::new("(a) + -(b)");
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 10));
+    if let ExprKind::Binary(add) = expr.kind {
+        assert_eq!(add.lhs.pos, Position::new(0, 3));
+        assert_eq!(add.rhs.pos, Position::new(6, 10));
+        if let ExprKind::Unary(neg) = add.rhs.kind {
+            assert_eq!(neg.operand.pos, Position::new(7, 10));
+        } else {
+            fail()
+        }
+    } else {
+        fail()
+    }
+}
+
+#[test]
+fn parse_if_expr() {
+    let mut parser = Parser::new("if a <= b { a } else if c { b } else { c }");
+    let expr = parser.parse_expr().unwrap();
+    if let ExprKind::If(if_expr) = expr.kind {
+        assert!(matches!(if_expr.condition.kind, ExprKind::Binary(_)));
+        assert_eq!(if_expr.then.pos, Position::new(10, 15));
+        assert!(matches!(
+            if_expr.otherwise.unwrap().kind,
+            ExprKind::If(IfExpr {
+                otherwise: Some(_),
+                ..
+            })
+        ));
+    } else {
+        fail()
+    }
+}
+
+#[test]
+fn parse_type_annotations() {
+    let mut parser = Parser::new("let xs: List<List<String>> = ys;");
+    let stmt = parser.parse_stmt().unwrap();
+    if let StmtKind::Let(let_stmt) = stmt.kind {
+        let ty = let_stmt.ty.unwrap();
+        assert_eq!(ty.pos, Position::new(8, 26));
+        if let TypeExprKind::Named(named) = ty.kind {
+            assert_eq!(named.name, "List");
+            assert_eq!(named.args[0].pos, Position::new(13, 25));
+        } else {
+            fail()
+        }
+    } else {
+        fail()
+    }
+
+    parser.reload("fn apply(f: fn(Number) -> Bool, x: Number) -> Bool { f(x) }");
+    let stmt = parser.parse_stmt().unwrap();
+    if let StmtKind::Fn(fn_stmt) = stmt.kind {
+        assert_eq!(
+            fn_stmt.params[0].ty.as_ref().unwrap().pos,
+            Position::new(12, 30)
+        );
+        assert!(matches!(
+            fn_stmt.params[0].ty.as_ref().unwrap().kind,
+            TypeExprKind::Fn(_)
+        ));
+        assert_eq!(fn_stmt.ret.unwrap().pos, Position::new(46, 50));
+    } else {
+        fail()
+    }
+
+    parser.reload("state count: Number = 0");
+    assert!(matches!(
+        parser.parse_stmt().unwrap().kind,
+        StmtKind::State(StateStmt { ty: Some(_), .. })
+    ));
+}
+
+#[test]
+fn parse_list_expr() {
+    let mut parser = Parser::new("[1, ..xs,\n 2,]");
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 14));
+    if let ExprKind::List(items) = expr.kind {
+        assert_eq!(items.len(), 3);
+        assert!(!items[0].spread);
+        assert!(items[1].spread);
+        assert_eq!(items[1].pos, Position::new(4, 8));
+        assert_eq!(items[1].value.pos, Position::new(6, 8));
+    } else {
+        fail()
+    }
+
+    parser.reload("[]");
+    assert_eq!(parser.parse_expr().unwrap().kind, ExprKind::List(vec![]));
+
+    // `..` needs both dots together
+    parser.reload("[. .xs]");
+    assert_eq!(parser.parse_expr(), None);
+}
+
+#[test]
+fn parse_index_expr() {
+    // Indexing and calls bind tighter than any operator, left to right
+    let mut parser = Parser::new("-f(x)[i + 1](y)[0]");
+    let expr = parser.parse_expr().unwrap();
+    if let ExprKind::Unary(unary) = expr.kind {
+        assert_eq!(unary.operand.pos, Position::new(1, 18));
+        if let ExprKind::Index(outer) = unary.operand.kind {
+            assert_eq!(outer.index.pos, Position::new(16, 17));
+            if let ExprKind::Call(call) = outer.indexed.kind {
+                if let ExprKind::Index(inner) = call.called.kind {
+                    assert_eq!(inner.indexed.pos, Position::new(1, 5));
+                    assert!(matches!(inner.index.kind, ExprKind::Binary(_)));
+                } else {
+                    fail()
+                }
+            } else {
+                fail()
+            }
+        } else {
+            fail()
+        }
+    } else {
+        fail()
+    }
+
+    // A list on the next line is not an index
+    let mut parser = Parser::new("let a = xs\n[1]");
+    let program = parser.parse_program().unwrap();
+    assert_eq!(program.stmts.len(), 2);
+}
+
+#[test]
+fn parse_record_expr() {
+    let mut parser = Parser::new("{ name: 'x', count,\n}");
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 21));
+    if let ExprKind::Record(record) = expr.kind {
+        assert_eq!(record.base, None);
+        assert_eq!(record.fields[0].name, "name");
+        assert_eq!(record.fields[0].pos, Position::new(2, 11));
+        assert!(record.fields[1].is_shorthand());
+        assert_eq!(record.fields[1].value.pos, Position::new(13, 18));
+    } else {
+        fail()
+    }
+
+    parser.reload("{ ..base, count: 2 }");
+    if let ExprKind::Record(record) = parser.parse_expr().unwrap().kind {
+        assert_eq!(record.base.unwrap().pos, Position::new(4, 8));
+        assert_eq!(record.fields.len(), 1);
+    } else {
+        fail()
+    }
+
+    // Anything else is a block
+    for input in ["{ name }", "{ }", "{ name; count }", "{ f(x) }"] {
+        parser.reload(input);
+        assert!(
+            matches!(parser.parse_expr().unwrap().kind, ExprKind::Block(_)),
+            "in {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn parse_field_expr() {
+    // Field access binds like calls and indexing
+    let mut parser = Parser::new("!a.b(c).d[0].e");
+    let expr = parser.parse_expr().unwrap();
+    if let ExprKind::Unary(unary) = expr.kind {
+        if let ExprKind::Field(e) = unary.operand.kind {
+            assert_eq!(e.field, "e");
+            assert_eq!(e.record.pos, Position::new(1, 12));
+            assert!(matches!(e.record.kind, ExprKind::Index(_)));
+        } else {
+            fail()
+        }
+    } else {
+        fail()
+    }
+
+    // The name must follow the dot
+    parser.reload("a. b");
+    assert_eq!(parser.parse_expr().unwrap().pos, Position::new(0, 1));
+}
+
+#[test]
+fn parse_lambda_expr() {
+    let mut parser = Parser::new("fn(x, y: Number) => x + y");
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 25));
+    if let ExprKind::Lambda(lambda) = expr.kind {
+        assert_eq!(lambda.params.len(), 2);
+        assert_eq!(lambda.params[1].pos, Position::new(6, 7));
+        assert!(!lambda.has_block_body());
+        assert!(matches!(lambda.body.kind, ExprKind::Binary(_)));
+    } else {
+        fail()
+    }
+
+    parser.reload("fn() -> Number { 1 }(2)");
+    let expr = parser.parse_expr().unwrap();
+    if let ExprKind::Call(call) = expr.kind {
+        if let ExprKind::Lambda(lambda) = call.called.kind {
+            assert!(lambda.has_block_body());
+            assert_eq!(lambda.ret.unwrap().pos, Position::new(8, 14));
+        } else {
+            fail()
+        }
+    } else {
+        fail()
+    }
+
+    // A named `fn` is still a statement
+    parser.reload("fn f() { 1 } fn() { 2 }");
+    let program = parser.parse_program().unwrap();
+    assert!(matches!(program.stmts[0].kind, StmtKind::Fn(_)));
+    assert!(matches!(
+        program.stmts[1].kind,
+        StmtKind::Expr(Expr {
+            kind: ExprKind::Lambda(_),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn parse_type_stmt() {
+    let mut parser =
+        Parser::new("type Shape<T> =\n    | Circle(T)\n    | Rect(Number, List<T>)\n    | Empty;");
+    let stmt = parser.parse_stmt().unwrap();
+    assert_eq!(stmt.pos, Position::new(0, 72));
+    if let StmtKind::Type(type_stmt) = stmt.kind {
+        assert_eq!(type_stmt.name, "Shape");
+        assert_eq!(type_stmt.params, vec!["T".to_string()]);
+        let names: Vec<&str> = type_stmt
+            .variants
+            .iter()
+            .map(|variant| variant.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Circle", "Rect", "Empty"]);
+        assert_eq!(type_stmt.variants[0].pos, Position::new(22, 31));
+        assert_eq!(type_stmt.variants[1].fields.len(), 2);
+        assert_eq!(type_stmt.variants[2].fields, vec![]);
+    } else {
+        fail()
+    }
+}
+
+#[test]
+fn parse_match_expr() {
+    let input = "match shape {\n    Circle(_) if r > 1 => 1,\n    Rect(w, Some(0)) => { w }\n    'x' => 2,\n}";
+    let mut parser = Parser::new(input);
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, input.len() as u32));
+    if let ExprKind::Match(match_expr) = expr.kind {
+        assert_eq!(match_expr.scrutinee.pos, Position::new(6, 11));
+        assert_eq!(match_expr.arms.len(), 3);
+
+        let first = &match_expr.arms[0];
+        assert_eq!(first.pos, Position::new(18, 41));
+        assert!(first.guard.is_some());
+        if let PatternKind::Variant(name, patterns) = &first.pattern.kind {
+            assert_eq!(name, "Circle");
+            assert_eq!(patterns[0].kind, PatternKind::Wildcard);
+        } else {
+            fail()
+        }
+
+        // The comma is optional after a block
+        let second = &match_expr.arms[1];
+        assert!(matches!(second.body.kind, ExprKind::Block(_)));
+        if let PatternKind::Variant(_, patterns) = &second.pattern.kind {
+            assert_eq!(patterns[0].kind, PatternKind::Identifier("w".to_string()));
+            assert!(matches!(&patterns[1].kind, PatternKind::Variant(name, _) if name == "Some"));
+        } else {
+            fail()
+        }
+
+        assert_eq!(
+            match_expr.arms[2].pattern.kind,
+            PatternKind::Literal(LiteralExpr::String("x".to_string()))
+        );
+    } else {
+        fail()
+    }
+
+    parser.reload("match x { 1 => a 2 => b }");
+    assert_eq!(parser.parse_expr(), None);
+}
+
+#[test]
+fn parse_import_stmt() {
+    let input = "import { Button, Card, } from \"./ui/button\";";
+    let stmt = Parser::new(input).parse_stmt().unwrap();
+    assert_eq!(stmt.pos, Position::new(0, input.len() as u32));
+    if let StmtKind::Import(import_stmt) = stmt.kind {
+        assert_eq!(
+            import_stmt.names,
+            vec![
+                ImportName::new("Button".into(), Position::new(9, 15)),
+                ImportName::new("Card".into(), Position::new(17, 21)),
+            ]
+        );
+        assert_eq!(import_stmt.path, "./ui/button");
+        assert_eq!(import_stmt.path_pos, Position::new(30, 43));
+    } else {
+        fail()
+    }
+
+    assert_eq!(Parser::new("import { a } \"./a\"").parse_stmt(), None);
+    assert_eq!(Parser::new("import { a } from a").parse_stmt(), None);
+}
+
+#[test]
+fn parse_export_stmt() {
+    let stmt = Parser::new("export fn f() { 1 };").parse_stmt().unwrap();
+    assert_eq!(stmt.pos, Position::new(0, 20));
+    if let StmtKind::Export(exported) = &stmt.kind {
+        assert_eq!(exported.pos, Position::new(7, 20));
+        assert!(matches!(exported.kind, StmtKind::Fn(_)));
+        assert_eq!(stmt.declaration(), exported.as_ref());
+    } else {
+        fail()
+    }
+
+    assert_eq!(Parser::new("export 1 + 2").parse_stmt(), None);
+    assert_eq!(
+        Parser::new("export import { a } from 'a'").parse_stmt(),
+        None
+    );
+}
+
+#[test]
+fn parse_assign_expr() {
+    let mut parser = Parser::new("count = count + 1 == 2");
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 22));
+    if let ExprKind::Assign(assign) = expr.kind {
+        assert_eq!(assign.target.pos, Position::new(0, 5));
+        assert!(matches!(assign.value.kind, ExprKind::Binary(_)));
+    } else {
+        fail()
+    }
+
+    // Comparisons and lambdas are not assignments
+    parser.reload("a == b");
+    assert!(matches!(
+        parser.parse_expr().unwrap().kind,
+        ExprKind::Binary(_)
+    ));
+    parser.reload("f(x = 1)");
+    assert!(matches!(
+        parser.parse_expr().unwrap().kind,
+        ExprKind::Call(_)
+    ));
+    parser.reload("a.b = 1");
+    assert_eq!(parser.parse_expr().unwrap().pos, Position::new(0, 3));
+}
+
+#[test]
+fn parse_element_expr() {
+    let source = "<Button label={\"Hi\"} on_click={inc}>{count}</Button>";
+    let mut parser = Parser::new(source);
+    let expr = parser.parse_expr().unwrap();
+    assert_eq!(expr.pos, Position::new(0, 52));
+    let ExprKind::Element(element) = expr.kind else {
+        return fail();
+    };
+    let tag = element.tag.unwrap();
+    assert_eq!(tag.name, "Button");
+    assert!(tag.is_component());
+    let names: Vec<&str> = element
+        .attributes
+        .iter()
+        .map(|attribute| attribute.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["label", "on_click"]);
+    assert_eq!(element.attributes[1].pos, Position::new(21, 35));
+    assert_eq!(element.children.len(), 1);
+    assert_eq!(element.children[0].pos, Position::new(36, 43));
+    assert!(matches!(element.children[0].kind, ChildKind::Expr(_)));
+
+    // Self-closing tags, bare attributes and names with digits and dashes
+    parser.reload("<input aria-label='name' disabled />");
+    let ExprKind::Element(element) = parser.parse_expr().unwrap().kind else {
+        return fail();
+    };
+    assert_eq!(element.attributes[0].name, "aria-label");
+    assert_eq!(element.attributes[1].value, None);
+    assert!(element.children.is_empty());
+
+    // Keywords are names in markup
+    parser.reload("<input type='text' />");
+    let ExprKind::Element(element) = parser.parse_expr().unwrap().kind else {
+        return fail();
+    };
+    assert_eq!(element.attributes[0].name, "type");
+
+    // Closing tags must match
+    parser.reload("<h1>title</h2>");
+    assert_eq!(parser.parse_expr(), None);
+}
+
+#[test]
+fn parse_element_children() {
+    let source = "<>\n    <h1>Hello, {name}!</h1>\n    text on\n    two lines\n</>";
+    let mut parser = Parser::new(source);
+    let ExprKind::Element(element) = parser.parse_expr().unwrap().kind else {
+        return fail();
+    };
+    assert_eq!(element.tag, None);
+    assert_eq!(element.children.len(), 2);
+    let ChildKind::Element(heading) = &element.children[0].kind else {
+        return fail();
+    };
+    let ExprKind::Element(heading) = &heading.kind else {
+        return fail();
+    };
+    assert_eq!(heading.children.len(), 3);
+    assert_eq!(
+        heading.children[0].kind,
+        ChildKind::Text("Hello, ".to_string())
+    );
+    assert_eq!(heading.children[2].kind, ChildKind::Text("!".to_string()));
+    assert_eq!(
+        element.children[1].kind,
+        ChildKind::Text("text on two lines".to_string())
+    );
+
+    // Markup text is not code, so quotes and slashes are kept
+    parser.reload("<p>Don't // stop</p>");
+    let ExprKind::Element(element) = parser.parse_expr().unwrap().kind else {
+        return fail();
+    };
+    assert_eq!(
+        element.children[0].kind,
+        ChildKind::Text("Don't // stop".to_string())
+    );
+}
+
+#[test]
+fn parse_component_stmt() {
+    let source = "component Counter(label: String, start) { <p>{label}</p> }";
+    let stmt = Parser::new(source).parse_stmt().unwrap();
+    assert_eq!(stmt.pos, Position::new(0, source.len() as u32));
+    let StmtKind::Component(component) = stmt.kind else {
+        return fail();
+    };
+    assert_eq!(component.name, "Counter");
+    let props: Vec<&str> = component
+        .props
+        .iter()
+        .map(|prop| prop.name.as_str())
+        .collect();
+    assert_eq!(props, vec!["label", "start"]);
+    assert!(component.props[0].ty.is_some());
+    assert!(matches!(component.body.kind, ExprKind::Block(_)));
+
+    let program = Parser::new("export component App() { <main /> }")
+        .parse_program()
+        .unwrap();
+    let StmtKind::Export(exported) = &program.stmts[0].kind else {
+        return fail();
+    };
+    assert!(matches!(exported.kind, StmtKind::Component(_)));
+}
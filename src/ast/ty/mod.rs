@@ -0,0 +1,49 @@
+use crate::lexer::Position;
+
+/// `Name` or `Name<Arg, ...>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NamedType {
+    pub name: String,
+    pub args: Vec<TypeExpr>,
+}
+
+impl NamedType {
+    pub fn new(name: String, args: Vec<TypeExpr>) -> Self {
+        Self { name, args }
+    }
+}
+
+/// `fn(Param, ...) -> Ret`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FnType {
+    pub params: Vec<TypeExpr>,
+    pub ret: Box<TypeExpr>,
+}
+
+impl FnType {
+    pub fn new(params: Vec<TypeExpr>, ret: TypeExpr) -> Self {
+        Self {
+            params,
+            ret: Box::new(ret),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeExprKind {
+    Named(NamedType),
+    Fn(FnType),
+}
+
+/// A type annotation as written in the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeExpr {
+    pub kind: TypeExprKind,
+    pub pos: Position,
+}
+
+impl TypeExpr {
+    pub fn new(kind: TypeExprKind, pos: Position) -> Self {
+        Self { kind, pos }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::ast::*;
+use crate::lexer::Position;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpanError {
+    // `start` is past `end`
+    Inverted(Position),
+    // A child reaches outside its parent
+    Escapes { parent: Position, child: Position },
+    // Two siblings overlap or are out of source order
+    Overlaps { first: Position, second: Position },
+}
+
+/// Checks that every span in the program is a valid range, contains the
+/// spans of the node's children, and that siblings follow each other in
+/// source order without overlapping.
+pub fn validate_program(program: &Program) -> Vec<SpanError> {
+    let mut validator = Validator::default();
+    let positions: Vec<Position> = program.stmts.iter().map(|stmt| stmt.pos).collect();
+    validator.siblings(None, &positions);
+    program.stmts.iter().for_each(|stmt| validator.stmt(stmt));
+    validator.errors
+}
+
+pub fn validate_stmt(stmt: &Stmt) -> Vec<SpanError> {
+    let mut validator = Validator::default();
+    validator.stmt(stmt);
+    validator.errors
+}
+
+pub fn validate_expr(expr: &Expr) -> Vec<SpanError> {
+    let mut validator = Validator::default();
+    validator.expr(expr);
+    validator.errors
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<SpanError>,
+}
+
+impl Validator {
+    fn siblings(&mut self, parent: Option<Position>, children: &[Position]) {
+        for (i, child) in children.iter().enumerate() {
+            if child.start > child.end {
+                self.errors.push(SpanError::Inverted(*child));
+            }
+            if let Some(parent) = parent {
+                if !parent.contains(child) {
+                    self.errors.push(SpanError::Escapes {
+                        parent,
+                        child: *child,
+                    });
+                }
+            }
+            if let Some(previous) = i.checked_sub(1).map(|i| children[i]) {
+                if previous.end > child.start {
+                    self.errors.push(SpanError::Overlaps {
+                        first: previous,
+                        second: *child,
+                    });
+                }
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                let mut positions: Vec<Position> = let_stmt.ty.iter().map(|ty| ty.pos).collect();
+                positions.push(let_stmt.value.pos);
+                self.siblings(Some(stmt.pos), &positions);
+                let_stmt.ty.iter().for_each(|ty| self.ty(ty));
+                self.expr(&let_stmt.value);
+            }
+            StmtKind::State(state_stmt) => {
+                let mut positions: Vec<Position> = state_stmt.ty.iter().map(|ty| ty.pos).collect();
+                positions.push(state_stmt.value.pos);
+                self.siblings(Some(stmt.pos), &positions);
+                state_stmt.ty.iter().for_each(|ty| self.ty(ty));
+                self.expr(&state_stmt.value);
+            }
+            StmtKind::Fn(fn_stmt) => {
+                self.function(stmt.pos, &fn_stmt.params, &fn_stmt.ret, &fn_stmt.body)
+            }
+            StmtKind::Component(component) => {
+                self.function(stmt.pos, &component.props, &None, &component.body)
+            }
+            StmtKind::Type(type_stmt) => {
+                let positions: Vec<Position> = type_stmt
+                    .variants
+                    .iter()
+                    .map(|variant| variant.pos)
+                    .collect();
+                self.siblings(Some(stmt.pos), &positions);
+                for variant in type_stmt.variants.iter() {
+                    let positions: Vec<Position> =
+                        variant.fields.iter().map(|field| field.pos).collect();
+                    self.siblings(Some(variant.pos), &positions);
+                    variant.fields.iter().for_each(|field| self.ty(field));
+                }
+            }
+            StmtKind::Import(import_stmt) => {
+                let mut positions: Vec<Position> =
+                    import_stmt.names.iter().map(|name| name.pos).collect();
+                positions.push(import_stmt.path_pos);
+                self.siblings(Some(stmt.pos), &positions);
+            }
+            StmtKind::Export(exported) => {
+                self.siblings(Some(stmt.pos), &[exported.pos]);
+                self.stmt(exported);
+            }
+            StmtKind::Expr(expr) => {
+                self.siblings(Some(stmt.pos), &[expr.pos]);
+                self.expr(expr);
+            }
+        }
+    }
+
+    fn function(&mut self, pos: Position, params: &[Param], ret: &Option<TypeExpr>, body: &Expr) {
+        let mut positions = vec![];
+        for param in params.iter() {
+            positions.push(param.pos);
+            positions.extend(param.ty.iter().map(|ty| ty.pos));
+        }
+        positions.extend(ret.iter().map(|ret| ret.pos));
+        positions.push(body.pos);
+        self.siblings(Some(pos), &positions);
+        for param in params.iter() {
+            param.ty.iter().for_each(|ty| self.ty(ty));
+        }
+        ret.iter().for_each(|ret| self.ty(ret));
+        self.expr(body);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Variant(_, patterns) => {
+                let positions: Vec<Position> = patterns.iter().map(|pattern| pattern.pos).collect();
+                self.siblings(Some(pattern.pos), &positions);
+                patterns.iter().for_each(|pattern| self.pattern(pattern));
+            }
+            _ => self.siblings(None, &[pattern.pos]),
+        }
+    }
+
+    fn ty(&mut self, ty: &TypeExpr) {
+        match &ty.kind {
+            TypeExprKind::Named(named) => {
+                let positions: Vec<Position> = named.args.iter().map(|arg| arg.pos).collect();
+                self.siblings(Some(ty.pos), &positions);
+                named.args.iter().for_each(|arg| self.ty(arg));
+            }
+            TypeExprKind::Fn(fn_type) => {
+                let mut positions: Vec<Position> =
+                    fn_type.params.iter().map(|param| param.pos).collect();
+                positions.push(fn_type.ret.pos);
+                self.siblings(Some(ty.pos), &positions);
+                fn_type.params.iter().for_each(|param| self.ty(param));
+                self.ty(&fn_type.ret);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier(_) | ExprKind::Literal(_) => {
+                self.siblings(None, &[expr.pos]);
+            }
+            ExprKind::Call(call) => {
+                let mut positions = vec![call.called.pos];
+                positions.extend(call.args.iter().map(|arg| arg.pos));
+                self.siblings(Some(expr.pos), &positions);
+                self.expr(&call.called);
+                call.args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Binary(binary) => {
+                self.siblings(Some(expr.pos), &[binary.lhs.pos, binary.rhs.pos]);
+                self.expr(&binary.lhs);
+                self.expr(&binary.rhs);
+            }
+            ExprKind::Unary(unary) => {
+                self.siblings(Some(expr.pos), &[unary.operand.pos]);
+                self.expr(&unary.operand);
+            }
+            ExprKind::If(if_expr) => {
+                let mut positions = vec![if_expr.condition.pos, if_expr.then.pos];
+                positions.extend(if_expr.otherwise.iter().map(|otherwise| otherwise.pos));
+                self.siblings(Some(expr.pos), &positions);
+                self.expr(&if_expr.condition);
+                self.expr(&if_expr.then);
+                if let Some(otherwise) = &if_expr.otherwise {
+                    self.expr(otherwise);
+                }
+            }
+            ExprKind::Block(stmts) => {
+                let positions: Vec<Position> = stmts.iter().map(|stmt| stmt.pos).collect();
+                self.siblings(Some(expr.pos), &positions);
+                stmts.iter().for_each(|stmt| self.stmt(stmt));
+            }
+            ExprKind::List(items) => {
+                let positions: Vec<Position> = items.iter().map(|item| item.pos).collect();
+                self.siblings(Some(expr.pos), &positions);
+                for item in items {
+                    self.siblings(Some(item.pos), &[item.value.pos]);
+                    self.expr(&item.value);
+                }
+            }
+            ExprKind::Index(index) => {
+                self.siblings(Some(expr.pos), &[index.indexed.pos, index.index.pos]);
+                self.expr(&index.indexed);
+                self.expr(&index.index);
+            }
+            ExprKind::Record(record) => {
+                let mut positions: Vec<Position> =
+                    record.base.iter().map(|base| base.pos).collect();
+                positions.extend(record.fields.iter().map(|field| field.pos));
+                self.siblings(Some(expr.pos), &positions);
+                if let Some(base) = &record.base {
+                    self.expr(base);
+                }
+                for field in record.fields.iter() {
+                    self.siblings(Some(field.pos), &[field.value.pos]);
+                    self.expr(&field.value);
+                }
+            }
+            ExprKind::Field(field) => {
+                self.siblings(Some(expr.pos), &[field.record.pos]);
+                self.expr(&field.record);
+            }
+            ExprKind::Lambda(lambda) => {
+                self.function(expr.pos, &lambda.params, &lambda.ret, &lambda.body)
+            }
+            ExprKind::Match(match_expr) => {
+                let mut positions = vec![match_expr.scrutinee.pos];
+                positions.extend(match_expr.arms.iter().map(|arm| arm.pos));
+                self.siblings(Some(expr.pos), &positions);
+                self.expr(&match_expr.scrutinee);
+                for arm in match_expr.arms.iter() {
+                    let mut positions = vec![arm.pattern.pos];
+                    positions.extend(arm.guard.iter().map(|guard| guard.pos));
+                    positions.push(arm.body.pos);
+                    self.siblings(Some(arm.pos), &positions);
+                    self.pattern(&arm.pattern);
+                    arm.guard.iter().for_each(|guard| self.expr(guard));
+                    self.expr(&arm.body);
+                }
+            }
+            ExprKind::Assign(assign) => {
+                self.siblings(Some(expr.pos), &[assign.target.pos, assign.value.pos]);
+                self.expr(&assign.target);
+                self.expr(&assign.value);
+            }
+            ExprKind::Element(element) => {
+                let mut positions: Vec<Position> = element.tag.iter().map(|tag| tag.pos).collect();
+                positions.extend(element.attributes.iter().map(|attribute| attribute.pos));
+                positions.extend(element.children.iter().map(|child| child.pos));
+                self.siblings(Some(expr.pos), &positions);
+                for attribute in element.attributes.iter() {
+                    if let Some(value) = &attribute.value {
+                        self.siblings(Some(attribute.pos), &[value.pos]);
+                        self.expr(value);
+                    }
+                }
+                for child in element.children.iter() {
+                    if let Some(value) = child.expr() {
+                        self.siblings(Some(child.pos), &[value.pos]);
+                        self.expr(value);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,929 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::js::sourcemap::LineIndex;
+use crate::lexer::Position;
+use crate::resolve::{BindingId, BindingKind, Resolution};
+use crate::types::{Type, Typing};
+
+use super::{binding_names, COptions, RUNTIME_HEADER};
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+// C precedences, loosest first
+const OR: u8 = 4;
+const AND: u8 = 5;
+const EQUALITY: u8 = 9;
+const RELATIONAL: u8 = 10;
+const ADDITIVE: u8 = 12;
+const MULTIPLICATIVE: u8 = 13;
+const UNARY: u8 = 14;
+const CALL: u8 = 16;
+const PRIMARY: u8 = 17;
+
+const INDENT: &str = "    ";
+
+static UNIT: Type = Type::Unit;
+
+// Where the value of an expression in statement position goes
+#[derive(Clone, Debug)]
+enum Target {
+    Return,
+    Discard,
+    Assign(String),
+}
+
+// A C expression and the precedence of its outermost operator
+struct Code {
+    text: String,
+    precedence: u8,
+}
+
+impl Code {
+    fn new(text: impl Into<String>, precedence: u8) -> Self {
+        Self {
+            text: text.into(),
+            precedence,
+        }
+    }
+
+    // The text, in parentheses unless it binds at least as tightly as
+    // `min`
+    fn at(&self, min: u8) -> String {
+        match self.precedence < min {
+            true => format!("({})", self.text),
+            false => self.text.clone(),
+        }
+    }
+}
+
+pub(super) struct Generator<'a> {
+    resolution: &'a Resolution,
+    typing: &'a Typing,
+    lines: LineIndex,
+    // Quoted for `#line` directives
+    source_name: String,
+    names: HashMap<BindingId, String>,
+    // The top-level functions, which are the ones that can be called
+    functions: HashSet<BindingId>,
+    code: String,
+    indent: usize,
+    // The line of the source the next line of code is on, once a `#line`
+    // directive has set it
+    line: Option<u32>,
+    // Temporaries so far in the current function
+    temps: u32,
+}
+
+impl<'a> Generator<'a> {
+    pub(super) fn new(
+        source: &str,
+        resolution: &'a Resolution,
+        typing: &'a Typing,
+        options: &COptions,
+    ) -> Self {
+        Self {
+            resolution,
+            typing,
+            lines: LineIndex::new(source),
+            source_name: string_literal(&options.source_name),
+            names: binding_names(resolution),
+            functions: HashSet::new(),
+            code: String::new(),
+            indent: 0,
+            line: None,
+            temps: 0,
+        }
+    }
+
+    pub(super) fn finish(self) -> String {
+        self.code
+    }
+
+    // The prototypes and globals, then the functions, then `main` with the
+    // other statements. Each statement that fails is reported, and the
+    // others still checked.
+    pub(super) fn program(
+        &mut self,
+        program: &Program,
+    ) -> std::result::Result<(), Vec<Diagnostic>> {
+        let stmts: Vec<&Stmt> = program.stmts.iter().map(Stmt::declaration).collect();
+        let mut diagnostics = vec![];
+        let mut functions = vec![];
+        let mut globals = vec![];
+        for stmt in stmts.iter() {
+            match &stmt.kind {
+                StmtKind::Fn(fn_stmt) => {
+                    self.functions
+                        .insert(self.resolution.definitions[&stmt.pos]);
+                    functions.push((stmt.pos, fn_stmt));
+                }
+                StmtKind::Let(_) | StmtKind::State(_) => globals.push(stmt.pos),
+                _ => {}
+            }
+        }
+
+        self.line(&format!("#include \"{}\"", RUNTIME_HEADER));
+        self.newline();
+        let mut signatures = vec![];
+        for (pos, fn_stmt) in functions.iter() {
+            match self.signature(*pos, fn_stmt) {
+                Ok(signature) => {
+                    self.line(&format!("{};", signature));
+                    signatures.push((signature, *pos, *fn_stmt));
+                }
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+        for pos in globals {
+            let id = self.resolution.definitions[&pos];
+            match self.binding_type(id, pos) {
+                Ok(ty) => self.line(&format!("static {} {};", ty, self.names[&id])),
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+
+        for (signature, pos, fn_stmt) in signatures {
+            self.newline();
+            self.directive(pos);
+            self.temps = 0;
+            self.open(&format!("{} {{", signature));
+            let result = match &fn_stmt.body.kind {
+                ExprKind::Block(stmts) => self.stmts(stmts, &Target::Return),
+                _ => self.expr_into(&fn_stmt.body, &Target::Return),
+            };
+            if let Err(diagnostic) = result {
+                diagnostics.push(diagnostic);
+            }
+            self.close("}");
+        }
+
+        self.newline();
+        self.temps = 0;
+        self.open("int main(void) {");
+        for stmt in stmts {
+            if let Err(diagnostic) = self.top_level(stmt) {
+                diagnostics.push(diagnostic);
+            }
+        }
+        self.line("return 0;");
+        self.close("}");
+
+        match diagnostics.is_empty() {
+            true => Ok(()),
+            false => Err(diagnostics),
+        }
+    }
+
+    fn top_level(&mut self, stmt: &Stmt) -> Result<()> {
+        match &stmt.kind {
+            StmtKind::Fn(_) => Ok(()),
+            StmtKind::Let(LetStmt { value, .. }) | StmtKind::State(StateStmt { value, .. }) => {
+                self.directive(stmt.pos);
+                let name = self.names[&self.resolution.definitions[&stmt.pos]].clone();
+                self.expr_into(value, &Target::Assign(name))
+            }
+            _ => self.stmt(stmt),
+        }
+    }
+
+    // `static double z_name(double a, bool b)`
+    fn signature(&self, pos: Position, fn_stmt: &FnStmt) -> Result<String> {
+        let id = self.resolution.definitions[&pos];
+        let Some(scheme) = self.typing.binding_type(id) else {
+            return Err(Diagnostic::error(
+                format!("`{}` has no type", fn_stmt.name),
+                pos,
+            ));
+        };
+        let Type::Fn(params, ret) = &scheme.ty else {
+            unreachable!("functions have function types")
+        };
+        if !scheme.vars.is_empty() {
+            return Err(Diagnostic::error(
+                format!(
+                    "`{}` is generic, with the type `{}`, which C functions cannot be",
+                    fn_stmt.name, scheme.ty
+                ),
+                pos,
+            ));
+        }
+
+        let mut declared = vec![];
+        for (param, ty) in fn_stmt.params.iter().zip(params) {
+            let name = &self.names[&self.resolution.definitions[&param.pos]];
+            declared.push(format!("{} {}", c_type(ty, param.pos)?, name));
+        }
+        if declared.is_empty() {
+            declared.push("void".to_string());
+        }
+        Ok(format!(
+            "static {} {}({})",
+            c_type(ret, pos)?,
+            self.names[&id],
+            declared.join(", ")
+        ))
+    }
+
+    fn write(&mut self, text: &str) {
+        for char in text.chars() {
+            if char == '\n' {
+                self.line = self.line.map(|line| line + 1);
+            } else if self.code.is_empty() || self.code.ends_with('\n') {
+                for _ in 0..self.indent {
+                    self.code.push_str(INDENT);
+                }
+            }
+            self.code.push(char);
+        }
+    }
+
+    fn newline(&mut self) {
+        self.write("\n");
+    }
+
+    fn line(&mut self, text: &str) {
+        self.write(text);
+        self.newline();
+    }
+
+    // A line that starts a block, whose lines are indented
+    fn open(&mut self, text: &str) {
+        self.line(text);
+        self.indent += 1;
+    }
+
+    fn close(&mut self, text: &str) {
+        self.indent -= 1;
+        self.line(text);
+    }
+
+    // `} else {` and the like, between two blocks
+    fn reopen(&mut self, text: &str) {
+        self.indent -= 1;
+        self.line(text);
+        self.indent += 1;
+    }
+
+    // Sets the line of the source that the next line of code is for, unless
+    // it is on that line already
+    fn directive(&mut self, pos: Position) {
+        let line = self.source_line(pos);
+        if self.line == Some(line) {
+            return;
+        }
+        self.code
+            .push_str(&format!("#line {} {}\n", line, self.source_name));
+        self.line = Some(line);
+    }
+
+    // Counted from 1, as `#line` and `__LINE__` count
+    fn source_line(&self, pos: Position) -> u32 {
+        self.lines.location(pos.start).0 + 1
+    }
+
+    // A new local for a value the generated code needs to keep
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("zp_t{}", self.temps)
+    }
+
+    // A type the checker left open is one nothing constrains, like that of
+    // what the host's `print` returns, whose values are all `Unit`
+    fn type_of(&self, expr: &Expr) -> Result<&'a Type> {
+        match self.typing.type_of(&expr.pos) {
+            Some(Type::Var(_)) => Ok(&UNIT),
+            Some(ty) => Ok(ty),
+            None => Err(Diagnostic::error(
+                "the type of this expression is not known",
+                expr.pos,
+            )),
+        }
+    }
+
+    fn c_type_of(&self, expr: &Expr) -> Result<&'static str> {
+        c_type(self.type_of(expr)?, expr.pos)
+    }
+
+    fn binding_type(&self, id: BindingId, pos: Position) -> Result<&'static str> {
+        match self.typing.binding_type(id) {
+            Some(scheme) => c_type(&scheme.ty, pos),
+            None => Err(Diagnostic::error("this binding has no type", pos)),
+        }
+    }
+
+    // Runs the statements, with the value of the last one going to `target`
+    fn stmts(&mut self, stmts: &[Stmt], target: &Target) -> Result<()> {
+        let Some((last, init)) = stmts.split_last() else {
+            return self.unit(target);
+        };
+        for stmt in init {
+            self.stmt(stmt)?;
+        }
+        match &last.kind {
+            StmtKind::Expr(expr) => {
+                self.directive(last.pos);
+                self.expr_into(expr, target)
+            }
+            _ => {
+                self.stmt(last)?;
+                self.unit(target)
+            }
+        }
+    }
+
+    // Runs a statement whose value is not needed
+    fn stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        self.directive(stmt.pos);
+        match &stmt.kind {
+            StmtKind::Expr(expr) => self.expr_into(expr, &Target::Discard),
+            StmtKind::Let(LetStmt { value, .. }) | StmtKind::State(StateStmt { value, .. }) => {
+                let id = self.resolution.definitions[&stmt.pos];
+                let ty = self.binding_type(id, stmt.pos)?;
+                let name = self.names[&id].clone();
+                if let ExprKind::Block(_) | ExprKind::If(_) | ExprKind::Match(_) = value.kind {
+                    self.line(&format!("{} {};", ty, name));
+                    return self.expr_into(value, &Target::Assign(name));
+                }
+                let value = self.expr(value)?;
+                self.line(&format!("{} {} = {};", ty, name, value.text));
+                Ok(())
+            }
+            StmtKind::Fn(_) => Err(unsupported("nested functions", stmt.pos)),
+            StmtKind::Component(_) => Err(unsupported("components", stmt.pos)),
+            StmtKind::Type(_) => Err(unsupported("types", stmt.pos)),
+            StmtKind::Import(_) | StmtKind::Export(_) => Err(unsupported("modules", stmt.pos)),
+        }
+    }
+
+    fn unit(&mut self, target: &Target) -> Result<()> {
+        match target {
+            Target::Return => self.line("return ZP_UNIT;"),
+            Target::Assign(name) => self.line(&format!("{} = ZP_UNIT;", name)),
+            Target::Discard => {}
+        }
+        Ok(())
+    }
+
+    // Runs an expression in statement position, with its value going to
+    // `target`
+    fn expr_into(&mut self, expr: &Expr, target: &Target) -> Result<()> {
+        match &expr.kind {
+            ExprKind::Block(stmts) => {
+                self.open("{");
+                self.stmts(stmts, target)?;
+                self.close("}");
+                Ok(())
+            }
+            ExprKind::If(if_expr) => self.if_into(if_expr, target),
+            ExprKind::Match(match_expr) => self.match_into(expr, match_expr, target),
+            ExprKind::Assign(assign) => {
+                self.assign(assign)?;
+                self.unit(target)
+            }
+            _ => {
+                let code = self.expr(expr)?;
+                match target {
+                    Target::Return => self.line(&format!("return {};", code.text)),
+                    Target::Assign(name) => self.line(&format!("{} = {};", name, code.text)),
+                    // Nothing to run
+                    Target::Discard if is_pure(expr) => {}
+                    Target::Discard if matches!(expr.kind, ExprKind::Call(_)) => {
+                        self.line(&format!("{};", code.text))
+                    }
+                    Target::Discard => self.line(&format!("(void){};", code.at(UNARY))),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // The statements of a branch, in the braces of the `if` or `else`
+    fn branch(&mut self, expr: &Expr, target: &Target) -> Result<()> {
+        match &expr.kind {
+            ExprKind::Block(stmts) => self.stmts(stmts, target),
+            _ => self.expr_into(expr, target),
+        }
+    }
+
+    // An `if` and the `else if`s after it, as long as their conditions need
+    // no statements of their own
+    fn if_into(&mut self, if_expr: &IfExpr, target: &Target) -> Result<()> {
+        let condition = self.expr(&if_expr.condition)?;
+        self.open(&format!("if ({}) {{", condition.text));
+        let mut current = if_expr;
+        loop {
+            let Some(otherwise) = &current.otherwise else {
+                // Without an `else`, the value is `Unit`
+                self.branch(&current.then, &Target::Discard)?;
+                self.close("}");
+                return self.unit(target);
+            };
+            self.branch(&current.then, target)?;
+            match &otherwise.kind {
+                ExprKind::If(next) if !self.emits_statements(&next.condition) => {
+                    let condition = self.expr(&next.condition)?;
+                    self.reopen(&format!("}} else if ({}) {{", condition.text));
+                    current = next;
+                }
+                _ => {
+                    self.reopen("} else {");
+                    self.branch(otherwise, target)?;
+                    self.close("}");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn assign(&mut self, assign: &AssignExpr) -> Result<()> {
+        let id = self.resolution.uses.get(&assign.target.pos);
+        let Some(name) = id.and_then(|id| self.local_name(*id)) else {
+            return Err(unsupported("assigning this", assign.target.pos));
+        };
+        let value = self.expr(&assign.value)?;
+        self.line(&format!("{} = {};", name, value.text));
+        Ok(())
+    }
+
+    // The name of a binding that holds a value in C, unlike functions and
+    // host bindings
+    fn local_name(&self, id: BindingId) -> Option<String> {
+        let binding = self.resolution.binding(id);
+        match binding.kind {
+            BindingKind::Fn | BindingKind::Host => None,
+            _ => self.names.get(&id).cloned(),
+        }
+    }
+
+    // The subject goes to a temporary, and the arms are tried in a chain of
+    // `if`s, with the `else` of the last one stopping the program
+    fn match_into(&mut self, expr: &Expr, match_expr: &MatchExpr, target: &Target) -> Result<()> {
+        let irrefutable = match_expr
+            .arms
+            .iter()
+            .position(|arm| arm.guard.is_none() && self.always_matches(&arm.pattern));
+        let reached = match irrefutable {
+            Some(index) => &match_expr.arms[..=index],
+            None => &match_expr.arms[..],
+        };
+        let ty = self.type_of(&match_expr.scrutinee)?;
+        let subject = match irrefutable.is_none()
+            || reached
+                .iter()
+                .any(|arm| !matches!(arm.pattern.kind, PatternKind::Wildcard))
+        {
+            true => {
+                let value = self.expr(&match_expr.scrutinee)?;
+                let subject = self.temp();
+                self.line(&format!(
+                    "{} {} = {};",
+                    c_type(ty, match_expr.scrutinee.pos)?,
+                    subject,
+                    value.text
+                ));
+                subject
+            }
+            false => {
+                self.expr_into(&match_expr.scrutinee, &Target::Discard)?;
+                String::new()
+            }
+        };
+
+        // Whether an `if` of the chain is open, and the blocks opened to
+        // compute guards in
+        let mut chained = false;
+        let mut nested = 0;
+        for arm in reached {
+            let mut test = self.pattern(&arm.pattern, &subject, ty)?;
+            if let Some(guard) = &arm.guard {
+                if self.emits_statements(guard) {
+                    if chained {
+                        self.reopen("} else {");
+                        nested += 1;
+                        chained = false;
+                    }
+                    let guarded = self.temp();
+                    match test {
+                        Some(test) => {
+                            self.line(&format!("bool {} = false;", guarded));
+                            self.open(&format!("if ({}) {{", test.text));
+                            let guard = self.expr(guard)?;
+                            self.line(&format!("{} = {};", guarded, guard.text));
+                            self.close("}");
+                        }
+                        None => {
+                            let guard = self.expr(guard)?;
+                            self.line(&format!("bool {} = {};", guarded, guard.text));
+                        }
+                    }
+                    test = Some(Code::new(guarded, PRIMARY));
+                } else {
+                    let guard = self.expr(guard)?;
+                    test = Some(match test {
+                        Some(test) => {
+                            let text = format!("{} && {}", test.at(AND), guard.at(AND + 1));
+                            Code::new(text, AND)
+                        }
+                        None => guard,
+                    });
+                }
+            }
+
+            let Some(test) = test else {
+                // The arms after this one are never reached
+                if chained {
+                    self.reopen("} else {");
+                }
+                self.directive(arm.body.pos);
+                self.branch(&arm.body, target)?;
+                if chained {
+                    self.close("}");
+                }
+                return self.close_nested(nested);
+            };
+            match chained {
+                true => self.reopen(&format!("}} else if ({}) {{", test.text)),
+                false => self.open(&format!("if ({}) {{", test.text)),
+            }
+            chained = true;
+            self.directive(arm.body.pos);
+            self.branch(&arm.body, target)?;
+        }
+
+        if chained {
+            self.reopen("} else {");
+        }
+        let shown = match ty {
+            Type::Number => format!("zp_number_string({})", subject),
+            Type::Bool => format!("zp_bool_string({})", subject),
+            Type::String => format!("zp_quote({})", subject),
+            _ => "ZP_STRING(\"()\")".to_string(),
+        };
+        self.line(&format!(
+            "zp_no_match(__FILE__, {}, {});",
+            self.source_line(expr.pos),
+            shown
+        ));
+        if chained {
+            self.close("}");
+        }
+        self.close_nested(nested)
+    }
+
+    fn close_nested(&mut self, nested: usize) -> Result<()> {
+        for _ in 0..nested {
+            self.close("}");
+        }
+        Ok(())
+    }
+
+    fn always_matches(&self, pattern: &Pattern) -> bool {
+        match pattern.kind {
+            PatternKind::Wildcard => true,
+            PatternKind::Identifier(_) => !self.resolution.uses.contains_key(&pattern.pos),
+            _ => false,
+        }
+    }
+
+    // Whether the subject matches, or `None` for a pattern that always
+    // matches. A name the pattern binds is another name for the subject.
+    fn pattern(&mut self, pattern: &Pattern, subject: &str, ty: &Type) -> Result<Option<Code>> {
+        let resolution = self.resolution;
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(None),
+            PatternKind::Identifier(_) if resolution.uses.contains_key(&pattern.pos) => {
+                Err(unsupported("variants", pattern.pos))
+            }
+            PatternKind::Identifier(_) => {
+                let id = resolution.definitions[&pattern.pos];
+                self.names.insert(id, subject.to_string());
+                Ok(None)
+            }
+            PatternKind::Literal(literal) => Ok(Some(match (literal, ty) {
+                (LiteralExpr::Number(number), Type::Number) => {
+                    Code::new(format!("{} == {}.0", subject, number), EQUALITY)
+                }
+                (LiteralExpr::Bool(true), Type::Bool) => Code::new(subject, PRIMARY),
+                (LiteralExpr::Bool(false), Type::Bool) => Code::new(format!("!{}", subject), UNARY),
+                (LiteralExpr::String(string), Type::String) => Code::new(
+                    format!(
+                        "zp_string_eq({}, ZP_STRING({}))",
+                        subject,
+                        string_literal(string)
+                    ),
+                    CALL,
+                ),
+                _ => return Err(unsupported("this pattern", pattern.pos)),
+            })),
+            PatternKind::Variant(..) => Err(unsupported("variants", pattern.pos)),
+        }
+    }
+
+    // The C expression for `expr`, after the statements it needs
+    fn expr(&mut self, expr: &Expr) -> Result<Code> {
+        let code = match &expr.kind {
+            ExprKind::Literal(LiteralExpr::Number(number)) => {
+                Code::new(format!("{}.0", number), PRIMARY)
+            }
+            ExprKind::Literal(LiteralExpr::Bool(bool)) => Code::new(bool.to_string(), PRIMARY),
+            ExprKind::Literal(LiteralExpr::String(string)) => {
+                Code::new(format!("ZP_STRING({})", string_literal(string)), CALL)
+            }
+            ExprKind::Identifier(identifier) => {
+                let Some(id) = self.resolution.uses.get(&expr.pos) else {
+                    return Err(Diagnostic::error(
+                        format!("`{}` is not defined", identifier.ident),
+                        expr.pos,
+                    ));
+                };
+                match self.local_name(*id) {
+                    Some(name) => Code::new(name, PRIMARY),
+                    None if self.resolution.binding(*id).kind == BindingKind::Host
+                        && identifier.ident != "print" =>
+                    {
+                        return Err(Diagnostic::error(
+                            format!("`{}` has no definition in C", identifier.ident),
+                            expr.pos,
+                        ))
+                    }
+                    None => return Err(unsupported("functions as values", expr.pos)),
+                }
+            }
+            ExprKind::Call(call) => self.call(call)?,
+            ExprKind::Binary(binary) => self.binary(binary)?,
+            ExprKind::Unary(unary) => {
+                let operand = self.expr(&unary.operand)?;
+                let mut text = operand.at(UNARY);
+                // Not `--x`
+                if text.starts_with('-') {
+                    text = format!("({})", text);
+                }
+                Code::new(format!("{}{}", unary.op.symbol(), text), UNARY)
+            }
+            ExprKind::Block(_) | ExprKind::If(_) | ExprKind::Match(_) => {
+                let ty = self.c_type_of(expr)?;
+                let temp = self.temp();
+                self.line(&format!("{} {};", ty, temp));
+                self.expr_into(expr, &Target::Assign(temp.clone()))?;
+                Code::new(temp, PRIMARY)
+            }
+            ExprKind::Assign(assign) => {
+                self.assign(assign)?;
+                Code::new("ZP_UNIT", PRIMARY)
+            }
+            ExprKind::List(_) | ExprKind::Index(_) => return Err(unsupported("lists", expr.pos)),
+            ExprKind::Record(_) | ExprKind::Field(_) => {
+                return Err(unsupported("records", expr.pos))
+            }
+            ExprKind::Lambda(_) => return Err(unsupported("lambdas", expr.pos)),
+            ExprKind::Element(_) => return Err(unsupported("elements", expr.pos)),
+        };
+        Ok(code)
+    }
+
+    // The operands in order. C leaves the order of operands and arguments
+    // open, so those that must come before a later one with effects are
+    // kept in temporaries first.
+    fn operands(&mut self, exprs: &[&Expr]) -> Result<Vec<Code>> {
+        let mut codes = vec![];
+        for (i, expr) in exprs.iter().enumerate() {
+            let code = self.expr(expr)?;
+            if spilled(exprs, i) {
+                let temp = self.temp();
+                self.line(&format!(
+                    "{} {} = {};",
+                    self.c_type_of(expr)?,
+                    temp,
+                    code.text
+                ));
+                codes.push(Code::new(temp, PRIMARY));
+            } else {
+                codes.push(code);
+            }
+        }
+        Ok(codes)
+    }
+
+    fn call(&mut self, call: &CallExpr) -> Result<Code> {
+        let id = match &call.called.kind {
+            ExprKind::Identifier(_) => self.resolution.uses.get(&call.called.pos),
+            _ => None,
+        };
+        let function = match id {
+            Some(id) if self.functions.contains(id) => self.names[id].clone(),
+            Some(id)
+                if self.resolution.binding(*id).kind == BindingKind::Host
+                    && self.resolution.binding(*id).name == "print"
+                    && call.args.len() == 1 =>
+            {
+                let printed = match self.type_of(&call.args[0])? {
+                    Type::Number => "zp_print_number",
+                    Type::Bool => "zp_print_bool",
+                    Type::String => "zp_print_string",
+                    Type::Unit => "zp_print_unit",
+                    ty => {
+                        return Err(Diagnostic::error(
+                            format!("printing values of type `{}` cannot be compiled to C", ty),
+                            call.args[0].pos,
+                        ))
+                    }
+                };
+                printed.to_string()
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    "only calls of top-level functions and `print` can be compiled to C",
+                    call.called.pos,
+                ))
+            }
+        };
+        let args: Vec<&Expr> = call.args.iter().collect();
+        let args: Vec<String> = self
+            .operands(&args)?
+            .into_iter()
+            .map(|arg| arg.text)
+            .collect();
+        Ok(Code::new(
+            format!("{}({})", function, args.join(", ")),
+            CALL,
+        ))
+    }
+
+    fn binary(&mut self, binary: &BinaryExpr) -> Result<Code> {
+        let ty = self.type_of(&binary.lhs)?;
+        if let BinaryOp::And | BinaryOp::Or = binary.op {
+            return self.logical(binary);
+        }
+        // Values of type `Unit` are all equal, and only their effects count
+        if let (BinaryOp::Eq | BinaryOp::Ne, Type::Unit) = (binary.op, ty) {
+            for operand in [&binary.lhs, &binary.rhs] {
+                self.expr_into(operand, &Target::Discard)?;
+            }
+            return Ok(Code::new((binary.op == BinaryOp::Eq).to_string(), PRIMARY));
+        }
+
+        let operands = self.operands(&[&binary.lhs, &binary.rhs])?;
+        let [lhs, rhs] = &operands[..] else {
+            unreachable!("two operands")
+        };
+        let line = self.source_line(binary.rhs.pos);
+        let code = match (binary.op, ty) {
+            (BinaryOp::Add, Type::String) => {
+                Code::new(format!("zp_concat({}, {})", lhs.text, rhs.text), CALL)
+            }
+            (BinaryOp::Eq, Type::String) => {
+                Code::new(format!("zp_string_eq({}, {})", lhs.text, rhs.text), CALL)
+            }
+            (BinaryOp::Ne, Type::String) => {
+                Code::new(format!("!zp_string_eq({}, {})", lhs.text, rhs.text), UNARY)
+            }
+            (BinaryOp::Div, _) => Code::new(
+                format!("zp_div({}, {}, __FILE__, {})", lhs.text, rhs.text, line),
+                CALL,
+            ),
+            (BinaryOp::Rem, _) => Code::new(
+                format!("zp_rem({}, {}, __FILE__, {})", lhs.text, rhs.text, line),
+                CALL,
+            ),
+            (op, _) => {
+                let precedence = match op {
+                    BinaryOp::Eq | BinaryOp::Ne => EQUALITY,
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => RELATIONAL,
+                    BinaryOp::Add | BinaryOp::Sub => ADDITIVE,
+                    _ => MULTIPLICATIVE,
+                };
+                let text = format!(
+                    "{} {} {}",
+                    lhs.at(precedence),
+                    op.symbol(),
+                    rhs.at(precedence + 1)
+                );
+                Code::new(text, precedence)
+            }
+        };
+        Ok(code)
+    }
+
+    // Whether the code for an expression needs statements before it, which
+    // is where it cannot be the condition of an `else if`
+    fn emits_statements(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Identifier(_) => false,
+            ExprKind::Block(_) | ExprKind::If(_) | ExprKind::Match(_) | ExprKind::Assign(_) => true,
+            ExprKind::Unary(unary) => self.emits_statements(&unary.operand),
+            ExprKind::Binary(binary) => match binary.op {
+                BinaryOp::And | BinaryOp::Or => {
+                    self.emits_statements(&binary.lhs) || self.emits_statements(&binary.rhs)
+                }
+                BinaryOp::Eq | BinaryOp::Ne
+                    if self.type_of(&binary.lhs).is_ok_and(|ty| *ty == Type::Unit) =>
+                {
+                    !is_pure(&binary.lhs) || !is_pure(&binary.rhs)
+                }
+                _ => self.operands_emit_statements(&[&binary.lhs, &binary.rhs]),
+            },
+            ExprKind::Call(call) => {
+                let args: Vec<&Expr> = call.args.iter().collect();
+                self.operands_emit_statements(&args)
+            }
+            _ => false,
+        }
+    }
+
+    fn operands_emit_statements(&self, exprs: &[&Expr]) -> bool {
+        (0..exprs.len()).any(|i| spilled(exprs, i) || self.emits_statements(exprs[i]))
+    }
+
+    // `&&` and `||`, which only evaluate their right side when needed. One
+    // that needs statements is evaluated in an `if`.
+    fn logical(&mut self, binary: &BinaryExpr) -> Result<Code> {
+        let precedence = match binary.op {
+            BinaryOp::And => AND,
+            _ => OR,
+        };
+        let lhs = self.expr(&binary.lhs)?;
+        if !self.emits_statements(&binary.rhs) {
+            let rhs = self.expr(&binary.rhs)?;
+            let text = format!(
+                "{} {} {}",
+                lhs.at(precedence),
+                binary.op.symbol(),
+                rhs.at(precedence + 1)
+            );
+            return Ok(Code::new(text, precedence));
+        }
+        let temp = self.temp();
+        self.line(&format!("bool {} = {};", temp, lhs.text));
+        match binary.op {
+            BinaryOp::And => self.open(&format!("if ({}) {{", temp)),
+            _ => self.open(&format!("if (!{}) {{", temp)),
+        }
+        let rhs = self.expr(&binary.rhs)?;
+        self.line(&format!("{} = {};", temp, rhs.text));
+        self.close("}");
+        Ok(Code::new(temp, PRIMARY))
+    }
+}
+
+// The C type of values of type `ty`
+fn c_type(ty: &Type, pos: Position) -> Result<&'static str> {
+    match ty {
+        Type::Number => Ok("double"),
+        Type::Bool => Ok("bool"),
+        Type::String => Ok("zp_string"),
+        Type::Unit | Type::Var(_) => Ok("zp_unit"),
+        Type::Fn(..) => Err(unsupported("functions as values", pos)),
+        ty => Err(Diagnostic::error(
+            format!("values of type `{}` cannot be compiled to C", ty),
+            pos,
+        )),
+    }
+}
+
+fn unsupported(what: &str, pos: Position) -> Diagnostic {
+    Diagnostic::error(format!("{} cannot be compiled to C", what), pos)
+}
+
+// A C string literal of the UTF-8 bytes of `text`. Bytes outside of
+// printable ASCII are octal escapes of three digits, which a digit after
+// them cannot extend, and `?` is escaped so as not to start a trigraph.
+fn string_literal(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            0x20..=0x7E => literal.push(byte as char),
+            byte => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+// Whether an expression has no effects and cannot fail, so that it can be
+// left out when its value is not needed, or evaluated after others
+fn is_pure(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(_) | ExprKind::Identifier(_) => true,
+        ExprKind::Unary(unary) => is_pure(&unary.operand),
+        ExprKind::Binary(binary) => {
+            !matches!(binary.op, BinaryOp::Div | BinaryOp::Rem)
+                && is_pure(&binary.lhs)
+                && is_pure(&binary.rhs)
+        }
+        _ => false,
+    }
+}
+
+// Whether operand `i` of `exprs` is kept in a temporary: it is not a
+// literal, and an operand after it may have effects, like assigning the
+// binding it reads
+fn spilled(exprs: &[&Expr], i: usize) -> bool {
+    !matches!(exprs[i].kind, ExprKind::Literal(_))
+        && exprs[i + 1..].iter().any(|expr| !is_pure(expr))
+}
@@ -0,0 +1,200 @@
+mod codegen;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::Program;
+use crate::diagnostic::Diagnostic;
+use crate::resolve::{BindingId, BindingKind, Resolution, ScopeKind};
+use crate::types::Typing;
+
+use self::codegen::Generator;
+
+/// The name the generated code includes the runtime by.
+pub const RUNTIME_HEADER: &str = "zope.h";
+
+/// The runtime: strings, `print`, and the errors that stop a program.
+pub const RUNTIME: &str = include_str!("zope.h");
+
+// Keywords of C99, and names the headers of the runtime define that a local
+// would hide or clash with
+const RESERVED: &[&str] = &[
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "bool",
+    "true",
+    "false",
+    "errno",
+    "stdin",
+    "stdout",
+    "stderr",
+    "BUFSIZ",
+    "EOF",
+    "EXIT_FAILURE",
+    "EXIT_SUCCESS",
+    "FILENAME_MAX",
+    "FOPEN_MAX",
+    "HUGE_VAL",
+    "INFINITY",
+    "L_tmpnam",
+    "MB_CUR_MAX",
+    "NAN",
+    "NULL",
+    "RAND_MAX",
+    "SEEK_CUR",
+    "SEEK_END",
+    "SEEK_SET",
+    "TMP_MAX",
+];
+
+/// Whether a local binding cannot have `name` in C. Names with a leading
+/// underscore are the compiler's, and those starting with `zp_` or `ZP_` the
+/// runtime's and the generated code's.
+pub fn is_reserved(name: &str) -> bool {
+    RESERVED.contains(&name)
+        || name.starts_with('_')
+        || name.starts_with("zp_")
+        || name.starts_with("ZP_")
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct COptions {
+    // The source, as `#line` directives and runtime errors name it
+    pub source_name: String,
+}
+
+/// Compiles a program without resolution or type errors to a C99 file,
+/// which includes the `RUNTIME` as `RUNTIME_HEADER`. The `source` is the
+/// text it was parsed from, which spans point into.
+///
+/// Numbers are `double`, bools `bool`, strings the runtime's `zp_string`
+/// and `Unit` its `zp_unit`. Top-level functions become C functions, and
+/// the other top-level statements the body of `main`, with the top-level
+/// bindings as globals. Each statement is marked with a `#line` directive
+/// for its line in the source, and the runtime errors of `/`, `%` and
+/// `match` name that line too. `print` is the one host function.
+///
+/// The errors are for programs that use anything else, like lists,
+/// records, variants, lambdas, or generic functions.
+pub fn compile_program(
+    source: &str,
+    program: &Program,
+    resolution: &Resolution,
+    typing: &Typing,
+    options: &COptions,
+) -> Result<String, Vec<Diagnostic>> {
+    let mut generator = Generator::new(source, resolution, typing, options);
+    generator.program(program)?;
+    Ok(generator.finish())
+}
+
+/// The name of every binding in the generated code. Characters C does not
+/// allow in names are spelled as `_u` and their code point in hex.
+/// Top-level bindings are globals, with `z_` in front so that they do not
+/// clash with the C library. Other bindings keep their names, with `_`
+/// after a reserved one, or `v` before one with a leading underscore. A
+/// binding whose name is already taken in its scope gets `_` and a count
+/// after it, as C does not allow redeclaring a name in a scope, nor using
+/// the outer binding in the initializer of an inner one. Host bindings keep
+/// their names, as the runtime defines them.
+pub fn binding_names(resolution: &Resolution) -> HashMap<BindingId, String> {
+    let mut names: HashMap<BindingId, String> = HashMap::new();
+    let global = |id: BindingId| {
+        let scope = resolution.binding(id).scope;
+        resolution.scope(scope).kind == ScopeKind::Program
+    };
+
+    // Globals first, as every function can see them
+    let mut globals = HashSet::new();
+    for binding in resolution.bindings.iter() {
+        if binding.kind == BindingKind::Host {
+            names.insert(binding.id, binding.name.clone());
+        } else if global(binding.id) {
+            let name = unique(format!("z_{}", identifier(&binding.name)), &globals);
+            globals.insert(name.clone());
+            names.insert(binding.id, name);
+        }
+    }
+
+    for binding in resolution.bindings.iter() {
+        if names.contains_key(&binding.id) {
+            continue;
+        }
+        // The names of the earlier bindings this one is nested in
+        let mut taken = globals.clone();
+        let mut next = Some(binding.scope);
+        while let Some(scope) = next {
+            let scope = resolution.scope(scope);
+            for id in scope.bindings.iter() {
+                if let Some(name) = names.get(id).filter(|_| *id < binding.id) {
+                    taken.insert(name.clone());
+                }
+            }
+            next = scope.parent;
+        }
+        let name = identifier(&binding.name);
+        let name = match is_reserved(&name) {
+            true if name.starts_with('_') => format!("v{}", name),
+            true => format!("{}_", name),
+            false => name,
+        };
+        names.insert(binding.id, unique(name, &taken));
+    }
+    names
+}
+
+// `name` with only ASCII letters, digits and underscores
+fn identifier(name: &str) -> String {
+    let mut identifier = String::new();
+    for char in name.chars() {
+        match char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => identifier.push(char),
+            char => identifier.push_str(&format!("_u{:x}", char as u32)),
+        }
+    }
+    identifier
+}
+
+// `name`, or `name` with the first count after it that is not taken
+fn unique(name: String, taken: &HashSet<String>) -> String {
+    if !taken.contains(&name) {
+        return name;
+    }
+    (1..)
+        .map(|count| format!("{}_{}", name, count))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
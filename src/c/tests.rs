@@ -0,0 +1,243 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::rc::Rc;
+
+use super::*;
+use crate::ast::Parser;
+use crate::engine::Native;
+use crate::interp::{Function, Interpreter, Value};
+use crate::resolve::resolve_program_with;
+use crate::types::check_program;
+
+// Names the programs call without defining
+const HOST: &[&str] = &["print"];
+
+fn compile(name: &str, source: &str) -> Result<String, Vec<String>> {
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, HOST);
+    assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+    let typing = check_program(&program, &resolution);
+    assert!(!typing.has_errors(), "{:?}", typing.diagnostics);
+    let options = COptions {
+        source_name: name.to_string(),
+    };
+    compile_program(source, &program, &resolution, &typing, &options).map_err(|diagnostics| {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    })
+}
+
+// What the interpreter prints running `source`, with the error it stops
+// at. `print` writes strings as they are and other values as displayed.
+fn interpret(source: &str) -> (String, Option<String>) {
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, HOST);
+    let output = Rc::new(RefCell::new(String::new()));
+    let printed = output.clone();
+    let print = Native::new("print", 1, move |_, args| {
+        let mut output = printed.borrow_mut();
+        match &args[0] {
+            Value::String(string) => output.push_str(string),
+            value => output.push_str(&value.to_string()),
+        }
+        output.push('\n');
+        Ok(Value::Unit)
+    });
+    let mut interpreter = Interpreter::new(&resolution);
+    interpreter.set_global("print", Value::Function(Rc::new(Function::Native(print))));
+    let error = interpreter
+        .run(&program)
+        .err()
+        .map(|error| error.kind.to_string());
+    let output = output.borrow().clone();
+    (output, error)
+}
+
+// Builds the code with the system compiler and runs it, returning what it
+// printed and the error it stopped with. `None` if there is no compiler.
+fn run(name: &str, code: &str) -> Option<(String, Option<String>)> {
+    if Command::new("cc").arg("--version").output().is_err() {
+        eprintln!("no `cc` to build {} with", name);
+        return None;
+    }
+    let dir = env::temp_dir().join(format!("zope-c-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(RUNTIME_HEADER), RUNTIME).unwrap();
+    fs::write(dir.join("main.c"), code).unwrap();
+    let built = Command::new("cc")
+        .current_dir(&dir)
+        .args(["-std=c99", "-pedantic-errors", "-Wall", "-Werror"])
+        .args(["-o", "main", "main.c", "-lm"])
+        .output()
+        .unwrap();
+    assert!(
+        built.status.success(),
+        "{} does not build:\n{}",
+        name,
+        String::from_utf8_lossy(&built.stderr)
+    );
+    let ran = Command::new(dir.join("main")).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stdout = String::from_utf8(ran.stdout).unwrap();
+    let stderr = String::from_utf8(ran.stderr).unwrap();
+    let error = match ran.status.success() {
+        true => None,
+        false => Some(stderr.trim_end().to_string()),
+    };
+    Some((stdout, error))
+}
+
+// The programs in `tests/golden/c`, each with the code it compiles to next
+// to it, and printing what the interpreter prints once built. Run with
+// `UPDATE_GOLDEN=1` to write them anew.
+#[test]
+fn compile_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/c");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".zp"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+
+    let mut changed = vec![];
+    for name in names.iter() {
+        let source = fs::read_to_string(dir.join(name)).unwrap();
+        let code = compile(name, &source).unwrap();
+        let stem = name.trim_end_matches(".zp");
+        if let Some(ran) = run(stem, &code) {
+            assert_eq!(ran, interpret(&source), "{}", name);
+        }
+
+        let file = format!("{}.c", stem);
+        let path = dir.join(&file);
+        if update {
+            fs::write(&path, &code).unwrap();
+        } else if fs::read_to_string(&path).ok().as_deref() != Some(code.as_str()) {
+            changed.push(file);
+        }
+    }
+    assert!(
+        changed.is_empty(),
+        "differ from their golden files: {:?}",
+        changed
+    );
+}
+
+// Runtime errors stop the program after what it printed so far, at the
+// line of the source they happen on
+#[test]
+fn stop_at_runtime_errors() {
+    let programs = [
+        (
+            "divide",
+            "print(1);\nlet zero = 0;\nprint(1 / zero);\nprint(2);",
+            3,
+        ),
+        (
+            "remainder",
+            "fn f(n) {\n    n % (n - 1)\n}\nprint(f(3));\nprint(f(1));",
+            2,
+        ),
+    ];
+    for (name, source, line) in programs {
+        let code = compile("errors.zp", source).unwrap();
+        let Some((output, error)) = run(name, &code) else {
+            return;
+        };
+        let (expected_output, expected_error) = interpret(source);
+        assert_eq!(output, expected_output, "{}", name);
+        let expected_error = format!("errors.zp:{}: {}", line, expected_error.unwrap());
+        assert_eq!(error, Some(expected_error), "{}", name);
+    }
+}
+
+#[test]
+fn mark_lines_of_the_source() {
+    let source = "fn f(n) {\n    let m = n * 2;\n\n    m + 1\n}\n\nprint(f(1));\nprint(f(2));";
+    let code = compile("lines.zp", source).unwrap();
+    assert_eq!(
+        code,
+        "#include \"zope.h\"\n\nstatic double z_f(double n);\n\n\
+         #line 1 \"lines.zp\"\n\
+         static double z_f(double n) {\n    double m = n * 2.0;\n\
+         #line 4 \"lines.zp\"\n    return m + 1.0;\n}\n\n\
+         int main(void) {\n\
+         #line 7 \"lines.zp\"\n    zp_print_number(z_f(1.0));\n    zp_print_number(z_f(2.0));\n    \
+         return 0;\n}\n"
+    );
+}
+
+#[test]
+fn rename_reserved_and_shadowed_names() {
+    let source = "let int = 1; fn f(a) { let a = a; let zp_t = a; let _x = zp_t; _x } \
+                  let a = { let int = int; let é = int; é }; let a = a;";
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, HOST);
+    let names = binding_names(&resolution);
+    let mut renamed: Vec<&str> = resolution
+        .bindings
+        .iter()
+        .map(|binding| names[&binding.id].as_str())
+        .collect();
+    renamed.sort();
+    assert_eq!(
+        renamed,
+        vec!["a", "a_1", "int_", "print", "v_ue9", "v_x", "z_a", "z_a_1", "z_f", "z_int", "zp_t_"]
+    );
+    assert!(is_reserved("double") && is_reserved("NULL") && is_reserved("zp_string"));
+    assert!(!is_reserved("print"));
+}
+
+#[test]
+fn escape_string_literals() {
+    let code = compile("s.zp", "print('a\"b\\\\c??=\n\u{e9}1');").unwrap();
+    assert!(
+        code.contains("zp_print_string(ZP_STRING(\"a\\\"b\\\\\\\\c\\?\\?=\\012\\303\\2511\"));"),
+        "{}",
+        code
+    );
+}
+
+#[test]
+fn reject_what_has_no_c_counterpart() {
+    let errors = |source| compile("e.zp", source).unwrap_err();
+    assert_eq!(
+        errors("let xs = [1, 2];"),
+        vec![
+            "values of type `List<Number>` cannot be compiled to C",
+            "lists cannot be compiled to C"
+        ]
+    );
+    assert_eq!(
+        errors("fn f(n) { let g = fn(x) => x + 1; g(n) }"),
+        vec!["functions as values cannot be compiled to C"]
+    );
+    assert_eq!(
+        errors("fn d(n) { n * 2 }\nlet g = d;"),
+        vec!["functions as values cannot be compiled to C"; 2]
+    );
+    assert_eq!(
+        errors("fn id(x) { x }"),
+        vec!["`id` is generic, with the type `fn('a) -> 'a`, which C functions cannot be"]
+    );
+    assert_eq!(
+        errors("type T = A | B;\nlet t = A;"),
+        vec![
+            "values of type `T` cannot be compiled to C",
+            "types cannot be compiled to C"
+        ]
+    );
+    // Each statement is reported
+    assert_eq!(
+        errors("let a = [1];\nprint(1);\nlet b = { x: 1 };").len(),
+        4
+    );
+}
@@ -0,0 +1,174 @@
+/* The runtime of C compiled from zope: strings, printing and the errors
+ * that stop a program. Strings are never freed, as the programs are short
+ * lived. */
+#ifndef ZOPE_H
+#define ZOPE_H
+
+#include <math.h>
+#include <stdbool.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#if defined(__GNUC__)
+#define ZP_NORETURN __attribute__((noreturn))
+#else
+#define ZP_NORETURN
+#endif
+
+/* The value of type `Unit` */
+typedef int zp_unit;
+#define ZP_UNIT 0
+
+/* UTF-8 bytes, which are not changed once made */
+typedef struct {
+    const char *data;
+    size_t len;
+} zp_string;
+
+#define ZP_STRING(literal) ((zp_string){literal, sizeof(literal) - 1})
+
+static inline void *zp_alloc(size_t size) {
+    void *memory = malloc(size > 0 ? size : 1);
+    if (memory == NULL) {
+        fputs("out of memory\n", stderr);
+        exit(1);
+    }
+    return memory;
+}
+
+/* Stops the program with an error at a position of the zope source, which
+ * `#line` directives make `__FILE__` and `__LINE__` refer to */
+static inline ZP_NORETURN void zp_panic(const char *file, int line, zp_string message) {
+    fprintf(stderr, "%s:%d: %.*s\n", file, line, (int)message.len, message.data);
+    exit(1);
+}
+
+static inline zp_string zp_concat(zp_string a, zp_string b) {
+    char *data = zp_alloc(a.len + b.len);
+    memcpy(data, a.data, a.len);
+    memcpy(data + a.len, b.data, b.len);
+    return (zp_string){data, a.len + b.len};
+}
+
+static inline bool zp_string_eq(zp_string a, zp_string b) {
+    return a.len == b.len && memcmp(a.data, b.data, a.len) == 0;
+}
+
+static inline double zp_div(double a, double b, const char *file, int line) {
+    if (b == 0) {
+        zp_panic(file, line, ZP_STRING("division by zero"));
+    }
+    return a / b;
+}
+
+/* The remainder has the sign of the dividend */
+static inline double zp_rem(double a, double b, const char *file, int line) {
+    if (b == 0) {
+        zp_panic(file, line, ZP_STRING("division by zero"));
+    }
+    return fmod(a, b);
+}
+
+/* A number as the interpreter displays it: the fewest digits that read back
+ * as the same number, spelled out without an exponent */
+static inline zp_string zp_number_string(double number) {
+    if (isnan(number)) {
+        return ZP_STRING("NaN");
+    }
+    if (isinf(number)) {
+        return number > 0 ? ZP_STRING("inf") : ZP_STRING("-inf");
+    }
+    char scientific[32];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(scientific, sizeof scientific, "%.*e", precision, number);
+        if (strtod(scientific, NULL) == number) {
+            break;
+        }
+    }
+
+    /* `scientific` is like `-1.2345e+02` */
+    const char *at = scientific;
+    bool negative = *at == '-';
+    if (negative) {
+        at++;
+    }
+    char digits[20];
+    int count = 0;
+    for (; *at != 'e'; at++) {
+        if (*at != '.') {
+            digits[count++] = *at;
+        }
+    }
+    int exponent = atoi(at + 1);
+
+    /* At most 17 digits and 308 zeros on either side of the point */
+    char *data = zp_alloc(count + 330);
+    size_t len = 0;
+    if (negative) {
+        data[len++] = '-';
+    }
+    if (exponent < 0) {
+        data[len++] = '0';
+        data[len++] = '.';
+        for (int i = 0; i < -exponent - 1; i++) {
+            data[len++] = '0';
+        }
+        for (int i = 0; i < count; i++) {
+            data[len++] = digits[i];
+        }
+    } else {
+        for (int i = 0; i < count || i <= exponent; i++) {
+            if (i == exponent + 1) {
+                data[len++] = '.';
+            }
+            data[len++] = i < count ? digits[i] : '0';
+        }
+    }
+    return (zp_string){data, len};
+}
+
+static inline zp_string zp_bool_string(bool value) {
+    return value ? ZP_STRING("true") : ZP_STRING("false");
+}
+
+/* A string as a literal that reads back as it, in the quotes it does not
+ * hold */
+static inline zp_string zp_quote(zp_string string) {
+    char quote = memchr(string.data, '\'', string.len) != NULL ? '"' : '\'';
+    char *data = zp_alloc(string.len + 2);
+    data[0] = quote;
+    memcpy(data + 1, string.data, string.len);
+    data[string.len + 1] = quote;
+    return (zp_string){data, string.len + 2};
+}
+
+/* Stops the program at a `match` none of whose arms matched `value`,
+ * displayed as a literal */
+static inline ZP_NORETURN void zp_no_match(const char *file, int line, zp_string value) {
+    zp_string message = zp_concat(ZP_STRING("no arm matches `"), value);
+    zp_panic(file, line, zp_concat(message, ZP_STRING("`")));
+}
+
+/* `print`, which writes strings as they are and other values as the
+ * interpreter displays them, each on a line of its own */
+static inline zp_unit zp_print_string(zp_string string) {
+    fwrite(string.data, 1, string.len, stdout);
+    fputc('\n', stdout);
+    return ZP_UNIT;
+}
+
+static inline zp_unit zp_print_number(double number) {
+    return zp_print_string(zp_number_string(number));
+}
+
+static inline zp_unit zp_print_bool(bool value) {
+    return zp_print_string(zp_bool_string(value));
+}
+
+static inline zp_unit zp_print_unit(zp_unit unit) {
+    (void)unit;
+    return zp_print_string(ZP_STRING("()"));
+}
+
+#endif
@@ -0,0 +1,18 @@
+use std::fs;
+
+/// The sample programs in `tests/corpus`, as `(file name, source)` pairs
+/// sorted by file name.
+pub fn sources() -> Vec<(String, String)> {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+    let mut sources: Vec<(String, String)> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read_to_string(&path).unwrap())
+        })
+        .filter(|(name, _)| name.ends_with(".zp"))
+        .collect();
+    sources.sort();
+    sources
+}
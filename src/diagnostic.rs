@@ -0,0 +1,78 @@
+use std::fmt;
+
+use crate::lexer::Position;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A secondary span that explains a diagnostic, like the earlier binding
+/// a new one shadows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub message: String,
+    pub pos: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub pos: Position,
+    pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>, pos: Position) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            pos,
+            labels: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>, pos: Position) -> Self {
+        Self::new(Severity::Error, message, pos)
+    }
+
+    pub fn warning(message: impl Into<String>, pos: Position) -> Self {
+        Self::new(Severity::Warning, message, pos)
+    }
+
+    pub fn with_label(mut self, message: impl Into<String>, pos: Position) -> Self {
+        self.labels.push(Label {
+            message: message.into(),
+            pos,
+        });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}: {} at {}..{}",
+            severity, self.message, self.pos.start, self.pos.end
+        )?;
+        for label in self.labels.iter() {
+            write!(
+                f,
+                "\n  {} at {}..{}",
+                label.message, label.pos.start, label.pos.end
+            )?;
+        }
+        Ok(())
+    }
+}
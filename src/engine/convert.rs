@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::interp::Value;
+
+/// A Rust type that zope values convert into, like the arguments of a native
+/// function or the result of `Engine::eval_as`.
+pub trait FromValue: Sized {
+    /// The zope type of the values that convert, for errors.
+    fn expected() -> String;
+
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+/// A Rust type that converts into a zope value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    fn expected() -> String {
+        "a value".to_string()
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for f64 {
+    fn expected() -> String {
+        "Number".to_string()
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+// Only whole numbers in range convert to integers. The bound above is
+// exclusive, as the largest integers round up to it as `f64`s.
+macro_rules! integer {
+    (signed $($ty:ty),*) => {$(
+        integer!(@impl $ty, -(<$ty>::MIN as f64));
+    )*};
+    (unsigned $($ty:ty),*) => {$(
+        integer!(@impl $ty, 2f64.powi(<$ty>::BITS as i32));
+    )*};
+    (@impl $ty:ty, $end:expr) => {
+        impl FromValue for $ty {
+            fn expected() -> String {
+                format!("whole Number in range of {}", stringify!($ty))
+            }
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::Number(number)
+                        if number.fract() == 0.0
+                            && number >= <$ty>::MIN as f64
+                            && number < $end =>
+                    {
+                        Some(number as $ty)
+                    }
+                    _ => None,
+                }
+            }
+        }
+
+        impl IntoValue for $ty {
+            fn into_value(self) -> Value {
+                Value::Number(self as f64)
+            }
+        }
+    };
+}
+
+integer!(signed i32, i64);
+integer!(unsigned u32, usize);
+
+impl FromValue for bool {
+    fn expected() -> String {
+        "Bool".to_string()
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Bool(bool) => Some(bool),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromValue for String {
+    fn expected() -> String {
+        "String".to_string()
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(string) => Some(string.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::string(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::string(self)
+    }
+}
+
+impl FromValue for () {
+    fn expected() -> String {
+        "Unit".to_string()
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Unit => Some(()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Unit
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn expected() -> String {
+        format!("List<{}>", T::expected())
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::List(items) => {
+                let items = Rc::try_unwrap(items).unwrap_or_else(|items| (*items).clone());
+                items.into_iter().map(T::from_value).collect()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::list(self.into_iter().map(T::into_value).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    fn expected() -> String {
+        "Record".to_string()
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Record(fields) => {
+                let fields = Rc::try_unwrap(fields).unwrap_or_else(|fields| (*fields).clone());
+                fields
+                    .into_iter()
+                    .map(|(name, value)| Some((name, T::from_value(value)?)))
+                    .collect()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for BTreeMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::record(
+            self.into_iter()
+                .map(|(name, value)| (name, value.into_value()))
+                .collect(),
+        )
+    }
+}
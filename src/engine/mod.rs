@@ -0,0 +1,269 @@
+pub mod convert;
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Parser;
+use crate::diagnostic::Diagnostic;
+use crate::interp::{Function, Interpreter, Limits, RuntimeError, RuntimeErrorKind, Value};
+use crate::lexer::Position;
+use crate::resolve::resolve_program_with;
+use crate::vm::Vm;
+
+pub use self::convert::*;
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+type NativeFn = dyn Fn(&mut Context, Vec<Value>) -> Result<Value>;
+
+/// A function of the embedding application, called like any zope function.
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    function: Rc<NativeFn>,
+}
+
+impl Native {
+    pub fn new(
+        name: impl Into<String>,
+        arity: usize,
+        function: impl Fn(&mut Context, Vec<Value>) -> Result<Value> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arity,
+            function: Rc::new(function),
+        }
+    }
+
+    // The interpreter and the VM check the arity before
+    pub(crate) fn call(
+        &self,
+        caller: &mut dyn Caller,
+        args: Vec<Value>,
+        pos: Position,
+    ) -> Result<Value> {
+        (self.function)(&mut Context { caller, pos }, args)
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+// What runs the program a native function is called from
+pub(crate) trait Caller {
+    fn call(&mut self, function: &Value, args: Vec<Value>, pos: Position) -> Result<Value>;
+}
+
+impl Caller for Interpreter<'_> {
+    fn call(&mut self, function: &Value, args: Vec<Value>, pos: Position) -> Result<Value> {
+        Interpreter::call(self, function, args, pos)
+    }
+}
+
+impl Caller for Vm<'_> {
+    fn call(&mut self, function: &Value, args: Vec<Value>, pos: Position) -> Result<Value> {
+        Vm::call(self, function, args, pos)
+    }
+}
+
+/// What a native function sees of the call it is running for.
+pub struct Context<'a> {
+    caller: &'a mut dyn Caller,
+    pos: Position,
+}
+
+impl Context<'_> {
+    /// The span of the call expression.
+    pub fn pos(&self) -> Position {
+        self.pos
+    }
+
+    /// Calls a zope function, like one passed as an argument.
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value> {
+        self.caller.call(function, args, self.pos)
+    }
+
+    /// An error at the call with a message of the application.
+    pub fn error(&self, message: impl Into<String>) -> RuntimeError {
+        RuntimeError::new(RuntimeErrorKind::Native(message.into()), self.pos)
+    }
+
+    /// Converts an argument, failing at the call if it has another type.
+    pub fn arg<T: FromValue>(&self, value: Value) -> Result<T> {
+        let found = value.type_name().to_string();
+        T::from_value(value).ok_or_else(|| {
+            let kind = RuntimeErrorKind::Mismatch {
+                expected: T::expected(),
+                found,
+            };
+            RuntimeError::new(kind, self.pos)
+        })
+    }
+}
+
+/// A Rust closure that can be registered as a native function, with the
+/// tuple of its argument types.
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str) -> Native;
+}
+
+macro_rules! into_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoValue,
+            $($arg: FromValue),*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> Native {
+                let arity = <[&str]>::len(&[$(stringify!($arg)),*]);
+                Native::new(name, arity, move |context, args| {
+                    let mut args = args.into_iter();
+                    $(let $arg = context.arg::<$arg>(args.next().unwrap())?;)*
+                    Ok(self($($arg),*).into_value())
+                })
+            }
+        }
+    };
+}
+
+into_native!();
+into_native!(A);
+into_native!(A, B);
+into_native!(A, B, C);
+into_native!(A, B, C, D);
+
+/// Why `Engine::eval` did not return a value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    // Where parsing stopped
+    Parse(Position),
+    // The errors of the resolver
+    Resolve(Vec<Diagnostic>),
+    Runtime(RuntimeError),
+    // The result did not convert to the Rust type
+    Convert { expected: String, found: String },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Parse(pos) => write!(f, "cannot parse at offset {}", pos.start),
+            EvalError::Resolve(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            EvalError::Runtime(error) => write!(f, "{}", error),
+            EvalError::Convert { expected, found } => {
+                write!(
+                    f,
+                    "expected the result to be `{}`, found `{}`",
+                    expected, found
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl From<RuntimeError> for EvalError {
+    fn from(error: RuntimeError) -> Self {
+        EvalError::Runtime(error)
+    }
+}
+
+/// Evaluates zope source from Rust. Values and native functions defined on
+/// the engine are globals of every program it evaluates, visible to the
+/// resolver like the program's own top-level bindings.
+#[derive(Default)]
+pub struct Engine {
+    globals: Vec<(String, Value)>,
+    limits: Limits,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bounds each later `eval`, which starts with nothing used.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Defines a global. Defining a name again replaces its value.
+    pub fn set(&mut self, name: &str, value: impl IntoValue) {
+        let value = value.into_value();
+        match self.globals.iter_mut().find(|(global, _)| global == name) {
+            Some((_, old)) => *old = value,
+            None => self.globals.push((name.to_string(), value)),
+        }
+    }
+
+    /// Defines a native function that gets its arguments as zope values.
+    pub fn register(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Context, Vec<Value>) -> Result<Value> + 'static,
+    ) {
+        let native = Native::new(name, arity, function);
+        self.set(name, Value::Function(Rc::new(Function::Native(native))));
+    }
+
+    /// Defines a native function from a Rust closure, converting its
+    /// arguments and result.
+    pub fn register_fn<Args>(&mut self, name: &str, function: impl IntoNative<Args>) {
+        let native = function.into_native(name);
+        self.set(name, Value::Function(Rc::new(Function::Native(native))));
+    }
+
+    /// Parses, resolves and runs `source`, returning the value of its last
+    /// statement.
+    pub fn eval(&self, source: &str) -> std::result::Result<Value, EvalError> {
+        let mut parser = Parser::new(source);
+        let Some(program) = parser.parse_program() else {
+            let offset = parser.lexer.position();
+            return Err(EvalError::Parse(Position::new(offset, offset)));
+        };
+
+        let names: Vec<&str> = self.globals.iter().map(|(name, _)| name.as_str()).collect();
+        let resolution = resolve_program_with(&program, &names);
+        if resolution.has_errors() {
+            let errors = resolution.diagnostics.iter().filter(|d| d.is_error());
+            return Err(EvalError::Resolve(errors.cloned().collect()));
+        }
+
+        let mut interpreter = Interpreter::with_limits(&resolution, self.limits.clone());
+        for (name, value) in self.globals.iter() {
+            interpreter.set_global(name, value.clone());
+        }
+        Ok(interpreter.run(&program)?)
+    }
+
+    /// Evaluates `source` and converts the result.
+    pub fn eval_as<T: FromValue>(&self, source: &str) -> std::result::Result<T, EvalError> {
+        let value = self.eval(source)?;
+        let found = value.type_name().to_string();
+        T::from_value(value).ok_or_else(|| EvalError::Convert {
+            expected: T::expected(),
+            found,
+        })
+    }
+}
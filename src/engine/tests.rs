@@ -0,0 +1,194 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use super::*;
+use crate::interp::CancelHandle;
+use crate::resolve::resolve_program;
+use crate::vm::{compile_program, Vm};
+
+#[test]
+fn eval_with_host_values() {
+    let mut engine = Engine::new();
+    engine.set("answer", 42);
+    engine.set("name", "zope");
+    assert_eq!(engine.eval("answer + 1"), Ok(Value::Number(43.0)));
+    assert_eq!(
+        engine.eval_as::<String>("'hi ' + name"),
+        Ok("hi zope".into())
+    );
+
+    // The program's own bindings shadow the host's
+    engine.set("answer", 1);
+    assert_eq!(engine.eval_as::<i64>("let answer = 2;\nanswer"), Ok(2));
+    assert_eq!(engine.eval_as::<i64>("answer"), Ok(1));
+    assert_eq!(
+        engine.eval_as::<Vec<bool>>("[true, 1 > 2]"),
+        Ok(vec![true, false])
+    );
+}
+
+#[test]
+fn call_native_functions() {
+    let mut engine = Engine::new();
+    engine.register_fn("hypot", |a: f64, b: f64| (a * a + b * b).sqrt());
+    engine.register_fn("repeat", |text: String, times: usize| text.repeat(times));
+    engine.register_fn("keys", |record: BTreeMap<String, Value>| {
+        record.into_keys().collect::<Vec<_>>()
+    });
+    assert_eq!(engine.eval_as::<f64>("hypot(3, 4)"), Ok(5.0));
+    assert_eq!(
+        engine.eval("[repeat('ab', 2), hypot]").unwrap().to_string(),
+        "['abab', <fn hypot>]"
+    );
+    assert_eq!(
+        engine.eval_as::<Vec<String>>("keys({ b: 1, a: 2 })"),
+        Ok(vec!["a".into(), "b".into()])
+    );
+
+    // Natives can call zope functions back
+    engine.register("map", 2, |context, args| {
+        let [list, function] = <[Value; 2]>::try_from(args).unwrap();
+        let mut items = vec![];
+        for item in context.arg::<Vec<Value>>(list)? {
+            items.push(context.call(&function, vec![item])?);
+        }
+        Ok(items.into_value())
+    });
+    let input = "fn double(x) { x * 2 }\nmap([1, 2, 3], double)";
+    assert_eq!(engine.eval_as::<Vec<i32>>(input), Ok(vec![2, 4, 6]));
+}
+
+#[test]
+fn convert_integers_in_range() {
+    let number = |exponent: i32, offset: f64| Value::Number(2f64.powi(exponent) + offset);
+    assert_eq!(i32::from_value(number(31, -1.0)), Some(i32::MAX));
+    assert_eq!(i32::from_value(number(31, 0.0)), None);
+    assert_eq!(
+        i32::from_value(Value::Number(-2f64.powi(31))),
+        Some(i32::MIN)
+    );
+    assert_eq!(u32::from_value(number(32, -1.0)), Some(u32::MAX));
+    assert_eq!(u32::from_value(number(32, 0.0)), None);
+    assert_eq!(u32::from_value(Value::Number(-1.0)), None);
+    assert_eq!(i64::from_value(number(53, 0.0)), Some(1 << 53));
+    // `i64::MAX` and `u64::MAX` round up to these as `f64`s
+    assert_eq!(i64::from_value(number(63, 0.0)), None);
+    assert_eq!(
+        i64::from_value(Value::Number(-2f64.powi(63))),
+        Some(i64::MIN)
+    );
+    assert_eq!(usize::from_value(number(64, 0.0)), None);
+    assert_eq!(i32::from_value(Value::Number(0.5)), None);
+    assert_eq!(i32::from_value(Value::Number(f64::NAN)), None);
+
+    let mut engine = Engine::new();
+    engine.register_fn("repeat", |text: String, times: usize| text.repeat(times));
+    assert_eq!(
+        engine.eval("repeat('a', -1)").unwrap_err().to_string(),
+        "expected `whole Number in range of usize`, found `Number` at 0..15"
+    );
+}
+
+#[test]
+fn report_eval_errors() {
+    let mut engine = Engine::new();
+    engine.register("fail", 1, |context, args| {
+        Err(context.error(format!("failed with {}", args[0])))
+    });
+    engine.register_fn("half", |n: f64| n / 2.0);
+
+    assert_eq!(
+        engine.eval("let = 1"),
+        Err(EvalError::Parse(Position::new(0, 0)))
+    );
+    assert!(matches!(
+        engine.eval("99999999999"),
+        Err(EvalError::Parse(_))
+    ));
+    assert_eq!(
+        engine.eval("missing").unwrap_err().to_string(),
+        "error: undefined variable `missing` at 0..7"
+    );
+    let input = "1 + fail('x')";
+    assert_eq!(
+        engine.eval(input),
+        Err(EvalError::Runtime(RuntimeError::new(
+            RuntimeErrorKind::Native("failed with 'x'".into()),
+            Position::new(4, 13)
+        )))
+    );
+    assert_eq!(
+        engine.eval("half('x')").unwrap_err().to_string(),
+        "expected `Number`, found `String` at 0..9"
+    );
+    assert_eq!(
+        engine.eval("half(1, 2)").unwrap_err().to_string(),
+        "expected 1 arguments, found 2 at 0..10"
+    );
+    assert_eq!(
+        engine.eval_as::<Vec<f64>>("[half(3), 'x']"),
+        Err(EvalError::Convert {
+            expected: "List<Number>".into(),
+            found: "List".into()
+        })
+    );
+    let error = engine
+        .eval("fn f(n) { if n == 0 { 0 } else { f(n - 1) } }\nf(20000)")
+        .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            EvalError::Runtime(RuntimeError {
+                kind: RuntimeErrorKind::NativeStackExhausted(_),
+                ..
+            })
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn call_natives_from_the_vm() {
+    let input = "fn twice(f, x) { f(f(x)) }\nlog(twice(inc, 1))";
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, &["inc", "log"]);
+    assert!(!resolution.has_errors());
+    assert!(resolve_program(&program).has_errors());
+
+    let logged = Rc::new(RefCell::new(vec![]));
+    let log = {
+        let logged = logged.clone();
+        Native::new("log", 1, move |_, args| {
+            logged.borrow_mut().push(args[0].clone());
+            Ok(Value::Unit)
+        })
+    };
+    let inc = (|n: f64| n + 1.0).into_native("inc");
+
+    let bytecode = compile_program(&program, &resolution).unwrap();
+    let mut vm = Vm::new(&bytecode);
+    vm.set_global("log", Value::Function(Rc::new(Function::Native(log))));
+    vm.set_global("inc", Value::Function(Rc::new(Function::Native(inc))));
+    assert_eq!(vm.run(), Ok(Value::Unit));
+    assert_eq!(*logged.borrow(), vec![Value::Number(3.0)]);
+}
+
+#[test]
+fn cancel_from_a_native() {
+    let cancel = CancelHandle::new();
+    let mut engine = Engine::new();
+    engine.set_limits(Limits {
+        cancel: Some(cancel.clone()),
+        ..Limits::default()
+    });
+    engine.register_fn("stop", move || cancel.cancel());
+    let input = "fn count(n) { if n == 3 { stop() }; count(n + 1) }\ncount(0)";
+    let EvalError::Runtime(error) = engine.eval(input).unwrap_err() else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.kind, RuntimeErrorKind::Cancelled);
+    // At the first expression after the call
+    let start = input.find("count(n + 1)").unwrap() as u32;
+    assert_eq!(error.pos, Position::new(start, start + 12));
+}
@@ -0,0 +1,178 @@
+/// A document in the style of Wadler's "A prettier printer": text glued
+/// together with line breaks that a group either takes all of or none of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Doc {
+    Nil,
+    Text(String),
+    // A space, or a newline if the enclosing group breaks
+    Line,
+    // Nothing, or a newline if the enclosing group breaks
+    SoftLine,
+    // Always a newline
+    HardLine,
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub fn concat(docs: Vec<Doc>) -> Doc {
+        Doc::Concat(docs)
+    }
+
+    pub fn nest(self, indent: usize) -> Doc {
+        Doc::Nest(indent, Box::new(self))
+    }
+
+    pub fn group(self) -> Doc {
+        Doc::Group(Box::new(self))
+    }
+
+    /// Lays the document out, breaking the outermost groups first until
+    /// every line fits in `width` columns where possible.
+    pub fn render(&self, width: usize) -> String {
+        let mut output = String::new();
+        let mut column = 0;
+        // Indentation is written lazily so that blank lines stay empty
+        let mut pending_indent = None;
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Nil => {}
+                Doc::Text(text) => {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    if let Some(indent) = pending_indent.take() {
+                        output.extend(std::iter::repeat_n(' ', indent));
+                        column = indent;
+                    }
+                    output.push_str(text);
+                    column += text.chars().count();
+                }
+                Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                    if let Doc::Line = doc {
+                        output.push(' ');
+                        column += 1;
+                    }
+                }
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    output.push('\n');
+                    column = 0;
+                    pending_indent = Some(indent);
+                }
+                Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+                Doc::Group(doc) => {
+                    let column = pending_indent.unwrap_or(column);
+                    let mode = if fits(width as isize - column as isize, doc, &stack) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Concat(docs) => {
+                    for doc in docs.iter().rev() {
+                        stack.push((indent, mode, doc));
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+// Whether `doc` laid out flat, followed by the rest of the current line,
+// fits in `remaining` columns.
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut rest = rest.iter().rev();
+    let mut stack = vec![(Mode::Flat, doc)];
+
+    loop {
+        if remaining < 0 {
+            return false;
+        }
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Nil => {}
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::Nest(_, doc) => stack.push((mode, doc)),
+            Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => {
+                for doc in docs.iter().rev() {
+                    stack.push((mode, doc));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(args: Vec<&str>) -> Doc {
+        let mut inner = vec![Doc::SoftLine];
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                inner.push(Doc::text(","));
+                inner.push(Doc::Line);
+            }
+            inner.push(Doc::text(*arg));
+        }
+        Doc::concat(vec![
+            Doc::text("f("),
+            Doc::concat(inner).nest(4),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ])
+        .group()
+    }
+
+    #[test]
+    fn render_flat() {
+        assert_eq!(call(vec!["a", "b"]).render(80), "f(a, b)");
+    }
+
+    #[test]
+    fn render_broken() {
+        assert_eq!(
+            call(vec!["alpha", "beta"]).render(10),
+            "f(\n    alpha,\n    beta\n)"
+        );
+    }
+
+    #[test]
+    fn render_hard_line_keeps_blank_lines_empty() {
+        let doc = Doc::concat(vec![
+            Doc::text("a"),
+            Doc::HardLine,
+            Doc::HardLine,
+            Doc::text("b"),
+        ])
+        .nest(4);
+        assert_eq!(doc.render(80), "a\n\n    b");
+    }
+}
@@ -0,0 +1,648 @@
+pub mod doc;
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+
+pub use self::doc::*;
+use crate::ast::*;
+use crate::lexer::*;
+use crate::print::{print_pattern, print_type, quote, Precedence};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub width: usize,
+    pub indent: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatError {
+    // Where parsing stopped
+    pub pos: Position,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot parse source at offset {}", self.pos.start)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+pub fn format(source: &str) -> Result<String, FormatError> {
+    format_with(source, &Config::default())
+}
+
+pub fn format_with(source: &str, config: &Config) -> Result<String, FormatError> {
+    let mut parser = Parser::new(source);
+    let program = match parser.parse_program() {
+        Some(program) => program,
+        None => {
+            let offset = parser.lexer.position();
+            return Err(FormatError {
+                pos: Position::new(offset, offset),
+            });
+        }
+    };
+
+    let mut formatter = Formatter::new(source, &program, config);
+    let doc = formatter.program(&program);
+    let mut output = doc.render(config.width);
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Whether `source` is already formatted, i.e. formatting leaves it unchanged.
+pub fn check(source: &str) -> Result<bool, FormatError> {
+    Ok(format(source)? == source)
+}
+
+#[derive(Clone, Debug)]
+struct Comment {
+    text: String,
+    pos: Position,
+    // Whether code precedes the comment on its line
+    trailing: bool,
+}
+
+// Comments are not part of the AST. They are collected from the tokens and
+// put back between the statements they were found between; a comment
+// inside an expression moves to after its statement. The text of markup is
+// blanked out first, as `//` or a quote in it is not code.
+struct Formatter<'a> {
+    config: &'a Config,
+    chars: Vec<char>,
+    comments: Vec<Comment>,
+    next_comment: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &str, program: &Program, config: &'a Config) -> Self {
+        let chars: Vec<char> = source.chars().collect();
+        let mut code = chars.clone();
+        let mut texts = vec![];
+        program
+            .stmts
+            .iter()
+            .for_each(|stmt| stmt_texts(stmt, &mut texts));
+        for text in texts {
+            for char in code[text.start as usize..text.end as usize].iter_mut() {
+                if *char != '\n' {
+                    *char = ' ';
+                }
+            }
+        }
+        let code: String = code.into_iter().collect();
+
+        let mut comments = vec![];
+        let mut lexer = Lexer::new(code.chars());
+        while let Some(token) = lexer.bump() {
+            if let TokenKind::Comment(text) = token.kind {
+                let trailing = chars[..token.pos.start as usize]
+                    .iter()
+                    .rev()
+                    .find(|char| **char != ' ' && **char != '\t')
+                    .is_some_and(|char| *char != '\n');
+                comments.push(Comment {
+                    text: text.trim_end().to_string(),
+                    pos: token.pos,
+                    trailing,
+                });
+            }
+        }
+
+        Self {
+            config,
+            chars,
+            comments,
+            next_comment: 0,
+        }
+    }
+
+    fn program(&mut self, program: &Program) -> Doc {
+        self.stmts(&program.stmts, self.chars.len() as u32, false)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt], end: u32, block: bool) -> Doc {
+        let mut docs = vec![];
+        for (i, stmt) in stmts.iter().enumerate() {
+            self.comments_before(stmt.pos.start, &mut docs);
+            self.separator(stmt.pos.start, &mut docs);
+            let tail = block && i + 1 == stmts.len();
+            docs.push(self.stmt(stmt, !tail));
+        }
+        self.comments_before(end, &mut docs);
+        Doc::concat(docs)
+    }
+
+    fn comments_before(&mut self, offset: u32, docs: &mut Vec<Doc>) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.pos.start >= offset {
+                break;
+            }
+            let comment = comment.clone();
+            self.next_comment += 1;
+            if comment.trailing && !docs.is_empty() {
+                docs.push(Doc::text(format!(" //{}", comment.text)));
+            } else {
+                self.separator(comment.pos.start, docs);
+                docs.push(Doc::text(format!("//{}", comment.text)));
+            }
+        }
+    }
+
+    // Breaks the line before an item, keeping one blank line if the source
+    // had at least one.
+    fn separator(&self, offset: u32, docs: &mut Vec<Doc>) {
+        if docs.is_empty() {
+            return;
+        }
+        docs.push(Doc::HardLine);
+
+        let newlines = self.chars[..offset as usize]
+            .iter()
+            .rev()
+            .take_while(|char| Space::parse(char).is_some())
+            .filter(|char| **char == '\n')
+            .count();
+        if newlines > 1 {
+            docs.push(Doc::HardLine);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, semicolon: bool) -> Doc {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => Doc::concat(vec![
+                Doc::text(format!(
+                    "let {}{} = ",
+                    let_stmt.identifier,
+                    annotation(&let_stmt.ty)
+                )),
+                self.expr(&let_stmt.value),
+                Doc::text(";"),
+            ]),
+            StmtKind::State(state_stmt) => Doc::concat(vec![
+                Doc::text(format!(
+                    "state {}{} = ",
+                    state_stmt.identifier,
+                    annotation(&state_stmt.ty)
+                )),
+                self.expr(&state_stmt.value),
+                Doc::text(";"),
+            ]),
+            StmtKind::Fn(fn_stmt) => Doc::concat(vec![
+                Doc::text(format!("fn {}", fn_stmt.name)),
+                self.signature(&fn_stmt.params, &fn_stmt.ret),
+                Doc::text(" "),
+                self.expr(&fn_stmt.body),
+            ]),
+            StmtKind::Component(component) => Doc::concat(vec![
+                Doc::text(format!("component {}", component.name)),
+                self.signature(&component.props, &None),
+                Doc::text(" "),
+                self.expr(&component.body),
+            ]),
+            StmtKind::Type(type_stmt) => {
+                let mut head = format!("type {}", type_stmt.name);
+                if !type_stmt.params.is_empty() {
+                    head.push_str(&format!("<{}>", type_stmt.params.join(", ")));
+                }
+                head.push_str(" =");
+                let mut variants = vec![];
+                for (i, variant) in type_stmt.variants.iter().enumerate() {
+                    variants.push(Doc::Line);
+                    let mut text = if i > 0 {
+                        "| ".to_string()
+                    } else {
+                        String::new()
+                    };
+                    text.push_str(&variant.name);
+                    let fields: Vec<String> = variant.fields.iter().map(print_type).collect();
+                    if !fields.is_empty() {
+                        text.push_str(&format!("({})", fields.join(", ")));
+                    }
+                    variants.push(Doc::text(text));
+                }
+                // One variant per line if they do not fit on one
+                Doc::concat(vec![
+                    Doc::text(head),
+                    Doc::concat(variants).nest(self.config.indent),
+                    Doc::text(";"),
+                ])
+                .group()
+            }
+            StmtKind::Import(import_stmt) => {
+                let names: Vec<Doc> = import_stmt
+                    .names
+                    .iter()
+                    .map(|name| Doc::text(name.name.clone()))
+                    .collect();
+                Doc::concat(vec![
+                    Doc::text("import "),
+                    self.braced(names),
+                    Doc::text(format!(" from {};", quote(&import_stmt.path))),
+                ])
+            }
+            StmtKind::Export(exported) => {
+                Doc::concat(vec![Doc::text("export "), self.stmt(exported, semicolon)])
+            }
+            StmtKind::Expr(expr) => {
+                let doc = self.expr(expr);
+                if semicolon {
+                    Doc::concat(vec![doc, Doc::text(";")])
+                } else {
+                    doc
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Doc {
+        match &expr.kind {
+            ExprKind::Identifier(identifier) => Doc::text(identifier.ident.clone()),
+            ExprKind::Literal(LiteralExpr::Number(number)) => Doc::text(number.to_string()),
+            ExprKind::Literal(LiteralExpr::Bool(bool)) => Doc::text(bool.to_string()),
+            ExprKind::Literal(LiteralExpr::String(string)) => Doc::text(quote(string)),
+            ExprKind::Call(call) => {
+                let called = self.operand(&call.called, Precedence::Call);
+                // A lone block argument, or lambda with a block body, is
+                // already broken over lines, so it hugs the parentheses
+                // instead of being indented once more
+                if let [arg] = call.args.as_slice() {
+                    let hugs = match &arg.kind {
+                        ExprKind::Block(_) => true,
+                        ExprKind::Lambda(lambda) => lambda.has_block_body(),
+                        _ => false,
+                    };
+                    if hugs {
+                        let arg = self.expr(arg);
+                        return Doc::concat(vec![called, Doc::text("("), arg, Doc::text(")")]);
+                    }
+                }
+                let args: Vec<Doc> = call.args.iter().map(|arg| self.expr(arg)).collect();
+                Doc::concat(vec![called, self.parenthesized(args)])
+            }
+            ExprKind::Binary(binary) => {
+                let precedence = binary.op.precedence();
+                let lhs = self.operand(&binary.lhs, precedence);
+                let rhs = self.operand(&binary.rhs, precedence.next());
+                Doc::concat(vec![
+                    lhs,
+                    Doc::text(format!(" {}", binary.op.symbol())),
+                    Doc::concat(vec![Doc::Line, rhs]).nest(self.config.indent),
+                ])
+                .group()
+            }
+            ExprKind::Unary(unary) => Doc::concat(vec![
+                Doc::text(unary.op.symbol()),
+                self.operand(&unary.operand, Precedence::Unary),
+            ]),
+            ExprKind::If(if_expr) => {
+                let mut docs = vec![
+                    Doc::text("if "),
+                    self.expr(&if_expr.condition),
+                    Doc::text(" "),
+                    self.expr(&if_expr.then),
+                ];
+                if let Some(otherwise) = &if_expr.otherwise {
+                    docs.push(Doc::text(" else "));
+                    docs.push(self.expr(otherwise));
+                }
+                Doc::concat(docs)
+            }
+            ExprKind::Block(stmts) => {
+                let body = self.stmts(stmts, expr.pos.end, true);
+                if body == Doc::Concat(vec![]) {
+                    return Doc::text("{}");
+                }
+                Doc::concat(vec![
+                    Doc::text("{"),
+                    Doc::concat(vec![Doc::HardLine, body]).nest(self.config.indent),
+                    Doc::HardLine,
+                    Doc::text("}"),
+                ])
+            }
+            ExprKind::List(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| {
+                        let value = self.expr(&item.value);
+                        if item.spread {
+                            Doc::concat(vec![Doc::text(".."), value])
+                        } else {
+                            value
+                        }
+                    })
+                    .collect();
+                self.delimited("[", "]", items)
+            }
+            ExprKind::Index(index) => Doc::concat(vec![
+                self.operand(&index.indexed, Precedence::Call),
+                Doc::text("["),
+                self.expr(&index.index),
+                Doc::text("]"),
+            ]),
+            ExprKind::Record(record) => {
+                let mut items = vec![];
+                if let Some(base) = &record.base {
+                    items.push(Doc::concat(vec![Doc::text(".."), self.expr(base)]));
+                }
+                for field in record.fields.iter() {
+                    if field.is_shorthand() && record.allows_shorthand() {
+                        items.push(Doc::text(field.name.clone()));
+                    } else {
+                        items.push(Doc::concat(vec![
+                            Doc::text(format!("{}: ", field.name)),
+                            self.expr(&field.value),
+                        ]));
+                    }
+                }
+                self.braced(items)
+            }
+            ExprKind::Field(field) => Doc::concat(vec![
+                self.operand(&field.record, Precedence::Call),
+                Doc::text(format!(".{}", field.field)),
+            ]),
+            ExprKind::Assign(assign) => Doc::concat(vec![
+                self.expr(&assign.target),
+                Doc::text(" = "),
+                self.expr(&assign.value),
+            ]),
+            ExprKind::Match(match_expr) => {
+                let scrutinee = self.expr(&match_expr.scrutinee);
+                if match_expr.arms.is_empty() {
+                    return Doc::concat(vec![Doc::text("match "), scrutinee, Doc::text(" {}")]);
+                }
+                let mut arms = vec![];
+                for arm in match_expr.arms.iter() {
+                    self.comments_before(arm.pos.start, &mut arms);
+                    self.separator(arm.pos.start, &mut arms);
+                    let mut docs = vec![Doc::text(print_pattern(&arm.pattern))];
+                    if let Some(guard) = &arm.guard {
+                        docs.push(Doc::text(" if "));
+                        docs.push(self.expr(guard));
+                    }
+                    docs.push(Doc::text(" => "));
+                    docs.push(self.expr(&arm.body));
+                    docs.push(Doc::text(","));
+                    arms.push(Doc::concat(docs));
+                }
+                self.comments_before(expr.pos.end, &mut arms);
+                Doc::concat(vec![
+                    Doc::text("match "),
+                    scrutinee,
+                    Doc::text(" {"),
+                    Doc::concat(vec![Doc::HardLine, Doc::concat(arms)]).nest(self.config.indent),
+                    Doc::HardLine,
+                    Doc::text("}"),
+                ])
+            }
+            ExprKind::Lambda(lambda) => {
+                let arrow = if lambda.has_block_body() { " " } else { " => " };
+                Doc::concat(vec![
+                    Doc::text("fn"),
+                    self.signature(&lambda.params, &lambda.ret),
+                    Doc::text(arrow),
+                    self.expr(&lambda.body),
+                ])
+            }
+            ExprKind::Element(element) => self.element(element),
+        }
+    }
+
+    // The attributes go on their own lines if the opening tag is too long.
+    // Children do too, unless text in them starts or ends with a space, which
+    // a line break would remove.
+    fn element(&mut self, element: &ElementExpr) -> Doc {
+        let name = element.tag.as_ref().map_or("", |tag| tag.name.as_str());
+        let mut attributes = vec![];
+        for attribute in element.attributes.iter() {
+            attributes.push(Doc::Line);
+            let value = match &attribute.value {
+                Some(value) => match &value.kind {
+                    ExprKind::Literal(LiteralExpr::String(string)) => {
+                        Doc::text(format!("={}", quote(string)))
+                    }
+                    _ => Doc::concat(vec![Doc::text("={"), self.expr(value), Doc::text("}")]),
+                },
+                None => Doc::text(""),
+            };
+            attributes.push(Doc::concat(vec![Doc::text(attribute.name.clone()), value]));
+        }
+        let attributes = Doc::concat(attributes).nest(self.config.indent);
+
+        if element.tag.is_some() && element.children.is_empty() {
+            return Doc::concat(vec![
+                Doc::text(format!("<{}", name)),
+                attributes,
+                Doc::Line,
+                Doc::text("/>"),
+            ])
+            .group();
+        }
+        let open = Doc::concat(vec![
+            Doc::text(format!("<{}", name)),
+            attributes,
+            Doc::SoftLine,
+            Doc::text(">"),
+        ])
+        .group();
+
+        let breakable = element.children.iter().all(|child| match &child.kind {
+            ChildKind::Text(text) => text.trim() == text,
+            _ => true,
+        });
+        let mut children = vec![];
+        for child in element.children.iter() {
+            if breakable {
+                children.push(Doc::SoftLine);
+            }
+            children.push(match &child.kind {
+                ChildKind::Text(text) => Doc::text(text.clone()),
+                ChildKind::Expr(value) => {
+                    Doc::concat(vec![Doc::text("{"), self.expr(value), Doc::text("}")])
+                }
+                ChildKind::Element(value) => self.expr(value),
+            });
+        }
+        let close = Doc::text(format!("</{}>", name));
+        if !breakable {
+            return Doc::concat(vec![open, Doc::concat(children), close]);
+        }
+        Doc::concat(vec![
+            open,
+            Doc::concat(children).nest(self.config.indent),
+            Doc::SoftLine,
+            close,
+        ])
+        .group()
+    }
+
+    // `(a: A, b) -> Ret`
+    fn signature(&self, params: &[Param], ret: &Option<TypeExpr>) -> Doc {
+        let params = params
+            .iter()
+            .map(|param| Doc::text(format!("{}{}", param.name, annotation(&param.ty))))
+            .collect();
+        let ret = match ret {
+            Some(ret) => format!(" -> {}", print_type(ret)),
+            None => String::new(),
+        };
+        Doc::concat(vec![self.parenthesized(params), Doc::text(ret)])
+    }
+
+    // `(a, b)`, with one item per line if it does not fit
+    fn parenthesized(&self, items: Vec<Doc>) -> Doc {
+        self.delimited("(", ")", items)
+    }
+
+    fn delimited(&self, open: &str, close: &str, items: Vec<Doc>) -> Doc {
+        if items.is_empty() {
+            return Doc::text(format!("{}{}", open, close));
+        }
+
+        let mut inner = vec![Doc::SoftLine];
+        for (i, item) in items.into_iter().enumerate() {
+            if i > 0 {
+                inner.push(Doc::text(","));
+                inner.push(Doc::Line);
+            }
+            inner.push(item);
+        }
+        Doc::concat(vec![
+            Doc::text(open),
+            Doc::concat(inner).nest(self.config.indent),
+            Doc::SoftLine,
+            Doc::text(close),
+        ])
+        .group()
+    }
+
+    // Like `delimited`, but with spaces inside the braces when flat
+    fn braced(&self, items: Vec<Doc>) -> Doc {
+        if items.is_empty() {
+            return Doc::text("{}");
+        }
+
+        let mut inner = vec![Doc::Line];
+        for (i, item) in items.into_iter().enumerate() {
+            if i > 0 {
+                inner.push(Doc::text(","));
+                inner.push(Doc::Line);
+            }
+            inner.push(item);
+        }
+        Doc::concat(vec![
+            Doc::text("{"),
+            Doc::concat(inner).nest(self.config.indent),
+            Doc::Line,
+            Doc::text("}"),
+        ])
+        .group()
+    }
+
+    fn operand(&mut self, expr: &Expr, min: Precedence) -> Doc {
+        let doc = self.expr(expr);
+        if Precedence::of(expr) < min {
+            Doc::concat(vec![Doc::text("("), doc, Doc::text(")")])
+        } else {
+            doc
+        }
+    }
+}
+
+// The spans of the text children of the elements in a statement
+fn stmt_texts(stmt: &Stmt, texts: &mut Vec<Position>) {
+    match &stmt.kind {
+        StmtKind::Expr(expr) => expr_texts(expr, texts),
+        StmtKind::Let(let_stmt) => expr_texts(&let_stmt.value, texts),
+        StmtKind::State(state) => expr_texts(&state.value, texts),
+        StmtKind::Fn(fn_stmt) => expr_texts(&fn_stmt.body, texts),
+        StmtKind::Component(component) => expr_texts(&component.body, texts),
+        StmtKind::Export(stmt) => stmt_texts(stmt, texts),
+        StmtKind::Type(_) | StmtKind::Import(_) => {}
+    }
+}
+
+fn expr_texts(expr: &Expr, texts: &mut Vec<Position>) {
+    match &expr.kind {
+        ExprKind::Identifier(_) | ExprKind::Literal(_) => {}
+        ExprKind::Call(call) => {
+            expr_texts(&call.called, texts);
+            call.args.iter().for_each(|arg| expr_texts(arg, texts));
+        }
+        ExprKind::Binary(binary) => {
+            expr_texts(&binary.lhs, texts);
+            expr_texts(&binary.rhs, texts);
+        }
+        ExprKind::Unary(unary) => expr_texts(&unary.operand, texts),
+        ExprKind::If(if_expr) => {
+            expr_texts(&if_expr.condition, texts);
+            expr_texts(&if_expr.then, texts);
+            if let Some(otherwise) = &if_expr.otherwise {
+                expr_texts(otherwise, texts);
+            }
+        }
+        ExprKind::Block(stmts) => stmts.iter().for_each(|stmt| stmt_texts(stmt, texts)),
+        ExprKind::List(items) => items.iter().for_each(|item| expr_texts(&item.value, texts)),
+        ExprKind::Index(index) => {
+            expr_texts(&index.indexed, texts);
+            expr_texts(&index.index, texts);
+        }
+        ExprKind::Record(record) => {
+            if let Some(base) = &record.base {
+                expr_texts(base, texts);
+            }
+            record
+                .fields
+                .iter()
+                .for_each(|field| expr_texts(&field.value, texts));
+        }
+        ExprKind::Field(field) => expr_texts(&field.record, texts),
+        ExprKind::Lambda(lambda) => expr_texts(&lambda.body, texts),
+        ExprKind::Match(match_expr) => {
+            expr_texts(&match_expr.scrutinee, texts);
+            for arm in match_expr.arms.iter() {
+                arm.guard.iter().for_each(|guard| expr_texts(guard, texts));
+                expr_texts(&arm.body, texts);
+            }
+        }
+        ExprKind::Assign(assign) => {
+            expr_texts(&assign.target, texts);
+            expr_texts(&assign.value, texts);
+        }
+        ExprKind::Element(element) => {
+            for attribute in element.attributes.iter() {
+                attribute
+                    .value
+                    .iter()
+                    .for_each(|value| expr_texts(value, texts));
+            }
+            for child in element.children.iter() {
+                match child.expr() {
+                    Some(value) => expr_texts(value, texts),
+                    None => texts.push(child.pos),
+                }
+            }
+        }
+    }
+}
+
+// `: Type`, or nothing
+fn annotation(ty: &Option<TypeExpr>) -> String {
+    match ty {
+        Some(ty) => format!(": {}", print_type(ty)),
+        None => String::new(),
+    }
+}
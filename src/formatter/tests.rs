@@ -0,0 +1,203 @@
+use super::*;
+
+fn assert_formats(input: &str, expected: &str) {
+    let formatted = format(input).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format(&formatted).unwrap(), formatted, "not idempotent");
+}
+
+#[test]
+fn format_stmts() {
+    assert_formats(
+        "let  foo=bar(baz)\nfoo(1);;",
+        "let foo = bar(baz);\nfoo(1);\n",
+    );
+    assert_formats("", "");
+}
+
+#[test]
+fn format_blocks() {
+    assert_formats(
+        "let a = { let b = 1; f(b) }",
+        "let a = {\n    let b = 1;\n    f(b)\n};\n",
+    );
+    assert_formats("let a = {}", "let a = {};\n");
+    assert_formats("f({ g(x) })", "f({\n    g(x)\n});\n");
+}
+
+#[test]
+fn format_breaks_long_calls() {
+    let input = "print(concatenate_everything(first_really_long_argument_name_for_the_call_here))";
+    assert_formats(
+        input,
+        "print(\n    concatenate_everything(first_really_long_argument_name_for_the_call_here)\n);\n",
+    );
+
+    let config = Config {
+        width: 24,
+        indent: 2,
+    };
+    assert_eq!(
+        format_with("foo(bar(baz(quux)))", &config).unwrap(),
+        "foo(bar(baz(quux)));\n"
+    );
+    assert_eq!(
+        format_with("foo(bar(baz(quux_quux)))", &config).unwrap(),
+        "foo(\n  bar(baz(quux_quux))\n);\n"
+    );
+}
+
+#[test]
+fn format_keeps_comments() {
+    assert_formats(
+        "// header\nlet a = 1; // one\n\n\n// two\nlet b = {\n// inside\nf(a) // tail\n}\n// end",
+        "// header\nlet a = 1; // one\n\n// two\nlet b = {\n    // inside\n    f(a) // tail\n};\n// end\n",
+    );
+    assert_formats("f( // moved\nx)", "f(x); // moved\n");
+}
+
+#[test]
+fn format_keeps_blank_lines() {
+    assert_formats("a\n\n\nb\nc", "a;\n\nb;\nc;\n");
+}
+
+#[test]
+fn check_mode() {
+    assert_eq!(check("let a = 1;\n"), Ok(true));
+    assert_eq!(check("let a = 1"), Ok(false));
+    assert_eq!(
+        check("let a = 1; let = 2"),
+        Err(FormatError {
+            pos: Position::new(11, 11)
+        })
+    );
+}
+
+#[test]
+fn format_corpus_is_idempotent() {
+    for (name, source) in crate::corpus::sources() {
+        let formatted = format(&source).unwrap();
+        assert_eq!(format(&formatted).unwrap(), formatted, "in {}", name);
+    }
+}
+
+#[test]
+fn format_fn_and_state() {
+    assert_formats(
+        "fn add(a,b){ state c = a; c }\nadd( 1 ,2 ,)",
+        "fn add(a, b) {\n    state c = a;\n    c\n}\nadd(1, 2);\n",
+    );
+    assert_formats(
+        "send(first_argument_name, second_argument_name, third_argument_name, fourth_argument)",
+        "send(\n    first_argument_name,\n    second_argument_name,\n    third_argument_name,\n    fourth_argument\n);\n",
+    );
+    assert_formats("f()", "f();\n");
+}
+
+#[test]
+fn format_annotations() {
+    assert_formats(
+        "state  n:Number=0\nfn f(x :  List< String >,)->fn()->Unit{ g }",
+        "state n: Number = 0;\nfn f(x: List<String>) -> fn() -> Unit {\n    g\n}\n",
+    );
+}
+
+#[test]
+fn format_lists() {
+    assert_formats(
+        "let xs = [ 1,2 , ..ys, ]\nxs [0];(f(x))[ i ]",
+        "let xs = [1, 2, ..ys];\nxs;\n[0];\nf(x)[i];\n",
+    );
+    assert_formats(
+        "let names = ['first_really_long_name', 'second_really_long_name', ..the_remaining_names]",
+        "let names = [\n    'first_really_long_name',\n    'second_really_long_name',\n    ..the_remaining_names\n];\n",
+    );
+}
+
+#[test]
+fn format_records() {
+    assert_formats(
+        "let a = {name:'x',count , }\nlet b = {..a,count:a.count+1}\nlet c = { name: name }",
+        "let a = { name: 'x', count };\nlet b = { ..a, count: a.count + 1 };\nlet c = { name: name };\n",
+    );
+    assert_formats(
+        "let config = { name: 'a really long name for this record', count: 1, enabled: true }",
+        "let config = {\n    name: 'a really long name for this record',\n    count: 1,\n    enabled: true\n};\n",
+    );
+}
+
+#[test]
+fn format_lambdas() {
+    assert_formats(
+        "let inc = fn (x)=>x+1\non_click(fn(){ inc(1) })\nlet f = (fn(x) => x)(1) + fn(y: Number)->Bool=>true",
+        "let inc = fn(x) => x + 1;\non_click(fn() {\n    inc(1)\n});\nlet f = (fn(x) => x)(1) + (fn(y: Number) -> Bool => true);\n",
+    );
+}
+
+#[test]
+fn format_types_and_matches() {
+    assert_formats(
+        "type Option<T>=|Some(T)|None\nmatch o{Some( x )if x>1=>x,\n\n// nothing\nNone=>{ 0 }  _ => 1}",
+        "type Option<T> = Some(T) | None;\nmatch o {\n    Some(x) if x > 1 => x,\n\n    // nothing\n    None => {\n        0\n    },\n    _ => 1,\n};\n",
+    );
+    assert_formats(
+        "type Shape = Circle(Number) | Rectangle(Number, Number) | Triangle(Number, Number, Number)",
+        "type Shape =\n    Circle(Number)\n    | Rectangle(Number, Number)\n    | Triangle(Number, Number, Number);\n",
+    );
+    assert_formats("let a = match x {}", "let a = match x {};\n");
+}
+
+#[test]
+fn format_imports_and_exports() {
+    assert_formats(
+        "import {Button,Card,} from \"./button\"\nimport {} from './setup'\nexport   let a=1",
+        "import { Button, Card } from './button';\nimport {} from './setup';\nexport let a = 1;\n",
+    );
+    assert_formats(
+        "import { FirstComponentName, SecondComponentName, ThirdComponentName } from './components'",
+        "import {\n    FirstComponentName,\n    SecondComponentName,\n    ThirdComponentName\n} from './components';\n",
+    );
+}
+
+#[test]
+fn format_elements() {
+    assert_formats(
+        "let page=<div id={ id }><h1>Title</h1>{ body }</div>",
+        "let page = <div id={id}><h1>Title</h1>{body}</div>;\n",
+    );
+    // Children go on their own lines when they do not fit, unless that would
+    // drop the spaces around text
+    assert_formats(
+        "<ul class='items'><li>first item of the list</li><li>second item of the list</li></ul>",
+        "<ul class='items'>\n    <li>first item of the list</li>\n    <li>second item of the list</li>\n</ul>;\n",
+    );
+    assert_formats(
+        "<p>You have clicked the button {count} times since the page was opened</p>",
+        "<p>You have clicked the button {count} times since the page was opened</p>;\n",
+    );
+    // Text that looks like a comment or a string stays text
+    assert_formats(
+        "<a href='https://example.com'>Don't</a> // link",
+        "<a href='https://example.com'>Don't</a>; // link\n",
+    );
+}
+
+#[test]
+fn format_components() {
+    assert_formats(
+        "component Counter( step:Number,label ){state count=0;<button>{label}: {count}</button>}",
+        "component Counter(step: Number, label) {\n    state count = 0;\n    <button>{label}: {count}</button>\n}\n",
+    );
+    assert_formats(
+        "export component App(){<main />}",
+        "export component App() {\n    <main />\n}\n",
+    );
+}
+
+#[test]
+fn format_assignments() {
+    assert_formats(
+        "state n=0\nfn inc(){n=n+1}",
+        "state n = 0;\nfn inc() {\n    n = n + 1\n}\n",
+    );
+}
@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::value::Value;
+use crate::resolve::BindingId;
+
+/// The bindings of one function call, or of the program. Binding ids are
+/// unique, so blocks share the frame of their function.
+#[derive(Default)]
+pub(crate) struct Env {
+    values: RefCell<HashMap<BindingId, Value>>,
+    parent: Option<Rc<Env>>,
+}
+
+impl Env {
+    pub fn new(parent: Option<Rc<Env>>) -> Rc<Env> {
+        Rc::new(Self {
+            values: RefCell::new(HashMap::new()),
+            parent,
+        })
+    }
+
+    pub fn get(&self, id: BindingId) -> Option<Value> {
+        match self.values.borrow().get(&id) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.get(id),
+        }
+    }
+
+    /// Whether the binding has a value in this frame, leaving out the
+    /// enclosing ones.
+    pub fn contains(&self, id: BindingId) -> bool {
+        self.values.borrow().contains_key(&id)
+    }
+
+    pub fn define(&self, id: BindingId, value: Value) {
+        self.values.borrow_mut().insert(id, value);
+    }
+
+    /// Replaces the value of a binding in this frame or an enclosing one.
+    /// Returns whether the binding has a value to replace.
+    pub fn assign(&self, id: BindingId, value: Value) -> bool {
+        if let Some(old) = self.values.borrow_mut().get_mut(&id) {
+            *old = value;
+            return true;
+        }
+        match &self.parent {
+            Some(parent) => parent.assign(id, value),
+            None => false,
+        }
+    }
+}
+
+// Closures refer back to the frames they are defined in, so printing the
+// values could loop forever
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Env").finish_non_exhaustive()
+    }
+}
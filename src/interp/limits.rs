@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::stack::NativeStack;
+use super::RuntimeErrorKind;
+
+/// Bounds on what running a program may use, for untrusted scripts. `None`
+/// leaves a resource unbounded.
+///
+/// Whatever the limits, the interpreter stops with
+/// `RuntimeErrorKind::NativeStackExhausted` before its calls, which recurse on
+/// the native stack, run out of it. The VM keeps its calls on the heap.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    // Units of work: one per expression for the interpreter, and one per
+    // instruction for the VM
+    pub fuel: Option<u64>,
+    // Calls of zope functions in progress at once
+    pub max_depth: Option<usize>,
+    // Bytes allocated for strings, lists, records, variants and closures over
+    // the whole run. Values that are dropped do not give theirs back.
+    pub max_bytes: Option<usize>,
+    pub cancel: Option<CancelHandle>,
+}
+
+/// Stops a run from another thread, or from a native function. The run fails
+/// at the next expression or instruction it starts.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// What a run has used so far, against its limits
+#[derive(Debug, Default)]
+pub(crate) struct Meter {
+    limits: Limits,
+    fuel: u64,
+    depth: usize,
+    bytes: usize,
+    // Found on the first call
+    stack: Option<NativeStack>,
+}
+
+impl Meter {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn tick(&mut self) -> Result<(), RuntimeErrorKind> {
+        if self
+            .limits
+            .cancel
+            .as_ref()
+            .is_some_and(CancelHandle::is_cancelled)
+        {
+            return Err(RuntimeErrorKind::Cancelled);
+        }
+        self.fuel += 1;
+        match self.limits.fuel {
+            Some(fuel) if self.fuel > fuel => Err(RuntimeErrorKind::OutOfFuel),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn enter(&mut self) -> Result<(), RuntimeErrorKind> {
+        if let Some(max) = self.limits.max_depth {
+            if self.depth >= max {
+                return Err(RuntimeErrorKind::StackOverflow(max));
+            }
+        }
+        let stack = self.stack.get_or_insert_with(NativeStack::current);
+        if stack.exhausted() {
+            return Err(RuntimeErrorKind::NativeStackExhausted(stack.size));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), RuntimeErrorKind> {
+        self.bytes += bytes;
+        match self.limits.max_bytes {
+            Some(max) if self.bytes > max => Err(RuntimeErrorKind::OutOfMemory(max)),
+            _ => Ok(()),
+        }
+    }
+}
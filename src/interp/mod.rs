@@ -0,0 +1,833 @@
+mod env;
+pub mod limits;
+mod stack;
+#[cfg(test)]
+mod tests;
+pub mod value;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+use crate::resolve::{BindingId, Resolution, ScopeKind};
+
+use self::env::Env;
+pub use self::limits::*;
+pub use self::value::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+    DivisionByZero,
+    IndexOutOfBounds { index: f64, len: usize },
+    // The type name of the called value
+    NotCallable(String),
+    Arity { expected: usize, found: usize },
+    // The value no arm matched
+    NoMatchingArm(String),
+    MissingField(String),
+    // A binding without a value, like an import outside of its module graph
+    Unbound(String),
+    // For programs that were run without type checking
+    Mismatch { expected: String, found: String },
+    // Raised by a native function, with its message
+    Native(String),
+    // Limits of the run, with the limit that was reached
+    OutOfFuel,
+    StackOverflow(usize),
+    // The size of the native stack the interpreter's calls used up
+    NativeStackExhausted(usize),
+    OutOfMemory(usize),
+    Cancelled,
+}
+
+impl RuntimeErrorKind {
+    /// Whether the run stopped at one of its `Limits` rather than on an error
+    /// of the program.
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            RuntimeErrorKind::OutOfFuel
+                | RuntimeErrorKind::StackOverflow(_)
+                | RuntimeErrorKind::NativeStackExhausted(_)
+                | RuntimeErrorKind::OutOfMemory(_)
+                | RuntimeErrorKind::Cancelled
+        )
+    }
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            RuntimeErrorKind::IndexOutOfBounds { index, len } => write!(
+                f,
+                "index {} is out of bounds for a list of length {}",
+                index, len
+            ),
+            RuntimeErrorKind::NotCallable(ty) => write!(f, "`{}` is not a function", ty),
+            RuntimeErrorKind::Arity { expected, found } => {
+                write!(f, "expected {} arguments, found {}", expected, found)
+            }
+            RuntimeErrorKind::NoMatchingArm(value) => write!(f, "no arm matches `{}`", value),
+            RuntimeErrorKind::MissingField(name) => write!(f, "no field `{}` on this record", name),
+            RuntimeErrorKind::Unbound(name) => write!(f, "`{}` has no value", name),
+            RuntimeErrorKind::Mismatch { expected, found } => {
+                write!(f, "expected `{}`, found `{}`", expected, found)
+            }
+            RuntimeErrorKind::Native(message) => write!(f, "{}", message),
+            RuntimeErrorKind::OutOfFuel => write!(f, "ran out of fuel"),
+            RuntimeErrorKind::StackOverflow(max) => {
+                write!(f, "more than {} calls in progress", max)
+            }
+            RuntimeErrorKind::NativeStackExhausted(size) => {
+                write!(f, "calls used up the {} bytes of native stack", size)
+            }
+            RuntimeErrorKind::OutOfMemory(max) => write!(f, "allocated more than {} bytes", max),
+            RuntimeErrorKind::Cancelled => write!(f, "execution was cancelled"),
+        }
+    }
+}
+
+/// An error that stops evaluation, with the span of the failing expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub pos: Position,
+}
+
+impl RuntimeError {
+    pub fn new(kind: RuntimeErrorKind, pos: Position) -> Self {
+        Self { kind, pos }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.kind.to_string(), self.pos)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.pos.start, self.pos.end)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+/// Runs a resolved program, returning the value of its last statement.
+pub fn run_program(program: &Program, resolution: &Resolution) -> Result<Value> {
+    Interpreter::new(resolution).run(program)
+}
+
+/// A rendered instance of a component. Its frame keeps the component's
+/// `state` from one render to the next, while props are bound again on each.
+/// Only the interpreter renders instances, and the VM rejects components
+/// with `state`.
+#[derive(Debug, Default)]
+pub struct Instance {
+    frame: Option<Rc<Env>>,
+}
+
+impl Instance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Evaluates the AST directly. Identifiers are looked up by the binding the
+/// resolver found for them, so the program should resolve without errors.
+pub struct Interpreter<'a> {
+    resolution: &'a Resolution,
+    globals: Rc<Env>,
+    // Each `fn` and lambda is copied out of the AST once, on its first use
+    functions: HashMap<Position, Rc<FunctionDef>>,
+    meter: Meter,
+    tracked: Option<Tracked>,
+}
+
+// The top-level bindings an evaluation read and wrote, for the reactive
+// runtime
+#[derive(Debug, Default)]
+pub(crate) struct Tracked {
+    pub reads: Vec<BindingId>,
+    pub writes: Vec<(BindingId, Value)>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(resolution: &'a Resolution) -> Self {
+        Self::with_limits(resolution, Limits::default())
+    }
+
+    pub fn with_limits(resolution: &'a Resolution, limits: Limits) -> Self {
+        Self {
+            resolution,
+            globals: Env::new(None),
+            functions: HashMap::new(),
+            meter: Meter::new(limits),
+            tracked: None,
+        }
+    }
+
+    pub fn run(&mut self, program: &Program) -> Result<Value> {
+        let globals = self.globals.clone();
+        self.stmts(&program.stmts, &globals)
+    }
+
+    /// The value of a top-level binding, once `run` has reached it.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        let program = self.resolution.scopes.first()?;
+        program
+            .bindings
+            .iter()
+            .rev()
+            .find(|id| self.resolution.binding(**id).name == name)
+            .and_then(|id| self.globals.get(*id))
+    }
+
+    /// Gives a value to a top-level binding, like one the host defined.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        let program = self.resolution.scopes.first();
+        let binding = program.and_then(|program| {
+            program
+                .bindings
+                .iter()
+                .rev()
+                .find(|id| self.resolution.binding(**id).name == name)
+        });
+        if let Some(id) = binding {
+            self.globals.define(*id, value);
+        }
+    }
+
+    pub(crate) fn resolution(&self) -> &'a Resolution {
+        self.resolution
+    }
+
+    // Starts recording the top-level bindings that are read and written
+    pub(crate) fn track(&mut self) {
+        self.tracked = Some(Tracked::default());
+    }
+
+    pub(crate) fn take_tracked(&mut self) -> Tracked {
+        self.tracked.take().unwrap_or_default()
+    }
+
+    pub(crate) fn define_global(&mut self, id: BindingId, value: Value) {
+        self.globals.define(id, value);
+    }
+
+    // Defines the functions and variants of top-level statements, which
+    // are then run one at a time by `eval_global`
+    pub(crate) fn hoist(&mut self, stmts: &[Stmt]) -> Result<()> {
+        let globals = self.globals.clone();
+        self.hoist_in(stmts, &globals)
+    }
+
+    pub(crate) fn eval_global(&mut self, expr: &Expr) -> Result<Value> {
+        let globals = self.globals.clone();
+        self.expr(expr, &globals)
+    }
+
+    /// Calls a function value. Errors that are not inside the function, like
+    /// a wrong number of arguments, are reported at `pos`.
+    pub fn call(&mut self, function: &Value, args: Vec<Value>, pos: Position) -> Result<Value> {
+        let function = match function {
+            Value::Function(function) => function,
+            value => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorKind::NotCallable(value.type_name().to_string()),
+                    pos,
+                ))
+            }
+        };
+        if function.arity() != args.len() {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::Arity {
+                    expected: function.arity(),
+                    found: args.len(),
+                },
+                pos,
+            ));
+        }
+
+        match function.as_ref() {
+            Function::Closure(closure) => {
+                let env = Env::new(Some(closure.env.clone()));
+                self.enter(closure, args, &env, pos)
+            }
+            Function::Constructor(constructor) => {
+                let value = Value::Variant(Rc::new(VariantValue {
+                    ty: constructor.ty.clone(),
+                    name: constructor.name.clone(),
+                    id: constructor.id,
+                    fields: args,
+                }));
+                self.allocate(&value, pos)?;
+                Ok(value)
+            }
+            Function::Native(native) => native.call(self, args, pos),
+            // Bytecode closures need the VM to run
+            Function::Compiled(_) => Err(RuntimeError::new(
+                RuntimeErrorKind::NotCallable("Function".to_string()),
+                pos,
+            )),
+        }
+    }
+
+    /// Renders a component for `instance` with a record of props, as built by
+    /// `ElementValue::props`. The first render of an instance runs the
+    /// initializers of the component's `state`; later renders keep the values
+    /// it has, including those assigned by handlers it rendered. Any other
+    /// function is called with the props, as it has no state to keep.
+    pub fn render(
+        &mut self,
+        component: &Value,
+        props: Value,
+        instance: &mut Instance,
+        pos: Position,
+    ) -> Result<Value> {
+        let closure = match component {
+            Value::Function(function) => match function.as_ref() {
+                Function::Closure(closure) if closure.def.component => closure,
+                _ => return self.call(component, vec![props], pos),
+            },
+            value => return Err(mismatch("Component", value, pos)),
+        };
+        let frame = instance
+            .frame
+            .get_or_insert_with(|| Env::new(Some(closure.env.clone())))
+            .clone();
+        self.enter(closure, vec![props], &frame, pos)
+    }
+
+    // Runs the body of a closure in `env`, its frame for this call
+    fn enter(
+        &mut self,
+        closure: &Closure,
+        args: Vec<Value>,
+        env: &Rc<Env>,
+        pos: Position,
+    ) -> Result<Value> {
+        if closure.def.component {
+            let props = args.into_iter().next().unwrap_or(Value::Unit);
+            let Value::Record(props) = props else {
+                return Err(mismatch("Record", &props, pos));
+            };
+            for param in closure.def.params.iter() {
+                let name = &self.resolution.binding(*param).name;
+                let value = props.get(name).cloned().ok_or_else(|| {
+                    RuntimeError::new(RuntimeErrorKind::MissingField(name.clone()), pos)
+                })?;
+                env.define(*param, value);
+            }
+        } else {
+            for (param, arg) in closure.def.params.iter().zip(args) {
+                env.define(*param, arg);
+            }
+        }
+        self.meter
+            .enter()
+            .map_err(|kind| RuntimeError::new(kind, pos))?;
+        let value = self.expr(&closure.def.body, env);
+        self.meter.exit();
+        value
+    }
+
+    // Functions and variants are defined before the statements run, as the
+    // resolver lets them be used anywhere in their scope
+    fn stmts(&mut self, stmts: &[Stmt], env: &Rc<Env>) -> Result<Value> {
+        self.hoist_in(stmts, env)?;
+        let mut value = Value::Unit;
+        for stmt in stmts.iter() {
+            value = self.stmt(stmt, env)?;
+        }
+        Ok(value)
+    }
+
+    fn hoist_in(&mut self, stmts: &[Stmt], env: &Rc<Env>) -> Result<()> {
+        for stmt in stmts.iter().map(Stmt::declaration) {
+            match &stmt.kind {
+                StmtKind::Fn(fn_stmt) => {
+                    let def = self.function_def(
+                        stmt.pos,
+                        Some(&fn_stmt.name),
+                        &fn_stmt.params,
+                        &fn_stmt.body,
+                        false,
+                    );
+                    self.define_function(stmt.pos, def, env)?;
+                }
+                StmtKind::Component(component) => {
+                    let def = self.function_def(
+                        stmt.pos,
+                        Some(&component.name),
+                        &component.props,
+                        &component.body,
+                        true,
+                    );
+                    self.define_function(stmt.pos, def, env)?;
+                }
+                StmtKind::Type(type_stmt) => self.define_variants(type_stmt, env),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn define_function(
+        &mut self,
+        pos: Position,
+        def: Rc<FunctionDef>,
+        env: &Rc<Env>,
+    ) -> Result<()> {
+        if let Some(id) = self.resolution.definitions.get(&pos) {
+            let value = closure(def, env);
+            self.allocate(&value, pos)?;
+            env.define(*id, value);
+        }
+        Ok(())
+    }
+
+    fn define_variants(&mut self, type_stmt: &TypeStmt, env: &Rc<Env>) {
+        for variant in type_stmt.variants.iter() {
+            let Some(id) = self.resolution.definitions.get(&variant.pos) else {
+                continue;
+            };
+            let value = if variant.fields.is_empty() {
+                Value::Variant(Rc::new(VariantValue {
+                    ty: type_stmt.name.clone(),
+                    name: variant.name.clone(),
+                    id: *id,
+                    fields: vec![],
+                }))
+            } else {
+                Value::Function(Rc::new(Function::Constructor(Constructor {
+                    ty: type_stmt.name.clone(),
+                    name: variant.name.clone(),
+                    id: *id,
+                    arity: variant.fields.len(),
+                })))
+            };
+            env.define(*id, value);
+        }
+    }
+
+    // The value of an expression statement, `Unit` for declarations
+    fn stmt(&mut self, stmt: &Stmt, env: &Rc<Env>) -> Result<Value> {
+        match &stmt.kind {
+            StmtKind::Let(LetStmt { value, .. }) | StmtKind::State(StateStmt { value, .. }) => {
+                let id = self.resolution.definitions.get(&stmt.pos).copied();
+                // The state of a component instance that rendered before
+                if let Some(id) = id.filter(|id| self.resolution.instance_state.contains(id)) {
+                    if env.contains(id) {
+                        return Ok(Value::Unit);
+                    }
+                }
+                let value = self.expr(value, env)?;
+                if let Some(id) = id {
+                    env.define(id, value);
+                }
+                Ok(Value::Unit)
+            }
+            StmtKind::Fn(_) | StmtKind::Component(_) | StmtKind::Type(_) | StmtKind::Import(_) => {
+                Ok(Value::Unit)
+            }
+            StmtKind::Export(exported) => self.stmt(exported, env),
+            StmtKind::Expr(expr) => self.expr(expr, env),
+        }
+    }
+
+    fn function_def(
+        &mut self,
+        pos: Position,
+        name: Option<&str>,
+        params: &[Param],
+        body: &Expr,
+        component: bool,
+    ) -> Rc<FunctionDef> {
+        let resolution = self.resolution;
+        self.functions
+            .entry(pos)
+            .or_insert_with(|| {
+                Rc::new(FunctionDef {
+                    name: name.map(str::to_string),
+                    params: params
+                        .iter()
+                        .map(|param| resolution.definitions[&param.pos])
+                        .collect(),
+                    body: body.clone(),
+                    component,
+                })
+            })
+            .clone()
+    }
+
+    fn allocate(&mut self, value: &Value, pos: Position) -> Result<()> {
+        self.meter
+            .allocate(value.allocated_size())
+            .map_err(|kind| RuntimeError::new(kind, pos))
+    }
+
+    fn expr(&mut self, expr: &Expr, env: &Rc<Env>) -> Result<Value> {
+        self.meter
+            .tick()
+            .map_err(|kind| RuntimeError::new(kind, expr.pos))?;
+        match &expr.kind {
+            ExprKind::Identifier(identifier) => self.lookup(&identifier.ident, expr.pos, env),
+            ExprKind::Literal(literal) => Ok(literal_value(literal)),
+            ExprKind::Call(call) => {
+                let called = self.expr(&call.called, env)?;
+                let mut args = vec![];
+                for arg in call.args.iter() {
+                    args.push(self.expr(arg, env)?);
+                }
+                self.call(&called, args, expr.pos)
+            }
+            ExprKind::Binary(binary) => {
+                let value = self.binary(expr, binary, env)?;
+                self.allocate(&value, expr.pos)?;
+                Ok(value)
+            }
+            ExprKind::Unary(unary) => {
+                let operand = self.expr(&unary.operand, env)?;
+                match unary.op {
+                    UnaryOp::Neg => Ok(Value::Number(-number(&operand, unary.operand.pos)?)),
+                    UnaryOp::Not => Ok(Value::Bool(!bool(&operand, unary.operand.pos)?)),
+                }
+            }
+            ExprKind::If(if_expr) => {
+                let condition = self.expr(&if_expr.condition, env)?;
+                if bool(&condition, if_expr.condition.pos)? {
+                    self.expr(&if_expr.then, env)
+                } else if let Some(otherwise) = &if_expr.otherwise {
+                    self.expr(otherwise, env)
+                } else {
+                    Ok(Value::Unit)
+                }
+            }
+            ExprKind::Block(stmts) => self.stmts(stmts, env),
+            // Each item is paid for as it is added, as in the VM, so that
+            // running out of memory stops at the item that grew the list
+            ExprKind::List(items) => {
+                let mut values = vec![];
+                for item in items.iter() {
+                    let value = self.expr(&item.value, env)?;
+                    let added = if item.spread {
+                        let spread = list(&value, item.value.pos)?;
+                        values.extend(spread.iter().cloned());
+                        spread.len()
+                    } else {
+                        values.push(value);
+                        1
+                    };
+                    self.meter
+                        .allocate(added * std::mem::size_of::<Value>())
+                        .map_err(|kind| RuntimeError::new(kind, item.value.pos))?;
+                }
+                Ok(Value::list(values))
+            }
+            ExprKind::Index(index) => {
+                let indexed = self.expr(&index.indexed, env)?;
+                let items = list(&indexed, index.indexed.pos)?;
+                let at = number(&self.expr(&index.index, env)?, index.index.pos)?;
+                if at < 0.0 || at.fract() != 0.0 || at as usize >= items.len() {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::IndexOutOfBounds {
+                            index: at,
+                            len: items.len(),
+                        },
+                        index.index.pos,
+                    ));
+                }
+                Ok(items[at as usize].clone())
+            }
+            ExprKind::Record(record) => {
+                let mut fields = match &record.base {
+                    Some(base) => {
+                        let value = self.expr(base, env)?;
+                        match value {
+                            Value::Record(fields) => fields.as_ref().clone(),
+                            value => return Err(mismatch("Record", &value, base.pos)),
+                        }
+                    }
+                    None => BTreeMap::new(),
+                };
+                for field in record.fields.iter() {
+                    let value = self.expr(&field.value, env)?;
+                    fields.insert(field.name.clone(), value);
+                }
+                let value = Value::record(fields);
+                self.allocate(&value, expr.pos)?;
+                Ok(value)
+            }
+            ExprKind::Field(field) => match self.expr(&field.record, env)? {
+                Value::Record(fields) => fields.get(&field.field).cloned().ok_or_else(|| {
+                    RuntimeError::new(
+                        RuntimeErrorKind::MissingField(field.field.clone()),
+                        expr.pos,
+                    )
+                }),
+                value => Err(mismatch("Record", &value, field.record.pos)),
+            },
+            ExprKind::Assign(assign) => self.assign(assign, env),
+            ExprKind::Lambda(lambda) => {
+                let def = self.function_def(expr.pos, None, &lambda.params, &lambda.body, false);
+                let value = closure(def, env);
+                self.allocate(&value, expr.pos)?;
+                Ok(value)
+            }
+            ExprKind::Match(match_expr) => {
+                let value = self.expr(&match_expr.scrutinee, env)?;
+                for arm in match_expr.arms.iter() {
+                    if !self.pattern(&arm.pattern, &value, env) {
+                        continue;
+                    }
+                    if let Some(guard) = &arm.guard {
+                        if !bool(&self.expr(guard, env)?, guard.pos)? {
+                            continue;
+                        }
+                    }
+                    return self.expr(&arm.body, env);
+                }
+                Err(RuntimeError::new(
+                    RuntimeErrorKind::NoMatchingArm(value.to_string()),
+                    expr.pos,
+                ))
+            }
+            ExprKind::Element(element) => {
+                let value = self.element(element, env)?;
+                self.allocate(&value, expr.pos)?;
+                Ok(value)
+            }
+        }
+    }
+
+    // Components are not called here, but by whatever renders the element
+    fn element(&mut self, element: &ElementExpr, env: &Rc<Env>) -> Result<Value> {
+        let tag = match &element.tag {
+            Some(tag) if tag.is_component() => {
+                ElementTag::Component(self.lookup(&tag.name, tag.pos, env)?)
+            }
+            Some(tag) => ElementTag::Html(tag.name.clone()),
+            None => ElementTag::Fragment,
+        };
+        let mut value = ElementValue::new(tag);
+        for attribute in element.attributes.iter() {
+            let attribute_value = match &attribute.value {
+                Some(expr) => self.expr(expr, env)?,
+                None => Value::Bool(true),
+            };
+            value
+                .attributes
+                .push((attribute.name.clone(), attribute_value));
+        }
+        for child in element.children.iter() {
+            match &child.kind {
+                ChildKind::Text(text) => value.push_child(Value::string(text.as_str())),
+                ChildKind::Expr(expr) | ChildKind::Element(expr) => {
+                    let child = self.expr(expr, env)?;
+                    value.push_child(child);
+                }
+            }
+        }
+        Ok(Value::Element(Rc::new(value)))
+    }
+
+    fn lookup(&mut self, name: &str, pos: Position, env: &Rc<Env>) -> Result<Value> {
+        let id = self.resolution.uses.get(&pos).copied();
+        if let (Some(tracked), Some(id)) = (&mut self.tracked, id) {
+            if is_global(self.resolution, id) {
+                tracked.reads.push(id);
+            }
+        }
+        id.and_then(|id| env.get(id))
+            .ok_or_else(|| RuntimeError::new(RuntimeErrorKind::Unbound(name.to_string()), pos))
+    }
+
+    fn assign(&mut self, assign: &AssignExpr, env: &Rc<Env>) -> Result<Value> {
+        let value = self.expr(&assign.value, env)?;
+        let target = &assign.target;
+        let id = self.resolution.uses.get(&target.pos).copied();
+        match id {
+            Some(id) if env.assign(id, value.clone()) => {
+                if let Some(tracked) = &mut self.tracked {
+                    if is_global(self.resolution, id) {
+                        tracked.writes.push((id, value));
+                    }
+                }
+                Ok(Value::Unit)
+            }
+            _ => {
+                let name = match &target.kind {
+                    ExprKind::Identifier(identifier) => identifier.ident.clone(),
+                    _ => String::new(),
+                };
+                Err(RuntimeError::new(
+                    RuntimeErrorKind::Unbound(name),
+                    target.pos,
+                ))
+            }
+        }
+    }
+
+    // Whether `value` matches, binding the names of the pattern if it does
+    fn pattern(&mut self, pattern: &Pattern, value: &Value, env: &Rc<Env>) -> bool {
+        match &pattern.kind {
+            PatternKind::Wildcard => true,
+            PatternKind::Literal(literal) => literal_value(literal) == *value,
+            PatternKind::Identifier(_) => match self.resolution.uses.get(&pattern.pos) {
+                Some(variant) => is_variant(value, *variant),
+                None => {
+                    if let Some(id) = self.resolution.definitions.get(&pattern.pos) {
+                        env.define(*id, value.clone());
+                    }
+                    true
+                }
+            },
+            PatternKind::Variant(_, patterns) => {
+                let Some(variant) = self.resolution.uses.get(&pattern.pos) else {
+                    return false;
+                };
+                match value {
+                    Value::Variant(value) if value.id == *variant => {
+                        value.fields.len() == patterns.len()
+                            && patterns
+                                .iter()
+                                .zip(value.fields.iter())
+                                .all(|(pattern, field)| self.pattern(pattern, field, env))
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn binary(&mut self, expr: &Expr, binary: &BinaryExpr, env: &Rc<Env>) -> Result<Value> {
+        let lhs = self.expr(&binary.lhs, env)?;
+        // `&&` and `||` only evaluate their right side when needed
+        match binary.op {
+            BinaryOp::And if !bool(&lhs, binary.lhs.pos)? => return Ok(Value::Bool(false)),
+            BinaryOp::Or if bool(&lhs, binary.lhs.pos)? => return Ok(Value::Bool(true)),
+            BinaryOp::And | BinaryOp::Or => {
+                let rhs = self.expr(&binary.rhs, env)?;
+                return Ok(Value::Bool(bool(&rhs, binary.rhs.pos)?));
+            }
+            _ => {}
+        }
+        let rhs = self.expr(&binary.rhs, env)?;
+
+        let numbers = || Ok((number(&lhs, binary.lhs.pos)?, number(&rhs, binary.rhs.pos)?));
+        let value = match binary.op {
+            BinaryOp::Add => match (&lhs, &rhs) {
+                (Value::String(a), Value::String(b)) => Value::string(format!("{}{}", a, b)),
+                (Value::String(_), _) => return Err(mismatch("String", &rhs, binary.rhs.pos)),
+                _ => {
+                    let (a, b) = numbers()?;
+                    Value::Number(a + b)
+                }
+            },
+            BinaryOp::Sub => {
+                let (a, b) = numbers()?;
+                Value::Number(a - b)
+            }
+            BinaryOp::Mul => {
+                let (a, b) = numbers()?;
+                Value::Number(a * b)
+            }
+            BinaryOp::Div | BinaryOp::Rem => {
+                let (a, b) = numbers()?;
+                if b == 0.0 {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorKind::DivisionByZero,
+                        expr.pos,
+                    ));
+                }
+                if binary.op == BinaryOp::Div {
+                    Value::Number(a / b)
+                } else {
+                    Value::Number(a % b)
+                }
+            }
+            BinaryOp::Eq => Value::Bool(lhs == rhs),
+            BinaryOp::Ne => Value::Bool(lhs != rhs),
+            BinaryOp::Lt => {
+                let (a, b) = numbers()?;
+                Value::Bool(a < b)
+            }
+            BinaryOp::Le => {
+                let (a, b) = numbers()?;
+                Value::Bool(a <= b)
+            }
+            BinaryOp::Gt => {
+                let (a, b) = numbers()?;
+                Value::Bool(a > b)
+            }
+            BinaryOp::Ge => {
+                let (a, b) = numbers()?;
+                Value::Bool(a >= b)
+            }
+            BinaryOp::And | BinaryOp::Or => unreachable!(),
+        };
+        Ok(value)
+    }
+}
+
+fn is_global(resolution: &Resolution, id: BindingId) -> bool {
+    let scope = resolution.binding(id).scope;
+    resolution.scope(scope).kind == ScopeKind::Program
+}
+
+fn closure(def: Rc<FunctionDef>, env: &Rc<Env>) -> Value {
+    Value::Function(Rc::new(Function::Closure(Closure {
+        def,
+        env: env.clone(),
+    })))
+}
+
+fn literal_value(literal: &LiteralExpr) -> Value {
+    match literal {
+        LiteralExpr::Number(number) => Value::Number(*number as f64),
+        LiteralExpr::Bool(bool) => Value::Bool(*bool),
+        LiteralExpr::String(string) => Value::string(string.as_str()),
+    }
+}
+
+// A variant without fields, as named by a pattern
+fn is_variant(value: &Value, id: BindingId) -> bool {
+    matches!(value, Value::Variant(variant) if variant.id == id && variant.fields.is_empty())
+}
+
+fn mismatch(expected: &str, found: &Value, pos: Position) -> RuntimeError {
+    RuntimeError::new(
+        RuntimeErrorKind::Mismatch {
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+        },
+        pos,
+    )
+}
+
+fn number(value: &Value, pos: Position) -> Result<f64> {
+    match value {
+        Value::Number(number) => Ok(*number),
+        value => Err(mismatch("Number", value, pos)),
+    }
+}
+
+fn bool(value: &Value, pos: Position) -> Result<bool> {
+    match value {
+        Value::Bool(bool) => Ok(*bool),
+        value => Err(mismatch("Bool", value, pos)),
+    }
+}
+
+fn list(value: &Value, pos: Position) -> Result<&Rc<Vec<Value>>> {
+    match value {
+        Value::List(items) => Ok(items),
+        value => Err(mismatch("List", value, pos)),
+    }
+}
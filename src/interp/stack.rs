@@ -0,0 +1,79 @@
+// The native stack of the running thread, which the interpreter's calls
+// recurse on. A run stops before it gets within `RESERVE` bytes of the end,
+// which is left for the call that reaches it and for reporting the error.
+
+const RESERVE: usize = 256 * 1024;
+
+// Where the stack is unknown, the calls in progress may use this much of it
+// from where the outermost one started, which fits the 2 MiB of a spawned
+// thread
+const FALLBACK: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct NativeStack {
+    // The lowest address the calls may reach, as the stack grows down
+    limit: usize,
+    // Bytes of stack there were above it
+    pub size: usize,
+}
+
+impl NativeStack {
+    /// The stack of the running thread, found from where it is now.
+    pub fn current() -> Self {
+        let here = position();
+        match bounds() {
+            Some((low, size)) if low + RESERVE < here => Self {
+                limit: low + RESERVE,
+                size,
+            },
+            _ => Self {
+                limit: here.saturating_sub(FALLBACK),
+                size: FALLBACK,
+            },
+        }
+    }
+
+    pub fn exhausted(&self) -> bool {
+        position() < self.limit
+    }
+}
+
+// The address of a local, which is as deep in the native stack as the caller
+#[inline(never)]
+fn position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// The lowest address and the size of the running thread's stack
+#[cfg(target_os = "linux")]
+fn bounds() -> Option<(usize, usize)> {
+    // Larger than `pthread_attr_t` on every target
+    #[repr(C, align(16))]
+    struct Attr([u8; 128]);
+
+    extern "C" {
+        fn pthread_self() -> usize;
+        fn pthread_getattr_np(thread: usize, attr: *mut Attr) -> i32;
+        fn pthread_attr_getstack(attr: *const Attr, addr: *mut usize, size: *mut usize) -> i32;
+        fn pthread_attr_destroy(attr: *mut Attr) -> i32;
+    }
+
+    let mut attr = Attr([0; 128]);
+    let (mut low, mut size) = (0, 0);
+    // SAFETY: `attr` is large enough for the attributes, which are only read
+    // once `pthread_getattr_np` has filled them in, and destroyed after
+    unsafe {
+        if pthread_getattr_np(pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let found = pthread_attr_getstack(&attr, &mut low, &mut size);
+        pthread_attr_destroy(&mut attr);
+        (found == 0 && low != 0).then_some((low, size))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bounds() -> Option<(usize, usize)> {
+    None
+}
@@ -0,0 +1,354 @@
+use super::*;
+use crate::resolve::resolve_program;
+
+fn parse(input: &str) -> (Program, Resolution) {
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program(&program);
+    assert!(!resolution.has_errors(), "in {}", input);
+    (program, resolution)
+}
+
+fn run(input: &str) -> Result<Value> {
+    let (program, resolution) = parse(input);
+    run_program(&program, &resolution)
+}
+
+fn display(input: &str) -> String {
+    run(input).unwrap().to_string()
+}
+
+// The span of the first occurrence of `text` in `input`
+fn span(input: &str, text: &str) -> Position {
+    let start = input.find(text).unwrap() as u32;
+    Position::new(start, start + text.len() as u32)
+}
+
+fn first_char(input: &str, text: &str) -> Position {
+    let start = span(input, text).start;
+    Position::new(start, start + 1)
+}
+
+#[test]
+fn eval_operators() {
+    assert_eq!(run("1 + 2 * 3 - 4").unwrap(), Value::Number(3.0));
+    assert_eq!(run("7 / 2").unwrap(), Value::Number(3.5));
+    assert_eq!(run("7 % 4").unwrap(), Value::Number(3.0));
+    assert_eq!(run("-(2 - 5)").unwrap(), Value::Number(3.0));
+    assert_eq!(run("1 < 2 && !(2 >= 3)").unwrap(), Value::Bool(true));
+    assert_eq!(run("'ab' + \"cd\"").unwrap(), Value::string("abcd"));
+    assert_eq!(
+        run("[1, { a: 'x' }] == [1, { a: 'x' }]").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(run("'a' != 'a'").unwrap(), Value::Bool(false));
+    // The right side is not evaluated
+    assert_eq!(run("false && 1 / 0 == 1").unwrap(), Value::Bool(false));
+    assert_eq!(run("true || [][0]").unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn eval_bindings_and_blocks() {
+    assert_eq!(
+        display("let a = 1; let b = { let a = 2; a + 1 }; a + b"),
+        "4"
+    );
+    assert_eq!(display("state count = 1; count"), "1");
+    assert_eq!(
+        display("if 1 > 2 { 'a' } else if true { 'b' } else { 'c' }"),
+        "'b'"
+    );
+    assert_eq!(run("if false { 1 }").unwrap(), Value::Unit);
+    assert_eq!(run("{}").unwrap(), Value::Unit);
+    assert_eq!(run("let a = 1;").unwrap(), Value::Unit);
+}
+
+#[test]
+fn eval_functions() {
+    let fib = "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }\nfib(15)";
+    assert_eq!(display(fib), "610");
+
+    // Functions can be used before their statement
+    let even = "fn main() { is_even(10) }\nfn is_even(n) { if n == 0 { true } else { is_odd(n - 1) } }\nfn is_odd(n) { if n == 0 { false } else { is_even(n - 1) } }\nmain()";
+    assert_eq!(display(even), "true");
+
+    let adder = "fn adder(n) { fn(x) => x + n }\nlet add_two = adder(2);\nlet add_five = adder(5);\n[add_two(1), add_five(1), adder(10)(1)]";
+    assert_eq!(display(adder), "[3, 6, 11]");
+
+    let compose =
+        "let compose = fn(f, g) => fn(x) => g(f(x));\ncompose(fn(x) => x * 2, fn(x) { x + 1 })(5)";
+    assert_eq!(display(compose), "11");
+    assert_eq!(
+        display("fn f() { 1 }\nlet g = fn(x) => x;\n[f, g]"),
+        "[<fn f>, <fn>]"
+    );
+}
+
+#[test]
+fn eval_lists_and_records() {
+    assert_eq!(
+        display("let xs = [1, 2];\n[0, ..xs, ..[], 3]"),
+        "[0, 1, 2, 3]"
+    );
+    assert_eq!(display("['a', 'b', 'c'][1]"), "'b'");
+    assert_eq!(
+        display("let user = { name: 'ada', age: 36 };\nlet name = 'bob';\n{ ..user, name, admin: true }"),
+        "{ admin: true, age: 36, name: 'bob' }"
+    );
+    assert_eq!(
+        display("let point = { x: 1, y: 2 };\npoint.x + point.y"),
+        "3"
+    );
+}
+
+#[test]
+fn eval_elements() {
+    assert_eq!(
+        display("let items = ['a', 'b'];\n<ul class='list' hidden>{items}{if false { 1 }}</ul>"),
+        "<ul class='list' hidden={true}>ab</ul>"
+    );
+    assert_eq!(
+        display("fn Button(props) { <button /> }\nlet n = 2;\n<><Button label={n} />Count: {n}</>"),
+        "<><Button label={2} />Count: {2}</>"
+    );
+}
+
+#[test]
+fn render_component_instances() {
+    let input = "component Counter(step) {\n    state count = 0;\n    <button onclick={fn() { count = count + step }}>{count}</button>\n}\nlet app = <Counter step={2} />;";
+    let (program, resolution) = parse(input);
+    let mut interpreter = Interpreter::new(&resolution);
+    interpreter.run(&program).unwrap();
+    let Some(Value::Element(app)) = interpreter.global("app") else {
+        panic!("`app` is not an element");
+    };
+    let ElementTag::Component(counter) = &app.tag else {
+        panic!("`app` is not a component");
+    };
+    let pos = span(input, "<Counter step={2} />");
+
+    // Each instance keeps its own state from one render to the next
+    let mut first = Instance::new();
+    let mut second = Instance::new();
+    let rendered = interpreter
+        .render(counter, app.props(), &mut first, pos)
+        .unwrap();
+    let Value::Element(button) = rendered else {
+        panic!("the component did not render an element");
+    };
+    let onclick = button.attributes[0].1.clone();
+    interpreter.call(&onclick, vec![], pos).unwrap();
+    interpreter.call(&onclick, vec![], pos).unwrap();
+    let rendered = interpreter.render(counter, app.props(), &mut first, pos);
+    assert_eq!(
+        rendered.unwrap().to_string(),
+        "<button onclick={<fn>}>{4}</button>"
+    );
+    let rendered = interpreter.render(counter, app.props(), &mut second, pos);
+    assert_eq!(
+        rendered.unwrap().to_string(),
+        "<button onclick={<fn>}>{0}</button>"
+    );
+
+    // Props are bound by name, and called directly a component starts over
+    assert_eq!(
+        interpreter.render(
+            counter,
+            Value::record(BTreeMap::new()),
+            &mut Instance::new(),
+            pos
+        ),
+        Err(RuntimeError::new(
+            RuntimeErrorKind::MissingField("step".to_string()),
+            pos
+        ))
+    );
+    assert_eq!(
+        display("component Label(text) { <b>{text}</b> }\nLabel({ text: 'hi', extra: 1 })"),
+        "<b>hi</b>"
+    );
+}
+
+#[test]
+fn eval_matches() {
+    let input = "type Option<T> = Some(T) | None;\nfn map(o, f) {\n    match o {\n        Some(x) => Some(f(x)),\n        None => None,\n    }\n}\n[map(Some(1), fn(x) => x + 1), map(None, fn(x) => x)]";
+    assert_eq!(display(input), "[Some(2), None]");
+
+    let input = "type List = Cons(Number, List) | Nil;\nfn sum(list) {\n    match list {\n        Cons(x, rest) if x > 0 => x + sum(rest),\n        Cons(_, rest) => sum(rest),\n        Nil => 0,\n    }\n}\nsum(Cons(1, Cons(-5, Cons(2, Nil))))";
+    assert_eq!(display(input), "3");
+
+    let input = "fn name(n) { match n { 0 => 'zero', 1 => 'one', other => 'many' } }\n[name(0), name(1), name(7)]";
+    assert_eq!(display(input), "['zero', 'one', 'many']");
+
+    // Variants are told apart by their declaration, not their name
+    let input = "type A = Same | Other;\nlet a = Same;\n{\n    type B = Same;\n    match a { Same => 'b', _ => 'a' }\n}";
+    assert_eq!(display(input), "'a'");
+}
+
+#[test]
+fn call_from_rust() {
+    let (program, resolution) = parse("fn greet(name) { 'hello ' + name }\nlet later = 1;");
+    let mut interpreter = Interpreter::new(&resolution);
+    assert_eq!(interpreter.global("later"), None);
+    interpreter.run(&program).unwrap();
+    assert_eq!(interpreter.global("later"), Some(Value::Number(1.0)));
+
+    let greet = interpreter.global("greet").unwrap();
+    let pos = Position::new(0, 0);
+    assert_eq!(
+        interpreter.call(&greet, vec![Value::string("zope")], pos),
+        Ok(Value::string("hello zope"))
+    );
+    assert_eq!(
+        interpreter.call(&greet, vec![], pos),
+        Err(RuntimeError::new(
+            RuntimeErrorKind::Arity {
+                expected: 1,
+                found: 0
+            },
+            pos
+        ))
+    );
+}
+
+#[test]
+fn report_runtime_errors() {
+    let error = |input: &str| run(input).unwrap_err();
+
+    let input = "fn f(x) { 10 / x }\nf(0)";
+    assert_eq!(
+        error(input),
+        RuntimeError::new(RuntimeErrorKind::DivisionByZero, span(input, "10 / x"))
+    );
+    let input = "let xs = [1, 2];\nxs[2]";
+    assert_eq!(
+        error(input).to_string(),
+        "index 2 is out of bounds for a list of length 2 at 20..21"
+    );
+    let input = "let n = 1;\nn(2)";
+    assert_eq!(
+        error(input),
+        RuntimeError::new(
+            RuntimeErrorKind::NotCallable("Number".into()),
+            span(input, "n(2)")
+        )
+    );
+    let input = "fn f(a, b) { a }\nf(1)";
+    assert_eq!(error(input).pos, span(input, "f(1)"));
+    let input = "type T = A | B;\nmatch B { A => 1 }";
+    assert_eq!(
+        error(input),
+        RuntimeError::new(
+            RuntimeErrorKind::NoMatchingArm("B".into()),
+            span(input, "match B { A => 1 }")
+        )
+    );
+    let input = "fn get(r) { r.missing }\nget({ a: 1, b: 2 })";
+    assert_eq!(
+        error(input).to_diagnostic(),
+        Diagnostic::error(
+            "no field `missing` on this record",
+            span(input, "r.missing")
+        )
+    );
+    let input = "import { x } from './x';\nx + 1";
+    assert_eq!(
+        error(input),
+        RuntimeError::new(
+            RuntimeErrorKind::Unbound("x".into()),
+            first_char(input, "x + 1")
+        )
+    );
+    let input = "if 1 { 2 }";
+    assert_eq!(
+        error(input).to_string(),
+        "expected `Bool`, found `Number` at 3..4"
+    );
+}
+
+#[test]
+fn enforce_limits() {
+    let limited = |input: &str, limits: Limits| {
+        let (program, resolution) = parse(input);
+        Interpreter::with_limits(&resolution, limits)
+            .run(&program)
+            .unwrap_err()
+    };
+
+    let input = "fn loop(n) { loop(n + 1) }\nloop(0)";
+    let limits = Limits {
+        fuel: Some(100),
+        ..Limits::default()
+    };
+    let error = limited(input, limits);
+    assert_eq!(error.kind, RuntimeErrorKind::OutOfFuel);
+    assert!(error.kind.is_limit());
+
+    let limits = Limits {
+        max_depth: Some(50),
+        ..Limits::default()
+    };
+    assert_eq!(
+        limited(input, limits),
+        RuntimeError::new(
+            RuntimeErrorKind::StackOverflow(50),
+            span(input, "loop(n + 1)")
+        )
+    );
+
+    // Without limits, recursion stops before the native stack runs out
+    let input = "fn f(n) { if n == 0 { 0 } else { f(n - 1) } }\nf(20000)";
+    let error = limited(input, Limits::default());
+    assert!(matches!(
+        error.kind,
+        RuntimeErrorKind::NativeStackExhausted(_)
+    ));
+    assert!(error.kind.is_limit());
+    assert_eq!(error.pos, span(input, "f(n - 1)"));
+    let (program, resolution) = parse("fn f(n) { if n == 0 { 0 } else { 1 + f(n - 1) } }\nf(30)");
+    assert_eq!(
+        Interpreter::new(&resolution).run(&program),
+        Ok(Value::Number(30.0))
+    );
+
+    let input = "fn grow(xs) { grow([..xs, 1]) }\ngrow([])";
+    let limits = Limits {
+        max_bytes: Some(1000),
+        max_depth: Some(1000),
+        ..Limits::default()
+    };
+    let error = limited(input, limits);
+    // At the spread, which grows the list past the limit
+    let start = span(input, "xs, 1").start;
+    assert_eq!(
+        error,
+        RuntimeError::new(
+            RuntimeErrorKind::OutOfMemory(1000),
+            Position::new(start, start + 2)
+        )
+    );
+    assert_eq!(
+        error.to_string(),
+        "allocated more than 1000 bytes at 22..24"
+    );
+
+    let cancel = CancelHandle::new();
+    cancel.cancel();
+    let limits = Limits {
+        cancel: Some(cancel),
+        ..Limits::default()
+    };
+    assert_eq!(
+        limited("1 + 2", limits),
+        RuntimeError::new(RuntimeErrorKind::Cancelled, span("1 + 2", "1 + 2"))
+    );
+    // Within its limits a program runs as before
+    let (program, resolution) = parse("fn f(n) { if n == 0 { 0 } else { f(n - 1) } }\nf(10)");
+    let limits = Limits {
+        fuel: Some(1000),
+        max_depth: Some(11),
+        max_bytes: Some(1000),
+        cancel: Some(CancelHandle::new()),
+    };
+    let mut interpreter = Interpreter::with_limits(&resolution, limits);
+    assert_eq!(interpreter.run(&program), Ok(Value::Number(0.0)));
+}
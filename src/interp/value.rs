@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::env::Env;
+use crate::ast::Expr;
+use crate::print::quote;
+use crate::resolve::BindingId;
+
+/// A runtime value. Lists, records, variants, functions and elements are
+/// shared, so cloning a value is cheap.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+    String(Rc<str>),
+    List(Rc<Vec<Value>>),
+    Record(Rc<BTreeMap<String, Value>>),
+    Variant(Rc<VariantValue>),
+    Function(Rc<Function>),
+    Element(Rc<ElementValue>),
+    Unit,
+}
+
+impl Value {
+    pub fn string(string: impl Into<Rc<str>>) -> Value {
+        Value::String(string.into())
+    }
+
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(items))
+    }
+
+    pub fn record(fields: BTreeMap<String, Value>) -> Value {
+        Value::Record(Rc::new(fields))
+    }
+
+    // The bytes the value allocated itself, leaving out the values it shares,
+    // for `Limits::max_bytes`
+    pub(crate) fn allocated_size(&self) -> usize {
+        let value = std::mem::size_of::<Value>();
+        match self {
+            Value::Number(_) | Value::Bool(_) | Value::Unit => 0,
+            Value::String(string) => string.len(),
+            Value::List(items) => items.len() * value,
+            Value::Record(fields) => fields.keys().map(|name| name.len() + value).sum(),
+            Value::Variant(variant) => {
+                std::mem::size_of::<VariantValue>() + variant.fields.len() * value
+            }
+            Value::Function(_) => std::mem::size_of::<Function>(),
+            Value::Element(element) => {
+                let attributes: usize = element
+                    .attributes
+                    .iter()
+                    .map(|(name, _)| name.len() + value)
+                    .sum();
+                std::mem::size_of::<ElementValue>() + attributes + element.children.len() * value
+            }
+        }
+    }
+
+    /// The name of the value's type, for error messages.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Number(_) => "Number",
+            Value::Bool(_) => "Bool",
+            Value::String(_) => "String",
+            Value::List(_) => "List",
+            Value::Record(_) => "Record",
+            Value::Variant(variant) => &variant.ty,
+            Value::Function(_) => "Function",
+            Value::Element(_) => "Element",
+            Value::Unit => "Unit",
+        }
+    }
+}
+
+/// Structural equality. Functions are only equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Record(a), Value::Record(b)) => a == b,
+            (Value::Variant(a), Value::Variant(b)) => a.id == b.id && a.fields == b.fields,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Element(a), Value::Element(b)) => a == b,
+            (Value::Unit, Value::Unit) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Values are displayed like the literals that build them.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Bool(bool) => write!(f, "{}", bool),
+            Value::String(string) => write!(f, "{}", quote(string)),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Record(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    let separator = if i > 0 { "," } else { "" };
+                    write!(f, "{} {}: {}", separator, name, value)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Variant(variant) => {
+                write!(f, "{}", variant.name)?;
+                if !variant.fields.is_empty() {
+                    write!(f, "(")?;
+                    for (i, field) in variant.fields.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", field)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Value::Function(function) => match function.name() {
+                Some(name) => write!(f, "<fn {}>", name),
+                None => write!(f, "<fn>"),
+            },
+            Value::Element(element) => write!(f, "{}", element),
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// The tag of an element value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementTag {
+    Html(String),
+    // The function that renders it, which is only called by a renderer
+    Component(Value),
+    Fragment,
+}
+
+/// An element built by markup. Attributes keep their source order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementValue {
+    pub tag: ElementTag,
+    pub attributes: Vec<(String, Value)>,
+    pub children: Vec<Value>,
+}
+
+impl ElementValue {
+    pub fn new(tag: ElementTag) -> Self {
+        Self {
+            tag,
+            attributes: vec![],
+            children: vec![],
+        }
+    }
+
+    /// Adds the value of a child. The items of a list become children
+    /// themselves, and `()`, as left by an `if` without `else`, is dropped.
+    pub fn push_child(&mut self, child: Value) {
+        match child {
+            Value::List(items) => items.iter().for_each(|item| self.push_child(item.clone())),
+            Value::Unit => {}
+            child => self.children.push(child),
+        }
+    }
+
+    /// The record a component is called with: the attributes but `key`, which
+    /// is for renderers, and the children as a fragment if there are any.
+    pub fn props(&self) -> Value {
+        let mut props: BTreeMap<String, Value> = self
+            .attributes
+            .iter()
+            .filter(|(name, _)| name != "key")
+            .cloned()
+            .collect();
+        if !self.children.is_empty() {
+            let mut children = ElementValue::new(ElementTag::Fragment);
+            children.children = self.children.clone();
+            props.insert("children".to_string(), Value::Element(Rc::new(children)));
+        }
+        Value::record(props)
+    }
+}
+
+/// Elements are displayed like the markup that builds them, with values
+/// other than strings in braces.
+impl fmt::Display for ElementValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match &self.tag {
+            ElementTag::Html(name) => name.clone(),
+            ElementTag::Component(Value::Function(function)) => {
+                function.name().unwrap_or("fn").to_string()
+            }
+            ElementTag::Component(value) => format!("{{{}}}", value),
+            ElementTag::Fragment => String::new(),
+        };
+        write!(f, "<{}", name)?;
+        for (attribute, value) in self.attributes.iter() {
+            match value {
+                Value::String(_) => write!(f, " {}={}", attribute, value)?,
+                value => write!(f, " {}={{{}}}", attribute, value)?,
+            }
+        }
+        if self.children.is_empty() && self.tag != ElementTag::Fragment {
+            return write!(f, " />");
+        }
+        write!(f, ">")?;
+        for child in self.children.iter() {
+            match child {
+                Value::String(string) => write!(f, "{}", string)?,
+                Value::Element(element) => write!(f, "{}", element)?,
+                child => write!(f, "{{{}}}", child)?,
+            }
+        }
+        write!(f, "</{}>", name)
+    }
+}
+
+/// A value built by a variant of a `type`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantValue {
+    // The `type` it belongs to
+    pub ty: String,
+    pub name: String,
+    // The variant's binding, which tells apart variants of the same name
+    pub id: BindingId,
+    pub fields: Vec<Value>,
+}
+
+#[derive(Debug)]
+pub enum Function {
+    Closure(Closure),
+    // A variant with fields, called to build it
+    Constructor(Constructor),
+    // A function compiled to bytecode, run by the VM
+    Compiled(crate::vm::Closure),
+    // A function of the embedding application
+    Native(crate::engine::Native),
+}
+
+impl Function {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Function::Closure(closure) => closure.def.name.as_deref(),
+            Function::Constructor(constructor) => Some(&constructor.name),
+            Function::Compiled(closure) => closure.proto.name.as_deref(),
+            Function::Native(native) => Some(&native.name),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Function::Closure(closure) if closure.def.component => 1,
+            Function::Closure(closure) => closure.def.params.len(),
+            Function::Constructor(constructor) => constructor.arity,
+            Function::Compiled(closure) => closure.proto.arity as usize,
+            Function::Native(native) => native.arity,
+        }
+    }
+}
+
+/// A `fn` statement or lambda with the environment it was created in.
+#[derive(Debug)]
+pub struct Closure {
+    pub(crate) def: Rc<FunctionDef>,
+    pub(crate) env: Rc<Env>,
+}
+
+// The code of a function, shared by every closure created from it
+#[derive(Debug)]
+pub(crate) struct FunctionDef {
+    pub name: Option<String>,
+    pub params: Vec<BindingId>,
+    pub body: Expr,
+    // Takes a record of props, which are bound to the params by name
+    pub component: bool,
+}
+
+#[derive(Debug)]
+pub struct Constructor {
+    pub ty: String,
+    pub name: String,
+    pub id: BindingId,
+    pub arity: usize,
+}
@@ -0,0 +1,64 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+/// Replaces the uses of each copy with the value it copies, and of each phi
+/// that has the same value for every predecessor with that value, and
+/// removes them. Phis whose other values become the same are replaced in
+/// turn. Phis in blocks the entry does not reach can have only each other's
+/// values, and are left as they are.
+pub fn propagate_copies(function: &mut Function) {
+    loop {
+        let mut replacements: HashMap<Value, Value> = HashMap::new();
+        for inst in function.insts() {
+            match &inst.kind {
+                InstKind::Copy(value) => {
+                    replacements.insert(inst.result, *value);
+                }
+                InstKind::Phi(incoming) => {
+                    let mut values = incoming
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .filter(|value| *value != inst.result);
+                    if let Some(first) = values.next() {
+                        if values.all(|value| value == first) {
+                            replacements.insert(inst.result, first);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        // The values of a chain that comes back to itself, which has no
+        // value to end at
+        let mut cyclic = HashSet::new();
+        for start in replacements.keys() {
+            let mut chain = vec![*start];
+            while let Some(next) = replacements.get(chain.last().unwrap()) {
+                if let Some(i) = chain.iter().position(|value| value == next) {
+                    cyclic.extend(chain.drain(i..));
+                    break;
+                }
+                chain.push(*next);
+            }
+        }
+        replacements.retain(|value, _| !cyclic.contains(value));
+        if replacements.is_empty() {
+            return;
+        }
+
+        // The value at the end of a chain of copies
+        let resolve = |mut value: Value| {
+            while let Some(copied) = replacements.get(&value) {
+                value = *copied;
+            }
+            value
+        };
+        function.replace_uses(|value| replacements.contains_key(&value).then(|| resolve(value)));
+        for block in function.blocks.iter_mut() {
+            block
+                .insts
+                .retain(|inst| !replacements.contains_key(&inst.result));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+/// Removes the blocks the entry does not reach, with the values phis had
+/// for them, and the instructions whose values nothing needs. Calls, and
+/// divisions by what may be zero, are kept for their effects.
+pub fn eliminate_dead_code(function: &mut Function) {
+    let reachable: HashSet<BlockId> = function.reachable().into_iter().collect();
+    function
+        .blocks
+        .retain(|block| reachable.contains(&block.id));
+    for inst in function
+        .blocks
+        .iter_mut()
+        .flat_map(|block| block.insts.iter_mut())
+    {
+        if let InstKind::Phi(incoming) = &mut inst.kind {
+            incoming.retain(|(block, _)| reachable.contains(block));
+        }
+    }
+
+    let definitions: HashMap<Value, &InstKind> = function
+        .insts()
+        .map(|inst| (inst.result, &inst.kind))
+        .collect();
+    let divisors: HashSet<Value> = function
+        .insts()
+        .filter(|inst| match inst.kind {
+            InstKind::Const(Const::Number(number)) => number != 0.0,
+            _ => false,
+        })
+        .map(|inst| inst.result)
+        .collect();
+    let needed = |kind: &InstKind| match kind {
+        InstKind::Binary(op, _, rhs) if op.traps() => !divisors.contains(rhs),
+        kind => kind.has_effects(),
+    };
+
+    // The values the terminators and the needed instructions use, and those
+    // the instructions that define them use in turn
+    let mut live = HashSet::new();
+    let mut work = vec![];
+    for block in function.blocks.iter() {
+        work.extend(block.terminator.operand());
+        for inst in block.insts.iter().filter(|inst| needed(&inst.kind)) {
+            work.push(inst.result);
+        }
+    }
+    while let Some(value) = work.pop() {
+        if !live.insert(value) {
+            continue;
+        }
+        if let Some(kind) = definitions.get(&value) {
+            work.extend(kind.operands());
+        }
+    }
+
+    for block in function.blocks.iter_mut() {
+        block.insts.retain(|inst| live.contains(&inst.result));
+    }
+}
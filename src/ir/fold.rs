@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// Replaces the instructions whose operands are constants with the constant
+/// they give, and branches on a constant with a jump to the block it picks.
+/// Dividing by zero is left to trap when the function runs. The blocks that
+/// are no longer reached are left for dead code elimination.
+pub fn fold_constants(function: &mut Function) {
+    let mut constants: HashMap<Value, Const> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for inst in function
+            .blocks
+            .iter_mut()
+            .flat_map(|block| block.insts.iter_mut())
+        {
+            if let InstKind::Const(value) = &inst.kind {
+                constants.insert(inst.result, value.clone());
+            } else if let Some(value) = fold(&inst.kind, &constants) {
+                constants.insert(inst.result, value.clone());
+                inst.kind = InstKind::Const(value);
+                changed = true;
+            }
+        }
+
+        // The edges not taken, whose values the phis they went to lose
+        let mut removed = vec![];
+        for block in function.blocks.iter_mut() {
+            let Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } = block.terminator
+            else {
+                continue;
+            };
+            let Some(Const::Bool(condition)) = constants.get(&condition) else {
+                continue;
+            };
+            let (taken, skipped) = match condition {
+                true => (then, otherwise),
+                false => (otherwise, then),
+            };
+            block.terminator = Terminator::Jump(taken);
+            if skipped != taken {
+                removed.push((block.id, skipped));
+            }
+            changed = true;
+        }
+        for (from, to) in removed {
+            let Some(block) = function.block_mut(to) else {
+                continue;
+            };
+            for inst in block.insts.iter_mut() {
+                if let InstKind::Phi(incoming) = &mut inst.kind {
+                    incoming.retain(|(block, _)| *block != from);
+                }
+            }
+        }
+    }
+}
+
+// The constant an instruction gives, if its operands are known
+fn fold(kind: &InstKind, constants: &HashMap<Value, Const>) -> Option<Const> {
+    use Const::{Bool, Number};
+
+    match kind {
+        InstKind::Unary(op, operand) => match (op, constants.get(operand)?) {
+            (UnaryOp::Neg, Number(number)) => Some(Number(-number)),
+            (UnaryOp::Not, Bool(bool)) => Some(Bool(!bool)),
+            _ => None,
+        },
+        InstKind::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (constants.get(lhs)?, constants.get(rhs)?);
+            match (op, lhs, rhs) {
+                (BinaryOp::Eq, lhs, rhs) => Some(Bool(lhs == rhs)),
+                (BinaryOp::Ne, lhs, rhs) => Some(Bool(lhs != rhs)),
+                (BinaryOp::Concat, Const::String(lhs), Const::String(rhs)) => {
+                    Some(Const::String(format!("{}{}", lhs, rhs)))
+                }
+                (op, Number(a), Number(b)) => match op {
+                    BinaryOp::Add => Some(Number(a + b)),
+                    BinaryOp::Sub => Some(Number(a - b)),
+                    BinaryOp::Mul => Some(Number(a * b)),
+                    BinaryOp::Div | BinaryOp::Rem if *b == 0.0 => None,
+                    BinaryOp::Div => Some(Number(a / b)),
+                    BinaryOp::Rem => Some(Number(a % b)),
+                    BinaryOp::Lt => Some(Bool(a < b)),
+                    BinaryOp::Le => Some(Bool(a <= b)),
+                    BinaryOp::Gt => Some(Bool(a > b)),
+                    BinaryOp::Ge => Some(Bool(a >= b)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        // Where every predecessor brings the same constant
+        InstKind::Phi(incoming) => {
+            let (first, rest) = incoming.split_first()?;
+            let value = constants.get(&first.1)?;
+            rest.iter()
+                .all(|(_, other)| constants.get(other).is_some_and(|other| other.same(value)))
+                .then(|| value.clone())
+        }
+        InstKind::Const(_) | InstKind::Copy(_) | InstKind::Call(..) => None,
+    }
+}
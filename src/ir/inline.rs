@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+/// The most instructions other than phis a function can have to be inlined.
+pub const INLINE_LIMIT: usize = 12;
+
+/// Replaces the calls of small functions that do not call themselves with
+/// copies of their blocks. The parameters are copies of the arguments, and
+/// the returns jump to a block after the call, whose phi is the value of
+/// the call. The calls in the copied blocks are left as they are, so that
+/// functions that call each other are inlined once.
+pub fn inline(module: &mut Module) {
+    let callees = module.functions.clone();
+    let inlinable: Vec<bool> = callees
+        .iter()
+        .enumerate()
+        .map(|(index, function)| is_inlinable(index, function))
+        .collect();
+    for (index, function) in module.functions.iter_mut().enumerate() {
+        // The blocks copied from callees, whose calls are left alone
+        let mut copied = HashSet::new();
+        let mut b = 0;
+        while b < function.blocks.len() {
+            let block = &function.blocks[b];
+            let call = block
+                .insts
+                .iter()
+                .enumerate()
+                .find_map(|(i, inst)| match inst.kind {
+                    InstKind::Call(Callee::Function(callee), _)
+                        if callee as usize != index && inlinable[callee as usize] =>
+                    {
+                        Some((i, callee as usize))
+                    }
+                    _ => None,
+                });
+            match call {
+                Some((i, callee)) if !copied.contains(&block.id) => {
+                    copied.extend(inline_call(function, b, i, &callees[callee]));
+                }
+                _ => b += 1,
+            }
+        }
+    }
+}
+
+fn is_inlinable(index: usize, function: &Function) -> bool {
+    let insts: Vec<&Inst> = function
+        .insts()
+        .filter(|inst| !matches!(inst.kind, InstKind::Phi(_)))
+        .collect();
+    let recursive = insts
+        .iter()
+        .any(|inst| matches!(inst.kind, InstKind::Call(Callee::Function(callee), _) if callee as usize == index));
+    insts.len() <= INLINE_LIMIT && !recursive
+}
+
+// Inlines the call at `i` of the block at `b`, and returns the ids of the
+// blocks copied from the callee
+fn inline_call(function: &mut Function, b: usize, i: usize, callee: &Function) -> Vec<BlockId> {
+    let base = function.block_id().0;
+    let after = BlockId(base);
+    let block_id = |id: BlockId| BlockId(base + 1 + id.0);
+
+    let mut values = HashMap::new();
+    for param in callee.params.iter() {
+        values.insert(*param, function.value(callee.type_of(*param)));
+    }
+    for inst in callee.insts() {
+        values.insert(inst.result, function.value(callee.type_of(inst.result)));
+    }
+
+    // The instructions after the call go to the block after it, which the
+    // phis of the blocks it goes to then have values for
+    let block = &mut function.blocks[b];
+    let rest = block.insts.split_off(i + 1);
+    let call = block.insts.pop().unwrap();
+    let InstKind::Call(_, args) = call.kind else {
+        unreachable!("inlining a call")
+    };
+    let entry = block_id(callee.blocks[0].id);
+    let terminator = std::mem::replace(&mut block.terminator, Terminator::Jump(entry));
+    for (param, arg) in callee.params.iter().zip(args) {
+        block.insts.push(Inst {
+            result: values[param],
+            kind: InstKind::Copy(arg),
+        });
+    }
+    let from = block.id;
+    for successor in terminator.successors() {
+        let insts = function
+            .block_mut(successor)
+            .into_iter()
+            .flat_map(|block| block.insts.iter_mut());
+        for inst in insts {
+            if let InstKind::Phi(incoming) = &mut inst.kind {
+                for (block, _) in incoming.iter_mut().filter(|(block, _)| *block == from) {
+                    *block = after;
+                }
+            }
+        }
+    }
+
+    let mut returns = vec![];
+    let mut blocks = vec![];
+    for block in callee.blocks.iter() {
+        let id = block_id(block.id);
+        let mut copy = Block::new(id, block.terminator.clone());
+        for inst in block.insts.iter() {
+            let mut kind = inst.kind.clone();
+            for operand in kind.operands_mut() {
+                *operand = values[operand];
+            }
+            if let InstKind::Phi(incoming) = &mut kind {
+                for (block, _) in incoming.iter_mut() {
+                    *block = block_id(*block);
+                }
+            }
+            copy.insts.push(Inst {
+                result: values[&inst.result],
+                kind,
+            });
+        }
+        copy.terminator = match &block.terminator {
+            Terminator::Jump(target) => Terminator::Jump(block_id(*target)),
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => Terminator::Branch {
+                condition: values[condition],
+                then: block_id(*then),
+                otherwise: block_id(*otherwise),
+            },
+            Terminator::Return(value) => {
+                returns.push((id, values[value]));
+                Terminator::Jump(after)
+            }
+            Terminator::Unreachable => Terminator::Unreachable,
+        };
+        blocks.push(copy);
+    }
+
+    let mut after = Block::new(after, terminator);
+    after.insts.push(Inst {
+        result: call.result,
+        kind: InstKind::Phi(returns),
+    });
+    after.insts.extend(rest);
+    let copied = blocks.iter().map(|block| block.id).collect();
+    blocks.push(after);
+    function.blocks.splice(b + 1..b + 1, blocks);
+    copied
+}
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    self, BinaryExpr, CallExpr, Expr, ExprKind, FnStmt, IfExpr, LetStmt, LiteralExpr, MatchExpr,
+    Pattern, PatternKind, Program, StateStmt, Stmt, StmtKind,
+};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+use crate::resolve::{BindingId, BindingKind, Resolution};
+use crate::types::{Type, Typing};
+
+use super::*;
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+/// Lowers the top-level functions of a checked program to a module with a
+/// function for each, in SSA form. Bindings are the values they are bound
+/// to, and those assigned in a branch meet in a phi after it. `&&`, `||`,
+/// `if` and `match` are branches, and matching no arm is unreachable.
+///
+/// The other top-level statements are left out. The errors are for the
+/// functions that use anything but numbers, bools, strings and calls of
+/// top-level or host functions, or that are generic.
+pub fn lower_program(
+    program: &Program,
+    resolution: &Resolution,
+    typing: &Typing,
+) -> std::result::Result<Module, Vec<Diagnostic>> {
+    let mut module = Module::default();
+    let mut diagnostics = vec![];
+    for function in lower_functions(program, resolution, typing) {
+        match function {
+            Ok(function) => module.functions.push(function),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    match diagnostics.is_empty() {
+        true => Ok(module),
+        false => Err(diagnostics),
+    }
+}
+
+/// Lowers each top-level function of a checked program on its own, in the
+/// order they are declared, for backends that report what they cannot
+/// compile of the functions that do lower. Calls refer to functions by
+/// their index in this order.
+pub fn lower_functions(
+    program: &Program,
+    resolution: &Resolution,
+    typing: &Typing,
+) -> Vec<std::result::Result<Function, Diagnostic>> {
+    let functions: Vec<(Position, &FnStmt)> = program
+        .stmts
+        .iter()
+        .map(Stmt::declaration)
+        .filter_map(|stmt| match &stmt.kind {
+            StmtKind::Fn(fn_stmt) => Some((stmt.pos, fn_stmt)),
+            _ => None,
+        })
+        .collect();
+    // Numbered first, so that functions can call those declared after them
+    let indices = functions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (pos, _))| Some((*resolution.definitions.get(pos)?, i as u32)))
+        .collect();
+    let lowering = Lowering {
+        resolution,
+        typing,
+        indices,
+    };
+    functions
+        .into_iter()
+        .map(|(pos, fn_stmt)| lowering.function(pos, fn_stmt))
+        .collect()
+}
+
+struct Lowering<'a> {
+    resolution: &'a Resolution,
+    typing: &'a Typing,
+    // Of the top-level functions, by binding
+    indices: HashMap<BindingId, u32>,
+}
+
+impl Lowering<'_> {
+    fn function(&self, pos: Position, fn_stmt: &FnStmt) -> Result<Function> {
+        let scheme = self
+            .resolution
+            .definitions
+            .get(&pos)
+            .and_then(|id| self.typing.binding_type(*id));
+        let Some(scheme) = scheme else {
+            return Err(Diagnostic::error(
+                format!("`{}` has no type", fn_stmt.name),
+                pos,
+            ));
+        };
+        let Type::Fn(params, ret) = &scheme.ty else {
+            unreachable!("functions have function types")
+        };
+        if !scheme.vars.is_empty() {
+            return Err(Diagnostic::error(
+                format!(
+                    "`{}` is generic, with the type `{}`, which functions of the IR cannot be",
+                    fn_stmt.name, scheme.ty
+                ),
+                pos,
+            ));
+        }
+
+        let mut builder = Builder {
+            lowering: self,
+            function: Function::new(fn_stmt.name.clone(), value_type(ret, pos)?),
+            current: 0,
+            next_block: 0,
+            bindings: HashMap::new(),
+            pending: HashMap::new(),
+        };
+        for (param, ty) in fn_stmt.params.iter().zip(params) {
+            let value = builder.function.value(value_type(ty, param.pos)?);
+            builder.function.params.push(value);
+            let id = self.resolution.definitions[&param.pos];
+            builder.bindings.insert(id, value);
+        }
+        let entry = builder.new_block();
+        builder.enter(entry, None);
+        let value = builder.expr(&fn_stmt.body)?;
+        builder.terminate(Terminator::Return(value));
+        Ok(builder.function)
+    }
+}
+
+// The IR type of values of type `ty`
+fn value_type(ty: &Type, pos: Position) -> Result<ValueType> {
+    match ty {
+        Type::Number => Ok(ValueType::Number),
+        Type::Bool => Ok(ValueType::Bool),
+        Type::String => Ok(ValueType::String),
+        // A type the checker left open is one nothing constrains, like that
+        // of what the host's `print` returns, whose values are all `Unit`
+        Type::Unit | Type::Var(_) => Ok(ValueType::Unit),
+        Type::Fn(..) => Err(unsupported("functions as values", pos)),
+        ty => Err(Diagnostic::error(
+            format!("values of type `{}` cannot be lowered to the IR", ty),
+            pos,
+        )),
+    }
+}
+
+fn constant(literal: &LiteralExpr) -> Const {
+    match literal {
+        LiteralExpr::Number(number) => Const::Number(*number as f64),
+        LiteralExpr::Bool(bool) => Const::Bool(*bool),
+        LiteralExpr::String(string) => Const::String(string.clone()),
+    }
+}
+
+fn unsupported(what: &str, pos: Position) -> Diagnostic {
+    Diagnostic::error(format!("{} cannot be lowered to the IR", what), pos)
+}
+
+// The way from the end of one block to the start of another, with the value
+// of each binding there, and the value it brings to the other block
+struct Edge {
+    from: BlockId,
+    bindings: HashMap<BindingId, Value>,
+    value: Option<Value>,
+}
+
+// The blocks of one function. As there are no loops, every edge to a block
+// is known by the time its instructions are lowered.
+struct Builder<'l, 'a> {
+    lowering: &'l Lowering<'a>,
+    function: Function,
+    // The index of the block being lowered
+    current: usize,
+    next_block: u32,
+    // The value of each binding in scope
+    bindings: HashMap<BindingId, Value>,
+    // The edges to the blocks not lowered yet
+    pending: HashMap<BlockId, Vec<Edge>>,
+}
+
+impl Builder<'_, '_> {
+    fn new_block(&mut self) -> BlockId {
+        self.next_block += 1;
+        BlockId(self.next_block - 1)
+    }
+
+    fn emit(&mut self, ty: ValueType, kind: InstKind) -> Value {
+        let result = self.function.value(ty);
+        self.function.blocks[self.current]
+            .insts
+            .push(Inst { result, kind });
+        result
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        self.function.blocks[self.current].terminator = terminator;
+    }
+
+    fn edge(&mut self, to: BlockId, value: Option<Value>) {
+        let edge = Edge {
+            from: self.function.blocks[self.current].id,
+            bindings: self.bindings.clone(),
+            value,
+        };
+        self.pending.entry(to).or_default().push(edge);
+    }
+
+    fn jump(&mut self, to: BlockId, value: Option<Value>) {
+        self.edge(to, value);
+        self.terminate(Terminator::Jump(to));
+    }
+
+    fn branch(&mut self, condition: Value, then: BlockId, otherwise: BlockId) {
+        self.edge(then, None);
+        self.edge(otherwise, None);
+        self.terminate(Terminator::Branch {
+            condition,
+            then,
+            otherwise,
+        });
+    }
+
+    // Starts lowering into the block, with the bindings that have different
+    // values on the edges to it, and the value they bring if `ty` is given,
+    // as phis
+    fn enter(&mut self, id: BlockId, ty: Option<ValueType>) -> Option<Value> {
+        let edges = self.pending.remove(&id).unwrap_or_default();
+        self.function
+            .blocks
+            .push(Block::new(id, Terminator::Unreachable));
+        self.current = self.function.blocks.len() - 1;
+
+        let value = match (ty, edges.as_slice()) {
+            (Some(_), [edge]) => edge.value,
+            (Some(ty), edges) => {
+                let incoming = edges
+                    .iter()
+                    .map(|edge| (edge.from, edge.value.expect("a value on every edge")))
+                    .collect();
+                Some(self.emit(ty, InstKind::Phi(incoming)))
+            }
+            (None, _) => None,
+        };
+
+        let Some((first, rest)) = edges.split_first() else {
+            return value;
+        };
+        // Those in scope on every edge, of which the others are not
+        let mut ids: Vec<BindingId> = first
+            .bindings
+            .keys()
+            .filter(|id| rest.iter().all(|edge| edge.bindings.contains_key(id)))
+            .copied()
+            .collect();
+        ids.sort();
+        let mut bindings = HashMap::new();
+        for id in ids {
+            let incoming: Vec<(BlockId, Value)> = edges
+                .iter()
+                .map(|edge| (edge.from, edge.bindings[&id]))
+                .collect();
+            let binding = match incoming.iter().all(|(_, value)| *value == incoming[0].1) {
+                true => incoming[0].1,
+                false => {
+                    let ty = self.function.type_of(incoming[0].1);
+                    self.emit(ty, InstKind::Phi(incoming))
+                }
+            };
+            bindings.insert(id, binding);
+        }
+        self.bindings = bindings;
+        value
+    }
+
+    fn type_of(&self, expr: &Expr) -> Result<ValueType> {
+        match self.lowering.typing.type_of(&expr.pos) {
+            Some(ty) => value_type(ty, expr.pos),
+            None => Err(Diagnostic::error(
+                "the type of this expression is not known",
+                expr.pos,
+            )),
+        }
+    }
+
+    fn unit(&mut self) -> Value {
+        self.emit(ValueType::Unit, InstKind::Const(Const::Unit))
+    }
+
+    // The value of an expression a binding is bound to. One that is another
+    // binding is copied, so that each binding has a value of its own until
+    // copy propagation.
+    fn bound(&mut self, expr: &Expr) -> Result<Value> {
+        let value = self.expr(expr)?;
+        match expr.kind {
+            ExprKind::Identifier(_) => {
+                let ty = self.function.type_of(value);
+                Ok(self.emit(ty, InstKind::Copy(value)))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    // The value of the last statement, or `Unit`
+    fn stmts(&mut self, stmts: &[Stmt]) -> Result<Value> {
+        let mut last = None;
+        for stmt in stmts {
+            last = None;
+            match &stmt.kind {
+                StmtKind::Expr(expr) => last = Some(self.expr(expr)?),
+                StmtKind::Let(LetStmt { value, .. }) | StmtKind::State(StateStmt { value, .. }) => {
+                    let value = self.bound(value)?;
+                    let id = self.lowering.resolution.definitions[&stmt.pos];
+                    self.bindings.insert(id, value);
+                }
+                StmtKind::Fn(_) | StmtKind::Component(_) => {
+                    return Err(unsupported("nested functions", stmt.pos))
+                }
+                StmtKind::Type(_) => return Err(unsupported("types", stmt.pos)),
+                StmtKind::Import(_) | StmtKind::Export(_) => {
+                    return Err(unsupported("modules", stmt.pos))
+                }
+            }
+        }
+        match last {
+            Some(value) => Ok(value),
+            None => Ok(self.unit()),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Value> {
+        let value = match &expr.kind {
+            ExprKind::Literal(literal) => {
+                let value = constant(literal);
+                self.emit(value.ty(), InstKind::Const(value))
+            }
+            ExprKind::Identifier(identifier) => {
+                let id = self.lowering.resolution.uses.get(&expr.pos);
+                match id.and_then(|id| self.bindings.get(id)) {
+                    Some(value) => *value,
+                    None if id.is_some_and(|id| self.lowering.indices.contains_key(id)) => {
+                        return Err(unsupported("functions as values", expr.pos))
+                    }
+                    None => {
+                        return Err(Diagnostic::error(
+                            format!(
+                                "`{}` cannot be lowered to the IR, as only parameters, \
+                                 local bindings and top-level functions can",
+                                identifier.ident
+                            ),
+                            expr.pos,
+                        ))
+                    }
+                }
+            }
+            ExprKind::Block(stmts) => self.stmts(stmts)?,
+            ExprKind::Binary(binary) => self.binary(binary)?,
+            ExprKind::Unary(unary) => {
+                let operand = self.expr(&unary.operand)?;
+                match unary.op {
+                    ast::UnaryOp::Neg => {
+                        self.emit(ValueType::Number, InstKind::Unary(UnaryOp::Neg, operand))
+                    }
+                    ast::UnaryOp::Not => {
+                        self.emit(ValueType::Bool, InstKind::Unary(UnaryOp::Not, operand))
+                    }
+                }
+            }
+            ExprKind::If(if_expr) => self.if_expr(expr, if_expr)?,
+            ExprKind::Call(call) => self.call(expr, call)?,
+            ExprKind::Match(match_expr) => self.match_expr(expr, match_expr)?,
+            ExprKind::Assign(assign) => {
+                let id = self.lowering.resolution.uses.get(&assign.target.pos);
+                let Some(id) = id.filter(|id| self.bindings.contains_key(id)).copied() else {
+                    return Err(unsupported("assigning this", assign.target.pos));
+                };
+                let value = self.bound(&assign.value)?;
+                self.bindings.insert(id, value);
+                self.unit()
+            }
+            ExprKind::List(_) | ExprKind::Index(_) => return Err(unsupported("lists", expr.pos)),
+            ExprKind::Record(_) | ExprKind::Field(_) => {
+                return Err(unsupported("records", expr.pos))
+            }
+            ExprKind::Lambda(_) => return Err(unsupported("lambdas", expr.pos)),
+            ExprKind::Element(_) => return Err(unsupported("elements", expr.pos)),
+        };
+        Ok(value)
+    }
+
+    fn binary(&mut self, binary: &BinaryExpr) -> Result<Value> {
+        let lhs = self.expr(&binary.lhs)?;
+        // The right side is only evaluated if needed, in a block of its own
+        if let ast::BinaryOp::And | ast::BinaryOp::Or = binary.op {
+            let rhs_block = self.new_block();
+            let join = self.new_block();
+            self.edge(join, Some(lhs));
+            self.edge(rhs_block, None);
+            let (then, otherwise) = match binary.op {
+                ast::BinaryOp::And => (rhs_block, join),
+                _ => (join, rhs_block),
+            };
+            self.terminate(Terminator::Branch {
+                condition: lhs,
+                then,
+                otherwise,
+            });
+            self.enter(rhs_block, None);
+            let rhs = self.expr(&binary.rhs)?;
+            self.jump(join, Some(rhs));
+            return Ok(self.enter(join, Some(ValueType::Bool)).unwrap());
+        }
+
+        let rhs = self.expr(&binary.rhs)?;
+        let operands = self.function.type_of(lhs);
+        let (op, ty) = match binary.op {
+            ast::BinaryOp::Add if operands == ValueType::String => {
+                (BinaryOp::Concat, ValueType::String)
+            }
+            ast::BinaryOp::Add => (BinaryOp::Add, ValueType::Number),
+            ast::BinaryOp::Sub => (BinaryOp::Sub, ValueType::Number),
+            ast::BinaryOp::Mul => (BinaryOp::Mul, ValueType::Number),
+            ast::BinaryOp::Div => (BinaryOp::Div, ValueType::Number),
+            ast::BinaryOp::Rem => (BinaryOp::Rem, ValueType::Number),
+            ast::BinaryOp::Eq => (BinaryOp::Eq, ValueType::Bool),
+            ast::BinaryOp::Ne => (BinaryOp::Ne, ValueType::Bool),
+            ast::BinaryOp::Lt => (BinaryOp::Lt, ValueType::Bool),
+            ast::BinaryOp::Le => (BinaryOp::Le, ValueType::Bool),
+            ast::BinaryOp::Gt => (BinaryOp::Gt, ValueType::Bool),
+            ast::BinaryOp::Ge => (BinaryOp::Ge, ValueType::Bool),
+            ast::BinaryOp::And | ast::BinaryOp::Or => unreachable!("branched on above"),
+        };
+        Ok(self.emit(ty, InstKind::Binary(op, lhs, rhs)))
+    }
+
+    fn if_expr(&mut self, expr: &Expr, if_expr: &IfExpr) -> Result<Value> {
+        let condition = self.expr(&if_expr.condition)?;
+        let then = self.new_block();
+        let Some(otherwise) = &if_expr.otherwise else {
+            // Without an `else`, the value is `Unit`
+            let join = self.new_block();
+            self.branch(condition, then, join);
+            self.enter(then, None);
+            self.expr(&if_expr.then)?;
+            self.jump(join, None);
+            self.enter(join, None);
+            return Ok(self.unit());
+        };
+        let else_block = self.new_block();
+        let join = self.new_block();
+        self.branch(condition, then, else_block);
+        self.enter(then, None);
+        let value = self.expr(&if_expr.then)?;
+        self.jump(join, Some(value));
+        self.enter(else_block, None);
+        let value = self.expr(otherwise)?;
+        self.jump(join, Some(value));
+        Ok(self.enter(join, Some(self.type_of(expr)?)).unwrap())
+    }
+
+    fn call(&mut self, expr: &Expr, call: &CallExpr) -> Result<Value> {
+        let resolution = self.lowering.resolution;
+        let id = match &call.called.kind {
+            ExprKind::Identifier(_) => resolution.uses.get(&call.called.pos),
+            _ => None,
+        };
+        let callee = match id {
+            Some(id) if self.lowering.indices.contains_key(id) => {
+                Callee::Function(self.lowering.indices[id])
+            }
+            Some(id) if resolution.binding(*id).kind == BindingKind::Host => {
+                Callee::Host(resolution.binding(*id).name.clone())
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    "only calls of top-level and host functions can be lowered to the IR",
+                    call.called.pos,
+                ))
+            }
+        };
+        let mut args = vec![];
+        for arg in call.args.iter() {
+            args.push(self.expr(arg)?);
+        }
+        let ty = self.type_of(expr)?;
+        Ok(self.emit(ty, InstKind::Call(callee, args)))
+    }
+
+    // The arms are tried in turn, each in a block of its own, and the blocks
+    // of their bodies go to the block after the `match`
+    fn match_expr(&mut self, expr: &Expr, match_expr: &MatchExpr) -> Result<Value> {
+        let subject = self.expr(&match_expr.scrutinee)?;
+        let join = self.new_block();
+        let mut exhaustive = false;
+        for arm in match_expr.arms.iter() {
+            let test = self.pattern(&arm.pattern, subject)?;
+            if test.is_none() && arm.guard.is_none() {
+                let value = self.expr(&arm.body)?;
+                self.jump(join, Some(value));
+                // The arms after this one are never reached
+                exhaustive = true;
+                break;
+            }
+            let next = self.new_block();
+            if let Some(test) = test {
+                let matched = self.new_block();
+                self.branch(test, matched, next);
+                self.enter(matched, None);
+            }
+            if let Some(guard) = &arm.guard {
+                let guard = self.expr(guard)?;
+                let passed = self.new_block();
+                self.branch(guard, passed, next);
+                self.enter(passed, None);
+            }
+            let value = self.expr(&arm.body)?;
+            self.jump(join, Some(value));
+            self.enter(next, None);
+        }
+        if !exhaustive {
+            self.terminate(Terminator::Unreachable);
+        }
+        Ok(self.enter(join, Some(self.type_of(expr)?)).unwrap())
+    }
+
+    // Whether the subject matches, or `None` for a pattern that always does
+    fn pattern(&mut self, pattern: &Pattern, subject: Value) -> Result<Option<Value>> {
+        let resolution = self.lowering.resolution;
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(None),
+            PatternKind::Identifier(_) if resolution.uses.contains_key(&pattern.pos) => {
+                Err(unsupported("variants", pattern.pos))
+            }
+            PatternKind::Identifier(_) => {
+                let id = resolution.definitions[&pattern.pos];
+                self.bindings.insert(id, subject);
+                Ok(None)
+            }
+            PatternKind::Literal(literal) => {
+                let value = constant(literal);
+                let value = self.emit(value.ty(), InstKind::Const(value));
+                let test = InstKind::Binary(BinaryOp::Eq, subject, value);
+                Ok(Some(self.emit(ValueType::Bool, test)))
+            }
+            PatternKind::Variant(..) => Err(unsupported("variants", pattern.pos)),
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! An SSA form of typed functions, with a verifier and the passes that
+//! optimize it. The wasm backend compiles optimized modules, and the C and
+//! JavaScript backends still lower the AST themselves.
+
+pub mod copy;
+pub mod dce;
+pub mod fold;
+pub mod inline;
+pub mod lower;
+#[cfg(test)]
+mod tests;
+mod text;
+pub mod verify;
+
+use std::fmt;
+
+pub use self::copy::*;
+pub use self::dce::*;
+pub use self::fold::*;
+pub use self::inline::*;
+pub use self::lower::*;
+pub use self::verify::*;
+
+/// The types of values. Every value has one, and so do the operands each
+/// instruction takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Number,
+    Bool,
+    String,
+    Unit,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Number => "number",
+            ValueType::Bool => "bool",
+            ValueType::String => "string",
+            ValueType::Unit => "unit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A value of a function, defined once, by a parameter or an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Value(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub u32);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Const {
+    Number(f64),
+    Bool(bool),
+    String(String),
+    Unit,
+}
+
+impl Const {
+    pub fn ty(&self) -> ValueType {
+        match self {
+            Const::Number(_) => ValueType::Number,
+            Const::Bool(_) => ValueType::Bool,
+            Const::String(_) => ValueType::String,
+            Const::Unit => ValueType::Unit,
+        }
+    }
+
+    /// Whether the constants are the same value, unlike `==`, which has
+    /// `NaN` differ from itself and `0` be the same as `-0`.
+    pub fn same(&self, other: &Const) -> bool {
+        match (self, other) {
+            (Const::Number(a), Const::Number(b)) => a.to_bits() == b.to_bits(),
+            (a, b) => a == b,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+/// The operators of the source, other than `&&` and `||`, which are
+/// branches, and with `+` on strings as a `Concat` of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinaryOp {
+    /// Whether the operator can stop the program, as `/` and `%` do when
+    /// dividing by zero.
+    pub fn traps(&self) -> bool {
+        matches!(self, BinaryOp::Div | BinaryOp::Rem)
+    }
+}
+
+/// What a call calls: a function of the module by index, or one of the
+/// host's by name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Callee {
+    Function(u32),
+    Host(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstKind {
+    Const(Const),
+    Copy(Value),
+    Unary(UnaryOp, Value),
+    Binary(BinaryOp, Value, Value),
+    Call(Callee, Vec<Value>),
+    // The value each predecessor of the block brings
+    Phi(Vec<(BlockId, Value)>),
+}
+
+impl InstKind {
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            InstKind::Const(_) => vec![],
+            InstKind::Copy(value) | InstKind::Unary(_, value) => vec![*value],
+            InstKind::Binary(_, lhs, rhs) => vec![*lhs, *rhs],
+            InstKind::Call(_, args) => args.clone(),
+            InstKind::Phi(incoming) => incoming.iter().map(|(_, value)| *value).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            InstKind::Const(_) => vec![],
+            InstKind::Copy(value) | InstKind::Unary(_, value) => vec![value],
+            InstKind::Binary(_, lhs, rhs) => vec![lhs, rhs],
+            InstKind::Call(_, args) => args.iter_mut().collect(),
+            InstKind::Phi(incoming) => incoming.iter_mut().map(|(_, value)| value).collect(),
+        }
+    }
+
+    /// Whether the instruction does more than define its value, so that it
+    /// must run even if the value is not used.
+    pub fn has_effects(&self) -> bool {
+        match self {
+            InstKind::Call(..) => true,
+            InstKind::Binary(op, ..) => op.traps(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inst {
+    pub result: Value,
+    pub kind: InstKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch {
+        condition: Value,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+    Return(Value),
+    // Where matching no arm traps
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Branch { condition, .. } => vec![condition],
+            Terminator::Return(value) => vec![value],
+            Terminator::Jump(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn operand(&self) -> Option<Value> {
+        match self {
+            Terminator::Branch { condition, .. } => Some(*condition),
+            Terminator::Return(value) => Some(*value),
+            Terminator::Jump(_) | Terminator::Unreachable => None,
+        }
+    }
+}
+
+/// Instructions that run in order, the phis first, and the terminator that
+/// ends them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub id: BlockId,
+    pub insts: Vec<Inst>,
+    pub terminator: Terminator,
+}
+
+impl Block {
+    pub fn new(id: BlockId, terminator: Terminator) -> Self {
+        Self {
+            id,
+            insts: vec![],
+            terminator,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Value>,
+    pub ret: ValueType,
+    // In the order of the text format, the first one the entry
+    pub blocks: Vec<Block>,
+    // The type of each value, by number
+    pub values: Vec<ValueType>,
+}
+
+impl Function {
+    pub fn new(name: impl Into<String>, ret: ValueType) -> Self {
+        Self {
+            name: name.into(),
+            params: vec![],
+            ret,
+            blocks: vec![],
+            values: vec![],
+        }
+    }
+
+    pub fn value(&mut self, ty: ValueType) -> Value {
+        self.values.push(ty);
+        Value((self.values.len() - 1) as u32)
+    }
+
+    pub fn type_of(&self, value: Value) -> ValueType {
+        self.values[value.0 as usize]
+    }
+
+    /// An id no block has yet.
+    pub fn block_id(&self) -> BlockId {
+        let next = self.blocks.iter().map(|block| block.id.0 + 1).max();
+        BlockId(next.unwrap_or(0))
+    }
+
+    pub fn block(&self, id: BlockId) -> Option<&Block> {
+        self.blocks.iter().find(|block| block.id == id)
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> Option<&mut Block> {
+        self.blocks.iter_mut().find(|block| block.id == id)
+    }
+
+    pub fn insts(&self) -> impl Iterator<Item = &Inst> {
+        self.blocks.iter().flat_map(|block| block.insts.iter())
+    }
+
+    /// The blocks the entry reaches, in the order of a depth-first search
+    /// that visits successors after the blocks they follow.
+    pub fn reachable(&self) -> Vec<BlockId> {
+        let Some(entry) = self.blocks.first() else {
+            return vec![];
+        };
+        let mut order = vec![];
+        let mut stack = vec![entry.id];
+        while let Some(id) = stack.pop() {
+            if order.contains(&id) {
+                continue;
+            }
+            order.push(id);
+            if let Some(block) = self.block(id) {
+                stack.extend(block.terminator.successors().into_iter().rev());
+            }
+        }
+        order
+    }
+
+    /// The blocks that end going to `id`, each once.
+    pub fn predecessors(&self, id: BlockId) -> Vec<BlockId> {
+        self.blocks
+            .iter()
+            .filter(|block| block.terminator.successors().contains(&id))
+            .map(|block| block.id)
+            .collect()
+    }
+
+    /// Replaces each use of a value with the one `replace` gives for it,
+    /// if any.
+    pub fn replace_uses(&mut self, replace: impl Fn(Value) -> Option<Value>) {
+        for block in self.blocks.iter_mut() {
+            let operands = block
+                .insts
+                .iter_mut()
+                .flat_map(|inst| inst.kind.operands_mut());
+            for operand in operands.chain(block.terminator.operands_mut()) {
+                if let Some(value) = replace(*operand) {
+                    *operand = value;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Module {
+    pub functions: Vec<Function>,
+}
+
+/// The passes over a module, which leave it well-formed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    FoldConstants,
+    EliminateDeadCode,
+    PropagateCopies,
+    Inline,
+}
+
+impl Pass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::FoldConstants => "fold",
+            Pass::EliminateDeadCode => "dce",
+            Pass::PropagateCopies => "copy",
+            Pass::Inline => "inline",
+        }
+    }
+
+    pub fn run(&self, module: &mut Module) {
+        match self {
+            Pass::FoldConstants => module.functions.iter_mut().for_each(fold_constants),
+            Pass::EliminateDeadCode => module.functions.iter_mut().for_each(eliminate_dead_code),
+            Pass::PropagateCopies => module.functions.iter_mut().for_each(propagate_copies),
+            Pass::Inline => inline(module),
+        }
+    }
+}
+
+/// The passes `optimize` runs, in order. Those after inlining run twice, as
+/// phis for blocks that folding leaves unreached only lose their values
+/// once the blocks are eliminated.
+pub const PIPELINE: &[Pass] = &[
+    Pass::Inline,
+    Pass::PropagateCopies,
+    Pass::FoldConstants,
+    Pass::EliminateDeadCode,
+    Pass::PropagateCopies,
+    Pass::FoldConstants,
+    Pass::EliminateDeadCode,
+];
+
+/// Runs the passes in order, verifying the module before the first one and
+/// after each of them.
+pub fn run_passes(module: &mut Module, passes: &[Pass]) -> Result<(), VerifyError> {
+    verify(module)?;
+    for pass in passes {
+        pass.run(module);
+        verify(module).map_err(|error| error.after(*pass))?;
+    }
+    Ok(())
+}
+
+/// Runs the `PIPELINE` over a module.
+pub fn optimize(module: &mut Module) -> Result<(), VerifyError> {
+    run_passes(module, PIPELINE)
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::*;
+use crate::ast::Parser;
+use crate::resolve::resolve_program_with;
+use crate::types::check_program;
+
+// Names the golden programs call without defining
+const HOST: &[&str] = &["print"];
+
+fn lower(source: &str) -> std::result::Result<Module, Vec<String>> {
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, HOST);
+    assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+    let typing = check_program(&program, &resolution);
+    assert!(!typing.has_errors(), "{:?}", typing.diagnostics);
+    lower_program(&program, &resolution, &typing).map_err(|diagnostics| {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    })
+}
+
+// What a function returns for the arguments, and what it prints, or the
+// error it stops with
+fn eval(
+    module: &Module,
+    index: usize,
+    args: Vec<Const>,
+    output: &mut Vec<String>,
+) -> Result<Const, String> {
+    let function = &module.functions[index];
+    let mut values: HashMap<Value, Const> = function.params.iter().copied().zip(args).collect();
+    let mut previous = None;
+    let mut block = &function.blocks[0];
+    loop {
+        for inst in block.insts.iter() {
+            let value = match &inst.kind {
+                InstKind::Const(value) => value.clone(),
+                InstKind::Copy(value) => values[value].clone(),
+                InstKind::Unary(op, operand) => match (op, &values[operand]) {
+                    (UnaryOp::Neg, Const::Number(number)) => Const::Number(-number),
+                    (UnaryOp::Not, Const::Bool(bool)) => Const::Bool(!bool),
+                    _ => unreachable!("verified"),
+                },
+                InstKind::Binary(op, lhs, rhs) => match (op, &values[lhs], &values[rhs]) {
+                    (BinaryOp::Eq, lhs, rhs) => Const::Bool(lhs == rhs),
+                    (BinaryOp::Ne, lhs, rhs) => Const::Bool(lhs != rhs),
+                    (BinaryOp::Concat, Const::String(lhs), Const::String(rhs)) => {
+                        Const::String(format!("{}{}", lhs, rhs))
+                    }
+                    (BinaryOp::Div | BinaryOp::Rem, _, Const::Number(b)) if *b == 0.0 => {
+                        return Err("division by zero".to_string())
+                    }
+                    (op, Const::Number(a), Const::Number(b)) => match op {
+                        BinaryOp::Add => Const::Number(a + b),
+                        BinaryOp::Sub => Const::Number(a - b),
+                        BinaryOp::Mul => Const::Number(a * b),
+                        BinaryOp::Div => Const::Number(a / b),
+                        BinaryOp::Rem => Const::Number(a % b),
+                        BinaryOp::Lt => Const::Bool(a < b),
+                        BinaryOp::Le => Const::Bool(a <= b),
+                        BinaryOp::Gt => Const::Bool(a > b),
+                        BinaryOp::Ge => Const::Bool(a >= b),
+                        _ => unreachable!("verified"),
+                    },
+                    _ => unreachable!("verified"),
+                },
+                InstKind::Call(Callee::Function(callee), args) => {
+                    let args = args.iter().map(|arg| values[arg].clone()).collect();
+                    eval(module, *callee as usize, args, output)?
+                }
+                InstKind::Call(Callee::Host(_), args) => {
+                    output.push(values[&args[0]].to_string());
+                    Const::Unit
+                }
+                InstKind::Phi(incoming) => {
+                    let (_, value) = incoming
+                        .iter()
+                        .find(|(from, _)| Some(*from) == previous)
+                        .unwrap();
+                    values[value].clone()
+                }
+            };
+            values.insert(inst.result, value);
+        }
+        let next = match &block.terminator {
+            Terminator::Jump(target) => *target,
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => match values[condition] {
+                Const::Bool(true) => *then,
+                _ => *otherwise,
+            },
+            Terminator::Return(value) => return Ok(values[value].clone()),
+            Terminator::Unreachable => return Err("unreachable".to_string()),
+        };
+        previous = Some(block.id);
+        block = function.block(next).unwrap();
+    }
+}
+
+// What each function returns and prints for a few arguments
+fn results(module: &Module) -> Vec<(Result<Const, String>, Vec<String>)> {
+    let mut results = vec![];
+    for (index, function) in module.functions.iter().enumerate() {
+        for sample in 0..5 {
+            let args = function
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, param)| {
+                    let i = sample + i;
+                    match function.type_of(*param) {
+                        ValueType::Number => Const::Number([0.0, 1.0, -3.0, 7.0, 12.0][i % 5]),
+                        ValueType::Bool => Const::Bool(i % 2 == 0),
+                        ValueType::String => {
+                            Const::String(["", "world", "zope"][i % 3].to_string())
+                        }
+                        ValueType::Unit => Const::Unit,
+                    }
+                })
+                .collect();
+            let mut output = vec![];
+            let result = eval(module, index, args, &mut output);
+            results.push((result, output));
+        }
+    }
+    results
+}
+
+// The programs in `tests/golden/ir/<dir>`, each with its IR after the
+// pass, if any, next to it. The pass must change something, and keep what
+// the functions return and print. Run with `UPDATE_GOLDEN=1` to write them
+// anew.
+fn golden(dir: &str, pass: Option<Pass>) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/ir")
+        .join(dir);
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".zp"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+
+    let mut changed = vec![];
+    for name in names.iter() {
+        let source = fs::read_to_string(dir.join(name)).unwrap();
+        let mut module = lower(&source).unwrap();
+        verify(&module).unwrap_or_else(|error| panic!("{}: {}", name, error));
+        if let Some(pass) = pass {
+            let before = (module.to_text(), results(&module));
+            run_passes(&mut module, &[pass]).unwrap_or_else(|error| panic!("{}: {}", name, error));
+            assert_ne!(
+                module.to_text(),
+                before.0,
+                "{}: nothing to {}",
+                name,
+                pass.name()
+            );
+            assert_eq!(results(&module), before.1, "{}", name);
+        }
+
+        let file = format!("{}.ir", name.trim_end_matches(".zp"));
+        let path = dir.join(&file);
+        let actual = module.to_text();
+        if update {
+            fs::write(&path, &actual).unwrap();
+        } else if fs::read_to_string(&path).ok().as_deref() != Some(actual.as_str()) {
+            changed.push(file);
+        }
+    }
+    assert!(
+        changed.is_empty(),
+        "differ from their golden files: {:?}",
+        changed
+    );
+}
+
+#[test]
+fn lower_golden_files() {
+    golden("lower", None);
+}
+
+#[test]
+fn fold_golden_files() {
+    golden("fold", Some(Pass::FoldConstants));
+}
+
+#[test]
+fn dce_golden_files() {
+    golden("dce", Some(Pass::EliminateDeadCode));
+}
+
+#[test]
+fn copy_golden_files() {
+    golden("copy", Some(Pass::PropagateCopies));
+}
+
+#[test]
+fn inline_golden_files() {
+    golden("inline", Some(Pass::Inline));
+}
+
+#[test]
+fn write_the_text_format() {
+    let module =
+        lower("fn add(a, b) { a + b }\nfn f(c) { if c { add(1, 2) } else { 0 } }").unwrap();
+    assert_eq!(
+        module.to_text(),
+        "fn @add(%0: number, %1: number) -> number {\nb0:\n    \
+         %2: number = add %0, %1\n    return %2\n}\n\n\
+         fn @f(%0: bool) -> number {\nb0:\n    branch %0, b1, b2\n\
+         b1:\n    %1: number = const 1\n    %2: number = const 2\n    \
+         %3: number = call @add(%1, %2)\n    jump b3\n\
+         b2:\n    %4: number = const 0\n    jump b3\n\
+         b3:\n    %5: number = phi [b1: %3], [b2: %4]\n    return %5\n}\n"
+    );
+}
+
+#[test]
+fn optimize_a_module() {
+    let mut module = lower(
+        "fn double(n) { n * 2 }\n\
+         fn six() { let three = if true { 3 } else { 4 }; double(three) }\n\
+         fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }",
+    )
+    .unwrap();
+    let fib = module.functions[2].clone();
+    optimize(&mut module).unwrap();
+    assert_eq!(
+        module.to_text(),
+        "fn @double(%0: number) -> number {\nb0:\n    \
+         %1: number = const 2\n    %2: number = mul %0, %1\n    return %2\n}\n\n\
+         fn @six() -> number {\nb0:\n    jump b1\nb1:\n    jump b3\nb3:\n    jump b5\n\
+         b5:\n    %7: number = const 6\n    jump b4\nb4:\n    return %7\n}\n\n\
+         fn @fib(%0: number) -> number {\nb0:\n    %1: number = const 2\n    \
+         %2: bool = lt %0, %1\n    branch %2, b1, b2\nb1:\n    jump b3\n\
+         b2:\n    %3: number = const 1\n    %4: number = sub %0, %3\n    \
+         %5: number = call @fib(%4)\n    %6: number = const 2\n    \
+         %7: number = sub %0, %6\n    %8: number = call @fib(%7)\n    \
+         %9: number = add %5, %8\n    jump b3\n\
+         b3:\n    %10: number = phi [b1: %0], [b2: %9]\n    return %10\n}\n"
+    );
+    // Nothing to do for a recursive function
+    assert_eq!(module.functions[2], fib);
+}
+
+#[test]
+fn propagate_copies_through_unreached_cycles() {
+    // b0: return %0
+    // b1: %1 = phi [b2: %2], %3 = copy %1, jump b2
+    // b2: %2 = phi [b1: %1], jump b1
+    let mut function = Function::new("f", ValueType::Number);
+    let param = function.value(ValueType::Number);
+    function.params.push(param);
+    let p1 = function.value(ValueType::Number);
+    let p2 = function.value(ValueType::Number);
+    let copy = function.value(ValueType::Number);
+    function
+        .blocks
+        .push(Block::new(BlockId(0), Terminator::Return(param)));
+    let mut b1 = Block::new(BlockId(1), Terminator::Jump(BlockId(2)));
+    b1.insts.push(Inst {
+        result: p1,
+        kind: InstKind::Phi(vec![(BlockId(2), p2)]),
+    });
+    b1.insts.push(Inst {
+        result: copy,
+        kind: InstKind::Copy(p1),
+    });
+    let mut b2 = Block::new(BlockId(2), Terminator::Jump(BlockId(1)));
+    b2.insts.push(Inst {
+        result: p2,
+        kind: InstKind::Phi(vec![(BlockId(1), p1)]),
+    });
+    function.blocks.extend([b1, b2]);
+    let mut module = Module {
+        functions: vec![function],
+    };
+
+    // The phis are kept, and the copy of one of them goes
+    assert_eq!(run_passes(&mut module, &[Pass::PropagateCopies]), Ok(()));
+    let function = &module.functions[0];
+    assert_eq!(function.insts().count(), 2);
+    assert!(function
+        .insts()
+        .all(|inst| matches!(inst.kind, InstKind::Phi(_))));
+    assert_eq!(optimize(&mut module), Ok(()));
+}
+
+#[test]
+fn verify_well_formedness() {
+    let source = "fn f(n, c) {\n    if c { n + 1 } else { n }\n}";
+    let module = lower(source).unwrap();
+    assert_eq!(verify(&module), Ok(()));
+    let error = |change: &dyn Fn(&mut Function)| {
+        let mut module = module.clone();
+        change(&mut module.functions[0]);
+        verify(&module).unwrap_err().to_string()
+    };
+
+    // b0: branch %1, b1, b2
+    // b1: %2 = const 1, %3 = add %0, %2, jump b3
+    // b2: jump b3
+    // b3: %4 = phi [b1: %3], [b2: %0], return %4
+    assert_eq!(
+        error(&|f| f.blocks[1].insts.swap(0, 1)),
+        "in @f: %2 is used in b1 before it is defined"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[3].terminator = Terminator::Return(Value(3))),
+        "in @f: %3 is used in b3, which b1 where it is defined does not dominate"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[3].terminator = Terminator::Return(Value(9))),
+        "in @f: %9 is used in b3 but never defined"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[2].terminator = Terminator::Jump(BlockId(7))),
+        "in @f: b2 goes to b7, which does not exist"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[2].terminator = Terminator::Return(Value(0))),
+        "in @f: the phi %4 has a value for b2, which does not go to b3"
+    );
+    assert_eq!(
+        error(&|f| {
+            let InstKind::Phi(incoming) = &mut f.blocks[3].insts[0].kind else {
+                unreachable!()
+            };
+            incoming.pop();
+        }),
+        "in @f: the phi %4 has no value for b2"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[1].insts[0].kind = InstKind::Const(Const::Bool(true))),
+        "in @f: %2 is a number, but `const` gives a bool"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[0].terminator = Terminator::Return(Value(1))),
+        "in @f: %1 is a bool, where `return` takes a number"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[1].insts[1].kind = InstKind::Binary(BinaryOp::Add, Value(1), Value(2))),
+        "in @f: %1 is a bool, where `add` takes a number"
+    );
+    assert_eq!(
+        error(&|f| f.blocks[1].insts[1].result = Value(2)),
+        "in @f: %2 is defined twice"
+    );
+    assert_eq!(
+        error(&|f| {
+            let InstKind::Phi(incoming) = &mut f.blocks[3].insts[0].kind else {
+                unreachable!()
+            };
+            incoming[1].0 = BlockId(1);
+        }),
+        "in @f: the phi %4 has two values for b1"
+    );
+
+    // Unreached blocks only need to use values that are defined
+    let mut unreached = module.clone();
+    let f = &mut unreached.functions[0];
+    f.blocks[0].terminator = Terminator::Jump(BlockId(1));
+    let InstKind::Phi(incoming) = &mut f.blocks[3].insts[0].kind else {
+        unreachable!()
+    };
+    incoming.remove(1);
+    f.blocks[2].terminator = Terminator::Return(Value(3));
+    assert_eq!(verify(&unreached), Ok(()));
+
+    let error = VerifyError {
+        function: "f".to_string(),
+        message: "b0 goes to the entry b0".to_string(),
+        pass: None,
+    };
+    assert_eq!(
+        error.after(Pass::Inline).to_string(),
+        "in @f after inline: b0 goes to the entry b0"
+    );
+}
+
+#[test]
+fn reject_what_has_no_lowering() {
+    let errors = |source| lower(source).unwrap_err();
+    assert_eq!(
+        errors("fn f(n) { [n + 1] }"),
+        vec!["values of type `List<Number>` cannot be lowered to the IR"]
+    );
+    assert_eq!(
+        errors("fn f(n) { let g = fn(x) => x + 1; g(n) }"),
+        vec!["lambdas cannot be lowered to the IR"]
+    );
+    assert_eq!(
+        errors("let limit = 3;\nfn f(n) { n < limit }"),
+        vec!["`limit` cannot be lowered to the IR, as only parameters, local bindings and top-level functions can"]
+    );
+    assert_eq!(
+        errors("fn id(x) { x }"),
+        vec!["`id` is generic, with the type `fn('a) -> 'a`, which functions of the IR cannot be"]
+    );
+    // Other functions are still lowered, and errors are for each function
+    assert_eq!(
+        errors("fn a(n) { [n] }\nfn b(n) { n + 1 }\nfn c(n) { let xs = [n]; n }").len(),
+        2
+    );
+}
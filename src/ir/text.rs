@@ -0,0 +1,150 @@
+use std::fmt;
+
+use super::*;
+use crate::print::quote;
+
+// The text format: each function with its blocks, and each value with its
+// type where it is defined, as in
+//
+//     fn @add(%0: number, %1: number) -> number {
+//     b0:
+//         %2: number = add %0, %1
+//         return %2
+//     }
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+impl fmt::Display for Const {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Const::Number(number) => write!(f, "{}", number),
+            Const::Bool(bool) => write!(f, "{}", bool),
+            Const::String(string) => write!(f, "{}", quote(string)),
+            Const::Unit => write!(f, "()"),
+        }
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UnaryOp::Neg => "neg",
+            UnaryOp::Not => "not",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::Rem => "rem",
+            BinaryOp::Concat => "concat",
+            BinaryOp::Eq => "eq",
+            BinaryOp::Ne => "ne",
+            BinaryOp::Lt => "lt",
+            BinaryOp::Le => "le",
+            BinaryOp::Gt => "gt",
+            BinaryOp::Ge => "ge",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => write!(f, "branch {}, {}, {}", condition, then, otherwise),
+            Terminator::Return(value) => write!(f, "return {}", value),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Module {
+    /// The module in the text format, with the names of the functions it
+    /// calls.
+    pub fn to_text(&self) -> String {
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .map(|function| self.function_text(function))
+            .collect();
+        functions.join("\n")
+    }
+
+    fn function_text(&self, function: &Function) -> String {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| format!("{}: {}", param, function.type_of(*param)))
+            .collect();
+        let mut out = format!(
+            "fn @{}({}) -> {} {{\n",
+            function.name,
+            params.join(", "),
+            function.ret
+        );
+        for block in function.blocks.iter() {
+            out.push_str(&format!("{}:\n", block.id));
+            for inst in block.insts.iter() {
+                out.push_str(&format!(
+                    "    {}: {} = {}\n",
+                    inst.result,
+                    function.type_of(inst.result),
+                    self.inst_text(&inst.kind)
+                ));
+            }
+            out.push_str(&format!("    {}\n", block.terminator));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn inst_text(&self, kind: &InstKind) -> String {
+        let list = |values: &[Value]| {
+            let values: Vec<String> = values.iter().map(Value::to_string).collect();
+            values.join(", ")
+        };
+        match kind {
+            InstKind::Const(value) => format!("const {}", value),
+            InstKind::Copy(value) => format!("copy {}", value),
+            InstKind::Unary(op, operand) => format!("{} {}", op, operand),
+            InstKind::Binary(op, lhs, rhs) => format!("{} {}, {}", op, lhs, rhs),
+            InstKind::Call(Callee::Function(index), args) => {
+                let name = match self.functions.get(*index as usize) {
+                    Some(function) => format!("@{}", function.name),
+                    None => format!("@{}", index),
+                };
+                format!("call {}({})", name, list(args))
+            }
+            InstKind::Call(Callee::Host(name), args) => format!("call {}({})", name, list(args)),
+            InstKind::Phi(incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(block, value)| format!("[{}: {}]", block, value))
+                    .collect();
+                format!("phi {}", incoming.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    pub function: String,
+    pub message: String,
+    // The pass the module was not well-formed after, if any
+    pub pass: Option<Pass>,
+}
+
+impl VerifyError {
+    pub fn after(self, pass: Pass) -> Self {
+        Self {
+            pass: Some(pass),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pass {
+            Some(pass) => write!(
+                f,
+                "in @{} after {}: {}",
+                self.function,
+                pass.name(),
+                self.message
+            ),
+            None => write!(f, "in @{}: {}", self.function, self.message),
+        }
+    }
+}
+
+/// Checks that each function of the module is well-formed: that its blocks
+/// go to blocks it has, that each value is defined once, before its uses and
+/// in a block that dominates them, that phis come first and have a value for
+/// each predecessor, and that the types of operands are those instructions
+/// take. Blocks the entry does not reach are only checked to use values the
+/// function defines.
+pub fn verify(module: &Module) -> std::result::Result<(), VerifyError> {
+    for function in module.functions.iter() {
+        Verifier { module, function }
+            .function()
+            .map_err(|message| VerifyError {
+                function: function.name.clone(),
+                message,
+                pass: None,
+            })?;
+    }
+    Ok(())
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+struct Verifier<'a> {
+    module: &'a Module,
+    function: &'a Function,
+}
+
+// Where a value is defined: in which block, and by the instruction at which
+// index, or by a parameter
+#[derive(Clone, Copy)]
+enum Definition {
+    Param,
+    Inst(BlockId, usize),
+}
+
+impl Verifier<'_> {
+    fn function(&self) -> Result<()> {
+        let function = self.function;
+        let Some(entry) = function.blocks.first() else {
+            return Err("there are no blocks".to_string());
+        };
+
+        let mut ids = HashSet::new();
+        for block in function.blocks.iter() {
+            if !ids.insert(block.id) {
+                return Err(format!("{} is defined twice", block.id));
+            }
+        }
+        for block in function.blocks.iter() {
+            for target in block.terminator.successors() {
+                if !ids.contains(&target) {
+                    return Err(format!(
+                        "{} goes to {}, which does not exist",
+                        block.id, target
+                    ));
+                }
+                if target == entry.id {
+                    return Err(format!("{} goes to the entry {}", block.id, target));
+                }
+            }
+        }
+
+        let mut definitions = HashMap::new();
+        let params = function
+            .params
+            .iter()
+            .map(|param| (*param, Definition::Param));
+        let insts = function.blocks.iter().flat_map(|block| {
+            let insts = block.insts.iter().enumerate();
+            insts.map(|(i, inst)| (inst.result, Definition::Inst(block.id, i)))
+        });
+        for (value, definition) in params.chain(insts) {
+            if value.0 as usize >= function.values.len() {
+                return Err(format!("{} has no type", value));
+            }
+            if definitions.insert(value, definition).is_some() {
+                return Err(format!("{} is defined twice", value));
+            }
+        }
+
+        let dominators = self.dominators();
+        for block in function.blocks.iter() {
+            self.block(block, &definitions, &dominators)?;
+        }
+        Ok(())
+    }
+
+    // The blocks that dominate each block the entry reaches, which are those
+    // every way from the entry to it goes through, itself included
+    fn dominators(&self) -> HashMap<BlockId, HashSet<BlockId>> {
+        let function = self.function;
+        let reachable = function.reachable();
+        let all: HashSet<BlockId> = reachable.iter().copied().collect();
+        let mut dominators: HashMap<BlockId, HashSet<BlockId>> =
+            reachable.iter().map(|id| (*id, all.clone())).collect();
+        dominators.insert(reachable[0], HashSet::from([reachable[0]]));
+        let predecessors: HashMap<BlockId, Vec<BlockId>> = reachable
+            .iter()
+            .map(|id| (*id, function.predecessors(*id)))
+            .collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in reachable.iter().skip(1) {
+                let mut common: Option<HashSet<BlockId>> = None;
+                for predecessor in predecessors[id].iter() {
+                    let Some(theirs) = dominators.get(predecessor) else {
+                        continue;
+                    };
+                    common = Some(match common {
+                        Some(common) => common.intersection(theirs).copied().collect(),
+                        None => theirs.clone(),
+                    });
+                }
+                let mut common = common.unwrap_or_default();
+                common.insert(*id);
+                if common != dominators[id] {
+                    dominators.insert(*id, common);
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+
+    fn block(
+        &self,
+        block: &Block,
+        definitions: &HashMap<Value, Definition>,
+        dominators: &HashMap<BlockId, HashSet<BlockId>>,
+    ) -> Result<()> {
+        let function = self.function;
+        // Whether `value` is defined before the instruction at `index` of
+        // `at`. Blocks the entry does not reach have no dominators.
+        let available = |value: Value, at: BlockId, index: usize| -> Result<()> {
+            let Some(definition) = definitions.get(&value) else {
+                return Err(format!("{} is used in {} but never defined", value, at));
+            };
+            let Some(dominating) = dominators.get(&at) else {
+                return Ok(());
+            };
+            match *definition {
+                Definition::Param => Ok(()),
+                Definition::Inst(block, i) if block == at && i < index => Ok(()),
+                Definition::Inst(block, _) if block == at => {
+                    Err(format!("{} is used in {} before it is defined", value, at))
+                }
+                Definition::Inst(block, _) if dominating.contains(&block) => Ok(()),
+                Definition::Inst(block, _) => Err(format!(
+                    "{} is used in {}, which {} where it is defined does not dominate",
+                    value, at, block
+                )),
+            }
+        };
+
+        let predecessors = function.predecessors(block.id);
+        let mut phis = true;
+        for (i, inst) in block.insts.iter().enumerate() {
+            match &inst.kind {
+                InstKind::Phi(incoming) => {
+                    if !phis {
+                        return Err(format!(
+                            "the phi {} in {} comes after other instructions",
+                            inst.result, block.id
+                        ));
+                    }
+                    let mut seen = HashSet::new();
+                    for (from, value) in incoming.iter() {
+                        if !predecessors.contains(from) {
+                            return Err(format!(
+                                "the phi {} has a value for {}, which does not go to {}",
+                                inst.result, from, block.id
+                            ));
+                        }
+                        if !seen.insert(*from) {
+                            return Err(format!(
+                                "the phi {} has two values for {}",
+                                inst.result, from
+                            ));
+                        }
+                        // Defined by the end of the block it comes from
+                        available(*value, *from, usize::MAX)?;
+                    }
+                    if let Some(missing) = predecessors.iter().find(|id| !seen.contains(id)) {
+                        return Err(format!(
+                            "the phi {} has no value for {}",
+                            inst.result, missing
+                        ));
+                    }
+                }
+                kind => {
+                    phis = false;
+                    for operand in kind.operands() {
+                        available(operand, block.id, i)?;
+                    }
+                }
+            }
+            self.inst(inst)?;
+        }
+
+        if let Some(operand) = block.terminator.operand() {
+            available(operand, block.id, usize::MAX)?;
+        }
+        match block.terminator {
+            Terminator::Branch { condition, .. } => {
+                self.expect(condition, ValueType::Bool, "branch")
+            }
+            Terminator::Return(value) => self.expect(value, function.ret, "return"),
+            Terminator::Jump(_) | Terminator::Unreachable => Ok(()),
+        }
+    }
+
+    fn expect(&self, value: Value, ty: ValueType, what: &str) -> Result<()> {
+        match self.function.values.get(value.0 as usize) {
+            Some(actual) if *actual == ty => Ok(()),
+            Some(actual) => Err(format!(
+                "{} is a {}, where `{}` takes a {}",
+                value, actual, what, ty
+            )),
+            None => Err(format!("{} has no type", value)),
+        }
+    }
+
+    // The types of the operands and the result
+    fn inst(&self, inst: &Inst) -> Result<()> {
+        let result = self.function.type_of(inst.result);
+        let (operands, ty) = match &inst.kind {
+            InstKind::Const(value) => (vec![], value.ty()),
+            InstKind::Copy(value) => (vec![(*value, result)], result),
+            InstKind::Unary(op, operand) => {
+                let ty = match op {
+                    UnaryOp::Neg => ValueType::Number,
+                    UnaryOp::Not => ValueType::Bool,
+                };
+                (vec![(*operand, ty)], ty)
+            }
+            InstKind::Binary(op, lhs, rhs) => {
+                let (operand, ty) = match op {
+                    BinaryOp::Add
+                    | BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Rem => (ValueType::Number, ValueType::Number),
+                    BinaryOp::Concat => (ValueType::String, ValueType::String),
+                    BinaryOp::Eq | BinaryOp::Ne => (
+                        self.function
+                            .values
+                            .get(lhs.0 as usize)
+                            .copied()
+                            .unwrap_or(ValueType::Unit),
+                        ValueType::Bool,
+                    ),
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                        (ValueType::Number, ValueType::Bool)
+                    }
+                };
+                (vec![(*lhs, operand), (*rhs, operand)], ty)
+            }
+            InstKind::Call(Callee::Function(index), args) => {
+                let Some(callee) = self.module.functions.get(*index as usize) else {
+                    return Err(format!(
+                        "{} calls the function {}, which does not exist",
+                        inst.result, index
+                    ));
+                };
+                if args.len() != callee.params.len() {
+                    return Err(format!(
+                        "{} calls @{} with {} arguments, not {}",
+                        inst.result,
+                        callee.name,
+                        args.len(),
+                        callee.params.len()
+                    ));
+                }
+                let params = callee.params.iter().map(|param| callee.type_of(*param));
+                (args.iter().copied().zip(params).collect(), callee.ret)
+            }
+            // The host's functions take and return anything
+            InstKind::Call(Callee::Host(_), _) => (vec![], result),
+            InstKind::Phi(incoming) => {
+                let operands = incoming.iter().map(|(_, value)| (*value, result));
+                (operands.collect(), result)
+            }
+        };
+
+        let what = self.name(&inst.kind);
+        for (operand, ty) in operands {
+            self.expect(operand, ty, &what)?;
+        }
+        if result != ty {
+            return Err(format!(
+                "{} is a {}, but `{}` gives a {}",
+                inst.result, result, what, ty
+            ));
+        }
+        Ok(())
+    }
+
+    fn name(&self, kind: &InstKind) -> String {
+        match kind {
+            InstKind::Const(_) => "const".to_string(),
+            InstKind::Copy(_) => "copy".to_string(),
+            InstKind::Unary(op, _) => op.to_string(),
+            InstKind::Binary(op, ..) => op.to_string(),
+            InstKind::Call(Callee::Function(index), _) => {
+                match self.module.functions.get(*index as usize) {
+                    Some(function) => format!("call @{}", function.name),
+                    None => "call".to_string(),
+                }
+            }
+            InstKind::Call(Callee::Host(name), _) => format!("call {}", name),
+            InstKind::Phi(_) => "phi".to_string(),
+        }
+    }
+}
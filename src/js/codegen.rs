@@ -0,0 +1,1047 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::*;
+use crate::lexer::Position;
+use crate::reactive::graph::quote;
+use crate::resolve::{BindingId, Resolution};
+use crate::types::{Type, Typing};
+
+use super::binding_names;
+use super::sourcemap::{LineIndex, Mapping};
+
+// JavaScript precedences, loosest first. Arrows and `?:` are at the level of
+// assignments.
+const ASSIGN: u8 = 2;
+const OR: u8 = 4;
+const AND: u8 = 5;
+const EQUALITY: u8 = 9;
+const RELATIONAL: u8 = 10;
+const ADDITIVE: u8 = 12;
+const MULTIPLICATIVE: u8 = 13;
+const UNARY: u8 = 14;
+const CALL: u8 = 17;
+const PRIMARY: u8 = 20;
+
+const INDENT: &str = "  ";
+
+// Functions the generated code calls, added to the end of a module that
+// needs them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Helper {
+    Eq,
+    Element,
+}
+
+impl Helper {
+    fn code(&self) -> &'static str {
+        match self {
+            Helper::Eq => {
+                "// Structural equality of lists, records and variants
+function $eq(a, b) {
+  if (a === b) {
+    return true;
+  }
+  if (typeof a !== \"object\" || typeof b !== \"object\" || a === null || b === null) {
+    return false;
+  }
+  for (const key in a) {
+    if (!(key in b) || !$eq(a[key], b[key])) {
+      return false;
+    }
+  }
+  for (const key in b) {
+    if (!(key in a)) {
+      return false;
+    }
+  }
+  return true;
+}
+"
+            }
+            Helper::Element => {
+                "// An element, with the lists among its children flattened
+function $h(tag, props, ...children) {
+  return { tag, props, children: children.flat(Infinity) };
+}
+"
+            }
+        }
+    }
+}
+
+// Where the value of an expression in statement position goes
+#[derive(Clone, Debug)]
+enum Target {
+    Return,
+    Discard,
+    Assign(String),
+}
+
+pub(super) struct Generator<'a> {
+    resolution: &'a Resolution,
+    typing: &'a Typing,
+    lines: LineIndex,
+    names: HashMap<BindingId, String>,
+    code: String,
+    // Where the next character goes, in UTF-16 columns
+    line: u32,
+    column: u32,
+    indent: usize,
+    // Whether the indentation of the current line is yet to be written
+    line_start: bool,
+    mappings: Vec<Mapping>,
+    map_names: Vec<String>,
+    helpers: BTreeSet<Helper>,
+    // Exported bindings whose names had to change, with the exported name
+    renamed_exports: Vec<(String, String)>,
+    // Matches so far, which number their labels and subjects
+    matches: u32,
+}
+
+impl<'a> Generator<'a> {
+    pub(super) fn new(source: &str, resolution: &'a Resolution, typing: &'a Typing) -> Self {
+        Self {
+            resolution,
+            typing,
+            lines: LineIndex::new(source),
+            names: binding_names(resolution),
+            code: String::new(),
+            line: 0,
+            column: 0,
+            indent: 0,
+            line_start: true,
+            mappings: vec![],
+            map_names: vec![],
+            helpers: BTreeSet::new(),
+            renamed_exports: vec![],
+            matches: 0,
+        }
+    }
+
+    pub(super) fn program(&mut self, program: &Program) {
+        self.stmts(&program.stmts, Target::Discard);
+    }
+
+    /// The code, its mappings and the names they refer to.
+    pub(super) fn finish(mut self) -> (String, Vec<Mapping>, Vec<String>) {
+        if !self.renamed_exports.is_empty() {
+            let names: Vec<String> = self
+                .renamed_exports
+                .iter()
+                .map(|(name, exported)| format!("{} as {}", name, exported))
+                .collect();
+            self.line(&format!("export {{ {} }};", names.join(", ")));
+        }
+        for helper in std::mem::take(&mut self.helpers) {
+            self.newline();
+            self.write(helper.code());
+        }
+        (self.code, self.mappings, self.map_names)
+    }
+
+    fn write(&mut self, text: &str) {
+        for char in text.chars() {
+            if char == '\n' {
+                self.code.push('\n');
+                self.line += 1;
+                self.column = 0;
+                self.line_start = true;
+                continue;
+            }
+            self.write_indent();
+            self.code.push(char);
+            self.column += char.len_utf16() as u32;
+        }
+    }
+
+    fn write_indent(&mut self) {
+        if self.line_start {
+            self.line_start = false;
+            for _ in 0..self.indent {
+                self.code.push_str(INDENT);
+                self.column += INDENT.len() as u32;
+            }
+        }
+    }
+
+    fn newline(&mut self) {
+        self.write("\n");
+    }
+
+    fn line(&mut self, text: &str) {
+        self.write(text);
+        self.newline();
+    }
+
+    // Maps the next character to the start of `pos`. Of several nodes that
+    // start at the same character, the innermost one is kept. `renamed` is
+    // the source name of an identifier that has another name here.
+    fn mark(&mut self, pos: Position, renamed: Option<&str>) {
+        self.write_indent();
+        let (source_line, source_column) = self.lines.location(pos.start);
+        let name =
+            renamed.map(
+                |name| match self.map_names.iter().position(|known| known == name) {
+                    Some(index) => index as u32,
+                    None => {
+                        self.map_names.push(name.to_string());
+                        (self.map_names.len() - 1) as u32
+                    }
+                },
+            );
+        let mapping = Mapping {
+            generated_line: self.line,
+            generated_column: self.column,
+            source_line,
+            source_column,
+            name,
+        };
+        match self.mappings.last_mut() {
+            Some(last)
+                if last.generated_line == self.line && last.generated_column == self.column =>
+            {
+                *last = mapping
+            }
+            _ => self.mappings.push(mapping),
+        }
+    }
+
+    // Writes the name of the binding declared or used at `pos`
+    fn name(&mut self, pos: Position, name: &str) {
+        let id = self
+            .resolution
+            .definitions
+            .get(&pos)
+            .or_else(|| self.resolution.uses.get(&pos));
+        let renamed = id.map_or(name, |id| &self.names[id]).to_string();
+        self.mark(pos, Some(name).filter(|name| *name != renamed));
+        self.write(&renamed);
+    }
+
+    // Writes the name a statement declares. Its span is that of the whole
+    // statement, so the name is only mapped if it was renamed.
+    fn declared(&mut self, pos: Position, name: &str) {
+        let renamed = self.renamed(pos, name);
+        if renamed != name {
+            self.mark(pos, Some(name));
+        }
+        self.write(&renamed);
+    }
+
+    fn renamed(&self, pos: Position, name: &str) -> String {
+        self.resolution
+            .definitions
+            .get(&pos)
+            .map_or(name, |id| &self.names[id])
+            .to_string()
+    }
+
+    // `export ` if the binding keeps its name, and otherwise an entry in the
+    // `export { .. as .. }` at the end
+    fn export(&mut self, exported: bool, pos: Position, name: &str) {
+        if !exported {
+            return;
+        }
+        let renamed = self.renamed(pos, name);
+        if renamed == name {
+            self.write("export ");
+        } else {
+            self.renamed_exports.push((renamed, name.to_string()));
+        }
+    }
+
+    // Returns whether the statements end by returning or throwing
+    fn stmts(&mut self, stmts: &[Stmt], target: Target) -> bool {
+        // Imports and then constructors first, as both are hoisted
+        let mut previous = None;
+        for stmt in stmts.iter() {
+            if let StmtKind::Import(import) = &stmt.kind {
+                self.import(stmt.pos, import);
+                previous = Some(stmt.pos);
+            }
+        }
+        for stmt in stmts.iter() {
+            if let StmtKind::Type(type_stmt) = &stmt.declaration().kind {
+                self.variants(type_stmt, stmt.declaration() != stmt);
+                previous = Some(stmt.pos);
+            }
+        }
+
+        for (i, stmt) in stmts.iter().enumerate() {
+            if matches!(
+                stmt.declaration().kind,
+                StmtKind::Type(_) | StmtKind::Import(_)
+            ) {
+                continue;
+            }
+            // Statements apart in the source are apart here too
+            if let Some(previous) = previous {
+                let (end, _) = self.lines.location(previous.end);
+                let (start, _) = self.lines.location(stmt.pos.start);
+                if start > end + 1 {
+                    self.newline();
+                }
+            }
+            previous = Some(stmt.pos);
+            match &stmt.kind {
+                StmtKind::Expr(expr) if i + 1 == stmts.len() => return self.tail(expr, target),
+                _ => self.stmt(stmt),
+            }
+        }
+        self.unit(&target);
+        false
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.declaration(stmt, false);
+    }
+
+    fn declaration(&mut self, stmt: &Stmt, exported: bool) {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => self.binding(
+                stmt.pos,
+                "const",
+                &let_stmt.identifier,
+                &let_stmt.value,
+                exported,
+            ),
+            StmtKind::State(state_stmt) => self.binding(
+                stmt.pos,
+                "let",
+                &state_stmt.identifier,
+                &state_stmt.value,
+                exported,
+            ),
+            StmtKind::Fn(fn_stmt) => {
+                self.mark(stmt.pos, None);
+                self.export(exported, stmt.pos, &fn_stmt.name);
+                self.write("function ");
+                self.function(
+                    stmt.pos,
+                    &fn_stmt.name,
+                    &fn_stmt.params,
+                    &fn_stmt.body,
+                    false,
+                );
+            }
+            StmtKind::Component(component) => {
+                self.mark(stmt.pos, None);
+                self.export(exported, stmt.pos, &component.name);
+                self.write("function ");
+                self.function(
+                    stmt.pos,
+                    &component.name,
+                    &component.props,
+                    &component.body,
+                    true,
+                );
+            }
+            // Written out before the other statements of their block
+            StmtKind::Type(_) | StmtKind::Import(_) => {}
+            StmtKind::Export(exported) => self.declaration(exported, true),
+            StmtKind::Expr(expr) => {
+                self.scoped(expr, Target::Discard);
+            }
+        }
+    }
+
+    // A `let` is `const`, and a `state` is `let`. Values that take statements
+    // to compute are assigned at the end of them.
+    fn binding(&mut self, pos: Position, keyword: &str, name: &str, value: &Expr, exported: bool) {
+        self.mark(pos, None);
+        self.export(exported, pos, name);
+        let renamed = self.renamed(pos, name);
+        if is_expression(value) {
+            self.write(&format!("{} ", keyword));
+            self.declared(pos, name);
+            self.write(" = ");
+            self.expr(value, ASSIGN);
+            self.line(";");
+        } else {
+            self.write("let ");
+            self.declared(pos, name);
+            self.line(";");
+            self.scoped(value, Target::Assign(renamed));
+        }
+    }
+
+    fn function(&mut self, pos: Position, name: &str, params: &[Param], body: &Expr, props: bool) {
+        self.declared(pos, name);
+        self.write("(");
+        if props && !params.is_empty() {
+            self.write("{ ");
+            for (i, param) in params.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                let renamed = self.renamed(param.pos, &param.name);
+                if renamed != param.name {
+                    self.write(&format!("{}: ", param.name));
+                }
+                self.name(param.pos, &param.name);
+            }
+            self.write(" }");
+        } else {
+            self.params(params);
+        }
+        self.line(") {");
+        self.indented(|generator| {
+            generator.tail(body, Target::Return);
+        });
+        self.line("}");
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.name(param.pos, &param.name);
+        }
+    }
+
+    fn indented<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.indent += 1;
+        let result = f(self);
+        self.indent -= 1;
+        result
+    }
+
+    fn variants(&mut self, type_stmt: &TypeStmt, exported: bool) {
+        for variant in type_stmt.variants.iter() {
+            self.mark(variant.pos, None);
+            self.export(exported, variant.pos, &variant.name);
+            if variant.fields.is_empty() {
+                self.write("const ");
+                self.name(variant.pos, &variant.name);
+                self.line(&format!(
+                    " = {{ tag: {}, fields: [] }};",
+                    quote(&variant.name)
+                ));
+            } else {
+                self.write("function ");
+                self.name(variant.pos, &variant.name);
+                self.line("(...fields) {");
+                self.line(&format!(
+                    "{}return {{ tag: {}, fields }};",
+                    INDENT,
+                    quote(&variant.name)
+                ));
+                self.line("}");
+            }
+        }
+    }
+
+    fn import(&mut self, pos: Position, import: &ImportStmt) {
+        self.mark(pos, None);
+        self.write("import ");
+        if !import.names.is_empty() {
+            self.write("{ ");
+            for (i, name) in import.names.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                let renamed = self.renamed(name.pos, &name.name);
+                if renamed != name.name {
+                    self.write(&format!("{} as ", name.name));
+                }
+                self.name(name.pos, &name.name);
+            }
+            self.write(" } from ");
+        }
+        self.mark(import.path_pos, None);
+        self.line(&format!("{};", quote(&module_path(&import.path))));
+    }
+
+    // An expression in statement position, in a block of its own if it
+    // declares anything
+    fn scoped(&mut self, expr: &Expr, target: Target) -> bool {
+        match &expr.kind {
+            ExprKind::Block(stmts) if !stmts.is_empty() => {
+                self.mark(expr.pos, None);
+                self.line("{");
+                let exits = self.indented(|generator| generator.stmts(stmts, target));
+                self.line("}");
+                exits
+            }
+            _ => self.tail(expr, target),
+        }
+    }
+
+    // The value of an expression given to `target`. The statements of a block
+    // are written in the enclosing one, which is safe as it is the last
+    // statement there and its names differ from those around it.
+    fn tail(&mut self, expr: &Expr, target: Target) -> bool {
+        match &expr.kind {
+            ExprKind::Block(stmts) => self.stmts(stmts, target),
+            ExprKind::If(if_expr) => {
+                self.mark(expr.pos, None);
+                self.if_stmt(if_expr, target)
+            }
+            ExprKind::Match(match_expr) => {
+                self.mark(expr.pos, None);
+                self.match_stmt(match_expr, target)
+            }
+            ExprKind::Assign(assign) => {
+                self.mark(expr.pos, None);
+                self.assignment(assign);
+                self.line(";");
+                self.unit(&target);
+                false
+            }
+            _ => {
+                self.mark(expr.pos, None);
+                match &target {
+                    Target::Return => self.write("return "),
+                    Target::Assign(name) => self.write(&format!("{} = ", name)),
+                    Target::Discard if starts_with_brace(expr) => {
+                        self.write("(");
+                        self.expr(expr, 0);
+                        self.line(");");
+                        return false;
+                    }
+                    Target::Discard => {}
+                }
+                self.expr(expr, 0);
+                self.line(";");
+                matches!(target, Target::Return)
+            }
+        }
+    }
+
+    fn unit(&mut self, target: &Target) {
+        if let Target::Assign(name) = target {
+            self.line(&format!("{} = undefined;", name));
+        }
+    }
+
+    fn if_stmt(&mut self, if_expr: &IfExpr, target: Target) -> bool {
+        self.write("if (");
+        self.expr(&if_expr.condition, 0);
+        self.line(") {");
+        let then = self.indented(|generator| generator.tail(&if_expr.then, target.clone()));
+        match if_expr.otherwise.as_deref() {
+            Some(Expr {
+                kind: ExprKind::If(otherwise),
+                pos,
+            }) => {
+                self.write("} else ");
+                self.mark(*pos, None);
+                let otherwise = self.if_stmt(otherwise, target);
+                then && otherwise
+            }
+            Some(otherwise) => {
+                self.line("} else {");
+                let otherwise = self.indented(|generator| generator.tail(otherwise, target));
+                self.line("}");
+                then && otherwise
+            }
+            None => {
+                if let Target::Assign(_) = target {
+                    self.line("} else {");
+                    self.indented(|generator| generator.unit(&target));
+                }
+                self.line("}");
+                false
+            }
+        }
+    }
+
+    // Arms are tried in turn, each leaving the match with `return`, or with
+    // `break` out of a labeled block if the match is not the last statement
+    // of a function
+    fn match_stmt(&mut self, match_expr: &MatchExpr, target: Target) -> bool {
+        let number = self.matches;
+        self.matches += 1;
+        let label = match target {
+            Target::Return => None,
+            _ => {
+                let label = format!("$match{}", number);
+                self.line(&format!("{}: {{", label));
+                self.indent += 1;
+                Some(label)
+            }
+        };
+        let subject = match &match_expr.scrutinee.kind {
+            ExprKind::Identifier(identifier) => {
+                let pos = match_expr.scrutinee.pos;
+                match self.resolution.uses.get(&pos) {
+                    Some(id) => self.names[id].clone(),
+                    None => identifier.ident.clone(),
+                }
+            }
+            _ => {
+                let subject = format!("$subject{}", number);
+                self.write(&format!("const {} = ", subject));
+                self.expr(&match_expr.scrutinee, ASSIGN);
+                self.line(";");
+                subject
+            }
+        };
+
+        let mut exhaustive = false;
+        for arm in match_expr.arms.iter() {
+            let mut tests = vec![];
+            let mut bindings = vec![];
+            self.pattern(&arm.pattern, subject.clone(), &mut tests, &mut bindings);
+            self.mark(arm.pos, None);
+            let braced = !tests.is_empty() || arm.guard.is_some();
+            if !tests.is_empty() {
+                self.line(&format!("if ({}) {{", tests.join(" && ")));
+            } else if braced {
+                self.line("{");
+            }
+            if braced {
+                self.indent += 1;
+            }
+            for (pos, name, value) in bindings {
+                self.mark(pos, None);
+                self.write("const ");
+                self.name(pos, &name);
+                self.line(&format!(" = {};", value));
+            }
+            if let Some(guard) = &arm.guard {
+                self.write("if (");
+                self.expr(guard, 0);
+                self.line(") {");
+                self.indent += 1;
+            }
+            let exits = self.tail(&arm.body, target.clone());
+            if !exits {
+                match &label {
+                    Some(label) => self.line(&format!("break {};", label)),
+                    None => self.line("return;"),
+                }
+            }
+            if arm.guard.is_some() {
+                self.indent -= 1;
+                self.line("}");
+            }
+            if braced {
+                self.indent -= 1;
+                self.line("}");
+            } else {
+                // Later arms are never reached
+                exhaustive = true;
+                break;
+            }
+        }
+        if !exhaustive {
+            self.line("throw new Error(\"no arm matches\");");
+        }
+        if label.is_some() {
+            self.indent -= 1;
+            self.line("}");
+        }
+        label.is_none()
+    }
+
+    // Adds the conditions under which `subject` matches the pattern, and the
+    // bindings it makes, with their spans, names and values
+    fn pattern(
+        &mut self,
+        pattern: &Pattern,
+        subject: String,
+        tests: &mut Vec<String>,
+        bindings: &mut Vec<(Position, String, String)>,
+    ) {
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Literal(literal) => {
+                tests.push(format!("{} === {}", subject, self.literal(literal)))
+            }
+            PatternKind::Identifier(name) => match self.resolution.uses.get(&pattern.pos) {
+                Some(_) => tests.push(format!("{}.tag === {}", subject, quote(name))),
+                None => bindings.push((pattern.pos, name.clone(), subject)),
+            },
+            PatternKind::Variant(name, patterns) => {
+                tests.push(format!("{}.tag === {}", subject, quote(name)));
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field = format!("{}.fields[{}]", subject, i);
+                    self.pattern(pattern, field, tests, bindings);
+                }
+            }
+        }
+    }
+
+    fn literal(&self, literal: &LiteralExpr) -> String {
+        match literal {
+            LiteralExpr::Number(number) => number.to_string(),
+            LiteralExpr::Bool(bool) => bool.to_string(),
+            LiteralExpr::String(string) => quote(string),
+        }
+    }
+
+    fn assignment(&mut self, assign: &AssignExpr) {
+        self.expr(&assign.target, PRIMARY);
+        self.write(" = ");
+        self.expr(&assign.value, ASSIGN);
+    }
+
+    fn precedence(&self, expr: &Expr) -> u8 {
+        match &expr.kind {
+            ExprKind::Identifier(_)
+            | ExprKind::Literal(_)
+            | ExprKind::List(_)
+            | ExprKind::Record(_) => PRIMARY,
+            ExprKind::Block(stmts) => match stmts.as_slice() {
+                [] => PRIMARY,
+                [Stmt {
+                    kind: StmtKind::Expr(expr),
+                    ..
+                }] => self.precedence(expr),
+                _ => CALL,
+            },
+            ExprKind::Binary(binary) => match binary.op {
+                BinaryOp::Eq if !self.primitive(&binary.lhs) => CALL,
+                BinaryOp::Ne if !self.primitive(&binary.lhs) => UNARY,
+                BinaryOp::Or => OR,
+                BinaryOp::And => AND,
+                BinaryOp::Eq | BinaryOp::Ne => EQUALITY,
+                BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => RELATIONAL,
+                BinaryOp::Add | BinaryOp::Sub => ADDITIVE,
+                BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => MULTIPLICATIVE,
+            },
+            ExprKind::Unary(_) | ExprKind::Assign(_) => UNARY,
+            ExprKind::If(_) | ExprKind::Lambda(_) => ASSIGN,
+            ExprKind::Call(_)
+            | ExprKind::Index(_)
+            | ExprKind::Field(_)
+            | ExprKind::Match(_)
+            | ExprKind::Element(_) => CALL,
+        }
+    }
+
+    // Whether values of the type of `expr` are equal exactly when `===`
+    // says so
+    fn primitive(&self, expr: &Expr) -> bool {
+        matches!(
+            self.typing.type_of(&expr.pos),
+            Some(Type::Number | Type::Bool | Type::String | Type::Unit)
+        )
+    }
+
+    // An expression in a context that binds at least as tightly as `min`
+    fn expr(&mut self, expr: &Expr, min: u8) {
+        if let ExprKind::Block(stmts) = &expr.kind {
+            if let [Stmt {
+                kind: StmtKind::Expr(inner),
+                ..
+            }] = stmts.as_slice()
+            {
+                return self.expr(inner, min);
+            }
+        }
+        let parens = self.precedence(expr) < min;
+        if parens {
+            self.write("(");
+        }
+        self.mark(expr.pos, None);
+        match &expr.kind {
+            ExprKind::Identifier(identifier) => self.name(expr.pos, &identifier.ident),
+            ExprKind::Literal(literal) => {
+                let literal = self.literal(literal);
+                self.write(&literal);
+            }
+            ExprKind::Block(stmts) if stmts.is_empty() => self.write("undefined"),
+            ExprKind::Block(_) | ExprKind::Match(_) => {
+                // Run at once, so that it can hold statements
+                self.line("(() => {");
+                self.indented(|generator| generator.tail(expr, Target::Return));
+                self.write("})()");
+            }
+            ExprKind::Binary(binary) => self.binary(binary),
+            ExprKind::Unary(unary) => {
+                match unary.op {
+                    UnaryOp::Neg => self.write("-"),
+                    UnaryOp::Not => self.write("!"),
+                }
+                // `- -a` rather than the decrement `--a`
+                let nested = matches!(
+                    &unary.operand.kind,
+                    ExprKind::Unary(UnaryExpr {
+                        op: UnaryOp::Neg,
+                        ..
+                    })
+                );
+                let min = if nested { PRIMARY } else { UNARY };
+                self.expr(&unary.operand, min);
+            }
+            ExprKind::If(if_expr) => {
+                self.expr(&if_expr.condition, OR);
+                self.write(" ? ");
+                self.expr(&if_expr.then, ASSIGN);
+                self.write(" : ");
+                match &if_expr.otherwise {
+                    Some(otherwise) => self.expr(otherwise, ASSIGN),
+                    None => self.write("undefined"),
+                }
+            }
+            ExprKind::Call(call) => {
+                self.expr(&call.called, CALL);
+                self.write("(");
+                self.args(&call.args);
+                self.write(")");
+            }
+            ExprKind::List(items) => {
+                self.write("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    if item.spread {
+                        self.mark(item.pos, None);
+                        self.write("...");
+                    }
+                    self.expr(&item.value, ASSIGN);
+                }
+                self.write("]");
+            }
+            ExprKind::Index(index) => {
+                self.expr(&index.indexed, CALL);
+                self.write("[");
+                self.expr(&index.index, 0);
+                self.write("]");
+            }
+            ExprKind::Record(record) => self.record(record),
+            ExprKind::Field(field) => {
+                self.expr(&field.record, CALL);
+                self.write(&format!(".{}", field.field));
+            }
+            ExprKind::Lambda(lambda) => self.lambda(lambda),
+            // Assignments have no value
+            ExprKind::Assign(assign) => {
+                self.write("void (");
+                self.assignment(assign);
+                self.write(")");
+            }
+            ExprKind::Element(element) => self.element(element),
+        }
+        if parens {
+            self.write(")");
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.expr(arg, ASSIGN);
+        }
+    }
+
+    fn binary(&mut self, binary: &BinaryExpr) {
+        if matches!(binary.op, BinaryOp::Eq | BinaryOp::Ne) && !self.primitive(&binary.lhs) {
+            self.helpers.insert(Helper::Eq);
+            if binary.op == BinaryOp::Ne {
+                self.write("!");
+            }
+            self.write("$eq(");
+            self.expr(&binary.lhs, ASSIGN);
+            self.write(", ");
+            self.expr(&binary.rhs, ASSIGN);
+            self.write(")");
+            return;
+        }
+        let (operator, precedence) = match binary.op {
+            BinaryOp::Or => ("||", OR),
+            BinaryOp::And => ("&&", AND),
+            BinaryOp::Eq => ("===", EQUALITY),
+            BinaryOp::Ne => ("!==", EQUALITY),
+            BinaryOp::Lt => ("<", RELATIONAL),
+            BinaryOp::Le => ("<=", RELATIONAL),
+            BinaryOp::Gt => (">", RELATIONAL),
+            BinaryOp::Ge => (">=", RELATIONAL),
+            BinaryOp::Add => ("+", ADDITIVE),
+            BinaryOp::Sub => ("-", ADDITIVE),
+            BinaryOp::Mul => ("*", MULTIPLICATIVE),
+            BinaryOp::Div => ("/", MULTIPLICATIVE),
+            BinaryOp::Rem => ("%", MULTIPLICATIVE),
+        };
+        self.expr(&binary.lhs, precedence);
+        self.write(&format!(" {} ", operator));
+        self.expr(&binary.rhs, precedence + 1);
+    }
+
+    fn record(&mut self, record: &RecordExpr) {
+        if record.base.is_none() && record.fields.is_empty() {
+            self.write("{}");
+            return;
+        }
+        self.write("{ ");
+        if let Some(base) = &record.base {
+            self.write("...");
+            self.expr(base, ASSIGN);
+            if !record.fields.is_empty() {
+                self.write(", ");
+            }
+        }
+        for (i, field) in record.fields.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.mark(field.pos, None);
+            // `{ name }` for `{ name: name }`
+            if let ExprKind::Identifier(identifier) = &field.value.kind {
+                let id = self.resolution.uses.get(&field.value.pos);
+                if id.map_or(&identifier.ident, |id| &self.names[id]) == &field.name {
+                    self.name(field.value.pos, &identifier.ident);
+                    continue;
+                }
+            }
+            self.write(&format!("{}: ", property(&field.name)));
+            self.expr(&field.value, ASSIGN);
+        }
+        self.write(" }");
+    }
+
+    fn lambda(&mut self, lambda: &LambdaExpr) {
+        self.write("(");
+        self.params(&lambda.params);
+        self.write(") => ");
+        let body = unwrap_block(&lambda.body);
+        if !is_expression(body) || matches!(body.kind, ExprKind::Assign(_)) {
+            self.line("{");
+            self.indented(|generator| generator.tail(&lambda.body, Target::Return));
+            self.write("}");
+        } else if starts_with_brace(body) {
+            self.write("(");
+            self.expr(body, 0);
+            self.write(")");
+        } else {
+            self.expr(body, ASSIGN);
+        }
+    }
+
+    // `$h(tag, props, ...children)`, with elements among the children on
+    // lines of their own
+    fn element(&mut self, element: &ElementExpr) {
+        self.helpers.insert(Helper::Element);
+        self.write("$h(");
+        match &element.tag {
+            Some(tag) if tag.is_component() => self.name(tag.pos, &tag.name),
+            Some(tag) => {
+                self.mark(tag.pos, None);
+                self.write(&quote(&tag.name));
+            }
+            None => self.write("null"),
+        }
+        self.write(", ");
+        if element.attributes.is_empty() {
+            self.write("null");
+        } else {
+            self.write("{ ");
+            for (i, attribute) in element.attributes.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                self.mark(attribute.pos, None);
+                self.write(&format!("{}: ", property(&attribute.name)));
+                match &attribute.value {
+                    Some(value) => self.expr(value, ASSIGN),
+                    None => self.write("true"),
+                }
+            }
+            self.write(" }");
+        }
+
+        let nested = element
+            .children
+            .iter()
+            .any(|child| matches!(child.kind, ChildKind::Element(_)));
+        if nested {
+            self.line(",");
+            self.indent += 1;
+        }
+        for (i, child) in element.children.iter().enumerate() {
+            if !nested {
+                self.write(", ");
+            }
+            match &child.kind {
+                ChildKind::Text(text) => {
+                    self.mark(child.pos, None);
+                    self.write(&quote(text));
+                }
+                ChildKind::Expr(expr) | ChildKind::Element(expr) => self.expr(expr, ASSIGN),
+            }
+            if nested && i + 1 < element.children.len() {
+                self.line(",");
+            }
+        }
+        if nested {
+            self.indent -= 1;
+            self.newline();
+        }
+        self.write(")");
+    }
+}
+
+// Whether an expression can be written without statements of its own. The
+// others are a function called at once when they are needed as a value.
+fn is_expression(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Block(stmts) => match stmts.as_slice() {
+            [] => true,
+            [Stmt {
+                kind: StmtKind::Expr(expr),
+                ..
+            }] => is_expression(expr),
+            _ => false,
+        },
+        ExprKind::If(if_expr) => {
+            is_expression(&if_expr.then) && if_expr.otherwise.as_deref().is_none_or(is_expression)
+        }
+        ExprKind::Match(_) | ExprKind::Assign(_) => false,
+        _ => true,
+    }
+}
+
+fn unwrap_block(expr: &Expr) -> &Expr {
+    match &expr.kind {
+        ExprKind::Block(stmts) => match stmts.as_slice() {
+            [Stmt {
+                kind: StmtKind::Expr(expr),
+                ..
+            }] => unwrap_block(expr),
+            _ => expr,
+        },
+        _ => expr,
+    }
+}
+
+// Whether the code of an expression starts with `{`, which would be taken for
+// a block at the start of a statement or the body of an arrow
+fn starts_with_brace(expr: &Expr) -> bool {
+    match &unwrap_block(expr).kind {
+        ExprKind::Record(_) => true,
+        ExprKind::Binary(binary) => starts_with_brace(&binary.lhs),
+        ExprKind::Call(call) => starts_with_brace(&call.called),
+        ExprKind::Index(index) => starts_with_brace(&index.indexed),
+        ExprKind::Field(field) => starts_with_brace(&field.record),
+        ExprKind::If(if_expr) => starts_with_brace(&if_expr.condition),
+        _ => false,
+    }
+}
+
+// A property name, quoted unless it is an identifier, like `aria-label`
+fn property(name: &str) -> String {
+    let identifier = name
+        .chars()
+        .all(|char| char.is_alphanumeric() || char == '_' || char == '$')
+        && !name.starts_with(|char: char| char.is_ascii_digit());
+    match identifier {
+        true => name.to_string(),
+        false => quote(name),
+    }
+}
+
+// The module an import refers to, compiled next to this one
+fn module_path(path: &str) -> String {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    match file.rsplit_once('.') {
+        Some((stem, "zp")) => format!("{}{}.js", &path[..path.len() - file.len()], stem),
+        Some(_) => path.to_string(),
+        None => format!("{}.js", path),
+    }
+}
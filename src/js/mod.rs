@@ -0,0 +1,163 @@
+mod codegen;
+pub mod sourcemap;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::Program;
+use crate::resolve::{BindingId, BindingKind, Resolution, ScopeId};
+use crate::types::Typing;
+
+use self::codegen::Generator;
+pub use self::sourcemap::*;
+
+// Words that cannot name a binding in a strict mode module, and globals that
+// the generated code relies on
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+    "Error",
+    "Infinity",
+    "NaN",
+    "undefined",
+];
+
+pub fn is_reserved(name: &str) -> bool {
+    RESERVED.contains(&name)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JsOptions {
+    // The source and the generated file, as the source map names them
+    pub source_name: String,
+    pub file: String,
+    // Whether the source map holds the source text
+    pub source_content: bool,
+}
+
+/// An ES module and the map from it back to its source.
+#[derive(Clone, Debug)]
+pub struct JsModule {
+    pub code: String,
+    pub source_map: SourceMap,
+}
+
+/// Compiles a module without resolution or type errors to an ES module. The
+/// `source` is the text it was parsed from, which spans point into.
+///
+/// Variants are objects with a `tag` and their `fields`, elements are built
+/// by a `$h` function in the style of JSX, and values of other types are
+/// their JavaScript counterparts. `state` is a `let` binding, leaving it to
+/// the host to render again when it changes.
+pub fn compile_module(
+    source: &str,
+    program: &Program,
+    resolution: &Resolution,
+    typing: &Typing,
+    options: &JsOptions,
+) -> JsModule {
+    let mut generator = Generator::new(source, resolution, typing);
+    generator.program(program);
+    let (mut code, mappings, names) = generator.finish();
+    if !options.file.is_empty() {
+        code.push_str(&format!("//# sourceMappingURL={}.map\n", options.file));
+    }
+    JsModule {
+        code,
+        source_map: SourceMap {
+            file: options.file.clone(),
+            source: options.source_name.clone(),
+            source_content: options.source_content.then(|| source.to_string()),
+            names,
+            mappings,
+        },
+    }
+}
+
+/// The name of every binding in the generated code. Reserved words get a `$`
+/// in front, which no source name has. A binding that shadows another of the
+/// same name gets a `$` and a count after its name, as JavaScript does not
+/// allow redeclaring a name in a scope, nor using the outer binding in the
+/// initializer of an inner one. Host bindings keep their names, as the host
+/// defines them.
+pub fn binding_names(resolution: &Resolution) -> HashMap<BindingId, String> {
+    let mut names = HashMap::new();
+    for binding in resolution.bindings.iter() {
+        if binding.kind == BindingKind::Host {
+            names.insert(binding.id, binding.name.clone());
+            continue;
+        }
+        let visible = ancestors(resolution, binding.scope);
+        let shadowed = resolution.bindings[..binding.id.0 as usize]
+            .iter()
+            .filter(|earlier| earlier.name == binding.name && visible.contains(&earlier.scope))
+            .count();
+        let mut name = match is_reserved(&binding.name) {
+            true => format!("${}", binding.name),
+            false => binding.name.clone(),
+        };
+        if shadowed > 0 {
+            name = format!("{}${}", name, shadowed);
+        }
+        names.insert(binding.id, name);
+    }
+    names
+}
+
+// The scope and the scopes around it
+fn ancestors(resolution: &Resolution, scope: ScopeId) -> HashSet<ScopeId> {
+    let mut scopes = HashSet::new();
+    let mut next = Some(scope);
+    while let Some(scope) = next {
+        scopes.insert(scope);
+        next = resolution.scope(scope).parent;
+    }
+    scopes
+}
@@ -0,0 +1,211 @@
+use std::fmt::Write;
+
+use crate::reactive::graph::quote;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A point of the generated code and the point of the source it came from.
+/// Lines and columns start at 0, and columns count UTF-16 code units, as
+/// source maps do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub generated_line: u32,
+    pub generated_column: u32,
+    pub source_line: u32,
+    pub source_column: u32,
+    // An index into the names of the map, for an identifier that was renamed
+    pub name: Option<u32>,
+}
+
+/// A version 3 source map of a file generated from a single source.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub file: String,
+    pub source: String,
+    // The text of the source, for tools that cannot reach the file
+    pub source_content: Option<String>,
+    pub names: Vec<String>,
+    // Ordered by generated line and column
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    /// The map as JSON, one field per line.
+    pub fn to_json(&self) -> String {
+        let names: Vec<String> = self.names.iter().map(|name| quote(name)).collect();
+        let mut out = String::from("{\n  \"version\": 3,\n");
+        writeln!(out, "  \"file\": {},", quote(&self.file)).unwrap();
+        writeln!(out, "  \"sources\": [{}],", quote(&self.source)).unwrap();
+        if let Some(content) = &self.source_content {
+            writeln!(out, "  \"sourcesContent\": [{}],", quote(content)).unwrap();
+        }
+        writeln!(out, "  \"names\": [{}],", names.join(", ")).unwrap();
+        writeln!(
+            out,
+            "  \"mappings\": {}",
+            quote(&encode_mappings(&self.mappings))
+        )
+        .unwrap();
+        out.push('}');
+        out
+    }
+}
+
+/// Appends `value` as a base64 VLQ: the sign in the lowest bit, then groups
+/// of five bits from the lowest, each but the last with the continuation bit.
+pub fn encode_vlq(value: i64, out: &mut String) {
+    let mut rest = if value < 0 {
+        (value.unsigned_abs() << 1) | 1
+    } else {
+        (value as u64) << 1
+    };
+    loop {
+        let mut digit = rest & 0b11111;
+        rest >>= 5;
+        if rest > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if rest == 0 {
+            break;
+        }
+    }
+}
+
+/// Reads one base64 VLQ from the start of `chars`, or `None` if they do not
+/// start with a whole one.
+pub fn decode_vlq(chars: &mut impl Iterator<Item = char>) -> Option<i64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let char = chars.next()?;
+        let digit = BASE64.iter().position(|base| *base as char == char)? as u64;
+        if shift > 60 {
+            return None;
+        }
+        value |= (digit & 0b11111) << shift;
+        shift += 5;
+        if digit & 0b100000 == 0 {
+            break;
+        }
+    }
+    let magnitude = (value >> 1) as i64;
+    Some(if value & 1 == 1 {
+        -magnitude
+    } else {
+        magnitude
+    })
+}
+
+/// The `mappings` field of a map: lines separated by `;` and segments by `,`,
+/// each segment holding the differences from the segment before it. The
+/// generated column starts over on each line, the rest carry on.
+pub fn encode_mappings(mappings: &[Mapping]) -> String {
+    let mut out = String::new();
+    let mut line = 0;
+    let mut column = 0;
+    let mut source_line = 0;
+    let mut source_column = 0;
+    let mut name = 0;
+    for (i, mapping) in mappings.iter().enumerate() {
+        if mapping.generated_line > line {
+            for _ in line..mapping.generated_line {
+                out.push(';');
+            }
+            line = mapping.generated_line;
+            column = 0;
+        } else if i > 0 {
+            out.push(',');
+        }
+        encode_vlq(mapping.generated_column as i64 - column as i64, &mut out);
+        // Every segment is of the first and only source
+        encode_vlq(0, &mut out);
+        encode_vlq(mapping.source_line as i64 - source_line as i64, &mut out);
+        encode_vlq(
+            mapping.source_column as i64 - source_column as i64,
+            &mut out,
+        );
+        if let Some(index) = mapping.name {
+            encode_vlq(index as i64 - name as i64, &mut out);
+            name = index;
+        }
+        column = mapping.generated_column;
+        source_line = mapping.source_line;
+        source_column = mapping.source_column;
+    }
+    out
+}
+
+/// The segments of a `mappings` field, or `None` if it is malformed. Segments
+/// without a source are skipped, and all others are taken to be of the first
+/// source.
+pub fn decode_mappings(mappings: &str) -> Option<Vec<Mapping>> {
+    let mut decoded = vec![];
+    let (mut source_line, mut source_column, mut name) = (0i64, 0i64, 0i64);
+    for (line, segments) in mappings.split(';').enumerate() {
+        let mut column = 0i64;
+        for segment in segments.split(',').filter(|segment| !segment.is_empty()) {
+            let mut chars = segment.chars().peekable();
+            let mut fields = vec![];
+            while chars.peek().is_some() {
+                fields.push(decode_vlq(&mut chars)?);
+            }
+            column += fields[0];
+            if fields.len() == 1 {
+                continue;
+            }
+            let [_, _, line_delta, column_delta, rest @ ..] = fields.as_slice() else {
+                return None;
+            };
+            source_line += line_delta;
+            source_column += column_delta;
+            let mapping_name = match rest {
+                [] => None,
+                [delta] => {
+                    name += delta;
+                    Some(u32::try_from(name).ok()?)
+                }
+                _ => return None,
+            };
+            decoded.push(Mapping {
+                generated_line: line as u32,
+                generated_column: u32::try_from(column).ok()?,
+                source_line: u32::try_from(source_line).ok()?,
+                source_column: u32::try_from(source_column).ok()?,
+                name: mapping_name,
+            });
+        }
+    }
+    Some(decoded)
+}
+
+/// The line and column of every character offset of a source, as spans hold
+/// character offsets and source maps want lines and UTF-16 columns.
+pub struct LineIndex {
+    // Per offset, and one past the end
+    locations: Vec<(u32, u32)>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut locations = Vec::with_capacity(source.len() + 1);
+        let (mut line, mut column) = (0, 0);
+        for char in source.chars() {
+            locations.push((line, column));
+            if char == '\n' {
+                line += 1;
+                column = 0;
+            } else {
+                column += char.len_utf16() as u32;
+            }
+        }
+        locations.push((line, column));
+        Self { locations }
+    }
+
+    /// The line and column of `offset`, which is clamped to the source.
+    pub fn location(&self, offset: u32) -> (u32, u32) {
+        let last = self.locations.len() - 1;
+        self.locations[(offset as usize).min(last)]
+    }
+}
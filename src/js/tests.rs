@@ -0,0 +1,212 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::*;
+use crate::ast::Parser;
+use crate::resolve::resolve_program_with;
+use crate::types::check_program;
+
+// Names the golden programs call without defining
+const HOST: &[&str] = &["print"];
+
+fn compile(name: &str, source: &str) -> JsModule {
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, HOST);
+    assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+    let typing = check_program(&program, &resolution);
+    assert!(!typing.has_errors(), "{:?}", typing.diagnostics);
+    let stem = name.trim_end_matches(".zp");
+    let options = JsOptions {
+        source_name: name.to_string(),
+        file: format!("{}.js", stem),
+        source_content: false,
+    };
+    compile_module(source, &program, &resolution, &typing, &options)
+}
+
+// The programs in `tests/golden/js`, each with the code and source map it
+// compiles to next to it. Run with `UPDATE_GOLDEN=1` to write them anew.
+#[test]
+fn compile_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/js");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".zp"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+
+    let mut changed = vec![];
+    for name in names.iter() {
+        let source = fs::read_to_string(dir.join(name)).unwrap();
+        let module = compile(name, &source);
+        let stem = name.trim_end_matches(".zp");
+        let outputs = [
+            (format!("{}.js", stem), module.code),
+            (
+                format!("{}.js.map", stem),
+                module.source_map.to_json() + "\n",
+            ),
+        ];
+        for (file, actual) in outputs {
+            let path = dir.join(&file);
+            if update {
+                fs::write(&path, &actual).unwrap();
+            } else if fs::read_to_string(&path).ok().as_deref() != Some(actual.as_str()) {
+                changed.push(file);
+            }
+        }
+    }
+    assert!(
+        changed.is_empty(),
+        "differ from their golden files: {:?}",
+        changed
+    );
+}
+
+#[test]
+fn encode_vlq_values() {
+    let encode = |value| {
+        let mut out = String::new();
+        encode_vlq(value, &mut out);
+        out
+    };
+    assert_eq!(encode(0), "A");
+    assert_eq!(encode(1), "C");
+    assert_eq!(encode(-1), "D");
+    assert_eq!(encode(15), "e");
+    assert_eq!(encode(16), "gB");
+    assert_eq!(encode(-17), "jB");
+    assert_eq!(encode(123456), "gkxH");
+
+    for value in [0, 1, -1, 31, 32, -1000, 1 << 40, -(1 << 40)] {
+        let encoded = encode(value);
+        assert_eq!(decode_vlq(&mut encoded.chars()), Some(value));
+    }
+    assert_eq!(decode_vlq(&mut "g".chars()), None);
+    assert_eq!(decode_vlq(&mut "!".chars()), None);
+}
+
+#[test]
+fn encode_mappings_by_line() {
+    let mapping = |generated_line, generated_column, source_line, source_column| Mapping {
+        generated_line,
+        generated_column,
+        source_line,
+        source_column,
+        name: None,
+    };
+    let mappings = vec![
+        mapping(0, 0, 0, 0),
+        mapping(0, 6, 0, 4),
+        Mapping {
+            name: Some(0),
+            ..mapping(2, 2, 1, 0)
+        },
+        mapping(2, 10, 3, 8),
+    ];
+    let encoded = encode_mappings(&mappings);
+    assert_eq!(encoded, "AAAA,MAAI;;EACJA,QAEQ");
+    assert_eq!(decode_mappings(&encoded), Some(mappings));
+    assert_eq!(decode_mappings("AAAA,MA"), None);
+}
+
+// Every mapping leads from a name in the code to the same name in the
+// source. A renamed one is mapped with its source name, to the statement
+// declaring it or to a use.
+#[test]
+fn map_names_to_source() {
+    let source = "let total = 1;\nlet total = total + 1;\nfn class(x) {\n    x + total\n}";
+    let module = compile("names.zp", source);
+    assert_eq!(
+        module.code,
+        "const total = 1;\nconst total$1 = total + 1;\nfunction $class(x) {\n  return x + total$1;\n}\n\
+         //# sourceMappingURL=names.js.map\n"
+    );
+    assert_eq!(module.source_map.names, vec!["total", "class"]);
+
+    let lines: Vec<&str> = module.code.lines().collect();
+    let source_lines: Vec<&str> = source.lines().collect();
+    let word = |line: &str, column: u32| -> String {
+        line[column as usize..]
+            .chars()
+            .take_while(|char| char.is_alphanumeric() || *char == '$' || *char == '_')
+            .collect()
+    };
+    let mappings = decode_mappings(&encode_mappings(&module.source_map.mappings)).unwrap();
+    assert_eq!(mappings, module.source_map.mappings);
+    let mut renamed = 0;
+    for mapping in mappings.iter() {
+        let generated = word(
+            lines[mapping.generated_line as usize],
+            mapping.generated_column,
+        );
+        let original = word(
+            source_lines[mapping.source_line as usize],
+            mapping.source_column,
+        );
+        match mapping.name {
+            Some(name) => {
+                renamed += 1;
+                let name = &module.source_map.names[name as usize];
+                assert!(*name == original || ["let", "fn"].contains(&original.as_str()));
+                assert!(generated.contains(name.as_str()), "{}", generated);
+            }
+            None if is_reserved(&generated) => {}
+            None if !generated.is_empty() && !original.is_empty() => {
+                let keywords = [("const", "let"), ("function", "fn"), ("return", "x")];
+                assert!(
+                    generated == original || keywords.contains(&(&generated, &original)),
+                    "{} for {}",
+                    generated,
+                    original
+                );
+            }
+            None => {}
+        }
+    }
+    assert_eq!(renamed, 3);
+}
+
+#[test]
+fn rename_shadowed_and_reserved_names() {
+    let source =
+        "let a = 1; let a = a; { let a = a; a }; fn f(a) { let a = a; a } let b = fn(this) => this";
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, &["print"]);
+    let names = binding_names(&resolution);
+    let mut renamed: Vec<&str> = resolution
+        .bindings
+        .iter()
+        .map(|binding| names[&binding.id].as_str())
+        .collect();
+    renamed.sort();
+    assert_eq!(
+        renamed,
+        vec!["$this", "a", "a$1", "a$2", "a$2", "a$3", "b", "f", "print"]
+    );
+    assert!(is_reserved("class") && is_reserved("undefined"));
+    assert!(!is_reserved("print"));
+}
+
+#[test]
+fn embed_source_content() {
+    let source = "let a = 'x';";
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, &[]);
+    let typing = check_program(&program, &resolution);
+    let options = JsOptions {
+        source_content: true,
+        ..JsOptions::default()
+    };
+    let module = compile_module(source, &program, &resolution, &typing, &options);
+    assert_eq!(module.code, "const a = \"x\";\n");
+    assert_eq!(
+        module.source_map.to_json(),
+        "{\n  \"version\": 3,\n  \"file\": \"\",\n  \"sources\": [\"\"],\n  \
+         \"sourcesContent\": [\"let a = 'x';\"],\n  \"names\": [],\n  \"mappings\": \"AAAA,UAAQ\"\n}"
+    );
+}
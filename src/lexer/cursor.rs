@@ -12,7 +12,7 @@ impl<'a> Cursor<'a> {
     }
 
     #[inline]
-    pub fn peek(&self) -> Chars {
+    pub fn peek(&self) -> Chars<'_> {
         self.chars.clone()
     }
 
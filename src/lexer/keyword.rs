@@ -4,22 +4,51 @@ pub enum Keyword {
     Fn,
     State,
     If,
+    Else,
     True,
     False,
+    Type,
+    Match,
+    Import,
+    Export,
+    Component,
 }
 
 impl Keyword {
-    pub fn parse(ident: &String) -> Option<Keyword> {
-        match ident.as_str() {
+    pub fn parse(ident: &str) -> Option<Keyword> {
+        match ident {
             "let" => Some(Keyword::Let),
             "fn" => Some(Keyword::Fn),
             "if" => Some(Keyword::If),
+            "else" => Some(Keyword::Else),
             "state" => Some(Keyword::State),
             "true" => Some(Keyword::True),
             "false" => Some(Keyword::False),
+            "type" => Some(Keyword::Type),
+            "match" => Some(Keyword::Match),
+            "import" => Some(Keyword::Import),
+            "export" => Some(Keyword::Export),
+            "component" => Some(Keyword::Component),
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Let => "let",
+            Keyword::Fn => "fn",
+            Keyword::State => "state",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::True => "true",
+            Keyword::False => "false",
+            Keyword::Type => "type",
+            Keyword::Match => "match",
+            Keyword::Import => "import",
+            Keyword::Export => "export",
+            Keyword::Component => "component",
+        }
+    }
 }
 
 #[cfg(test)]
@@ -28,9 +57,15 @@ mod tests {
 
     #[test]
     fn parse_keyword() {
-        assert_eq!(Keyword::parse(&"let".into()), Some(Keyword::Let));
-        assert_eq!(Keyword::parse(&"fn".into()), Some(Keyword::Fn));
-        assert_eq!(Keyword::parse(&"state".into()), Some(Keyword::State));
-        assert_eq!(Keyword::parse(&"hello_world".into()), None);
+        assert_eq!(Keyword::parse("let"), Some(Keyword::Let));
+        assert_eq!(Keyword::parse("fn"), Some(Keyword::Fn));
+        assert_eq!(Keyword::parse("state"), Some(Keyword::State));
+        assert_eq!(Keyword::parse("else"), Some(Keyword::Else));
+        assert_eq!(Keyword::parse("match"), Some(Keyword::Match));
+        assert_eq!(Keyword::parse("import"), Some(Keyword::Import));
+        assert_eq!(Keyword::parse("component"), Some(Keyword::Component));
+        assert_eq!(Keyword::parse("from"), None);
+        assert_eq!(Keyword::parse("hello_world"), None);
+        assert_eq!(Keyword::Component.as_str(), "component");
     }
 }
@@ -28,6 +28,20 @@ pub enum TokenKind {
     Operator(Operator),
     NumberChar(NumberChar),
     Separation(Separation),
+    // `//` up to the end of the line, without the slashes
+    Comment(String),
+    // Characters between the tags of markup, read in `LexerMode::Markup`
+    Text(String),
+}
+
+/// What the lexer reads. The parser switches to `Markup` for the children
+/// of an element, where anything but `<`, `>`, `{` and `}` is text, and back
+/// to `Code` for tags and the expressions in braces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LexerMode {
+    #[default]
+    Code,
+    Markup,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -45,23 +59,48 @@ impl Token {
 #[derive(Clone, Debug)]
 pub struct Lexer<'a> {
     cursor: Cursor<'a>,
+    mode: LexerMode,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(chars: Chars<'a>) -> Self {
         Self {
             cursor: Cursor::new(chars),
+            mode: LexerMode::Code,
         }
     }
 
-    fn parse(cursor: &mut Cursor<'_>) -> Option<Token> {
+    fn parse(cursor: &mut Cursor<'_>, mode: LexerMode) -> Option<Token> {
         let mut pos = Position {
             start: cursor.position,
-            end: cursor.position,
+            end: cursor.position + 1,
         };
+        if mode == LexerMode::Markup && !is_markup_char(cursor.first()?) {
+            let mut text = String::new();
+            while let Some(char) = cursor.first().filter(|char| !is_markup_char(*char)) {
+                text.push(char);
+                cursor.bump();
+            }
+            pos.end = cursor.position;
+            return Some(Token::new(TokenKind::Text(text), pos));
+        }
         let char = &cursor.bump()?;
 
-        if let Some(kind) = Self::parse_char(char) {
+        if *char == '/' && cursor.first() == Some('/') {
+            cursor.bump();
+            pos.end += 1;
+            let mut comment = String::new();
+            while let Some(char) = cursor.first() {
+                if char == '\n' {
+                    break;
+                }
+                comment.push(char);
+                cursor.bump();
+                pos.end += 1;
+            }
+
+            Some(Token::new(TokenKind::Comment(comment), pos))
+        } else if let Some(kind) = Self::parse_char(char) {
             Some(Token { pos, kind })
         } else if let Some(separator) = Separator::parse(char) {
             let mut string = String::new();
@@ -88,7 +127,7 @@ impl<'a> Lexer<'a> {
                 identifier.push(curr_char);
                 if let Some(char) = cursor.first() {
                     curr_char = char;
-                    if let Some(_) = Self::parse_char(&curr_char) {
+                    if Self::parse_char(&curr_char).is_some() {
                         break;
                     } else {
                         cursor.bump();
@@ -114,21 +153,24 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn bump(&mut self) -> Option<Token> {
-        let bumped = Self::parse(&mut self.cursor);
-        bumped
+        Self::parse(&mut self.cursor, self.mode)
+    }
+
+    pub fn mode(&self) -> LexerMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: LexerMode) {
+        self.mode = mode;
     }
 
     pub fn ignore_some(&mut self, matcher: fn(&TokenKind) -> bool) -> Option<Token> {
         let mut new_cursor = self.cursor.clone();
         let mut last = None;
-        loop {
-            if let Some(token) = Self::parse(&mut new_cursor) {
-                if matcher(&token.kind) {
-                    last = Some(token);
-                    self.cursor = new_cursor.clone();
-                } else {
-                    break;
-                }
+        while let Some(token) = Self::parse(&mut new_cursor, self.mode) {
+            if matcher(&token.kind) {
+                last = Some(token);
+                self.cursor = new_cursor.clone();
             } else {
                 break;
             }
@@ -137,18 +179,17 @@ impl<'a> Lexer<'a> {
         last
     }
 
+    /// Skips whitespace and comments, which carry no meaning for the parser.
     pub fn ignore_spaces(&mut self) -> Option<Token> {
-        self.ignore_some(|kind| match kind {
-            TokenKind::Space(_) => true,
-            _ => false,
-        })
+        self.ignore_some(|kind| matches!(kind, TokenKind::Space(_) | TokenKind::Comment(_)))
     }
 
     pub fn ignore_semicolon(&mut self) -> Option<Token> {
-        self.ignore_some(|kind| match kind {
-            TokenKind::Punctuation(Punctuation::Semicolon) => true,
-            _ => false,
-        })
+        self.ignore_some(|kind| matches!(kind, TokenKind::Punctuation(Punctuation::Semicolon)))
+    }
+
+    pub fn position(&self) -> u32 {
+        self.cursor.position
     }
 
     pub fn ignore(&mut self) {
@@ -174,10 +215,13 @@ impl<'a> Lexer<'a> {
             Some(TokenKind::Punctuation(result))
         } else if let Some(result) = NumberChar::parse(char) {
             Some(TokenKind::NumberChar(result))
-        } else if let Some(result) = Operator::parse(char) {
-            Some(TokenKind::Operator(result))
         } else {
-            None
+            Operator::parse(char).map(TokenKind::Operator)
         }
     }
 }
+
+// The characters that end text in markup
+fn is_markup_char(char: char) -> bool {
+    matches!(char, '<' | '>' | '{' | '}')
+}
@@ -2,6 +2,13 @@
 pub enum Operator {
     Equal,
     Slash,
+    Plus,
+    Minus,
+    Star,
+    Percent,
+    Bang,
+    Ampersand,
+    Pipe,
 }
 
 impl Operator {
@@ -10,6 +17,20 @@ impl Operator {
             Some(Operator::Equal)
         } else if *char == '/' {
             Some(Operator::Slash)
+        } else if *char == '+' {
+            Some(Operator::Plus)
+        } else if *char == '-' {
+            Some(Operator::Minus)
+        } else if *char == '*' {
+            Some(Operator::Star)
+        } else if *char == '%' {
+            Some(Operator::Percent)
+        } else if *char == '!' {
+            Some(Operator::Bang)
+        } else if *char == '&' {
+            Some(Operator::Ampersand)
+        } else if *char == '|' {
+            Some(Operator::Pipe)
         } else {
             None
         }
@@ -24,6 +45,13 @@ mod tests {
     fn parse_bracket() {
         assert_eq!(Operator::parse(&'='), Some(Operator::Equal));
         assert_eq!(Operator::parse(&'/'), Some(Operator::Slash));
+        assert_eq!(Operator::parse(&'+'), Some(Operator::Plus));
+        assert_eq!(Operator::parse(&'-'), Some(Operator::Minus));
+        assert_eq!(Operator::parse(&'*'), Some(Operator::Star));
+        assert_eq!(Operator::parse(&'%'), Some(Operator::Percent));
+        assert_eq!(Operator::parse(&'!'), Some(Operator::Bang));
+        assert_eq!(Operator::parse(&'&'), Some(Operator::Ampersand));
+        assert_eq!(Operator::parse(&'|'), Some(Operator::Pipe));
         assert_eq!(Operator::parse(&' '), None);
     }
 }
@@ -1,4 +1,5 @@
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A half-open range `start..end` of char offsets into the source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Position {
     pub start: u32,
     pub end: u32,
@@ -9,27 +10,59 @@ impl Position {
         Self { start, end }
     }
 
+    pub fn len(&self) -> u32 {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `other` lies entirely within this range.
+    pub fn contains(&self, other: &Position) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// The smallest range covering both.
+    pub fn to(&self, other: &Position) -> Position {
+        Position::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
     pub fn get(&self, string: &str) -> Option<String> {
-        let mut new_str = "".to_string();
-
-        let mut chars = string.chars();
-        let mut i = 0;
-        loop {
-            if i >= self.start {
-                break;
-            }
-            chars.next();
-            i += 1;
+        if self.start > self.end {
+            return None;
         }
-
-        loop {
-            new_str.push(chars.next()?);
-            if i >= self.end {
-                break;
-            }
-            i += 1;
+        let taken: String = string
+            .chars()
+            .skip(self.start as usize)
+            .take(self.len() as usize)
+            .collect();
+        if taken.chars().count() == self.len() as usize {
+            Some(taken)
+        } else {
+            None
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_position() {
+        assert_eq!(Position::new(1, 3).get("abcd"), Some("bc".to_string()));
+        assert_eq!(Position::new(2, 2).get("abcd"), Some("".to_string()));
+        assert_eq!(Position::new(3, 5).get("abcd"), None);
+    }
 
-        Some(new_str)
+    #[test]
+    fn contains_position() {
+        let outer = Position::new(2, 8);
+        assert!(outer.contains(&Position::new(2, 8)));
+        assert!(outer.contains(&Position::new(3, 5)));
+        assert!(!outer.contains(&Position::new(1, 5)));
+        assert!(!outer.contains(&Position::new(5, 9)));
+        assert_eq!(outer.to(&Position::new(7, 10)), Position::new(2, 10));
     }
 }
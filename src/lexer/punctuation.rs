@@ -3,6 +3,7 @@ pub enum Punctuation {
     Comma,
     FullStop,
     Semicolon,
+    Colon,
 }
 
 impl Punctuation {
@@ -13,6 +14,8 @@ impl Punctuation {
             Some(Punctuation::FullStop)
         } else if *char == ';' {
             Some(Punctuation::Semicolon)
+        } else if *char == ':' {
+            Some(Punctuation::Colon)
         } else {
             None
         }
@@ -28,6 +31,7 @@ mod tests {
         assert_eq!(Punctuation::parse(&','), Some(Punctuation::Comma));
         assert_eq!(Punctuation::parse(&'.'), Some(Punctuation::FullStop));
         assert_eq!(Punctuation::parse(&';'), Some(Punctuation::Semicolon));
+        assert_eq!(Punctuation::parse(&':'), Some(Punctuation::Colon));
         assert_eq!(Punctuation::parse(&' '), None);
     }
 }
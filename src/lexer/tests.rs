@@ -8,7 +8,7 @@ fn parse_bracket() {
     assert_eq!(
         token,
         Token {
-            pos: Position { start: 0, end: 0 },
+            pos: Position { start: 0, end: 1 },
             kind: TokenKind::Bracket(Bracket::OpenParen)
         }
     );
@@ -24,7 +24,7 @@ fn parse_keyword() {
     assert_eq!(
         token,
         Token {
-            pos: Position { start: 0, end: 2 },
+            pos: Position { start: 0, end: 3 },
             kind: TokenKind::Keyword(Keyword::Let)
         }
     );
@@ -37,7 +37,7 @@ fn parse_keyword() {
     assert_eq!(
         token,
         Token {
-            pos: Position { start: 4, end: 5 },
+            pos: Position { start: 4, end: 6 },
             kind: TokenKind::Keyword(Keyword::If)
         }
     );
@@ -53,7 +53,7 @@ fn parse_separation() {
     assert_eq!(
         token,
         Token {
-            pos: Position { start: 0, end: 12 },
+            pos: Position { start: 0, end: 13 },
             kind: TokenKind::Separation(Separation::new(
                 Separator::SingleQuote,
                 "hello world".to_string()
@@ -63,3 +63,45 @@ fn parse_separation() {
     let char = token.pos.get(test_str);
     assert_eq!(char, Some("'hello world'".to_string()));
 }
+
+#[test]
+fn parse_comment() {
+    let test_str = "a // hello\nb";
+    let mut lexer = Lexer::new(test_str.chars());
+    lexer.bump();
+    lexer.bump();
+    let token = lexer.bump().unwrap();
+    assert_eq!(
+        token,
+        Token {
+            pos: Position { start: 2, end: 10 },
+            kind: TokenKind::Comment(" hello".to_string())
+        }
+    );
+    assert_eq!(lexer.bump().unwrap().kind, TokenKind::Space(Space::NewLine));
+}
+
+#[test]
+fn parse_markup_text() {
+    let test_str = "Don't // stop{x}";
+    let mut lexer = Lexer::new(test_str.chars());
+    lexer.set_mode(LexerMode::Markup);
+    assert_eq!(
+        lexer.bump().unwrap(),
+        Token {
+            pos: Position { start: 0, end: 13 },
+            kind: TokenKind::Text("Don't // stop".to_string())
+        }
+    );
+    assert_eq!(
+        lexer.bump().unwrap().kind,
+        TokenKind::Bracket(Bracket::OpenCurly)
+    );
+
+    // Back in code mode, the rest is read as tokens again
+    lexer.set_mode(LexerMode::Code);
+    assert_eq!(
+        lexer.bump().unwrap().kind,
+        TokenKind::Identifier("x".to_string())
+    );
+}
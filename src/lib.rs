@@ -1,2 +1,19 @@
 pub mod ast;
+pub mod c;
+#[cfg(test)]
+mod corpus;
+pub mod diagnostic;
+pub mod engine;
+pub mod formatter;
+pub mod interp;
+pub mod ir;
+pub mod js;
 pub mod lexer;
+pub mod module;
+pub mod print;
+pub mod reactive;
+pub mod render;
+pub mod resolve;
+pub mod types;
+pub mod vm;
+pub mod wasm;
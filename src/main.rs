@@ -0,0 +1,105 @@
+use std::{env, fs, process};
+
+use zope::ast::Parser;
+use zope::formatter;
+use zope::reactive::extract_graph;
+use zope::resolve::resolve_program;
+
+const USAGE: &str = "usage: zope fmt [--check] <file>...\n       zope graph [--json] <file>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(String::as_str) {
+        Some("fmt") => fmt(&args[1..]),
+        Some("graph") => graph(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    };
+    process::exit(code);
+}
+
+// Formats the files in place, or with `--check` only lists the files that
+// are not formatted yet.
+fn fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let mut code = 0;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                code = 1;
+                continue;
+            }
+        };
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                code = 1;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", path);
+            code = 1;
+        } else if let Err(error) = fs::write(path, formatted) {
+            eprintln!("{}: {}", path, error);
+            code = 1;
+        }
+    }
+    code
+}
+
+// Prints the reactive graph of a file as DOT, or with `--json` as JSON, and
+// warns about its cycles.
+fn graph(args: &[String]) -> i32 {
+    let json = args.iter().any(|arg| arg == "--json");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let [path] = paths[..] else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            return 1;
+        }
+    };
+    let mut parser = Parser::new(&source);
+    let Some(program) = parser.parse_program() else {
+        eprintln!(
+            "{}: cannot parse at offset {}",
+            path,
+            parser.lexer.position()
+        );
+        return 1;
+    };
+    // Names the file does not define, like those of the host, are not in
+    // the graph
+    let resolution = resolve_program(&program);
+
+    let graph = extract_graph(&program, &resolution);
+    for diagnostic in graph.diagnostics() {
+        eprintln!("{}: {}", path, diagnostic);
+    }
+    match json {
+        true => print!("{}", graph.to_json()),
+        false => print!("{}", graph.to_dot()),
+    }
+    0
+}
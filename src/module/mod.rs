@@ -0,0 +1,386 @@
+pub mod provider;
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+use crate::resolve::*;
+
+pub use self::provider::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModuleId(pub u32);
+
+/// A parsed and resolved source file. Diagnostics about its imports, like a
+/// name the imported module does not export, are in its resolution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    pub id: ModuleId,
+    // Relative to the root directory, with the `.zp` extension
+    pub path: PathBuf,
+    pub source: String,
+    pub program: Program,
+    pub resolution: Resolution,
+    // The module of each top-level import, in source order
+    pub imports: Vec<ModuleId>,
+    pub exports: HashMap<String, BindingId>,
+    // Imported binding to the exported binding it refers to
+    pub links: HashMap<BindingId, (ModuleId, BindingId)>,
+}
+
+impl Module {
+    fn new(id: ModuleId, path: PathBuf, source: String, program: Program) -> Module {
+        let resolution = resolve_program(&program);
+        let mut exports = HashMap::new();
+        for stmt in program.stmts.iter() {
+            let StmtKind::Export(exported) = &stmt.kind else {
+                continue;
+            };
+            let mut export = |name: &str, pos: Position| {
+                if let Some(id) = resolution.definitions.get(&pos) {
+                    exports.insert(name.to_string(), *id);
+                }
+            };
+            match &exported.kind {
+                StmtKind::Let(LetStmt { identifier, .. })
+                | StmtKind::State(StateStmt { identifier, .. }) => export(identifier, exported.pos),
+                StmtKind::Fn(fn_stmt) => export(&fn_stmt.name, exported.pos),
+                StmtKind::Component(component) => export(&component.name, exported.pos),
+                // The type itself is named by the checker, not the resolver,
+                // so only its variants can be imported
+                StmtKind::Type(type_stmt) => {
+                    for variant in type_stmt.variants.iter() {
+                        export(&variant.name, variant.pos);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            id,
+            path,
+            source,
+            program,
+            resolution,
+            imports: vec![],
+            exports,
+            links: HashMap::new(),
+        }
+    }
+
+    fn import_stmts(&self) -> impl Iterator<Item = &ImportStmt> {
+        self.program
+            .stmts
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Import(import_stmt) => Some(import_stmt),
+                _ => None,
+            })
+    }
+
+    // Links each imported name to the export it refers to
+    fn link(&mut self, modules: &[Module]) {
+        let mut links = HashMap::new();
+        let mut diagnostics = vec![];
+        for (import_stmt, id) in self.import_stmts().zip(self.imports.iter()) {
+            let imported = &modules[id.0 as usize];
+            for name in import_stmt.names.iter() {
+                let Some(binding) = self.resolution.definitions.get(&name.pos) else {
+                    continue;
+                };
+                if let Some(export) = imported.exports.get(&name.name) {
+                    links.insert(*binding, (*id, *export));
+                    continue;
+                }
+                let diagnostic = match imported.top_level(&name.name) {
+                    Some(definition) => Diagnostic::error(
+                        format!("`{}` is not exported by `{}`", name.name, import_stmt.path),
+                        name.pos,
+                    )
+                    .with_label(
+                        format!("defined here in `{}`", imported.path.display()),
+                        definition.pos,
+                    ),
+                    None => Diagnostic::error(
+                        format!("`{}` has no export `{}`", import_stmt.path, name.name),
+                        name.pos,
+                    ),
+                };
+                diagnostics.push(diagnostic);
+            }
+        }
+        self.links = links;
+        self.resolution.diagnostics.extend(diagnostics);
+    }
+
+    fn top_level(&self, name: &str) -> Option<&Binding> {
+        let program = self.resolution.scopes.first()?;
+        program
+            .bindings
+            .iter()
+            .map(|id| self.resolution.binding(*id))
+            .find(|binding| binding.name == name)
+    }
+}
+
+/// Every module reachable from an entry module. Modules come after the
+/// modules they import, so the entry is the last one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleGraph {
+    pub modules: Vec<Module>,
+}
+
+impl ModuleGraph {
+    pub fn module(&self, id: ModuleId) -> &Module {
+        &self.modules[id.0 as usize]
+    }
+
+    pub fn entry(&self) -> &Module {
+        self.modules.last().unwrap()
+    }
+
+    /// The module at `path`, relative to the root directory.
+    pub fn find(&self, path: impl AsRef<Path>) -> Option<&Module> {
+        self.modules
+            .iter()
+            .find(|module| module.path == path.as_ref())
+    }
+
+    /// The module and binding that `binding` of `module` finally refers to,
+    /// following imports.
+    pub fn origin(&self, module: ModuleId, binding: BindingId) -> (ModuleId, BindingId) {
+        match self.module(module).links.get(&binding) {
+            Some((module, binding)) => self.origin(*module, *binding),
+            None => (module, binding),
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.modules
+            .iter()
+            .any(|module| module.resolution.has_errors())
+    }
+}
+
+/// The import a module was loaded for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportSite {
+    pub module: PathBuf,
+    // The path string of the import
+    pub pos: Position,
+}
+
+/// Why a module graph cannot be loaded. Paths are relative to the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    Read {
+        path: PathBuf,
+        message: String,
+        site: Option<ImportSite>,
+    },
+    // Where parsing stopped
+    Parse {
+        path: PathBuf,
+        pos: Position,
+    },
+    OutsideRoot {
+        import: String,
+        site: Option<ImportSite>,
+    },
+    // Each module imports the next one, and the last is the first again. `pos`
+    // is the import that closes the cycle.
+    Cycle {
+        cycle: Vec<PathBuf>,
+        pos: Position,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let site = match self {
+            LoadError::Read {
+                path,
+                message,
+                site,
+            } => {
+                write!(f, "cannot read `{}`: {}", path.display(), message)?;
+                site
+            }
+            LoadError::Parse { path, pos } => {
+                return write!(
+                    f,
+                    "cannot parse `{}` at offset {}",
+                    path.display(),
+                    pos.start
+                );
+            }
+            LoadError::OutsideRoot { import, site } => {
+                write!(f, "`{}` is outside the root directory", import)?;
+                site
+            }
+            LoadError::Cycle { cycle, pos } => {
+                let paths: Vec<String> = cycle
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                return write!(
+                    f,
+                    "import cycle: {} at {}..{}",
+                    paths.join(" -> "),
+                    pos.start,
+                    pos.end
+                );
+            }
+        };
+        if let Some(site) = site {
+            write!(
+                f,
+                "\n  imported by `{}` at {}..{}",
+                site.module.display(),
+                site.pos.start,
+                site.pos.end
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// The path an import refers to, relative to the root. Paths starting with
+/// `./` or `../` are relative to the importing module, and others to the root.
+/// Returns `None` for paths outside the root.
+pub fn resolve_path(importer: &Path, import: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    if import.starts_with("./") || import.starts_with("../") {
+        if let Some(dir) = importer.parent() {
+            path.push(dir);
+        }
+    }
+    for component in Path::new(import).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !path.pop() {
+                    return None;
+                }
+            }
+            Component::Normal(name) => path.push(name),
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    path.file_name()?;
+    if path.extension().is_none() {
+        path.set_extension("zp");
+    }
+    Some(path)
+}
+
+/// Loads a module and everything it imports through a `FileProvider`, with
+/// paths relative to a root directory.
+pub struct Loader<P> {
+    provider: P,
+    root: PathBuf,
+}
+
+#[derive(Default)]
+struct Load {
+    modules: Vec<Module>,
+    ids: HashMap<PathBuf, ModuleId>,
+    // Modules whose imports are being loaded, importers first
+    stack: Vec<PathBuf>,
+}
+
+impl<P: FileProvider> Loader<P> {
+    pub fn new(provider: P, root: impl Into<PathBuf>) -> Self {
+        Self {
+            provider,
+            root: root.into(),
+        }
+    }
+
+    pub fn load(&self, entry: &str) -> Result<ModuleGraph, LoadError> {
+        let path = resolve_path(Path::new(""), entry).ok_or_else(|| LoadError::OutsideRoot {
+            import: entry.to_string(),
+            site: None,
+        })?;
+        let mut load = Load::default();
+        self.visit(&mut load, path, None)?;
+        Ok(ModuleGraph {
+            modules: load.modules,
+        })
+    }
+
+    // Loads the imports of a module before the module itself
+    fn visit(
+        &self,
+        load: &mut Load,
+        path: PathBuf,
+        site: Option<ImportSite>,
+    ) -> Result<ModuleId, LoadError> {
+        if let Some(id) = load.ids.get(&path) {
+            return Ok(*id);
+        }
+        if let Some(start) = load.stack.iter().position(|loading| *loading == path) {
+            let mut cycle = load.stack[start..].to_vec();
+            cycle.push(path);
+            return Err(LoadError::Cycle {
+                cycle,
+                pos: site.map_or(Position::new(0, 0), |site| site.pos),
+            });
+        }
+
+        let source = match self.provider.read(&self.root.join(&path)) {
+            Ok(source) => source,
+            Err(error) => {
+                return Err(LoadError::Read {
+                    path,
+                    message: error.to_string(),
+                    site,
+                })
+            }
+        };
+        let mut parser = Parser::new(&source);
+        let Some(program) = parser.parse_program() else {
+            let offset = parser.lexer.position();
+            return Err(LoadError::Parse {
+                path,
+                pos: Position::new(offset, offset),
+            });
+        };
+
+        load.stack.push(path.clone());
+        let mut imports = vec![];
+        for stmt in program.stmts.iter() {
+            let StmtKind::Import(import_stmt) = &stmt.kind else {
+                continue;
+            };
+            let site = ImportSite {
+                module: path.clone(),
+                pos: import_stmt.path_pos,
+            };
+            let Some(imported) = resolve_path(&path, &import_stmt.path) else {
+                return Err(LoadError::OutsideRoot {
+                    import: import_stmt.path.clone(),
+                    site: Some(site),
+                });
+            };
+            imports.push(self.visit(load, imported, Some(site))?);
+        }
+        load.stack.pop();
+
+        let id = ModuleId(load.modules.len() as u32);
+        let mut module = Module::new(id, path.clone(), source, program);
+        module.imports = imports;
+        module.link(&load.modules);
+        load.modules.push(module);
+        load.ids.insert(path, id);
+        Ok(id)
+    }
+}
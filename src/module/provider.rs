@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where the loader gets module sources from.
+pub trait FileProvider {
+    fn read(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Reads modules from disk.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FsProvider;
+
+impl FileProvider for FsProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+/// Files kept in memory, keyed by the path the loader asks for.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryProvider {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: impl Into<PathBuf>, source: impl Into<String>) {
+        self.files.insert(path.into(), source.into());
+    }
+}
+
+impl FileProvider for MemoryProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file at {}", path.display()),
+            )
+        })
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+fn provider(files: &[(&str, &str)]) -> MemoryProvider {
+    let mut provider = MemoryProvider::new();
+    for (path, source) in files.iter() {
+        provider.insert(Path::new("app").join(path), *source);
+    }
+    provider
+}
+
+fn load(files: &[(&str, &str)], entry: &str) -> Result<ModuleGraph, LoadError> {
+    Loader::new(provider(files), "app").load(entry)
+}
+
+fn span(source: &str, text: &str) -> Position {
+    let start = source.find(text).unwrap() as u32;
+    Position::new(start, start + text.len() as u32)
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+#[test]
+fn resolve_import_paths() {
+    let app = Path::new("app.zp");
+    let button = Path::new("ui/button.zp");
+    assert_eq!(resolve_path(app, "./button"), Some("button.zp".into()));
+    assert_eq!(resolve_path(button, "./icon"), Some("ui/icon.zp".into()));
+    assert_eq!(resolve_path(button, "../theme"), Some("theme.zp".into()));
+    assert_eq!(resolve_path(button, "theme"), Some("theme.zp".into()));
+    assert_eq!(
+        resolve_path(button, "./data.txt"),
+        Some("ui/data.txt".into())
+    );
+    assert_eq!(resolve_path(app, "../secrets"), None);
+    assert_eq!(resolve_path(app, "/etc/passwd"), None);
+    assert_eq!(resolve_path(app, "."), None);
+}
+
+#[test]
+fn load_modules_in_dependency_order() {
+    let app = "import { Button } from './ui/button';\nimport { card } from './ui/card';\ncard(Button('ok'))";
+    let graph = load(
+        &[
+            ("main.zp", app),
+            (
+                "ui/button.zp",
+                "import { theme } from '../theme';\nexport fn Button(label) { label }",
+            ),
+            (
+                "ui/card.zp",
+                "import { theme } from '../theme';\nexport let card = fn(child) => child;",
+            ),
+            ("theme.zp", "export let theme = 'dark';"),
+        ],
+        "main",
+    )
+    .unwrap();
+
+    let order: Vec<&Path> = graph
+        .modules
+        .iter()
+        .map(|module| module.path.as_path())
+        .collect();
+    let expected = paths(&["theme.zp", "ui/button.zp", "ui/card.zp", "main.zp"]);
+    assert_eq!(
+        order,
+        expected.iter().map(PathBuf::as_path).collect::<Vec<_>>()
+    );
+    assert_eq!(graph.entry().imports, vec![ModuleId(1), ModuleId(2)]);
+    assert_eq!(graph.find("ui/card.zp").unwrap().imports, vec![ModuleId(0)]);
+    assert!(!graph.has_errors());
+
+    // A use of an imported name leads to the exported function
+    let main = graph.entry();
+    let start = span(app, "Button('").start;
+    let binding = main
+        .resolution
+        .resolve(&Position::new(start, start + 6))
+        .unwrap();
+    assert_eq!(binding.kind, BindingKind::Import);
+    assert_eq!(binding.pos, span(app, "Button"));
+    let (module, origin) = graph.origin(main.id, binding.id);
+    let module = graph.module(module);
+    assert_eq!(module.path, Path::new("ui/button.zp"));
+    assert_eq!(module.resolution.binding(origin).kind, BindingKind::Fn);
+    assert_eq!(module.exports["Button"], origin);
+}
+
+#[test]
+fn link_exported_variants() {
+    let shapes = "export type Shape = Circle(Number) | Square(Number);\ntype Hidden = Secret;";
+    let app = "import { Circle, Secret, Triangle } from './shapes';\nCircle(1)";
+    let graph = load(&[("shapes.zp", shapes), ("app.zp", app)], "app.zp").unwrap();
+
+    let main = graph.entry();
+    let circle = main.resolution.definitions[&span(app, "Circle")];
+    let (_, origin) = graph.origin(main.id, circle);
+    let shapes_module = graph.find("shapes.zp").unwrap();
+    assert_eq!(
+        shapes_module.resolution.binding(origin).pos,
+        span(shapes, "Circle(Number)")
+    );
+
+    let messages: Vec<String> = main
+        .resolution
+        .diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            format!(
+                "error: `Secret` is not exported by `./shapes` at 17..23\n  defined here in `shapes.zp` at {}..{}",
+                span(shapes, "Secret").start,
+                span(shapes, "Secret").end
+            ),
+            "error: `./shapes` has no export `Triangle` at 25..33".to_string(),
+        ]
+    );
+    assert!(graph.has_errors());
+}
+
+#[test]
+fn detect_import_cycles() {
+    let c = "import { a } from './a';\nexport let c = 1;";
+    let error = load(
+        &[
+            ("a.zp", "import { b } from './b';\nexport let a = 1;"),
+            ("b.zp", "import { c } from './c';\nexport let b = 1;"),
+            ("c.zp", c),
+        ],
+        "a",
+    )
+    .unwrap_err();
+    assert_eq!(
+        error,
+        LoadError::Cycle {
+            cycle: paths(&["a.zp", "b.zp", "c.zp", "a.zp"]),
+            pos: span(c, "'./a'"),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "import cycle: a.zp -> b.zp -> c.zp -> a.zp at 18..23"
+    );
+
+    let error = load(&[("a.zp", "import {} from './a'")], "a").unwrap_err();
+    assert!(matches!(error, LoadError::Cycle { cycle, .. } if cycle == paths(&["a.zp", "a.zp"])));
+}
+
+#[test]
+fn share_modules_imported_twice() {
+    let graph = load(
+        &[
+            ("a.zp", "import { b } from './b';\nimport { c } from './c';"),
+            ("b.zp", "import { c } from './c';\nexport let b = 1;"),
+            ("c.zp", "export let c = 1;"),
+        ],
+        "a",
+    )
+    .unwrap();
+    assert_eq!(graph.modules.len(), 3);
+    assert_eq!(graph.entry().imports, vec![ModuleId(1), ModuleId(0)]);
+}
+
+#[test]
+fn report_load_errors() {
+    let app = "import { x } from './missing';";
+    let error = load(&[("app.zp", app)], "app").unwrap_err();
+    match &error {
+        LoadError::Read { path, site, .. } => {
+            assert_eq!(path, Path::new("missing.zp"));
+            assert_eq!(
+                site,
+                &Some(ImportSite {
+                    module: "app.zp".into(),
+                    pos: span(app, "'./missing'"),
+                })
+            );
+        }
+        _ => panic!("expected a read error, got {:?}", error),
+    }
+
+    let error = load(&[("app.zp", "import { x } from '../up';")], "app").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`../up` is outside the root directory\n  imported by `app.zp` at 18..25"
+    );
+
+    let error = load(&[("app.zp", "let = 1;")], "app").unwrap_err();
+    assert_eq!(
+        error,
+        LoadError::Parse {
+            path: "app.zp".into(),
+            pos: Position::new(0, 0),
+        }
+    );
+}
+
+#[test]
+fn load_from_disk() {
+    let root = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/modules");
+    let graph = Loader::new(FsProvider, root).load("app").unwrap();
+    assert_eq!(graph.modules.len(), 3);
+    assert_eq!(graph.entry().path, Path::new("app.zp"));
+    assert!(!graph.has_errors());
+}
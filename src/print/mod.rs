@@ -0,0 +1,446 @@
+#[cfg(test)]
+mod tests;
+
+pub use crate::ast::Precedence;
+use crate::ast::*;
+
+const INDENT: &str = "    ";
+
+/// Wraps a string in quotes the lexer will read back unchanged. There are no
+/// escape sequences, so a string holding a single quote uses double quotes.
+pub fn quote(string: &str) -> String {
+    if string.contains('\'') {
+        format!("\"{}\"", string)
+    } else {
+        format!("'{}'", string)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Printer {
+    output: String,
+    indent: usize,
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+
+    pub fn expr(&mut self, expr: &Expr) {
+        self.operand(expr, Precedence::Lowest)
+    }
+
+    fn operand(&mut self, expr: &Expr, min: Precedence) {
+        if Precedence::of(expr) < min {
+            self.output.push('(');
+            self.expr(expr);
+            self.output.push(')');
+            return;
+        }
+
+        match &expr.kind {
+            ExprKind::Identifier(identifier) => self.output.push_str(&identifier.ident),
+            ExprKind::Literal(literal) => self.literal(literal),
+            ExprKind::Call(call) => {
+                self.operand(&call.called, Precedence::Call);
+                self.output.push('(');
+                for (i, arg) in call.args.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.expr(arg);
+                }
+                self.output.push(')');
+            }
+            ExprKind::Block(stmts) => self.block(stmts),
+            ExprKind::Binary(binary) => {
+                let precedence = binary.op.precedence();
+                self.operand(&binary.lhs, precedence);
+                self.output.push(' ');
+                self.output.push_str(binary.op.symbol());
+                self.output.push(' ');
+                self.operand(&binary.rhs, precedence.next());
+            }
+            ExprKind::Unary(unary) => {
+                self.output.push_str(unary.op.symbol());
+                self.operand(&unary.operand, Precedence::Unary);
+            }
+            ExprKind::If(if_expr) => {
+                self.output.push_str("if ");
+                self.expr(&if_expr.condition);
+                self.output.push(' ');
+                self.expr(&if_expr.then);
+                if let Some(otherwise) = &if_expr.otherwise {
+                    self.output.push_str(" else ");
+                    self.expr(otherwise);
+                }
+            }
+            ExprKind::List(items) => {
+                self.output.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    if item.spread {
+                        self.output.push_str("..");
+                    }
+                    self.expr(&item.value);
+                }
+                self.output.push(']');
+            }
+            ExprKind::Index(index) => {
+                self.operand(&index.indexed, Precedence::Call);
+                self.output.push('[');
+                self.expr(&index.index);
+                self.output.push(']');
+            }
+            ExprKind::Record(record) => {
+                self.output.push_str("{ ");
+                if let Some(base) = &record.base {
+                    self.output.push_str("..");
+                    self.expr(base);
+                    if !record.fields.is_empty() {
+                        self.output.push_str(", ");
+                    }
+                }
+                for (i, field) in record.fields.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str(&field.name);
+                    if !(field.is_shorthand() && record.allows_shorthand()) {
+                        self.output.push_str(": ");
+                        self.expr(&field.value);
+                    }
+                }
+                self.output.push_str(" }");
+            }
+            ExprKind::Field(field) => {
+                self.operand(&field.record, Precedence::Call);
+                self.output.push('.');
+                self.output.push_str(&field.field);
+            }
+            ExprKind::Assign(assign) => {
+                self.expr(&assign.target);
+                self.output.push_str(" = ");
+                self.expr(&assign.value);
+            }
+            ExprKind::Match(match_expr) => {
+                self.output.push_str("match ");
+                self.expr(&match_expr.scrutinee);
+                if match_expr.arms.is_empty() {
+                    self.output.push_str(" {}");
+                    return;
+                }
+                self.output.push_str(" {");
+                self.indent += 1;
+                for arm in match_expr.arms.iter() {
+                    self.newline();
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.output.push_str(" if ");
+                        self.expr(guard);
+                    }
+                    self.output.push_str(" => ");
+                    self.expr(&arm.body);
+                    self.output.push(',');
+                }
+                self.indent -= 1;
+                self.newline();
+                self.output.push('}');
+            }
+            ExprKind::Lambda(lambda) => {
+                self.output.push_str("fn");
+                self.signature(&lambda.params, &lambda.ret);
+                if !lambda.has_block_body() {
+                    self.output.push_str(" =>");
+                }
+                self.output.push(' ');
+                self.expr(&lambda.body);
+            }
+            ExprKind::Element(element) => self.element(element),
+        }
+    }
+
+    // On one line, as breaking it would add spaces to the text
+    fn element(&mut self, element: &ElementExpr) {
+        let name = element.tag.as_ref().map_or("", |tag| tag.name.as_str());
+        self.output.push('<');
+        self.output.push_str(name);
+        for attribute in element.attributes.iter() {
+            self.output.push(' ');
+            self.output.push_str(&attribute.name);
+            if let Some(value) = &attribute.value {
+                self.output.push('=');
+                self.attribute_value(value);
+            }
+        }
+        if element.tag.is_some() && element.children.is_empty() {
+            self.output.push_str(" />");
+            return;
+        }
+        self.output.push('>');
+        for child in element.children.iter() {
+            match &child.kind {
+                ChildKind::Text(text) => self.output.push_str(text),
+                ChildKind::Expr(value) => {
+                    self.output.push('{');
+                    self.expr(value);
+                    self.output.push('}');
+                }
+                ChildKind::Element(value) => self.expr(value),
+            }
+        }
+        self.output.push_str("</");
+        self.output.push_str(name);
+        self.output.push('>');
+    }
+
+    fn attribute_value(&mut self, value: &Expr) {
+        if let ExprKind::Literal(LiteralExpr::String(string)) = &value.kind {
+            self.output.push_str(&quote(string));
+        } else {
+            self.output.push('{');
+            self.expr(value);
+            self.output.push('}');
+        }
+    }
+
+    // `(a: A, b) -> Ret`
+    fn signature(&mut self, params: &[Param], ret: &Option<TypeExpr>) {
+        self.output.push('(');
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.output.push_str(&param.name);
+            self.annotation(&param.ty);
+        }
+        self.output.push(')');
+        if let Some(ret) = ret {
+            self.output.push_str(" -> ");
+            self.ty(ret);
+        }
+    }
+
+    pub fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Wildcard => self.output.push('_'),
+            PatternKind::Identifier(name) => self.output.push_str(name),
+            PatternKind::Literal(literal) => self.literal(literal),
+            PatternKind::Variant(name, patterns) => {
+                self.output.push_str(name);
+                self.output.push('(');
+                for (i, pattern) in patterns.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.pattern(pattern);
+                }
+                self.output.push(')');
+            }
+        }
+    }
+
+    fn literal(&mut self, literal: &LiteralExpr) {
+        match literal {
+            LiteralExpr::Number(number) => self.output.push_str(&number.to_string()),
+            LiteralExpr::Bool(true) => self.output.push_str("true"),
+            LiteralExpr::Bool(false) => self.output.push_str("false"),
+            LiteralExpr::String(string) => self.output.push_str(&quote(string)),
+        }
+    }
+
+    pub fn stmt(&mut self, stmt: &Stmt) {
+        self.stmt_inner(stmt, true)
+    }
+
+    fn stmt_inner(&mut self, stmt: &Stmt, semicolon: bool) {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                self.output.push_str("let ");
+                self.output.push_str(&let_stmt.identifier);
+                self.annotation(&let_stmt.ty);
+                self.output.push_str(" = ");
+                self.expr(&let_stmt.value);
+                self.output.push(';');
+            }
+            StmtKind::State(state_stmt) => {
+                self.output.push_str("state ");
+                self.output.push_str(&state_stmt.identifier);
+                self.annotation(&state_stmt.ty);
+                self.output.push_str(" = ");
+                self.expr(&state_stmt.value);
+                self.output.push(';');
+            }
+            StmtKind::Fn(fn_stmt) => {
+                self.output.push_str("fn ");
+                self.output.push_str(&fn_stmt.name);
+                self.signature(&fn_stmt.params, &fn_stmt.ret);
+                self.output.push(' ');
+                self.expr(&fn_stmt.body);
+            }
+            StmtKind::Component(component) => {
+                self.output.push_str("component ");
+                self.output.push_str(&component.name);
+                self.signature(&component.props, &None);
+                self.output.push(' ');
+                self.expr(&component.body);
+            }
+            StmtKind::Type(type_stmt) => {
+                self.output.push_str("type ");
+                self.output.push_str(&type_stmt.name);
+                if !type_stmt.params.is_empty() {
+                    self.output.push('<');
+                    self.output.push_str(&type_stmt.params.join(", "));
+                    self.output.push('>');
+                }
+                self.output.push_str(" = ");
+                for (i, variant) in type_stmt.variants.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(" | ");
+                    }
+                    self.output.push_str(&variant.name);
+                    if !variant.fields.is_empty() {
+                        self.output.push('(');
+                        self.types(&variant.fields);
+                        self.output.push(')');
+                    }
+                }
+                self.output.push(';');
+            }
+            StmtKind::Import(import_stmt) => {
+                self.output.push_str("import { ");
+                for (i, name) in import_stmt.names.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str(&name.name);
+                }
+                self.output.push_str(" } from ");
+                self.output.push_str(&quote(&import_stmt.path));
+                self.output.push(';');
+            }
+            StmtKind::Export(exported) => {
+                self.output.push_str("export ");
+                self.stmt_inner(exported, semicolon);
+            }
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+                if semicolon {
+                    self.output.push(';');
+                }
+            }
+        }
+    }
+
+    pub fn ty(&mut self, ty: &TypeExpr) {
+        match &ty.kind {
+            TypeExprKind::Named(named) => {
+                self.output.push_str(&named.name);
+                if !named.args.is_empty() {
+                    self.output.push('<');
+                    self.types(&named.args);
+                    self.output.push('>');
+                }
+            }
+            TypeExprKind::Fn(fn_type) => {
+                self.output.push_str("fn(");
+                self.types(&fn_type.params);
+                self.output.push_str(") -> ");
+                self.ty(&fn_type.ret);
+            }
+        }
+    }
+
+    fn types(&mut self, types: &[TypeExpr]) {
+        for (i, ty) in types.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.ty(ty);
+        }
+    }
+
+    fn annotation(&mut self, ty: &Option<TypeExpr>) {
+        if let Some(ty) = ty {
+            self.output.push_str(": ");
+            self.ty(ty);
+        }
+    }
+
+    /// Prints statements one per line, as at the top level of a file.
+    pub fn stmts(&mut self, stmts: &[Stmt]) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            self.stmt(stmt);
+        }
+    }
+
+    // The trailing expression statement of a block is its value, so it is
+    // printed without a semicolon.
+    fn block(&mut self, stmts: &[Stmt]) {
+        if stmts.is_empty() {
+            self.output.push_str("{}");
+            return;
+        }
+
+        self.output.push('{');
+        self.indent += 1;
+        for (i, stmt) in stmts.iter().enumerate() {
+            self.newline();
+            self.stmt_inner(stmt, i + 1 < stmts.len());
+        }
+        self.indent -= 1;
+        self.newline();
+        self.output.push('}');
+    }
+
+    fn newline(&mut self) {
+        self.output.push('\n');
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+}
+
+pub fn print_expr(expr: &Expr) -> String {
+    let mut printer = Printer::new();
+    printer.expr(expr);
+    printer.finish()
+}
+
+pub fn print_stmt(stmt: &Stmt) -> String {
+    let mut printer = Printer::new();
+    printer.stmt(stmt);
+    printer.finish()
+}
+
+pub fn print_stmts(stmts: &[Stmt]) -> String {
+    let mut printer = Printer::new();
+    printer.stmts(stmts);
+    printer.finish()
+}
+
+pub fn print_type(ty: &TypeExpr) -> String {
+    let mut printer = Printer::new();
+    printer.ty(ty);
+    printer.finish()
+}
+
+pub fn print_pattern(pattern: &Pattern) -> String {
+    let mut printer = Printer::new();
+    printer.pattern(pattern);
+    printer.finish()
+}
+
+pub fn print_program(program: &Program) -> String {
+    print_stmts(&program.stmts)
+}
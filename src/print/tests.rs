@@ -0,0 +1,606 @@
+use super::*;
+use crate::lexer::Position;
+
+fn erase_expr(expr: &mut Expr) {
+    expr.pos = Position::new(0, 0);
+    match &mut expr.kind {
+        ExprKind::Call(call) => {
+            erase_expr(&mut call.called);
+            call.args.iter_mut().for_each(erase_expr);
+        }
+        ExprKind::Block(stmts) => stmts.iter_mut().for_each(erase_stmt),
+        ExprKind::Binary(binary) => {
+            erase_expr(&mut binary.lhs);
+            erase_expr(&mut binary.rhs);
+        }
+        ExprKind::Unary(unary) => erase_expr(&mut unary.operand),
+        ExprKind::If(if_expr) => {
+            erase_expr(&mut if_expr.condition);
+            erase_expr(&mut if_expr.then);
+            if let Some(otherwise) = &mut if_expr.otherwise {
+                erase_expr(otherwise);
+            }
+        }
+        ExprKind::List(items) => {
+            for item in items.iter_mut() {
+                item.pos = Position::new(0, 0);
+                erase_expr(&mut item.value);
+            }
+        }
+        ExprKind::Index(index) => {
+            erase_expr(&mut index.indexed);
+            erase_expr(&mut index.index);
+        }
+        ExprKind::Record(record) => {
+            record.base.iter_mut().for_each(|base| erase_expr(base));
+            for field in record.fields.iter_mut() {
+                field.pos = Position::new(0, 0);
+                erase_expr(&mut field.value);
+            }
+        }
+        ExprKind::Field(field) => erase_expr(&mut field.record),
+        ExprKind::Assign(assign) => {
+            erase_expr(&mut assign.target);
+            erase_expr(&mut assign.value);
+        }
+        ExprKind::Lambda(lambda) => {
+            for param in lambda.params.iter_mut() {
+                param.pos = Position::new(0, 0);
+                param.ty.iter_mut().for_each(erase_type);
+            }
+            lambda.ret.iter_mut().for_each(erase_type);
+            erase_expr(&mut lambda.body);
+        }
+        ExprKind::Match(match_expr) => {
+            erase_expr(&mut match_expr.scrutinee);
+            for arm in match_expr.arms.iter_mut() {
+                arm.pos = Position::new(0, 0);
+                erase_pattern(&mut arm.pattern);
+                arm.guard.iter_mut().for_each(erase_expr);
+                erase_expr(&mut arm.body);
+            }
+        }
+        ExprKind::Element(element) => {
+            element
+                .tag
+                .iter_mut()
+                .for_each(|tag| tag.pos = Position::new(0, 0));
+            for attribute in element.attributes.iter_mut() {
+                attribute.pos = Position::new(0, 0);
+                attribute.value.iter_mut().for_each(erase_expr);
+            }
+            for child in element.children.iter_mut() {
+                child.pos = Position::new(0, 0);
+                match &mut child.kind {
+                    ChildKind::Text(_) => {}
+                    ChildKind::Expr(value) | ChildKind::Element(value) => erase_expr(value),
+                }
+            }
+        }
+        ExprKind::Identifier(_) | ExprKind::Literal(_) => {}
+    }
+}
+
+fn erase_pattern(pattern: &mut Pattern) {
+    pattern.pos = Position::new(0, 0);
+    if let PatternKind::Variant(_, patterns) = &mut pattern.kind {
+        patterns.iter_mut().for_each(erase_pattern);
+    }
+}
+
+fn erase_type(ty: &mut TypeExpr) {
+    ty.pos = Position::new(0, 0);
+    match &mut ty.kind {
+        TypeExprKind::Named(named) => named.args.iter_mut().for_each(erase_type),
+        TypeExprKind::Fn(fn_type) => {
+            fn_type.params.iter_mut().for_each(erase_type);
+            erase_type(&mut fn_type.ret);
+        }
+    }
+}
+
+fn erase_stmt(stmt: &mut Stmt) {
+    stmt.pos = Position::new(0, 0);
+    match &mut stmt.kind {
+        StmtKind::Let(let_stmt) => {
+            let_stmt.ty.iter_mut().for_each(erase_type);
+            erase_expr(&mut let_stmt.value);
+        }
+        StmtKind::State(state_stmt) => {
+            state_stmt.ty.iter_mut().for_each(erase_type);
+            erase_expr(&mut state_stmt.value);
+        }
+        StmtKind::Fn(fn_stmt) => {
+            for param in fn_stmt.params.iter_mut() {
+                param.pos = Position::new(0, 0);
+                param.ty.iter_mut().for_each(erase_type);
+            }
+            fn_stmt.ret.iter_mut().for_each(erase_type);
+            erase_expr(&mut fn_stmt.body);
+        }
+        StmtKind::Component(component) => {
+            for prop in component.props.iter_mut() {
+                prop.pos = Position::new(0, 0);
+                prop.ty.iter_mut().for_each(erase_type);
+            }
+            erase_expr(&mut component.body);
+        }
+        StmtKind::Type(type_stmt) => {
+            for variant in type_stmt.variants.iter_mut() {
+                variant.pos = Position::new(0, 0);
+                variant.fields.iter_mut().for_each(erase_type);
+            }
+        }
+        StmtKind::Import(import_stmt) => {
+            for name in import_stmt.names.iter_mut() {
+                name.pos = Position::new(0, 0);
+            }
+            import_stmt.path_pos = Position::new(0, 0);
+        }
+        StmtKind::Export(exported) => erase_stmt(exported),
+        StmtKind::Expr(expr) => erase_expr(expr),
+    }
+}
+
+fn parse_expr(input: &str) -> Expr {
+    Parser::new(input).parse_expr().unwrap()
+}
+
+fn expr(kind: ExprKind) -> Expr {
+    Expr::new(kind, Position::new(0, 0))
+}
+
+fn ident(name: &str) -> Expr {
+    expr(ExprKind::Identifier(IdentifierExpr::new(name.to_string())))
+}
+
+/// Xorshift, so the generated programs are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+fn gen_expr(rng: &mut Rng, depth: u32) -> Expr {
+    let choice = if depth == 0 {
+        rng.below(3)
+    } else {
+        rng.below(17)
+    };
+    match choice {
+        0 => ident(rng.pick(&["foo", "bar", "baz", "x", "add"])),
+        1 => expr(ExprKind::Literal(LiteralExpr::Number(
+            rng.below(100_000) as u32
+        ))),
+        2 => match rng.below(2) {
+            0 => expr(ExprKind::Literal(LiteralExpr::Bool(rng.below(2) == 0))),
+            _ => expr(ExprKind::Literal(LiteralExpr::String(
+                rng.pick(&["", "hello", "it's", "say \"hi\""]).to_string(),
+            ))),
+        },
+        3 => gen_block(rng, depth - 1),
+        4 | 5 => {
+            let ops = [
+                BinaryOp::Add,
+                BinaryOp::Sub,
+                BinaryOp::Mul,
+                BinaryOp::Div,
+                BinaryOp::Rem,
+                BinaryOp::Eq,
+                BinaryOp::Ne,
+                BinaryOp::Lt,
+                BinaryOp::Le,
+                BinaryOp::Gt,
+                BinaryOp::Ge,
+                BinaryOp::And,
+                BinaryOp::Or,
+            ];
+            let op = ops[rng.below(ops.len() as u64) as usize];
+            let lhs = gen_expr(rng, depth - 1);
+            let rhs = gen_expr(rng, depth - 1);
+            expr(ExprKind::Binary(BinaryExpr::new(op, lhs, rhs)))
+        }
+        6 => {
+            let op = if rng.below(2) == 0 {
+                UnaryOp::Neg
+            } else {
+                UnaryOp::Not
+            };
+            expr(ExprKind::Unary(UnaryExpr::new(
+                op,
+                gen_expr(rng, depth - 1),
+            )))
+        }
+        7 => {
+            let condition = gen_expr(rng, depth - 1);
+            let then = gen_block(rng, depth - 1);
+            let otherwise = match rng.below(3) {
+                0 => None,
+                1 => Some(gen_block(rng, depth - 1)),
+                _ => {
+                    let condition = gen_expr(rng, depth - 1);
+                    let then = gen_block(rng, depth - 1);
+                    Some(expr(ExprKind::If(IfExpr::new(condition, then, None))))
+                }
+            };
+            expr(ExprKind::If(IfExpr::new(condition, then, otherwise)))
+        }
+        8 => {
+            let called = gen_expr(rng, depth - 1);
+            let args = (0..rng.below(3))
+                .map(|_| gen_expr(rng, depth - 1))
+                .collect();
+            expr(ExprKind::Call(CallExpr::new(called, args)))
+        }
+        9 => {
+            let items = (0..rng.below(4))
+                .map(|_| {
+                    let value = gen_expr(rng, depth - 1);
+                    if rng.below(3) == 0 {
+                        ListItem::spread(value, Position::new(0, 0))
+                    } else {
+                        ListItem::single(value)
+                    }
+                })
+                .collect();
+            expr(ExprKind::List(items))
+        }
+        10 => {
+            let base = match rng.below(3) {
+                0 => Some(gen_expr(rng, depth - 1)),
+                _ => None,
+            };
+            let fields = (0..rng.below(3) + base.is_none() as u64)
+                .map(|_| {
+                    let name = rng.pick(&["x", "name", "count"]).to_string();
+                    let value = match rng.below(2) {
+                        0 => ident(&name),
+                        _ => gen_expr(rng, depth - 1),
+                    };
+                    RecordField::new(name, value, Position::new(0, 0))
+                })
+                .collect();
+            expr(ExprKind::Record(RecordExpr::new(base, fields)))
+        }
+        11 => {
+            let record = gen_expr(rng, depth - 1);
+            let field = rng.pick(&["x", "name", "count"]).to_string();
+            expr(ExprKind::Field(FieldExpr::new(record, field)))
+        }
+        12 => {
+            let body = match rng.below(2) {
+                0 => gen_block(rng, depth - 1),
+                _ => gen_expr(rng, depth - 1),
+            };
+            let mut lambda = LambdaExpr::new(gen_params(rng), body);
+            lambda.ret = gen_annotation(rng);
+            expr(ExprKind::Lambda(lambda))
+        }
+        13 => {
+            let arms = (0..rng.below(3))
+                .map(|_| {
+                    let pattern = gen_pattern(rng, 2);
+                    let mut arm =
+                        MatchArm::new(pattern, gen_expr(rng, depth - 1), Position::new(0, 0));
+                    if rng.below(3) == 0 {
+                        arm.guard = Some(gen_expr(rng, depth - 1));
+                    }
+                    arm
+                })
+                .collect();
+            expr(ExprKind::Match(MatchExpr::new(
+                gen_expr(rng, depth - 1),
+                arms,
+            )))
+        }
+        14 => {
+            let target = ident(rng.pick(&["foo", "count"]));
+            let value = gen_expr(rng, depth - 1);
+            expr(ExprKind::Assign(AssignExpr::new(target, value)))
+        }
+        15 => gen_element(rng, depth - 1),
+        _ => {
+            let indexed = gen_expr(rng, depth - 1);
+            let index = gen_expr(rng, depth - 1);
+            expr(ExprKind::Index(IndexExpr::new(indexed, index)))
+        }
+    }
+}
+
+fn gen_element(rng: &mut Rng, depth: u32) -> Expr {
+    let tag = match rng.below(4) {
+        0 => None,
+        _ => Some(Tag::new(
+            rng.pick(&["div", "h1", "Button"]).to_string(),
+            Position::new(0, 0),
+        )),
+    };
+    let attributes = match tag {
+        Some(_) => (0..rng.below(3))
+            .map(|_| {
+                let name = rng.pick(&["id", "on_click", "aria-label"]).to_string();
+                let value = match rng.below(3) {
+                    0 => None,
+                    1 => Some(expr(ExprKind::Literal(LiteralExpr::String(
+                        "it's".to_string(),
+                    )))),
+                    _ => Some(gen_expr(rng, depth)),
+                };
+                Attribute::new(name, value, Position::new(0, 0))
+            })
+            .collect(),
+        None => vec![],
+    };
+    let mut children: Vec<Child> = vec![];
+    for _ in 0..rng.below(4) {
+        let text = matches!(
+            children.last(),
+            Some(Child {
+                kind: ChildKind::Text(_),
+                ..
+            })
+        );
+        let kind = match rng.below(3) {
+            // Neighboring text would be read back as one child
+            0 if !text => ChildKind::Text(rng.pick(&["Hello, ", " it's ", "a // b"]).to_string()),
+            2 if depth > 0 => ChildKind::Element(gen_element(rng, depth - 1)),
+            _ => ChildKind::Expr(gen_expr(rng, depth)),
+        };
+        children.push(Child::new(kind, Position::new(0, 0)));
+    }
+    expr(ExprKind::Element(ElementExpr::new(
+        tag, attributes, children,
+    )))
+}
+
+fn gen_pattern(rng: &mut Rng, depth: u32) -> Pattern {
+    let kind = match rng.below(if depth == 0 { 3 } else { 4 }) {
+        0 => PatternKind::Wildcard,
+        1 => PatternKind::Identifier(rng.pick(&["x", "None", "rest"]).to_string()),
+        2 => match rng.below(3) {
+            0 => PatternKind::Literal(LiteralExpr::Number(rng.below(100) as u32)),
+            1 => PatternKind::Literal(LiteralExpr::Bool(rng.below(2) == 0)),
+            _ => PatternKind::Literal(LiteralExpr::String("it's".to_string())),
+        },
+        _ => {
+            let patterns = (0..rng.below(3))
+                .map(|_| gen_pattern(rng, depth - 1))
+                .collect();
+            PatternKind::Variant(rng.pick(&["Some", "Rect"]).to_string(), patterns)
+        }
+    };
+    Pattern::new(kind, Position::new(0, 0))
+}
+
+fn gen_block(rng: &mut Rng, depth: u32) -> Expr {
+    let stmts = (0..rng.below(3)).map(|_| gen_stmt(rng, depth)).collect();
+    expr(ExprKind::Block(stmts))
+}
+
+fn gen_type(rng: &mut Rng, depth: u32) -> TypeExpr {
+    let kind = match rng.below(if depth == 0 { 1 } else { 3 }) {
+        0 => TypeExprKind::Named(NamedType::new(
+            rng.pick(&["Number", "Bool", "String"]).to_string(),
+            vec![],
+        )),
+        1 => {
+            let args = (0..rng.below(3) + 1)
+                .map(|_| gen_type(rng, depth - 1))
+                .collect();
+            TypeExprKind::Named(NamedType::new("List".to_string(), args))
+        }
+        _ => {
+            let params = (0..rng.below(3))
+                .map(|_| gen_type(rng, depth - 1))
+                .collect();
+            TypeExprKind::Fn(FnType::new(params, gen_type(rng, depth - 1)))
+        }
+    };
+    TypeExpr::new(kind, Position::new(0, 0))
+}
+
+fn gen_annotation(rng: &mut Rng) -> Option<TypeExpr> {
+    match rng.below(2) {
+        0 => None,
+        _ => Some(gen_type(rng, 2)),
+    }
+}
+
+fn gen_params(rng: &mut Rng) -> Vec<Param> {
+    (0..rng.below(3))
+        .map(|_| {
+            let mut param = Param::new(rng.pick(&["p", "q"]).to_string(), Position::new(0, 0));
+            param.ty = gen_annotation(rng);
+            param
+        })
+        .collect()
+}
+
+fn gen_stmt(rng: &mut Rng, depth: u32) -> Stmt {
+    let name = rng.pick(&["a", "b", "value"]).to_string();
+    let kind = match rng.below(8) {
+        0 => {
+            let mut let_stmt = LetStmt::new(name, gen_expr(rng, depth));
+            let_stmt.ty = gen_annotation(rng);
+            StmtKind::Let(let_stmt)
+        }
+        1 => {
+            let mut state_stmt = StateStmt::new(name, gen_expr(rng, depth));
+            state_stmt.ty = gen_annotation(rng);
+            StmtKind::State(state_stmt)
+        }
+        2 => {
+            let params = gen_params(rng);
+            let mut fn_stmt = FnStmt::new(name, params, gen_block(rng, depth));
+            fn_stmt.ret = gen_annotation(rng);
+            StmtKind::Fn(fn_stmt)
+        }
+        3 => {
+            let variants = (0..rng.below(3) + 1)
+                .map(|_| {
+                    let fields = (0..rng.below(3)).map(|_| gen_type(rng, 1)).collect();
+                    Variant::new(
+                        rng.pick(&["Some", "None", "Rect"]).to_string(),
+                        fields,
+                        Position::new(0, 0),
+                    )
+                })
+                .collect();
+            let mut type_stmt = TypeStmt::new(rng.pick(&["Shape", "Option"]).to_string(), variants);
+            type_stmt.params = (0..rng.below(3))
+                .map(|_| rng.pick(&["T", "E"]).to_string())
+                .collect();
+            StmtKind::Type(type_stmt)
+        }
+        4 => {
+            let names = (0..rng.below(3))
+                .map(|_| ImportName::new(name.clone(), Position::new(0, 0)))
+                .collect();
+            let path = rng.pick(&["./button", "../it's"]).to_string();
+            StmtKind::Import(ImportStmt::new(names, path, Position::new(0, 0)))
+        }
+        5 => loop {
+            let stmt = gen_stmt(rng, depth);
+            if let StmtKind::Let(_)
+            | StmtKind::State(_)
+            | StmtKind::Fn(_)
+            | StmtKind::Component(_)
+            | StmtKind::Type(_) = stmt.kind
+            {
+                break StmtKind::Export(Box::new(stmt));
+            }
+        },
+        6 => {
+            let name = rng.pick(&["Button", "Counter"]).to_string();
+            StmtKind::Component(ComponentStmt::new(
+                name,
+                gen_params(rng),
+                gen_block(rng, depth),
+            ))
+        }
+        _ => StmtKind::Expr(gen_expr(rng, depth)),
+    };
+    Stmt::new(kind, Position::new(0, 0))
+}
+
+#[test]
+fn print_literals() {
+    assert_eq!(print_expr(&parse_expr("42")), "42");
+    assert_eq!(print_expr(&parse_expr("true")), "true");
+    assert_eq!(print_expr(&parse_expr("\"hello\"")), "'hello'");
+    assert_eq!(print_expr(&parse_expr("\"it's\"")), "\"it's\"");
+}
+
+#[test]
+fn print_calls() {
+    assert_eq!(print_expr(&parse_expr("foo(bar(1))")), "foo(bar(1))");
+    assert_eq!(print_expr(&parse_expr("((foo))(bar)")), "foo(bar)");
+    assert_eq!(print_expr(&parse_expr("add(1)(2)")), "add(1)(2)");
+}
+
+#[test]
+fn print_minimal_parens() {
+    assert_eq!(print_expr(&parse_expr("(1 + 2) * 3")), "(1 + 2) * 3");
+    assert_eq!(print_expr(&parse_expr("1 + (2 * 3)")), "1 + 2 * 3");
+    assert_eq!(print_expr(&parse_expr("(1 - 2) - 3")), "1 - 2 - 3");
+    assert_eq!(print_expr(&parse_expr("1 - (2 - 3)")), "1 - (2 - 3)");
+    assert_eq!(print_expr(&parse_expr("-(a(1))")), "-a(1)");
+    assert_eq!(print_expr(&parse_expr("!(a || b) && c")), "!(a || b) && c");
+    assert_eq!(
+        print_expr(&parse_expr("(a < b) == (c >= d)")),
+        "a < b == c >= d"
+    );
+}
+
+#[test]
+fn print_if() {
+    assert_eq!(
+        print_expr(&parse_expr("if a {1} else if b {2} else {3}")),
+        "if a {\n    1\n} else if b {\n    2\n} else {\n    3\n}"
+    );
+}
+
+#[test]
+fn print_let_stmt() {
+    let stmt = Parser::new("let foo = bar(baz)").parse_stmt().unwrap();
+    assert_eq!(print_stmt(&stmt), "let foo = bar(baz);");
+}
+
+#[test]
+fn print_fn_stmt() {
+    let stmt = Parser::new("fn add(a,b){state c=a\nc}")
+        .parse_stmt()
+        .unwrap();
+    assert_eq!(
+        print_stmt(&stmt),
+        "fn add(a, b) {\n    state c = a;\n    c\n}"
+    );
+}
+
+#[test]
+fn print_annotations() {
+    let stmt = Parser::new("fn f(g:fn(Number,Bool)->List<String>)->Bool{true}")
+        .parse_stmt()
+        .unwrap();
+    assert_eq!(
+        print_stmt(&stmt),
+        "fn f(g: fn(Number, Bool) -> List<String>) -> Bool {\n    true\n}"
+    );
+    let stmt = Parser::new("let a : Number = 1").parse_stmt().unwrap();
+    assert_eq!(print_stmt(&stmt), "let a: Number = 1;");
+}
+
+#[test]
+fn print_block() {
+    let block = expr(ExprKind::Block(vec![
+        Stmt::new(
+            StmtKind::Let(LetStmt::new("x".into(), ident("foo"))),
+            Position::new(0, 0),
+        ),
+        Stmt::new(StmtKind::Expr(ident("x")), Position::new(0, 0)),
+    ]));
+    assert_eq!(print_expr(&block), "{\n    let x = foo;\n    x\n}");
+    assert_eq!(print_expr(&expr(ExprKind::Block(vec![]))), "{}");
+}
+
+#[test]
+fn print_elements() {
+    let source = "<Button label = \"Hi\" on_click={ inc }>\n    You clicked {count}\n</Button>";
+    assert_eq!(
+        print_expr(&parse_expr(source)),
+        "<Button label='Hi' on_click={inc}>You clicked {count}</Button>"
+    );
+    assert_eq!(
+        print_expr(&parse_expr("<input disabled/>")),
+        "<input disabled />"
+    );
+    assert_eq!(print_expr(&parse_expr("< >< />")), "<></>");
+}
+
+#[test]
+fn round_trip() {
+    let mut rng = Rng(0x5eed_2a0f);
+    for _ in 0..500 {
+        let mut expected = gen_stmt(&mut rng, 3);
+        let printed = print_stmt(&expected);
+        let mut parsed = Parser::new(&printed)
+            .parse_stmt()
+            .unwrap_or_else(|| panic!("printed code does not parse: {}", printed));
+        erase_stmt(&mut expected);
+        erase_stmt(&mut parsed);
+        assert_eq!(parsed, expected, "round trip changed {}", printed);
+    }
+}
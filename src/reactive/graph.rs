@@ -0,0 +1,322 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+use crate::resolve::{BindingId, Resolution};
+
+use super::NodeId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphNodeKind {
+    State,
+    Derived,
+    Effect,
+    // A top-level function, like an event handler. Nodes that refer to it
+    // read and write what it does.
+    Function,
+}
+
+impl GraphNodeKind {
+    fn name(self) -> &'static str {
+        match self {
+            GraphNodeKind::State => "state",
+            GraphNodeKind::Derived => "derived",
+            GraphNodeKind::Effect => "effect",
+            GraphNodeKind::Function => "function",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphNode {
+    pub id: NodeId,
+    pub kind: GraphNodeKind,
+    // None for effects
+    pub name: Option<String>,
+    // The declaring statement
+    pub pos: Position,
+    // The state and derived values it reads, and the state it writes, also
+    // through the functions it refers to
+    pub reads: BTreeSet<NodeId>,
+    pub writes: BTreeSet<NodeId>,
+}
+
+/// The reactive values of a program and what each of them reads and writes,
+/// found without running it. A node that refers to a function is taken to
+/// call it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Graph {
+    // One per top-level `state`, `let`, `fn` and expression statement, in
+    // source order
+    pub nodes: Vec<GraphNode>,
+}
+
+pub fn extract_graph(program: &Program, resolution: &Resolution) -> Graph {
+    let mut nodes = vec![];
+    let mut bindings: HashMap<BindingId, NodeId> = HashMap::new();
+    for stmt in program.stmts.iter() {
+        let stmt = stmt.declaration();
+        let (kind, name) = match &stmt.kind {
+            StmtKind::State(state) => (GraphNodeKind::State, Some(&state.identifier)),
+            StmtKind::Let(let_stmt) => (GraphNodeKind::Derived, Some(&let_stmt.identifier)),
+            StmtKind::Fn(fn_stmt) => (GraphNodeKind::Function, Some(&fn_stmt.name)),
+            StmtKind::Component(component) => (GraphNodeKind::Function, Some(&component.name)),
+            StmtKind::Expr(_) => (GraphNodeKind::Effect, None),
+            StmtKind::Type(_) | StmtKind::Import(_) | StmtKind::Export(_) => continue,
+        };
+        let id = NodeId(nodes.len() as u32);
+        if let Some(binding) = resolution.definitions.get(&stmt.pos) {
+            bindings.insert(*binding, id);
+        }
+        nodes.push(GraphNode {
+            id,
+            kind,
+            name: name.cloned(),
+            pos: stmt.pos,
+            reads: BTreeSet::new(),
+            writes: BTreeSet::new(),
+        });
+    }
+
+    // What each node refers to directly, functions included
+    for (pos, binding) in resolution.uses.iter() {
+        let Some(used) = bindings.get(binding) else {
+            continue;
+        };
+        let Some(node) = nodes.iter_mut().find(|node| node.pos.contains(pos)) else {
+            continue;
+        };
+        match resolution.assigned.contains(pos) {
+            true => node.writes.insert(*used),
+            false => node.reads.insert(*used),
+        };
+    }
+
+    let direct = nodes.clone();
+    for node in nodes.iter_mut() {
+        let mut visited = HashSet::new();
+        let (reads, writes) = through_functions(&direct, node.id, &mut visited);
+        node.reads = reads;
+        node.writes = writes;
+    }
+    Graph { nodes }
+}
+
+// What a node reads and writes, with what the functions it refers to do
+fn through_functions(
+    nodes: &[GraphNode],
+    id: NodeId,
+    visited: &mut HashSet<NodeId>,
+) -> (BTreeSet<NodeId>, BTreeSet<NodeId>) {
+    visited.insert(id);
+    let node = &nodes[id.0 as usize];
+    let mut reads = BTreeSet::new();
+    let mut writes = node.writes.clone();
+    for read in node.reads.iter() {
+        if nodes[read.0 as usize].kind != GraphNodeKind::Function {
+            reads.insert(*read);
+        } else if visited.insert(*read) {
+            let (more_reads, more_writes) = through_functions(nodes, *read, visited);
+            reads.extend(more_reads);
+            writes.extend(more_writes);
+        }
+    }
+    (reads, writes)
+}
+
+impl Graph {
+    pub fn node(&self, id: NodeId) -> &GraphNode {
+        &self.nodes[id.0 as usize]
+    }
+
+    /// The first node declared with `name`.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .find(|node| node.name.as_deref() == Some(name))
+            .map(|node| node.id)
+    }
+
+    /// The nodes that read `id`.
+    pub fn readers(&self, id: NodeId) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|node| node.reads.contains(&id))
+            .map(|node| node.id)
+            .collect()
+    }
+
+    // What a change of `id` causes to run or change next. Functions only
+    // run when something calls them.
+    fn affects(&self, id: NodeId) -> Vec<NodeId> {
+        let node = self.node(id);
+        if node.kind == GraphNodeKind::Function {
+            return vec![];
+        }
+        let mut affected: Vec<NodeId> = self
+            .readers(id)
+            .into_iter()
+            .filter(|reader| self.node(*reader).kind != GraphNodeKind::Function)
+            .collect();
+        affected.extend(node.writes.iter());
+        affected
+    }
+
+    /// The groups of nodes that would update each other forever, each in the
+    /// order that one affects the next and the last the first.
+    pub fn cycles(&self) -> Vec<Vec<NodeId>> {
+        let mut cycles: Vec<Vec<NodeId>> = vec![];
+        for node in self.nodes.iter() {
+            if cycles.iter().any(|cycle| cycle.contains(&node.id)) {
+                continue;
+            }
+            if let Some(cycle) = self.cycle_through(node.id) {
+                cycles.push(cycle);
+            }
+        }
+        cycles
+    }
+
+    // The shortest way back to `start`
+    fn cycle_through(&self, start: NodeId) -> Option<Vec<NodeId>> {
+        let mut previous: HashMap<NodeId, NodeId> = HashMap::new();
+        let mut queue = vec![start];
+        while !queue.is_empty() {
+            let mut next = vec![];
+            for id in queue {
+                for affected in self.affects(id) {
+                    if affected == start {
+                        let mut cycle = vec![id];
+                        let mut at = id;
+                        while let Some(before) = previous.get(&at) {
+                            cycle.push(*before);
+                            at = *before;
+                        }
+                        cycle.reverse();
+                        return Some(cycle);
+                    }
+                    if let Entry::Vacant(entry) = previous.entry(affected) {
+                        entry.insert(id);
+                        next.push(affected);
+                    }
+                }
+            }
+            queue = next;
+        }
+        None
+    }
+
+    /// A warning for each cycle, with the declarations in it.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.cycles()
+            .into_iter()
+            .map(|cycle| {
+                let mut diagnostic = Diagnostic::warning(
+                    "reactive values depend on each other",
+                    self.node(cycle[0]).pos,
+                );
+                for id in cycle[1..].iter() {
+                    diagnostic = diagnostic.with_label("which affects this", self.node(*id).pos);
+                }
+                diagnostic
+            })
+            .collect()
+    }
+
+    /// The graph in Graphviz's DOT language. Reads are solid edges from the
+    /// value read, writes dashed edges to the state written.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph reactive {{").unwrap();
+        for node in self.nodes.iter() {
+            let shape = match node.kind {
+                GraphNodeKind::State => "box",
+                GraphNodeKind::Derived => "ellipse",
+                GraphNodeKind::Effect => "diamond",
+                GraphNodeKind::Function => "note",
+            };
+            writeln!(
+                out,
+                "    n{} [label={}, shape={}];",
+                node.id.0,
+                quote(&self.label(node)),
+                shape
+            )
+            .unwrap();
+        }
+        for node in self.nodes.iter() {
+            for read in node.reads.iter() {
+                writeln!(out, "    n{} -> n{};", read.0, node.id.0).unwrap();
+            }
+            for write in node.writes.iter() {
+                writeln!(out, "    n{} -> n{} [style=dashed];", node.id.0, write.0).unwrap();
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    /// The nodes and cycles as JSON, one node per line.
+    pub fn to_json(&self) -> String {
+        let ids = |ids: &mut dyn Iterator<Item = &NodeId>| {
+            let ids: Vec<String> = ids.map(|id| id.0.to_string()).collect();
+            format!("[{}]", ids.join(", "))
+        };
+
+        let mut out = String::new();
+        writeln!(out, "{{").unwrap();
+        writeln!(out, "  \"nodes\": [").unwrap();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let name = node.name.as_deref().map_or("null".to_string(), quote);
+            let separator = if i + 1 < self.nodes.len() { "," } else { "" };
+            writeln!(
+                out,
+                "    {{\"id\": {}, \"kind\": \"{}\", \"name\": {}, \"span\": [{}, {}], \"reads\": {}, \"writes\": {}}}{}",
+                node.id.0,
+                node.kind.name(),
+                name,
+                node.pos.start,
+                node.pos.end,
+                ids(&mut node.reads.iter()),
+                ids(&mut node.writes.iter()),
+                separator
+            )
+            .unwrap();
+        }
+        writeln!(out, "  ],").unwrap();
+        let cycles: Vec<String> = self
+            .cycles()
+            .iter()
+            .map(|cycle| ids(&mut cycle.iter()))
+            .collect();
+        writeln!(out, "  \"cycles\": [{}]", cycles.join(", ")).unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    fn label(&self, node: &GraphNode) -> String {
+        match &node.name {
+            Some(name) => name.clone(),
+            None => format!("{} {}..{}", node.kind.name(), node.pos.start, node.pos.end),
+        }
+    }
+}
+
+// A string literal of DOT or JSON, which escape names alike
+pub(crate) fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,385 @@
+pub mod graph;
+pub mod program;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::mem;
+
+use crate::diagnostic::Diagnostic;
+use crate::interp::{RuntimeError, Value};
+use crate::lexer::Position;
+
+pub use self::graph::*;
+pub use self::program::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    // A value that is only changed by writes
+    Signal,
+    // A value computed from other nodes
+    Derived,
+    // Run for what it does whenever the nodes it read change
+    Effect,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub id: NodeId,
+    pub kind: NodeKind,
+    pub name: Option<String>,
+    // The declaring statement
+    pub pos: Position,
+    // What an effect evaluated to last
+    pub value: Value,
+    // The nodes read the last time it was computed, and the nodes that read it
+    pub dependencies: BTreeSet<NodeId>,
+    pub dependents: BTreeSet<NodeId>,
+    // The signals it wrote the last time it was computed
+    pub writes: BTreeSet<NodeId>,
+}
+
+/// Computes derived values and runs effects for the runtime, which only
+/// keeps track of the graph. This is what ties the runtime to a way of
+/// running zope.
+pub trait Evaluator {
+    /// Computes a derived node or runs an effect, returning its value with
+    /// the nodes it read.
+    fn evaluate(&mut self, node: &Node) -> std::result::Result<(Value, Vec<NodeId>), RuntimeError>;
+
+    /// Makes the new value of a node visible to later evaluations.
+    fn update(&mut self, node: &Node);
+
+    /// The signals written since the last call, like by an effect.
+    fn writes(&mut self) -> Vec<(NodeId, Value)>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReactiveError {
+    Runtime(RuntimeError),
+    // The declarations of nodes that depend on each other, where each one
+    // is read or written by the next and the last by the first
+    Cycle(Vec<Position>),
+    // The declaration of a derived value or effect the host tried to set
+    NotSignal(Position),
+}
+
+impl ReactiveError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ReactiveError::Runtime(error) => error.to_diagnostic(),
+            ReactiveError::Cycle(spans) => {
+                let mut diagnostic =
+                    Diagnostic::error("reactive values depend on each other", spans[0]);
+                for pos in spans[1..].iter() {
+                    diagnostic = diagnostic.with_label("which affects this", *pos);
+                }
+                diagnostic
+            }
+            ReactiveError::NotSignal(pos) => {
+                Diagnostic::error("only `state` can be set from outside", *pos)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ReactiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReactiveError::Runtime(error) => write!(f, "{}", error),
+            ReactiveError::Cycle(spans) => {
+                write!(f, "reactive cycle through")?;
+                for (i, pos) in spans.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}..{}", separator, pos.start, pos.end)?;
+                }
+                Ok(())
+            }
+            ReactiveError::NotSignal(pos) => {
+                write!(f, "only `state` can be set, not {}..{}", pos.start, pos.end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReactiveError {}
+
+impl From<RuntimeError> for ReactiveError {
+    fn from(error: RuntimeError) -> Self {
+        ReactiveError::Runtime(error)
+    }
+}
+
+type Result<T> = std::result::Result<T, ReactiveError>;
+
+/// A graph of signals, derived values and effects. Writing a signal
+/// recomputes the nodes that read it, each at most once and only after
+/// everything it reads, so no node sees a mix of old and new values.
+pub struct Runtime<E> {
+    evaluator: E,
+    nodes: Vec<Node>,
+    // Nesting of `batch` calls
+    batches: usize,
+    // Signals written since the last flush
+    changed: BTreeSet<NodeId>,
+}
+
+impl<E: Evaluator> Runtime<E> {
+    pub fn new(evaluator: E) -> Self {
+        Self {
+            evaluator,
+            nodes: vec![],
+            batches: 0,
+            changed: BTreeSet::new(),
+        }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// The last node declared with `name`.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .rev()
+            .find(|node| node.name.as_deref() == Some(name))
+            .map(|node| node.id)
+    }
+
+    pub fn get(&self, id: NodeId) -> &Value {
+        &self.node(id).value
+    }
+
+    pub fn signal(&mut self, name: Option<String>, pos: Position, value: Value) -> NodeId {
+        let id = self.add(NodeKind::Signal, name, pos, value);
+        self.evaluator.update(&self.nodes[id.0 as usize]);
+        id
+    }
+
+    pub fn derived(&mut self, name: Option<String>, pos: Position) -> Result<NodeId> {
+        let id = self.add(NodeKind::Derived, name, pos, Value::Unit);
+        self.compute(id)?;
+        self.settle()?;
+        Ok(id)
+    }
+
+    pub fn effect(&mut self, pos: Position) -> Result<NodeId> {
+        let id = self.add(NodeKind::Effect, None, pos, Value::Unit);
+        self.compute(id)?;
+        self.settle()?;
+        Ok(id)
+    }
+
+    /// Writes a signal, and updates what depends on it unless in a batch.
+    /// Derived values and effects are only written by computing them.
+    pub fn set(&mut self, id: NodeId, value: Value) -> Result<()> {
+        let node = self.node(id);
+        if node.kind != NodeKind::Signal {
+            return Err(ReactiveError::NotSignal(node.pos));
+        }
+        self.write(id, value);
+        self.settle()
+    }
+
+    /// Runs `f`, holding back the updates of the signals it writes until it
+    /// returns, so that nodes reading several of them run once.
+    pub fn batch<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+        self.batches += 1;
+        let result = f(self);
+        self.batches -= 1;
+        let value = result?;
+        self.settle()?;
+        Ok(value)
+    }
+
+    /// Runs `f` with the evaluator, then updates what depends on the signals
+    /// it wrote, like those written by a function the host called.
+    pub fn with_evaluator<R>(&mut self, f: impl FnOnce(&mut E) -> R) -> Result<R> {
+        let value = f(&mut self.evaluator);
+        for (id, value) in self.evaluator.writes() {
+            self.write(id, value);
+        }
+        self.settle()?;
+        Ok(value)
+    }
+
+    fn add(&mut self, kind: NodeKind, name: Option<String>, pos: Position, value: Value) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(Node {
+            id,
+            kind,
+            name,
+            pos,
+            value,
+            dependencies: BTreeSet::new(),
+            dependents: BTreeSet::new(),
+            writes: BTreeSet::new(),
+        });
+        id
+    }
+
+    fn write(&mut self, id: NodeId, value: Value) {
+        let node = &mut self.nodes[id.0 as usize];
+        if node.value == value {
+            return;
+        }
+        node.value = value;
+        self.evaluator.update(&self.nodes[id.0 as usize]);
+        self.changed.insert(id);
+    }
+
+    fn settle(&mut self) -> Result<()> {
+        if self.batches > 0 {
+            return Ok(());
+        }
+        // Signals written by effects are updated in another round
+        while !self.changed.is_empty() {
+            let mut changed = mem::take(&mut self.changed);
+            for id in self.affected(&changed) {
+                let node = self.node(id);
+                if !node
+                    .dependencies
+                    .iter()
+                    .any(|dependency| changed.contains(dependency))
+                {
+                    continue;
+                }
+                if self.compute(id)? {
+                    changed.insert(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Evaluates a node, returning whether its value changed
+    fn compute(&mut self, id: NodeId) -> Result<bool> {
+        let (value, reads) = self.evaluator.evaluate(&self.nodes[id.0 as usize])?;
+        self.depend(id, reads.into_iter().collect())?;
+        let writes = self.evaluator.writes();
+        self.nodes[id.0 as usize].writes = writes.iter().map(|(signal, _)| *signal).collect();
+        for (signal, value) in writes {
+            if let Some(path) = self.path(signal, id) {
+                return Err(self.cycle(&path));
+            }
+            self.write(signal, value);
+        }
+
+        let node = &mut self.nodes[id.0 as usize];
+        if node.value == value {
+            return Ok(false);
+        }
+        node.value = value;
+        self.evaluator.update(&self.nodes[id.0 as usize]);
+        Ok(true)
+    }
+
+    fn depend(&mut self, id: NodeId, dependencies: BTreeSet<NodeId>) -> Result<()> {
+        for dependency in dependencies.iter() {
+            if let Some(mut path) = self.path(id, *dependency) {
+                path.pop();
+                path.insert(0, *dependency);
+                return Err(self.cycle(&path));
+            }
+        }
+
+        let old = mem::take(&mut self.nodes[id.0 as usize].dependencies);
+        for dependency in old {
+            self.nodes[dependency.0 as usize].dependents.remove(&id);
+        }
+        for dependency in dependencies.iter() {
+            self.nodes[dependency.0 as usize].dependents.insert(id);
+        }
+        self.nodes[id.0 as usize].dependencies = dependencies;
+        Ok(())
+    }
+
+    // The nodes from `from` to `to` along dependents and writes, if `from`
+    // affects `to`
+    fn path(&self, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(id) = queue.pop_front() {
+            if id == to {
+                let mut path = vec![to];
+                let mut at = to;
+                while let Some(before) = previous.get(&at) {
+                    path.push(*before);
+                    at = *before;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let node = self.node(id);
+            for next in node.dependents.iter().chain(node.writes.iter()) {
+                if !previous.contains_key(next) && *next != from {
+                    previous.insert(*next, id);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        None
+    }
+
+    fn cycle(&self, path: &[NodeId]) -> ReactiveError {
+        ReactiveError::Cycle(path.iter().map(|id| self.node(*id).pos).collect())
+    }
+
+    // What depends on the changed signals, each after all it depends on
+    fn affected(&self, changed: &BTreeSet<NodeId>) -> Vec<NodeId> {
+        let mut affected = BTreeSet::new();
+        let mut stack: Vec<NodeId> = changed.iter().copied().collect();
+        while let Some(id) = stack.pop() {
+            for dependent in self.node(id).dependents.iter() {
+                if affected.insert(*dependent) {
+                    stack.push(*dependent);
+                }
+            }
+        }
+
+        // Kahn's algorithm, taking the earliest declared node that is ready
+        let mut waiting: HashMap<NodeId, usize> = affected
+            .iter()
+            .map(|id| {
+                let count = self
+                    .node(*id)
+                    .dependencies
+                    .iter()
+                    .filter(|dependency| affected.contains(*dependency))
+                    .count();
+                (*id, count)
+            })
+            .collect();
+        let mut ready: BTreeSet<NodeId> = waiting
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut order = vec![];
+        while let Some(id) = ready.pop_first() {
+            order.push(id);
+            for dependent in self.node(id).dependents.iter() {
+                if let Some(count) = waiting.get_mut(dependent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(*dependent);
+                    }
+                }
+            }
+        }
+        order
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::interp::{Interpreter, RuntimeError, Value};
+use crate::lexer::Position;
+use crate::resolve::BindingId;
+
+use super::{Evaluator, Node, NodeId, ReactiveError, Runtime};
+
+/// Evaluates the nodes of a program with the interpreter. Each node is a
+/// top-level statement, and derived values and signals are its globals.
+pub struct Interpreted<'a> {
+    interpreter: Interpreter<'a>,
+    // The expression of each derived value and effect, by statement
+    exprs: HashMap<Position, &'a Expr>,
+    nodes: HashMap<BindingId, NodeId>,
+    writes: Vec<(NodeId, Value)>,
+}
+
+impl<'a> Interpreted<'a> {
+    pub fn new(interpreter: Interpreter<'a>) -> Self {
+        Self {
+            interpreter,
+            exprs: HashMap::new(),
+            nodes: HashMap::new(),
+            writes: vec![],
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter<'a> {
+        &self.interpreter
+    }
+
+    /// The interpreter, like to render a view of the program's state. What it
+    /// writes is not tracked.
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<'a> {
+        &mut self.interpreter
+    }
+
+    /// Calls a function of the program, like an event handler, recording
+    /// the signals it writes for `Runtime::with_evaluator`.
+    pub fn call(
+        &mut self,
+        function: &Value,
+        args: Vec<Value>,
+        pos: Position,
+    ) -> Result<Value, RuntimeError> {
+        self.interpreter.track();
+        let result = self.interpreter.call(function, args, pos);
+        self.record();
+        result
+    }
+
+    // Takes what the interpreter tracked, keeping the reads of nodes
+    fn record(&mut self) -> Vec<NodeId> {
+        let tracked = self.interpreter.take_tracked();
+        for (id, value) in tracked.writes {
+            if let Some(node) = self.nodes.get(&id) {
+                self.writes.push((*node, value));
+            }
+        }
+        let mut reads: Vec<NodeId> = tracked
+            .reads
+            .iter()
+            .filter_map(|id| self.nodes.get(id).copied())
+            .collect();
+        reads.sort();
+        reads.dedup();
+        reads
+    }
+}
+
+impl Evaluator for Interpreted<'_> {
+    fn evaluate(&mut self, node: &Node) -> Result<(Value, Vec<NodeId>), RuntimeError> {
+        let expr = self.exprs[&node.pos];
+        self.interpreter.track();
+        let value = self.interpreter.eval_global(expr);
+        let reads = self.record();
+        Ok((value?, reads))
+    }
+
+    fn update(&mut self, node: &Node) {
+        let resolution = self.interpreter.resolution();
+        if let Some(id) = resolution.definitions.get(&node.pos) {
+            self.nodes.insert(*id, node.id);
+            self.interpreter.define_global(*id, node.value.clone());
+        }
+    }
+
+    fn writes(&mut self) -> Vec<(NodeId, Value)> {
+        std::mem::take(&mut self.writes)
+    }
+}
+
+/// Runs a program reactively: top-level `state` bindings become signals,
+/// `let` bindings derived values and expression statements effects. A `let`
+/// that reads no state is computed once, as nothing can change it.
+pub fn run_reactive<'a>(
+    program: &'a Program,
+    mut interpreter: Interpreter<'a>,
+) -> Result<Runtime<Interpreted<'a>>, ReactiveError> {
+    interpreter.hoist(&program.stmts)?;
+    let mut runtime = Runtime::new(Interpreted::new(interpreter));
+    for stmt in program.stmts.iter() {
+        let stmt = stmt.declaration();
+        match &stmt.kind {
+            StmtKind::State(state) => {
+                let value = runtime.evaluator.interpreter.eval_global(&state.value)?;
+                runtime.signal(Some(state.identifier.clone()), stmt.pos, value);
+            }
+            StmtKind::Let(let_stmt) => {
+                runtime.evaluator.exprs.insert(stmt.pos, &let_stmt.value);
+                runtime.derived(Some(let_stmt.identifier.clone()), stmt.pos)?;
+            }
+            StmtKind::Expr(expr) => {
+                runtime.evaluator.exprs.insert(stmt.pos, expr);
+                runtime.effect(stmt.pos)?;
+            }
+            StmtKind::Fn(_)
+            | StmtKind::Component(_)
+            | StmtKind::Type(_)
+            | StmtKind::Import(_)
+            | StmtKind::Export(_) => {}
+        }
+    }
+    Ok(runtime)
+}
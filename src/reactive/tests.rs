@@ -0,0 +1,376 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::*;
+use crate::ast::{Parser, Program};
+use crate::engine::Native;
+use crate::interp::{Function, Interpreter, RuntimeErrorKind};
+use crate::resolve::{resolve_program_with, Resolution};
+
+type Logged = Rc<RefCell<Vec<Value>>>;
+
+fn parse(input: &str) -> (Program, Resolution) {
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, &["log"]);
+    assert!(!resolution.has_errors(), "in {}", input);
+    (program, resolution)
+}
+
+// Runs the program with a `log` function that records its argument
+fn run<'a>(
+    program: &'a Program,
+    resolution: &'a Resolution,
+) -> (Result<Runtime<Interpreted<'a>>>, Logged) {
+    let logged = Logged::default();
+    let log = {
+        let logged = logged.clone();
+        Native::new("log", 1, move |_, args| {
+            logged.borrow_mut().push(args[0].clone());
+            Ok(Value::Unit)
+        })
+    };
+    let mut interpreter = Interpreter::new(resolution);
+    interpreter.set_global("log", Value::Function(Rc::new(Function::Native(log))));
+    (run_reactive(program, interpreter), logged)
+}
+
+fn take(logged: &Logged) -> Vec<String> {
+    logged
+        .borrow_mut()
+        .drain(..)
+        .map(|value| value.to_string())
+        .collect()
+}
+
+// The span of the first line that starts with `text`
+fn line(input: &str, text: &str) -> Position {
+    let start = match input.starts_with(text) {
+        true => 0,
+        false => input.find(&format!("\n{}", text)).unwrap() + 1,
+    };
+    let end = start + input[start..].find(';').unwrap() + 1;
+    Position::new(start as u32, end as u32)
+}
+
+#[test]
+fn derive_from_state() {
+    let input = "state count = 1;\nlet double = count * 2;\nlet quad = double * 2;\nlet other = 5;";
+    let (program, resolution) = parse(input);
+    let (runtime, _) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    let count = runtime.find("count").unwrap();
+    let quad = runtime.find("quad").unwrap();
+    assert_eq!(runtime.get(quad), &Value::Number(4.0));
+
+    runtime.set(count, Value::Number(5.0)).unwrap();
+    assert_eq!(runtime.get(quad), &Value::Number(20.0));
+    assert_eq!(
+        runtime.evaluator().interpreter().global("double"),
+        Some(Value::Number(10.0))
+    );
+
+    let double = runtime.find("double").unwrap();
+    let other = runtime.find("other").unwrap();
+    assert_eq!(runtime.node(count).kind, NodeKind::Signal);
+    assert_eq!(runtime.node(quad).kind, NodeKind::Derived);
+    assert_eq!(runtime.node(quad).dependencies, [double].into());
+    assert_eq!(runtime.node(count).dependents, [double].into());
+    assert!(runtime.node(other).dependencies.is_empty());
+}
+
+#[test]
+fn rerun_only_dependents() {
+    let input = "state a = 1;\nstate b = 1;\nlog(a);\nlog(b * 10);";
+    let (program, resolution) = parse(input);
+    let (runtime, logged) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    assert_eq!(take(&logged), ["1", "10"]);
+
+    let b = runtime.find("b").unwrap();
+    runtime.set(b, Value::Number(2.0)).unwrap();
+    assert_eq!(take(&logged), ["20"]);
+    // Writing the same value changes nothing
+    runtime.set(b, Value::Number(2.0)).unwrap();
+    assert_eq!(take(&logged), Vec::<String>::new());
+}
+
+#[test]
+fn update_without_glitches() {
+    // `sum` reads `a` directly and through `b` and `c`, and only ever sees
+    // them agree
+    let input = "state a = 1;\nlet b = a + 1;\nlet c = a * 2;\nlet sum = b + c;\nlog([a, sum]);";
+    let (program, resolution) = parse(input);
+    let (runtime, logged) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    assert_eq!(take(&logged), ["[1, 4]"]);
+
+    let a = runtime.find("a").unwrap();
+    runtime.set(a, Value::Number(3.0)).unwrap();
+    assert_eq!(take(&logged), ["[3, 10]"]);
+
+    // Derived values that do not change stop the update
+    let input = "state n = 1;\nlet positive = n > 0;\nlog(positive);";
+    let (program, resolution) = parse(input);
+    let (runtime, logged) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    let n = runtime.find("n").unwrap();
+    runtime.set(n, Value::Number(2.0)).unwrap();
+    runtime.set(n, Value::Number(-1.0)).unwrap();
+    assert_eq!(take(&logged), ["true", "false"]);
+}
+
+#[test]
+fn batch_writes() {
+    let input = "state x = 1;\nstate y = 2;\nlog(x + y);";
+    let (program, resolution) = parse(input);
+    let (runtime, logged) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    let x = runtime.find("x").unwrap();
+    let y = runtime.find("y").unwrap();
+    runtime
+        .batch(|runtime| {
+            runtime.set(x, Value::Number(10.0))?;
+            runtime.set(y, Value::Number(20.0))?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(take(&logged), ["3", "30"]);
+}
+
+#[test]
+fn write_from_effects_and_functions() {
+    let input = "state celsius = 0;\nstate fahrenheit = 0;\nfahrenheit = celsius * 9 / 5 + 32;\n\
+                 log(fahrenheit);\nfn warm(by) { celsius = celsius + by; celsius }";
+    let (program, resolution) = parse(input);
+    let (runtime, logged) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    assert_eq!(take(&logged), ["32"]);
+
+    let celsius = runtime.find("celsius").unwrap();
+    runtime.set(celsius, Value::Number(100.0)).unwrap();
+    assert_eq!(take(&logged), ["212"]);
+
+    let warm = runtime.evaluator().interpreter().global("warm").unwrap();
+    let result = runtime.with_evaluator(|evaluator| {
+        evaluator.call(&warm, vec![Value::Number(5.0)], Position::new(0, 0))
+    });
+    assert_eq!(result, Ok(Ok(Value::Number(105.0))));
+    assert_eq!(take(&logged), ["221"]);
+}
+
+#[test]
+fn report_cycles() {
+    let input = "state count = 0;\ncount = count + 1;";
+    let (program, resolution) = parse(input);
+    let (runtime, _) = run(&program, &resolution);
+    let error = runtime.err().unwrap();
+    assert_eq!(
+        error,
+        ReactiveError::Cycle(vec![line(input, "state count"), line(input, "count =")])
+    );
+    assert_eq!(error.to_string(), "reactive cycle through 0..16, 17..35");
+
+    // Through a derived value, once the effect runs again
+    let input = "state a = 0;\nstate b = 0;\nlet c = a + 1;\nb = c;\na = b;";
+    let (program, resolution) = parse(input);
+    let (runtime, _) = run(&program, &resolution);
+    let error = runtime.err().unwrap();
+    assert_eq!(
+        error,
+        ReactiveError::Cycle(vec![
+            line(input, "state a"),
+            line(input, "let c"),
+            line(input, "b = c"),
+            line(input, "state b"),
+            line(input, "a = b"),
+        ])
+    );
+    let diagnostic = error.to_diagnostic();
+    assert_eq!(diagnostic.labels.len(), 4);
+}
+
+#[test]
+fn report_runtime_errors() {
+    let input = "state d = 1;\nlet q = 1 / d;";
+    let (program, resolution) = parse(input);
+    let (runtime, _) = run(&program, &resolution);
+    let mut runtime = runtime.unwrap();
+    let d = runtime.find("d").unwrap();
+    let Err(ReactiveError::Runtime(error)) = runtime.set(d, Value::Number(0.0)) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.kind, RuntimeErrorKind::DivisionByZero);
+
+    // Only signals can be set, and the derived value keeps its own
+    let q = runtime.find("q").unwrap();
+    assert_eq!(
+        runtime.set(q, Value::Number(5.0)),
+        Err(ReactiveError::NotSignal(line(input, "let q = 1 / d;")))
+    );
+    assert_eq!(
+        runtime.set(q, Value::Number(5.0)).unwrap_err().to_string(),
+        "only `state` can be set, not 13..27"
+    );
+    assert_ne!(runtime.get(q), &Value::Number(5.0));
+}
+
+// An evaluator that is not the interpreter, computing each node from the
+// nodes before it
+struct Sum {
+    writes: Vec<(NodeId, Value)>,
+    values: Vec<f64>,
+}
+
+impl Evaluator for Sum {
+    fn evaluate(&mut self, node: &Node) -> std::result::Result<(Value, Vec<NodeId>), RuntimeError> {
+        let reads: Vec<NodeId> = (0..node.id.0).map(NodeId).collect();
+        let sum = self.values[..node.id.0 as usize].iter().sum();
+        Ok((Value::Number(sum), reads))
+    }
+
+    fn update(&mut self, node: &Node) {
+        let Value::Number(value) = node.value else {
+            return;
+        };
+        let index = node.id.0 as usize;
+        self.values.resize(self.values.len().max(index + 1), 0.0);
+        self.values[index] = value;
+    }
+
+    fn writes(&mut self) -> Vec<(NodeId, Value)> {
+        std::mem::take(&mut self.writes)
+    }
+}
+
+#[test]
+fn run_with_any_evaluator() {
+    let pos = Position::new(0, 0);
+    let mut runtime = Runtime::new(Sum {
+        writes: vec![],
+        values: vec![],
+    });
+    let a = runtime.signal(Some("a".into()), pos, Value::Number(1.0));
+    let b = runtime.derived(Some("b".into()), pos).unwrap();
+    let c = runtime.derived(Some("c".into()), pos).unwrap();
+    assert_eq!(runtime.get(c), &Value::Number(2.0));
+    runtime.set(a, Value::Number(3.0)).unwrap();
+    assert_eq!(runtime.get(b), &Value::Number(3.0));
+    assert_eq!(runtime.get(c), &Value::Number(6.0));
+    assert_eq!(runtime.find("c"), Some(c));
+}
+
+fn extract(input: &str) -> Graph {
+    let (program, resolution) = parse(input);
+    extract_graph(&program, &resolution)
+}
+
+#[test]
+fn extract_reads_and_writes() {
+    let input = "state count = 0;\nlet double = count * 2;\nfn inc(by) { count = count + by }\n\
+                 fn click() { inc(1) }\nlog(double);\nexport let label = { let count = 1; count };";
+    let graph = extract(input);
+    let names: Vec<Option<&str>> = graph.nodes.iter().map(|n| n.name.as_deref()).collect();
+    assert_eq!(
+        names,
+        [
+            Some("count"),
+            Some("double"),
+            Some("inc"),
+            Some("click"),
+            None,
+            Some("label")
+        ]
+    );
+    let count = graph.find("count").unwrap();
+    let double = graph.find("double").unwrap();
+    assert_eq!(graph.node(double).kind, GraphNodeKind::Derived);
+    assert_eq!(graph.node(double).reads, [count].into());
+
+    // Through the functions a node refers to
+    let click = graph.node(graph.find("click").unwrap());
+    assert_eq!(click.kind, GraphNodeKind::Function);
+    assert_eq!(click.reads, [count].into());
+    assert_eq!(click.writes, [count].into());
+
+    let effect = &graph.nodes[4];
+    assert_eq!(effect.kind, GraphNodeKind::Effect);
+    assert_eq!(effect.pos, line(input, "log(double)"));
+    assert_eq!(effect.reads, [double].into());
+    assert_eq!(
+        graph.readers(count),
+        [double, graph.find("inc").unwrap(), click.id]
+    );
+    // The local `count` shadows the state
+    assert!(graph.node(graph.find("label").unwrap()).reads.is_empty());
+    assert_eq!(graph.cycles(), Vec::<Vec<NodeId>>::new());
+}
+
+#[test]
+fn find_static_cycles() {
+    let input =
+        "state a = 0;\nstate b = 0;\nlet c = a + 1;\nfn copy() { a = b }\nb = c;\ncopy();\nlog(a);";
+    let graph = extract(input);
+    let cycles: Vec<Vec<&str>> = graph
+        .cycles()
+        .iter()
+        .map(|cycle| {
+            let spans = cycle.iter().map(|id| graph.node(*id).pos);
+            spans
+                .map(|pos| &input[pos.start as usize..pos.end as usize])
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        cycles,
+        [[
+            "state a = 0;",
+            "let c = a + 1;",
+            "b = c;",
+            "state b = 0;",
+            "copy();"
+        ]]
+    );
+    let diagnostics = graph.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].pos, line(input, "state a"));
+    assert_eq!(diagnostics[0].labels.len(), 4);
+
+    // An effect that reads what it writes
+    let graph = extract("state n = 0;\nn = n + 1;");
+    assert_eq!(graph.cycles(), [[NodeId(0), NodeId(1)]]);
+}
+
+#[test]
+fn export_graphs() {
+    let input = "state count = 0;\nlet double = count * 2;\nfn reset() { count = 0 }\nlog(double);";
+    let graph = extract(input);
+    assert_eq!(
+        graph.to_dot(),
+        "digraph reactive {
+    n0 [label=\"count\", shape=box];
+    n1 [label=\"double\", shape=ellipse];
+    n2 [label=\"reset\", shape=note];
+    n3 [label=\"effect 66..78\", shape=diamond];
+    n0 -> n1;
+    n2 -> n0 [style=dashed];
+    n1 -> n3;
+}
+"
+    );
+    assert_eq!(
+        graph.to_json(),
+        r#"{
+  "nodes": [
+    {"id": 0, "kind": "state", "name": "count", "span": [0, 16], "reads": [], "writes": []},
+    {"id": 1, "kind": "derived", "name": "double", "span": [17, 40], "reads": [0], "writes": []},
+    {"id": 2, "kind": "function", "name": "reset", "span": [41, 65], "reads": [], "writes": [0]},
+    {"id": 3, "kind": "effect", "name": null, "span": [66, 78], "reads": [1], "writes": []}
+  ],
+  "cycles": []
+}
+"#
+    );
+
+    let graph = extract("state n = 0;\nn = n + 1;");
+    assert!(graph.to_json().ends_with("\"cycles\": [[0, 1]]\n}\n"));
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::reactive::graph::quote;
+
+use super::vdom::VNode;
+
+/// A change to a rendered tree. Nodes are found by paths of child indices
+/// from the top-level nodes, whose parent is the empty path, and each patch
+/// sees the tree as the patches before it left it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Patch {
+    // Inserts `node` as child `index` of `parent`
+    Create {
+        parent: Vec<usize>,
+        index: usize,
+        node: VNode,
+    },
+    Remove {
+        parent: Vec<usize>,
+        index: usize,
+    },
+    // Sets an attribute of an element, or removes it if `value` is `None`
+    SetAttribute {
+        path: Vec<usize>,
+        name: String,
+        value: Option<String>,
+    },
+    ReplaceText {
+        path: Vec<usize>,
+        text: String,
+    },
+    // Takes child `from` out of `parent`, and puts it back in as child `to`
+    Move {
+        parent: Vec<usize>,
+        from: usize,
+        to: usize,
+    },
+}
+
+impl Patch {
+    pub fn to_json(&self) -> String {
+        let path = |path: &[usize]| {
+            let indices: Vec<String> = path.iter().map(usize::to_string).collect();
+            format!("[{}]", indices.join(", "))
+        };
+        match self {
+            Patch::Create {
+                parent,
+                index,
+                node,
+            } => format!(
+                "{{\"op\": \"create\", \"parent\": {}, \"index\": {}, \"node\": {}}}",
+                path(parent),
+                index,
+                node.to_json()
+            ),
+            Patch::Remove { parent, index } => format!(
+                "{{\"op\": \"remove\", \"parent\": {}, \"index\": {}}}",
+                path(parent),
+                index
+            ),
+            Patch::SetAttribute {
+                path: element,
+                name,
+                value,
+            } => format!(
+                "{{\"op\": \"set_attribute\", \"path\": {}, \"name\": {}, \"value\": {}}}",
+                path(element),
+                quote(name),
+                value.as_deref().map_or("null".to_string(), quote)
+            ),
+            Patch::ReplaceText {
+                path: text_path,
+                text,
+            } => format!(
+                "{{\"op\": \"replace_text\", \"path\": {}, \"text\": {}}}",
+                path(text_path),
+                quote(text)
+            ),
+            Patch::Move { parent, from, to } => format!(
+                "{{\"op\": \"move\", \"parent\": {}, \"from\": {}, \"to\": {}}}",
+                path(parent),
+                from,
+                to
+            ),
+        }
+    }
+}
+
+/// The patches as a JSON array, one patch per line.
+pub fn patches_to_json(patches: &[Patch]) -> String {
+    let mut out = String::from("[\n");
+    for (i, patch) in patches.iter().enumerate() {
+        let separator = if i + 1 < patches.len() { "," } else { "" };
+        writeln!(out, "  {}{}", patch.to_json(), separator).unwrap();
+    }
+    out.push(']');
+    out
+}
+
+/// The patches that turn the nodes `old` into `new`. Children with the same
+/// key, or else the same position among the children without one, are
+/// patched in place when they have the same tag. Reordered children are
+/// moved, as few of them as possible.
+pub fn diff(old: &[VNode], new: &[VNode]) -> Vec<Patch> {
+    let mut patches = vec![];
+    children(&mut vec![], old, new, &mut patches);
+    patches
+}
+
+fn children(parent: &mut Vec<usize>, old: &[VNode], new: &[VNode], patches: &mut Vec<Patch>) {
+    let matched = reconcile(old, new);
+
+    // Removed from the last, so that the indices of the rest stay the same
+    let mut kept = vec![false; old.len()];
+    matched
+        .iter()
+        .flatten()
+        .for_each(|index| kept[*index] = true);
+    for index in (0..old.len()).rev().filter(|index| !kept[*index]) {
+        patches.push(Patch::Remove {
+            parent: parent.clone(),
+            index,
+        });
+    }
+
+    // From the last, every child that is not in the longest run of children
+    // already in order is put right before the child that follows it
+    let stable = in_order(&matched);
+    let mut live: Vec<Entry> = (0..old.len())
+        .filter(|index| kept[*index])
+        .map(Entry::Old)
+        .collect();
+    let entry = |i: usize| matched[i].map_or(Entry::New(i), Entry::Old);
+    for i in (0..new.len()).rev() {
+        let anchor = match i + 1 < new.len() {
+            true => position(&live, entry(i + 1)),
+            false => live.len(),
+        };
+        match matched[i] {
+            Some(_) if stable[i] => {}
+            Some(old_index) => {
+                let from = position(&live, Entry::Old(old_index));
+                let to = if from < anchor { anchor - 1 } else { anchor };
+                if from != to {
+                    live.remove(from);
+                    live.insert(to, Entry::Old(old_index));
+                    patches.push(Patch::Move {
+                        parent: parent.clone(),
+                        from,
+                        to,
+                    });
+                }
+            }
+            None => {
+                live.insert(anchor, Entry::New(i));
+                patches.push(Patch::Create {
+                    parent: parent.clone(),
+                    index: anchor,
+                    node: new[i].clone(),
+                });
+            }
+        }
+    }
+
+    for (i, old_index) in matched.iter().enumerate() {
+        if let Some(old_index) = old_index {
+            parent.push(i);
+            node(parent, &old[*old_index], &new[i], patches);
+            parent.pop();
+        }
+    }
+}
+
+// The old nodes being reordered, and the new nodes inserted among them
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Old(usize),
+    New(usize),
+}
+
+fn position(live: &[Entry], entry: Entry) -> usize {
+    live.iter().position(|live| *live == entry).unwrap()
+}
+
+fn node(path: &mut Vec<usize>, old: &VNode, new: &VNode, patches: &mut Vec<Patch>) {
+    match (old, new) {
+        (VNode::Text(old), VNode::Text(new)) if old != new => {
+            patches.push(Patch::ReplaceText {
+                path: path.clone(),
+                text: new.clone(),
+            });
+        }
+        (VNode::Element(old), VNode::Element(new)) => {
+            for name in old.attributes.keys() {
+                if !new.attributes.contains_key(name) {
+                    patches.push(Patch::SetAttribute {
+                        path: path.clone(),
+                        name: name.clone(),
+                        value: None,
+                    });
+                }
+            }
+            for (name, value) in new.attributes.iter() {
+                if old.attributes.get(name) != Some(value) {
+                    patches.push(Patch::SetAttribute {
+                        path: path.clone(),
+                        name: name.clone(),
+                        value: Some(value.clone()),
+                    });
+                }
+            }
+            children(path, &old.children, &new.children, patches);
+        }
+        // Unchanged text, as only nodes of the same kind are matched
+        _ => {}
+    }
+}
+
+// For each new node, the old node it is a new render of
+fn reconcile(old: &[VNode], new: &[VNode]) -> Vec<Option<usize>> {
+    let mut keyed: HashMap<&str, usize> = HashMap::new();
+    let mut unkeyed = vec![];
+    for (index, node) in old.iter().enumerate() {
+        match key(node) {
+            Some(key) => {
+                keyed.entry(key).or_insert(index);
+            }
+            None => unkeyed.push(index),
+        }
+    }
+
+    let mut unkeyed = unkeyed.into_iter();
+    new.iter()
+        .map(|node| {
+            let index = match key(node) {
+                Some(key) => keyed.remove(key),
+                None => unkeyed.next(),
+            };
+            index.filter(|index| same_kind(&old[*index], node))
+        })
+        .collect()
+}
+
+fn key(node: &VNode) -> Option<&str> {
+    match node {
+        VNode::Element(element) => element.key.as_deref(),
+        VNode::Text(_) => None,
+    }
+}
+
+fn same_kind(a: &VNode, b: &VNode) -> bool {
+    match (a, b) {
+        (VNode::Text(_), VNode::Text(_)) => true,
+        (VNode::Element(a), VNode::Element(b)) => a.tag == b.tag,
+        _ => false,
+    }
+}
+
+// Which of the matched new nodes are in the longest run whose old nodes are
+// in the same order, and so can stay where they are
+fn in_order(matched: &[Option<usize>]) -> Vec<bool> {
+    // Per length of a run, the index in `matched` of the run's last node
+    // with the smallest old index
+    let mut tails: Vec<usize> = vec![];
+    let mut previous: Vec<Option<usize>> = vec![None; matched.len()];
+    for (i, old_index) in matched.iter().enumerate() {
+        let Some(old_index) = old_index else {
+            continue;
+        };
+        let length = tails.partition_point(|tail| matched[*tail].unwrap() < *old_index);
+        previous[i] = length.checked_sub(1).map(|length| tails[length]);
+        if length == tails.len() {
+            tails.push(i);
+        } else {
+            tails[length] = i;
+        }
+    }
+
+    let mut stable = vec![false; matched.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        stable[i] = true;
+        next = previous[i];
+    }
+    stable
+}
+
+/// Applies patches to nodes, as a host would to what it displays. Returns
+/// the index of the first patch that does not fit the nodes, after which the
+/// nodes are left partly patched.
+pub fn apply(nodes: &mut Vec<VNode>, patches: &[Patch]) -> Result<(), usize> {
+    for (i, patch) in patches.iter().enumerate() {
+        apply_patch(nodes, patch).ok_or(i)?;
+    }
+    Ok(())
+}
+
+fn apply_patch(nodes: &mut Vec<VNode>, patch: &Patch) -> Option<()> {
+    match patch {
+        Patch::Create {
+            parent,
+            index,
+            node,
+        } => {
+            let children = children_at(nodes, parent)?;
+            if *index > children.len() {
+                return None;
+            }
+            children.insert(*index, node.clone());
+        }
+        Patch::Remove { parent, index } => {
+            let children = children_at(nodes, parent)?;
+            if *index >= children.len() {
+                return None;
+            }
+            children.remove(*index);
+        }
+        Patch::SetAttribute { path, name, value } => {
+            let VNode::Element(element) = node_at(nodes, path)? else {
+                return None;
+            };
+            match value {
+                Some(value) => element.attributes.insert(name.clone(), value.clone()),
+                None => element.attributes.remove(name),
+            };
+        }
+        Patch::ReplaceText { path, text } => {
+            let VNode::Text(old) = node_at(nodes, path)? else {
+                return None;
+            };
+            *old = text.clone();
+        }
+        Patch::Move { parent, from, to } => {
+            let children = children_at(nodes, parent)?;
+            if *from >= children.len() || *to >= children.len() {
+                return None;
+            }
+            let node = children.remove(*from);
+            children.insert(*to, node);
+        }
+    }
+    Some(())
+}
+
+fn children_at<'n>(nodes: &'n mut Vec<VNode>, path: &[usize]) -> Option<&'n mut Vec<VNode>> {
+    let mut children = nodes;
+    for index in path {
+        match children.get_mut(*index)? {
+            VNode::Element(element) => children = &mut element.children,
+            VNode::Text(_) => return None,
+        }
+    }
+    Some(children)
+}
+
+fn node_at<'n>(nodes: &'n mut Vec<VNode>, path: &[usize]) -> Option<&'n mut VNode> {
+    let (last, parent) = path.split_last()?;
+    children_at(nodes, parent)?.get_mut(*last)
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+// Elements that cannot have content, and so have no closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+pub fn is_void_element(name: &str) -> bool {
+    VOID_ELEMENTS.contains(&name)
+}
+
+/// Writes text for the content of an element, so that it cannot start a tag
+/// or a character reference. Text in `script` and `style` is escaped as well,
+/// as values must not be able to inject code.
+pub fn escape_text(text: &str, out: &mut impl fmt::Write) -> fmt::Result {
+    escape(text, out, false)
+}
+
+/// Writes the value of an attribute for between double quotes.
+pub fn escape_attribute(value: &str, out: &mut impl fmt::Write) -> fmt::Result {
+    escape(value, out, true)
+}
+
+fn escape(text: &str, out: &mut impl fmt::Write, quotes: bool) -> fmt::Result {
+    let mut start = 0;
+    for (i, char) in text.char_indices() {
+        let escaped = match char {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' if quotes => "&quot;",
+            _ => continue,
+        };
+        out.write_str(&text[start..i])?;
+        out.write_str(escaped)?;
+        start = i + 1;
+    }
+    out.write_str(&text[start..])
+}
@@ -0,0 +1,269 @@
+pub mod diff;
+pub mod html;
+#[cfg(test)]
+mod tests;
+pub mod vdom;
+
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+use crate::ast::Program;
+use crate::interp::{ElementTag, ElementValue, Interpreter, RuntimeError, RuntimeErrorKind, Value};
+use crate::lexer::Position;
+use crate::resolve::Resolution;
+
+pub use self::diff::*;
+pub use self::html::*;
+pub use self::vdom::*;
+
+// Components rendering components, past which one is assumed to render
+// itself forever
+const MAX_COMPONENT_DEPTH: usize = 128;
+
+#[derive(Debug)]
+pub enum RenderError {
+    Runtime(RuntimeError),
+    // A value with no HTML form, like a function as a child, with its type
+    NotRenderable(String),
+    // A void element, like `br`, that was given children
+    VoidChildren(String),
+    Fmt(fmt::Error),
+    Io(io::Error),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Runtime(error) => write!(f, "{}", error),
+            RenderError::NotRenderable(ty) => write!(f, "`{}` cannot be rendered as HTML", ty),
+            RenderError::VoidChildren(name) => write!(f, "`<{}>` cannot have children", name),
+            RenderError::Fmt(error) => write!(f, "{}", error),
+            RenderError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<RuntimeError> for RenderError {
+    fn from(error: RuntimeError) -> Self {
+        RenderError::Runtime(error)
+    }
+}
+
+impl From<fmt::Error> for RenderError {
+    fn from(error: fmt::Error) -> Self {
+        RenderError::Fmt(error)
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(error: io::Error) -> Self {
+        RenderError::Io(error)
+    }
+}
+
+type Result<T> = std::result::Result<T, RenderError>;
+
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    // Attributes by name rather than in the order they were written, so that
+    // snapshots do not change when markup is rearranged
+    pub sort_attributes: bool,
+}
+
+/// Runs a program and renders its top-level `root`, which is an element or
+/// a component called without props.
+pub fn render_program(
+    program: &Program,
+    resolution: &Resolution,
+    root: &str,
+    options: RenderOptions,
+) -> Result<String> {
+    let mut interpreter = Interpreter::new(resolution);
+    interpreter.run(program)?;
+    let binding = resolution
+        .scopes
+        .first()
+        .and_then(|scope| {
+            scope
+                .bindings
+                .iter()
+                .rev()
+                .find(|id| resolution.binding(**id).name == root)
+        })
+        .map(|id| resolution.binding(*id));
+    let (Some(binding), Some(value)) = (binding, interpreter.global(root)) else {
+        return Err(RenderError::Runtime(RuntimeError::new(
+            RuntimeErrorKind::Unbound(root.to_string()),
+            Position::new(0, 0),
+        )));
+    };
+    let pos = binding.pos;
+    let root = match value {
+        Value::Function(_) => {
+            Value::Element(Rc::new(ElementValue::new(ElementTag::Component(value))))
+        }
+        value => value,
+    };
+    HtmlRenderer::new(&mut interpreter, options)
+        .at(pos)
+        .render_to_string(&root)
+}
+
+/// Writes values out as HTML, calling the components of elements as it
+/// reaches them. Every component rendered is a new instance, with the
+/// initial value of its `state`.
+pub struct HtmlRenderer<'r, 'a> {
+    interpreter: &'r mut Interpreter<'a>,
+    options: RenderOptions,
+    // Where calls of components are reported to fail, as values have no span
+    pos: Position,
+    depth: usize,
+}
+
+impl<'r, 'a> HtmlRenderer<'r, 'a> {
+    pub fn new(interpreter: &'r mut Interpreter<'a>, options: RenderOptions) -> Self {
+        Self {
+            interpreter,
+            options,
+            pos: Position::new(0, 0),
+            depth: 0,
+        }
+    }
+
+    /// Reports the errors of calling components, like missing props, at
+    /// `pos`. Errors inside a component have spans of their own.
+    pub fn at(mut self, pos: Position) -> Self {
+        self.pos = pos;
+        self
+    }
+
+    pub fn render_to_string(&mut self, value: &Value) -> Result<String> {
+        let mut out = String::new();
+        self.render(value, &mut out)?;
+        Ok(out)
+    }
+
+    /// Streams the HTML of `value` to `out` as it is rendered.
+    pub fn render(&mut self, value: &Value, out: &mut impl fmt::Write) -> Result<()> {
+        match value {
+            Value::String(text) => escape_text(text, out)?,
+            Value::Number(_) | Value::Bool(_) => escape_text(&value.to_string(), out)?,
+            Value::List(items) => {
+                for item in items.iter() {
+                    self.render(item, out)?;
+                }
+            }
+            Value::Element(element) => self.element(element, out)?,
+            Value::Unit => {}
+            Value::Record(_) | Value::Variant(_) | Value::Function(_) => {
+                return Err(RenderError::NotRenderable(value.type_name().to_string()))
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `render`, to a byte stream such as a file or a socket.
+    pub fn render_io(&mut self, value: &Value, out: &mut impl io::Write) -> Result<()> {
+        let mut writer = IoWriter { out, error: None };
+        match self.render(value, &mut writer) {
+            Err(RenderError::Fmt(error)) => match writer.error {
+                Some(error) => Err(RenderError::Io(error)),
+                None => Err(RenderError::Fmt(error)),
+            },
+            result => result,
+        }
+    }
+
+    fn element(&mut self, element: &ElementValue, out: &mut impl fmt::Write) -> Result<()> {
+        let name = match &element.tag {
+            ElementTag::Html(name) => name,
+            ElementTag::Component(component) => return self.component(component, element, out),
+            ElementTag::Fragment => {
+                for child in element.children.iter() {
+                    self.render(child, out)?;
+                }
+                return Ok(());
+            }
+        };
+
+        write!(out, "<{}", name)?;
+        let mut attributes: Vec<&(String, Value)> = element.attributes.iter().collect();
+        if self.options.sort_attributes {
+            attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        for (attribute, value) in attributes {
+            self.attribute(attribute, value, out)?;
+        }
+        write!(out, ">")?;
+        if is_void_element(name) {
+            if !element.children.is_empty() {
+                return Err(RenderError::VoidChildren(name.clone()));
+            }
+            return Ok(());
+        }
+        for child in element.children.iter() {
+            self.render(child, out)?;
+        }
+        write!(out, "</{}>", name)?;
+        Ok(())
+    }
+
+    // `true` gives an attribute without a value and `false` leaves it out.
+    // Functions are event handlers, which only a client can attach, and
+    // `key` is for renderers.
+    fn attribute(&mut self, name: &str, value: &Value, out: &mut impl fmt::Write) -> Result<()> {
+        match value {
+            _ if name == "key" => {}
+            Value::Bool(true) => write!(out, " {}", name)?,
+            Value::Bool(false) | Value::Function(_) | Value::Unit => {}
+            Value::String(text) => {
+                write!(out, " {}=\"", name)?;
+                escape_attribute(text, out)?;
+                write!(out, "\"")?;
+            }
+            Value::Number(number) => write!(out, " {}=\"{}\"", name, number)?,
+            value => return Err(RenderError::NotRenderable(value.type_name().to_string())),
+        }
+        Ok(())
+    }
+
+    fn component(
+        &mut self,
+        component: &Value,
+        element: &ElementValue,
+        out: &mut impl fmt::Write,
+    ) -> Result<()> {
+        if self.depth == MAX_COMPONENT_DEPTH {
+            return Err(RenderError::Runtime(RuntimeError::new(
+                RuntimeErrorKind::StackOverflow(MAX_COMPONENT_DEPTH),
+                self.pos,
+            )));
+        }
+        let rendered = self
+            .interpreter
+            .call(component, vec![element.props()], self.pos)?;
+        self.depth += 1;
+        let result = self.render(&rendered, out);
+        self.depth -= 1;
+        result
+    }
+}
+
+// Passes text on to an `io::Write`, keeping the error that `fmt::Write` has
+// no room for
+struct IoWriter<'w, W> {
+    out: &'w mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.out.write_all(text.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::BTreeMap;
+
+use super::*;
+use crate::ast::Parser;
+use crate::reactive::run_reactive;
+use crate::resolve::resolve_program;
+
+fn render(input: &str, root: &str) -> Result<String> {
+    render_with(input, root, RenderOptions::default())
+}
+
+fn render_with(input: &str, root: &str, options: RenderOptions) -> Result<String> {
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program(&program);
+    assert!(!resolution.has_errors(), "in {}", input);
+    render_program(&program, &resolution, root, options)
+}
+
+#[test]
+fn render_elements() {
+    assert_eq!(
+        render(
+            "let page = <div id='main' hidden><h1>Title</h1>{1 + 2} items</div>;",
+            "page"
+        )
+        .unwrap(),
+        "<div id=\"main\" hidden><h1>Title</h1>3 items</div>"
+    );
+    assert_eq!(
+        render(
+            "let items = ['a', 'b'];\nlet page = <><ul>{items}</ul>{if false { 1 }}</>;",
+            "page"
+        )
+        .unwrap(),
+        "<ul>ab</ul>"
+    );
+    // Handlers and `false` are left out
+    assert_eq!(
+        render(
+            "let page = <button disabled={false} onclick={fn() => 1} tabindex={2}>Go</button>;",
+            "page"
+        )
+        .unwrap(),
+        "<button tabindex=\"2\">Go</button>"
+    );
+}
+
+#[test]
+fn render_escapes() {
+    let input = "let text = \"<script>alert('&')</script>\";\nlet page = <p title={'say \"hi\" & <go>'}>{text}</p>;";
+    assert_eq!(
+        render(input, "page").unwrap(),
+        "<p title=\"say &quot;hi&quot; &amp; &lt;go&gt;\">&lt;script&gt;alert('&amp;')&lt;/script&gt;</p>"
+    );
+    // `key` is not written out, whatever it holds
+    let input =
+        "let k = 'q\"';\nlet page = <ul><li key={k} title='t'>a</li><li key={2}>b</li></ul>;";
+    assert_eq!(
+        render(input, "page").unwrap(),
+        "<ul><li title=\"t\">a</li><li>b</li></ul>"
+    );
+}
+
+#[test]
+fn render_void_elements() {
+    assert_eq!(
+        render(
+            "let page = <p>a<br />b<img src='x.png' alt='' /></p>;",
+            "page"
+        )
+        .unwrap(),
+        "<p>a<br>b<img src=\"x.png\" alt=\"\"></p>"
+    );
+    assert!(matches!(
+        render("let page = <br>text</br>;", "page"),
+        Err(RenderError::VoidChildren(name)) if name == "br"
+    ));
+}
+
+#[test]
+fn render_sorted_attributes() {
+    let input = "let page = <input value='1' type='text' name='q' />;";
+    assert_eq!(
+        render(input, "page").unwrap(),
+        "<input value=\"1\" type=\"text\" name=\"q\">"
+    );
+    let options = RenderOptions {
+        sort_attributes: true,
+    };
+    assert_eq!(
+        render_with(input, "page", options).unwrap(),
+        "<input name=\"q\" type=\"text\" value=\"1\">"
+    );
+}
+
+#[test]
+fn render_components() {
+    let input = "component Counter(label: String, start) {\n    state count = start;\n    <button onclick={fn() { count = count + 1 }}>{label}: {count}</button>\n}\nfn Card(props) { <section class='card'>{props.children}</section> }\ncomponent App() {\n    <Card>\n        <Counter label='One' start={1} />\n        <Counter label='Two' start={2} />\n    </Card>\n}";
+    assert_eq!(
+        render(input, "App").unwrap(),
+        "<section class=\"card\"><button>One: 1</button><button>Two: 2</button></section>"
+    );
+
+    let input = "component Greeting(name) { <p>{name}</p> }\nlet page = <Greeting />;";
+    let Err(RenderError::Runtime(error)) = render(input, "page") else {
+        panic!("rendered without a prop");
+    };
+    assert_eq!(
+        error.kind,
+        RuntimeErrorKind::MissingField("name".to_string())
+    );
+
+    let input = "component Forever() { <div><Forever /></div> }";
+    let Err(RenderError::Runtime(error)) = render(input, "Forever") else {
+        panic!("rendered forever");
+    };
+    assert_eq!(
+        error.kind,
+        RuntimeErrorKind::StackOverflow(MAX_COMPONENT_DEPTH)
+    );
+    assert!(matches!(
+        render("let page = <p>{fn() => 1}</p>;", "page"),
+        Err(RenderError::NotRenderable(ty)) if ty == "Function"
+    ));
+}
+
+#[test]
+fn render_to_writers() {
+    let program = Parser::new("let page = <ul><li>first</li><li>second</li></ul>;")
+        .parse_program()
+        .unwrap();
+    let resolution = resolve_program(&program);
+    let mut interpreter = Interpreter::new(&resolution);
+    interpreter.run(&program).unwrap();
+    let page = interpreter.global("page").unwrap();
+    let mut renderer = HtmlRenderer::new(&mut interpreter, RenderOptions::default());
+
+    let mut bytes = vec![];
+    renderer.render_io(&page, &mut bytes).unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        "<ul><li>first</li><li>second</li></ul>"
+    );
+
+    // A full buffer fails with the error of the stream
+    let mut buffer = [0; 8];
+    let result = renderer.render_io(&page, &mut buffer.as_mut_slice());
+    assert!(
+        matches!(result, Err(RenderError::Io(error)) if error.kind() == io::ErrorKind::WriteZero)
+    );
+    assert_eq!(&buffer, b"<ul><li>");
+}
+
+#[test]
+fn render_corpus_app() {
+    let (_, source) = crate::corpus::sources()
+        .into_iter()
+        .find(|(name, _)| name == "components.zp")
+        .unwrap();
+    let options = RenderOptions {
+        sort_attributes: true,
+    };
+    assert_eq!(
+        render_with(&source, "App", options).unwrap(),
+        "<section><h2>Counters</h2><button>By one: 0</button><button>By ten: 0</button></section>"
+    );
+}
+
+fn text(text: &str) -> VNode {
+    VNode::Text(text.to_string())
+}
+
+fn element(tag: &str, key: Option<&str>, children: Vec<VNode>) -> VNode {
+    VNode::Element(VElement {
+        tag: tag.to_string(),
+        key: key.map(str::to_string),
+        attributes: BTreeMap::new(),
+        handlers: BTreeMap::new(),
+        children,
+    })
+}
+
+fn keyed(keys: &str) -> Vec<VNode> {
+    keys.chars()
+        .map(|key| element("li", Some(&key.to_string()), vec![text(&key.to_string())]))
+        .collect()
+}
+
+// Diffs `old` and `new`, checking that the patches turn one into the other
+fn diff_checked(old: &[VNode], new: &[VNode]) -> Vec<Patch> {
+    let patches = diff(old, new);
+    let mut patched = old.to_vec();
+    assert_eq!(apply(&mut patched, &patches), Ok(()));
+    assert_eq!(patched, new, "with {:?}", patches);
+    patches
+}
+
+fn component_tree(input: &str) -> (Program, Resolution) {
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program(&program);
+    assert!(!resolution.has_errors(), "in {}", input);
+    (program, resolution)
+}
+
+fn root(component: Value) -> Value {
+    Value::Element(Rc::new(ElementValue::new(ElementTag::Component(component))))
+}
+
+#[test]
+fn build_virtual_nodes() {
+    let input = "fn Item(props) { <li key={props.id} class='item' hidden={false}>{props.id}</li> }\nlet page = <>\n    <ul data-count={2}>{[<Item id={1} />, <Item id={2} />]}</ul>\n    <input disabled onchange={fn() => 1} />\n</>;";
+    let (program, resolution) = component_tree(input);
+    let mut interpreter = Interpreter::new(&resolution);
+    interpreter.run(&program).unwrap();
+    let mut tree = Tree::new(interpreter.global("page").unwrap());
+    let patches = tree.render(&mut interpreter).unwrap();
+    assert_eq!(patches.len(), 2);
+
+    let nodes: Vec<String> = tree.nodes().iter().map(VNode::to_json).collect();
+    assert_eq!(
+        nodes,
+        vec![
+            "{\"tag\": \"ul\", \"key\": null, \"attributes\": {\"data-count\": \"2\"}, \"events\": [], \"children\": [\
+             {\"tag\": \"li\", \"key\": \"1\", \"attributes\": {\"class\": \"item\"}, \"events\": [], \"children\": [{\"text\": \"1\"}]}, \
+             {\"tag\": \"li\", \"key\": \"2\", \"attributes\": {\"class\": \"item\"}, \"events\": [], \"children\": [{\"text\": \"2\"}]}]}",
+            "{\"tag\": \"input\", \"key\": null, \"attributes\": {\"disabled\": \"\"}, \"events\": [\"onchange\"], \"children\": []}",
+        ]
+    );
+    assert!(tree.handler(&[1], "onchange").is_some());
+    assert!(tree.handler(&[0, 0], "onchange").is_none());
+
+    // Nothing changed
+    assert_eq!(tree.render(&mut interpreter).unwrap(), vec![]);
+}
+
+#[test]
+fn diff_text_and_attributes() {
+    let mut old = element("p", None, vec![text("a"), text("b")]);
+    let mut new = element("p", None, vec![text("a"), text("c")]);
+    if let (VNode::Element(old), VNode::Element(new)) = (&mut old, &mut new) {
+        old.attributes.insert("class".to_string(), "x".to_string());
+        old.attributes.insert("id".to_string(), "main".to_string());
+        new.attributes.insert("id".to_string(), "main".to_string());
+        new.attributes.insert("title".to_string(), "t".to_string());
+    }
+    assert_eq!(
+        diff_checked(&[old], &[new]),
+        vec![
+            Patch::SetAttribute {
+                path: vec![0],
+                name: "class".to_string(),
+                value: None
+            },
+            Patch::SetAttribute {
+                path: vec![0],
+                name: "title".to_string(),
+                value: Some("t".to_string())
+            },
+            Patch::ReplaceText {
+                path: vec![0, 1],
+                text: "c".to_string()
+            },
+        ]
+    );
+
+    // A node of another kind replaces the old one
+    assert_eq!(
+        diff_checked(&[text("a")], &[element("b", None, vec![])]),
+        vec![
+            Patch::Remove {
+                parent: vec![],
+                index: 0
+            },
+            Patch::Create {
+                parent: vec![],
+                index: 0,
+                node: element("b", None, vec![])
+            },
+        ]
+    );
+}
+
+#[test]
+fn diff_keyed_children() {
+    let list = |keys| vec![element("ul", None, keyed(keys))];
+    let moved = |from, to| Patch::Move {
+        parent: vec![0],
+        from,
+        to,
+    };
+    assert_eq!(
+        diff_checked(&list("abcd"), &list("dabc")),
+        vec![moved(3, 0)]
+    );
+    assert_eq!(
+        diff_checked(&list("abcd"), &list("bcda")),
+        vec![moved(0, 3)]
+    );
+    assert_eq!(
+        diff_checked(&list("abcde"), &list("edcba")).len(),
+        4,
+        "all but one move"
+    );
+    assert_eq!(
+        diff_checked(&list("abc"), &list("axc")),
+        vec![
+            Patch::Remove {
+                parent: vec![0],
+                index: 1
+            },
+            Patch::Create {
+                parent: vec![0],
+                index: 1,
+                node: keyed("x").remove(0)
+            },
+        ]
+    );
+    assert_eq!(diff_checked(&list("abc"), &list("abc")), vec![]);
+}
+
+#[test]
+fn diff_random_trees() {
+    struct Rng(u64);
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+    }
+    fn gen_nodes(rng: &mut Rng, depth: u32) -> Vec<VNode> {
+        let mut keys: Vec<char> = "abcdef".chars().collect();
+        (0..rng.below(5))
+            .map(|_| match rng.below(if depth == 0 { 1 } else { 4 }) {
+                0 => text(["x", "y"][rng.below(2) as usize]),
+                choice => {
+                    let key = match choice {
+                        1 if !keys.is_empty() => Some(
+                            keys.remove(rng.below(keys.len() as u64) as usize)
+                                .to_string(),
+                        ),
+                        _ => None,
+                    };
+                    let mut node = element(["div", "span"][rng.below(2) as usize], None, vec![]);
+                    if let VNode::Element(element) = &mut node {
+                        element.key = key;
+                        if rng.below(2) == 0 {
+                            element
+                                .attributes
+                                .insert("class".to_string(), rng.below(3).to_string());
+                        }
+                        element.children = gen_nodes(rng, depth - 1);
+                    }
+                    node
+                }
+            })
+            .collect()
+    }
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let old = gen_nodes(&mut rng, 3);
+        let new = gen_nodes(&mut rng, 3);
+        diff_checked(&old, &new);
+    }
+}
+
+#[test]
+fn patches_as_json() {
+    let patches = vec![
+        Patch::Create {
+            parent: vec![],
+            index: 0,
+            node: element("p", Some("a"), vec![text("say \"hi\"")]),
+        },
+        Patch::SetAttribute {
+            path: vec![0],
+            name: "class".to_string(),
+            value: None,
+        },
+        Patch::Move {
+            parent: vec![0, 2],
+            from: 1,
+            to: 0,
+        },
+    ];
+    assert_eq!(
+        patches_to_json(&patches),
+        "[\n  {\"op\": \"create\", \"parent\": [], \"index\": 0, \"node\": {\"tag\": \"p\", \"key\": \"a\", \"attributes\": {}, \"events\": [], \"children\": [{\"text\": \"say \\\"hi\\\"\"}]}},\n  \
+         {\"op\": \"set_attribute\", \"path\": [0], \"name\": \"class\", \"value\": null},\n  \
+         {\"op\": \"move\", \"parent\": [0, 2], \"from\": 1, \"to\": 0}\n]"
+    );
+}
+
+#[test]
+fn patch_component_instances() {
+    let input = "component Counter(label) {\n    state count = 0;\n    <li>{label}: {count} <button onclick={fn() { count = count + 1 }}>+</button></li>\n}\nstate flipped = false;\nfn flip() { flipped = !flipped }\ncomponent App() {\n    let a = <Counter key='a' label='a' />;\n    let b = <Counter key='b' label='b' />;\n    <ul>{if flipped { [b, a] } else { [a, b] }}</ul>\n}";
+    let (program, resolution) = component_tree(input);
+    let mut interpreter = Interpreter::new(&resolution);
+    interpreter.run(&program).unwrap();
+    let mut tree = Tree::new(root(interpreter.global("App").unwrap()));
+    tree.render(&mut interpreter).unwrap();
+
+    let pos = Position::new(0, 0);
+    let click = tree.handler(&[0, 0, 4], "onclick").unwrap().clone();
+    interpreter.call(&click, vec![], pos).unwrap();
+    assert_eq!(
+        tree.render(&mut interpreter).unwrap(),
+        vec![Patch::ReplaceText {
+            path: vec![0, 0, 2],
+            text: "1".to_string()
+        }]
+    );
+
+    // Keyed instances keep their state as they move
+    let flip = interpreter.global("flip").unwrap();
+    interpreter.call(&flip, vec![], pos).unwrap();
+    assert_eq!(
+        tree.render(&mut interpreter).unwrap(),
+        vec![Patch::Move {
+            parent: vec![0],
+            from: 1,
+            to: 0
+        }]
+    );
+    let VNode::Element(list) = &tree.nodes()[0] else {
+        panic!("not a list");
+    };
+    assert_eq!(list.children[1], keyed_counter("a", 1));
+}
+
+fn keyed_counter(label: &str, count: u32) -> VNode {
+    let mut button = element("button", None, vec![text("+")]);
+    if let VNode::Element(button) = &mut button {
+        button.handlers.insert("onclick".to_string(), Value::Unit);
+    }
+    let children = vec![
+        text(label),
+        text(": "),
+        text(&count.to_string()),
+        text(" "),
+        button,
+    ];
+    element("li", Some(label), children)
+}
+
+#[test]
+fn patch_reactive_state() {
+    let input = "state count = 0;\nlet doubled = count * 2;\nfn inc() { count = count + 1 }\ncomponent App() {\n    <div class='app'>\n        <button onclick={inc}>Add</button>\n        <p>{count} doubled is {doubled}</p>\n    </div>\n}";
+    let (program, resolution) = component_tree(input);
+    let mut runtime = run_reactive(&program, Interpreter::new(&resolution)).unwrap();
+    let app = runtime.evaluator().interpreter().global("App").unwrap();
+    let mut tree = Tree::new(root(app));
+    let patches = runtime
+        .with_evaluator(|evaluator| tree.render(evaluator.interpreter_mut()))
+        .unwrap()
+        .unwrap();
+    assert!(matches!(patches[..], [Patch::Create { .. }]));
+
+    let inc = tree.handler(&[0, 0], "onclick").unwrap().clone();
+    runtime
+        .with_evaluator(|evaluator| evaluator.call(&inc, vec![], Position::new(0, 0)))
+        .unwrap()
+        .unwrap();
+    let patches = runtime
+        .with_evaluator(|evaluator| tree.render(evaluator.interpreter_mut()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        patches,
+        vec![
+            Patch::ReplaceText {
+                path: vec![0, 1, 0],
+                text: "1".to_string()
+            },
+            Patch::ReplaceText {
+                path: vec![0, 1, 2],
+                text: "2".to_string()
+            },
+        ]
+    );
+}
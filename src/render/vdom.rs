@@ -0,0 +1,298 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::interp::{
+    ElementTag, ElementValue, Instance, Interpreter, RuntimeError, RuntimeErrorKind, Value,
+};
+use crate::lexer::Position;
+use crate::reactive::graph::quote;
+
+use super::diff::{diff, Patch};
+use super::{RenderError, Result, MAX_COMPONENT_DEPTH};
+
+/// A node of a rendered tree, as a host displays it. Components, fragments
+/// and lists are gone, leaving their elements and text in place.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VNode {
+    Element(VElement),
+    Text(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct VElement {
+    pub tag: String,
+    // From the `key` attribute, which tells apart children of the same
+    // parent when they are reordered
+    pub key: Option<String>,
+    // With the values they would have in HTML, `true` being empty
+    pub attributes: BTreeMap<String, String>,
+    // Functions given as attributes, which the host calls on events
+    pub handlers: BTreeMap<String, Value>,
+    pub children: Vec<VNode>,
+}
+
+/// Handlers are compared by name, as every render creates new closures.
+impl PartialEq for VElement {
+    fn eq(&self, other: &VElement) -> bool {
+        self.tag == other.tag
+            && self.key == other.key
+            && self.attributes == other.attributes
+            && self.handlers.keys().eq(other.handlers.keys())
+            && self.children == other.children
+    }
+}
+
+impl VNode {
+    /// The node as JSON, with the names of its handlers as `events`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let element = match self {
+            VNode::Text(text) => {
+                write!(out, "{{\"text\": {}}}", quote(text)).unwrap();
+                return;
+            }
+            VNode::Element(element) => element,
+        };
+        let key = element.key.as_deref().map_or("null".to_string(), quote);
+        write!(
+            out,
+            "{{\"tag\": {}, \"key\": {}, \"attributes\": {{",
+            quote(&element.tag),
+            key
+        )
+        .unwrap();
+        for (i, (name, value)) in element.attributes.iter().enumerate() {
+            let separator = if i > 0 { ", " } else { "" };
+            write!(out, "{}{}: {}", separator, quote(name), quote(value)).unwrap();
+        }
+        let events: Vec<String> = element.handlers.keys().map(|name| quote(name)).collect();
+        write!(
+            out,
+            "}}, \"events\": [{}], \"children\": [",
+            events.join(", ")
+        )
+        .unwrap();
+        for (i, child) in element.children.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            child.write_json(out);
+        }
+        out.push_str("]}");
+    }
+}
+
+// Where a component is among the values of a render, which identifies its
+// instance from one render to the next. A key stands in for the position, so
+// that a keyed component keeps its state when it moves.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Slot {
+    Index(usize),
+    Key(String),
+}
+
+/// The rendered tree of a root value. Each render calls the components again,
+/// keeping the `state` of their instances, and compares the result with the
+/// tree of the previous render.
+pub struct Tree {
+    root: Value,
+    pos: Position,
+    nodes: Vec<VNode>,
+    instances: HashMap<Vec<Slot>, Instance>,
+}
+
+impl Tree {
+    pub fn new(root: Value) -> Self {
+        Self {
+            root,
+            pos: Position::new(0, 0),
+            nodes: vec![],
+            instances: HashMap::new(),
+        }
+    }
+
+    /// Reports the errors of calling components, like missing props, at
+    /// `pos`. Errors inside a component have spans of their own.
+    pub fn at(mut self, pos: Position) -> Self {
+        self.pos = pos;
+        self
+    }
+
+    /// The top-level nodes of the last render.
+    pub fn nodes(&self) -> &[VNode] {
+        &self.nodes
+    }
+
+    /// Renders the root again, returning the patches that turn the previous
+    /// render into this one. The first render creates every node. Instances
+    /// of components that are no longer rendered are dropped.
+    pub fn render(&mut self, interpreter: &mut Interpreter) -> Result<Vec<Patch>> {
+        let mut builder = Builder {
+            interpreter,
+            pos: self.pos,
+            old: &mut self.instances,
+            new: HashMap::new(),
+            path: vec![],
+            depth: 0,
+        };
+        let mut nodes = vec![];
+        builder.build(&self.root, &mut nodes)?;
+        self.instances = builder.new;
+        let patches = diff(&self.nodes, &nodes);
+        self.nodes = nodes;
+        Ok(patches)
+    }
+
+    /// The handler of `event`, like `onclick`, on the element at `path`, a
+    /// list of child indices from the top-level nodes.
+    pub fn handler(&self, path: &[usize], event: &str) -> Option<&Value> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.nodes.get(*first)?;
+        for index in rest {
+            match node {
+                VNode::Element(element) => node = element.children.get(*index)?,
+                VNode::Text(_) => return None,
+            }
+        }
+        match node {
+            VNode::Element(element) => element.handlers.get(event),
+            VNode::Text(_) => None,
+        }
+    }
+}
+
+struct Builder<'t, 'r, 'a> {
+    interpreter: &'r mut Interpreter<'a>,
+    pos: Position,
+    // Instances of the previous render, and those rendered so far
+    old: &'t mut HashMap<Vec<Slot>, Instance>,
+    new: HashMap<Vec<Slot>, Instance>,
+    // The slot of the value being built
+    path: Vec<Slot>,
+    depth: usize,
+}
+
+impl Builder<'_, '_, '_> {
+    fn build(&mut self, value: &Value, out: &mut Vec<VNode>) -> Result<()> {
+        match value {
+            Value::String(text) => out.push(VNode::Text(text.to_string())),
+            Value::Number(_) | Value::Bool(_) => out.push(VNode::Text(value.to_string())),
+            Value::List(items) => self.children(items, out)?,
+            Value::Element(element) => match &element.tag {
+                ElementTag::Html(tag) => {
+                    let element = self.element(tag, element)?;
+                    out.push(VNode::Element(element));
+                }
+                ElementTag::Component(component) => self.component(component, element, out)?,
+                ElementTag::Fragment => self.children(&element.children, out)?,
+            },
+            Value::Unit => {}
+            Value::Record(_) | Value::Variant(_) | Value::Function(_) => {
+                return Err(RenderError::NotRenderable(value.type_name().to_string()))
+            }
+        }
+        Ok(())
+    }
+
+    fn children(&mut self, children: &[Value], out: &mut Vec<VNode>) -> Result<()> {
+        for (i, child) in children.iter().enumerate() {
+            let slot = match child {
+                Value::Element(element) => key(element).map(Slot::Key),
+                _ => None,
+            };
+            self.path.push(slot.unwrap_or(Slot::Index(i)));
+            let result = self.build(child, out);
+            self.path.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    fn element(&mut self, tag: &str, element: &ElementValue) -> Result<VElement> {
+        let mut attributes = BTreeMap::new();
+        let mut handlers = BTreeMap::new();
+        for (name, value) in element.attributes.iter() {
+            match value {
+                _ if name == "key" => {}
+                Value::Bool(true) => {
+                    attributes.insert(name.clone(), String::new());
+                }
+                Value::Bool(false) | Value::Unit => {}
+                Value::String(text) => {
+                    attributes.insert(name.clone(), text.to_string());
+                }
+                Value::Number(number) => {
+                    attributes.insert(name.clone(), number.to_string());
+                }
+                Value::Function(_) => {
+                    handlers.insert(name.clone(), value.clone());
+                }
+                value => return Err(RenderError::NotRenderable(value.type_name().to_string())),
+            }
+        }
+        let mut children = vec![];
+        self.children(&element.children, &mut children)?;
+        Ok(VElement {
+            tag: tag.to_string(),
+            key: key(element),
+            attributes,
+            handlers,
+            children,
+        })
+    }
+
+    fn component(
+        &mut self,
+        component: &Value,
+        element: &ElementValue,
+        out: &mut Vec<VNode>,
+    ) -> Result<()> {
+        if self.depth == MAX_COMPONENT_DEPTH {
+            return Err(RenderError::Runtime(RuntimeError::new(
+                RuntimeErrorKind::StackOverflow(MAX_COMPONENT_DEPTH),
+                self.pos,
+            )));
+        }
+        let mut instance = self.old.remove(&self.path).unwrap_or_default();
+        let rendered =
+            self.interpreter
+                .render(component, element.props(), &mut instance, self.pos)?;
+        self.new.insert(self.path.clone(), instance);
+
+        // What a component renders is below its own slot
+        self.path.push(Slot::Index(0));
+        self.depth += 1;
+        let mut nodes = vec![];
+        let result = self.build(&rendered, &mut nodes);
+        self.depth -= 1;
+        self.path.pop();
+        result?;
+
+        // The key of a component goes to the element it renders, so that
+        // the diff can follow it too
+        if let [VNode::Element(rendered)] = nodes.as_mut_slice() {
+            if rendered.key.is_none() {
+                rendered.key = key(element);
+            }
+        }
+        out.append(&mut nodes);
+        Ok(())
+    }
+}
+
+fn key(element: &ElementValue) -> Option<String> {
+    element
+        .attributes
+        .iter()
+        .find(|(name, _)| name == "key")
+        .and_then(|(_, value)| match value {
+            Value::String(text) => Some(text.to_string()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        })
+}
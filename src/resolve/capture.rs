@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// The bindings each function or lambda refers to from an enclosing
+/// function, which a closure has to carry along with it. Bindings of the
+/// program scope are globals and never captured.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Captures {
+    // Function statement or lambda span to its captures, in order of first
+    // use. A function also captures what the functions nested in it capture
+    // from further out.
+    pub captures: HashMap<Position, Vec<BindingId>>,
+}
+
+impl Captures {
+    /// What the function statement or lambda at `pos` captures.
+    pub fn of(&self, pos: &Position) -> &[BindingId] {
+        self.captures.get(pos).map_or(&[], Vec::as_slice)
+    }
+}
+
+pub fn find_captures(resolution: &Resolution) -> Captures {
+    let mut captures = Captures::default();
+    for scope in resolution.scopes.iter() {
+        if scope.kind == ScopeKind::Function {
+            captures.captures.insert(scope.pos, vec![]);
+        }
+    }
+
+    let mut uses: Vec<(&Position, &BindingId)> = resolution.uses.iter().collect();
+    uses.sort();
+    for (pos, id) in uses {
+        let binding = resolution.binding(*id);
+        if resolution.scope(binding.scope).kind == ScopeKind::Program {
+            continue;
+        }
+
+        // Innermost first, until the function the binding belongs to
+        let mut functions: Vec<&Scope> = resolution
+            .scopes
+            .iter()
+            .filter(|scope| scope.kind == ScopeKind::Function && scope.pos.contains(pos))
+            .collect();
+        functions.sort_by_key(|scope| scope.pos.len());
+        for function in functions {
+            if encloses(resolution, function.id, binding.scope) {
+                break;
+            }
+            let captured = captures.captures.entry(function.pos).or_default();
+            if !captured.contains(id) {
+                captured.push(*id);
+            }
+        }
+    }
+    captures
+}
+
+// Whether `inner` is `outer` or nested in it
+fn encloses(resolution: &Resolution, outer: ScopeId, inner: ScopeId) -> bool {
+    let mut scope = Some(inner);
+    while let Some(id) = scope {
+        if id == outer {
+            return true;
+        }
+        scope = resolution.scope(id).parent;
+    }
+    false
+}
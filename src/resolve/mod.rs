@@ -0,0 +1,472 @@
+pub mod capture;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+
+pub use self::capture::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BindingId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingKind {
+    Let,
+    State,
+    Fn,
+    Component,
+    Param,
+    // A constructor of a `type`
+    Variant,
+    // Bound by a `match` pattern
+    Pattern,
+    // A name brought in by an `import`, linked to its module by the loader
+    Import,
+    // A name the embedding application defines, with no span in the source
+    Host,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Binding {
+    pub id: BindingId,
+    pub name: String,
+    pub kind: BindingKind,
+    // The declaring statement, or the parameter or imported name. Empty for
+    // host bindings.
+    pub pos: Position,
+    pub scope: ScopeId,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeKind {
+    Program,
+    Function,
+    Block,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scope {
+    pub id: ScopeId,
+    pub parent: Option<ScopeId>,
+    pub kind: ScopeKind,
+    pub pos: Position,
+    pub bindings: Vec<BindingId>,
+}
+
+/// What the resolver learned about a program. Identifier uses and
+/// declarations are keyed by their spans, which are unique within a source.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resolution {
+    pub bindings: Vec<Binding>,
+    pub scopes: Vec<Scope>,
+    // Identifier expression span to the binding it refers to
+    pub uses: HashMap<Position, BindingId>,
+    // Declaring statement or parameter span to the binding it introduces
+    pub definitions: HashMap<Position, BindingId>,
+    // Spans of the identifiers that are assigned to, which are also in `uses`
+    pub assigned: HashSet<Position>,
+    // `state` bindings declared in the body of a component, which every
+    // rendered instance of the component stores separately
+    pub instance_state: HashSet<BindingId>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolution {
+    pub fn binding(&self, id: BindingId) -> &Binding {
+        &self.bindings[id.0 as usize]
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0 as usize]
+    }
+
+    /// The binding an identifier expression at `pos` refers to.
+    pub fn resolve(&self, pos: &Position) -> Option<&Binding> {
+        self.uses.get(pos).map(|id| self.binding(*id))
+    }
+
+    /// The binding declared by the statement or parameter at `pos`.
+    pub fn definition(&self, pos: &Position) -> Option<&Binding> {
+        self.definitions.get(pos).map(|id| self.binding(*id))
+    }
+
+    /// Spans of every identifier that refers to `id`, in source order.
+    pub fn references(&self, id: BindingId) -> Vec<Position> {
+        let mut references: Vec<Position> = self
+            .uses
+            .iter()
+            .filter(|(_, binding)| **binding == id)
+            .map(|(pos, _)| *pos)
+            .collect();
+        references.sort();
+        references
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+pub fn resolve_program(program: &Program) -> Resolution {
+    resolve_program_with(program, &[])
+}
+
+/// Resolves a program in which the `host` names are defined before its first
+/// statement, as bindings of the program scope.
+pub fn resolve_program_with(program: &Program, host: &[&str]) -> Resolution {
+    let mut resolver = Resolver::default();
+    let end = program.stmts.last().map_or(0, |stmt| stmt.pos.end);
+    resolver.enter(ScopeKind::Program, Position::new(0, end));
+    for name in host {
+        resolver.declare_host(name);
+    }
+    resolver.stmts(&program.stmts);
+    resolver.exit();
+    resolver.resolution
+}
+
+#[derive(Default)]
+struct Resolver {
+    resolution: Resolution,
+    // Innermost last
+    stack: Vec<ScopeId>,
+    // Per scope, the names visible so far
+    names: Vec<HashMap<String, BindingId>>,
+    // Per scope, names declared by statements not reached yet
+    pending: Vec<HashMap<String, Position>>,
+    // Function scopes that are the body of a component
+    components: HashSet<ScopeId>,
+}
+
+impl Resolver {
+    fn enter(&mut self, kind: ScopeKind, pos: Position) {
+        let id = ScopeId(self.resolution.scopes.len() as u32);
+        self.resolution.scopes.push(Scope {
+            id,
+            parent: self.stack.last().copied(),
+            kind,
+            pos,
+            bindings: vec![],
+        });
+        self.names.push(HashMap::new());
+        self.pending.push(HashMap::new());
+        self.stack.push(id);
+    }
+
+    fn exit(&mut self) {
+        self.stack.pop();
+    }
+
+    fn current(&self) -> ScopeId {
+        *self.stack.last().unwrap()
+    }
+
+    fn lookup(&self, name: &str) -> Option<BindingId> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|scope| self.names[scope.0 as usize].get(name).copied())
+    }
+
+    fn declare(&mut self, name: &str, kind: BindingKind, pos: Position) {
+        if let Some(shadowed) = self.lookup(name) {
+            let shadowed = self.resolution.binding(shadowed);
+            let diagnostic =
+                Diagnostic::warning(format!("`{}` shadows an earlier binding", name), pos);
+            self.resolution.diagnostics.push(match shadowed.kind {
+                BindingKind::Host => diagnostic,
+                _ => diagnostic.with_label("earlier binding", shadowed.pos),
+            });
+        }
+
+        let scope = self.current();
+        let id = BindingId(self.resolution.bindings.len() as u32);
+        self.resolution.bindings.push(Binding {
+            id,
+            name: name.to_string(),
+            kind,
+            pos,
+            scope,
+        });
+        self.resolution.scopes[scope.0 as usize].bindings.push(id);
+        self.resolution.definitions.insert(pos, id);
+        self.names[scope.0 as usize].insert(name.to_string(), id);
+        self.pending[scope.0 as usize].remove(name);
+    }
+
+    // Host bindings are not in `definitions`, as they have no span of their own
+    fn declare_host(&mut self, name: &str) {
+        let scope = self.current();
+        let id = BindingId(self.resolution.bindings.len() as u32);
+        self.resolution.bindings.push(Binding {
+            id,
+            name: name.to_string(),
+            kind: BindingKind::Host,
+            pos: Position::new(0, 0),
+            scope,
+        });
+        self.resolution.scopes[scope.0 as usize].bindings.push(id);
+        self.names[scope.0 as usize].insert(name.to_string(), id);
+    }
+
+    fn use_identifier(&mut self, name: &str, pos: Position) {
+        if let Some(id) = self.lookup(name) {
+            self.resolution.uses.insert(pos, id);
+            return;
+        }
+
+        let later = self
+            .stack
+            .iter()
+            .rev()
+            .find_map(|scope| self.pending[scope.0 as usize].get(name).copied());
+        let diagnostic = match later {
+            Some(definition) => {
+                Diagnostic::error(format!("`{}` is used before its definition", name), pos)
+                    .with_label("defined here", definition)
+            }
+            None => Diagnostic::error(format!("undefined variable `{}`", name), pos),
+        };
+        self.resolution.diagnostics.push(diagnostic);
+    }
+
+    // Functions, variants and imports are visible in their whole scope so that
+    // they can refer to each other, while `let` and `state` bindings start after their statement.
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        let scope = self.current().0 as usize;
+        for stmt in stmts.iter() {
+            let stmt = stmt.declaration();
+            match &stmt.kind {
+                StmtKind::Let(LetStmt { identifier, .. })
+                | StmtKind::State(StateStmt { identifier, .. }) => {
+                    self.pending[scope]
+                        .entry(identifier.clone())
+                        .or_insert(stmt.pos);
+                }
+                StmtKind::Fn(fn_stmt) => self.declare(&fn_stmt.name, BindingKind::Fn, stmt.pos),
+                StmtKind::Component(component) => {
+                    self.declare(&component.name, BindingKind::Component, stmt.pos)
+                }
+                StmtKind::Type(type_stmt) => {
+                    for variant in type_stmt.variants.iter() {
+                        self.declare(&variant.name, BindingKind::Variant, variant.pos);
+                    }
+                }
+                StmtKind::Import(import_stmt) => {
+                    for name in import_stmt.names.iter() {
+                        self.declare(&name.name, BindingKind::Import, name.pos);
+                    }
+                }
+                StmtKind::Export(_) | StmtKind::Expr(_) => {}
+            }
+        }
+
+        for stmt in stmts.iter() {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                self.expr(&let_stmt.value);
+                self.declare(&let_stmt.identifier, BindingKind::Let, stmt.pos);
+            }
+            StmtKind::State(state_stmt) => {
+                self.expr(&state_stmt.value);
+                self.declare(&state_stmt.identifier, BindingKind::State, stmt.pos);
+                if self.in_component() {
+                    let id = self.resolution.definitions[&stmt.pos];
+                    self.resolution.instance_state.insert(id);
+                }
+            }
+            StmtKind::Fn(fn_stmt) => self.function(stmt.pos, &fn_stmt.params, &fn_stmt.body),
+            StmtKind::Component(component) => {
+                self.components
+                    .insert(ScopeId(self.resolution.scopes.len() as u32));
+                self.function(stmt.pos, &component.props, &component.body);
+            }
+            StmtKind::Type(_) => {}
+            StmtKind::Import(_) => {
+                if !self.at_top_level() {
+                    self.resolution.diagnostics.push(Diagnostic::error(
+                        "imports are only allowed at the top level",
+                        stmt.pos,
+                    ));
+                }
+            }
+            StmtKind::Export(exported) => {
+                if !self.at_top_level() {
+                    self.resolution.diagnostics.push(Diagnostic::error(
+                        "only top-level declarations can be exported",
+                        stmt.pos,
+                    ));
+                }
+                self.stmt(exported);
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    fn at_top_level(&self) -> bool {
+        self.resolution.scope(self.current()).kind == ScopeKind::Program
+    }
+
+    // Whether the innermost function is a component, so that its `state` is
+    // per instance. A lambda in the component body has its own frame and its
+    // `state` is created on each call.
+    fn in_component(&self) -> bool {
+        self.stack
+            .iter()
+            .rev()
+            .find(|scope| self.resolution.scope(**scope).kind == ScopeKind::Function)
+            .is_some_and(|scope| self.components.contains(scope))
+    }
+
+    fn function(&mut self, pos: Position, params: &[Param], body: &Expr) {
+        self.enter(ScopeKind::Function, pos);
+        for param in params.iter() {
+            self.declare(&param.name, BindingKind::Param, param.pos);
+        }
+        self.expr(body);
+        self.exit();
+    }
+
+    // A name in a pattern refers to a variant if there is one in scope, and
+    // binds a new variable otherwise
+    fn pattern(&mut self, pattern: &Pattern, bound: &mut HashMap<String, Position>) {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Literal(_) => {}
+            PatternKind::Identifier(name) => match self.variant(name) {
+                Some(id) => {
+                    self.resolution.uses.insert(pattern.pos, id);
+                }
+                None => {
+                    if let Some(first) = bound.insert(name.clone(), pattern.pos) {
+                        self.resolution.diagnostics.push(
+                            Diagnostic::error(
+                                format!("`{}` is bound more than once in this pattern", name),
+                                pattern.pos,
+                            )
+                            .with_label("first bound here", first),
+                        );
+                        return;
+                    }
+                    self.declare(name, BindingKind::Pattern, pattern.pos);
+                }
+            },
+            PatternKind::Variant(name, patterns) => {
+                match self.variant(name) {
+                    Some(id) => {
+                        self.resolution.uses.insert(pattern.pos, id);
+                    }
+                    None => self.resolution.diagnostics.push(Diagnostic::error(
+                        format!("undefined variant `{}`", name),
+                        pattern.pos,
+                    )),
+                }
+                for pattern in patterns.iter() {
+                    self.pattern(pattern, bound);
+                }
+            }
+        }
+    }
+
+    fn variant(&self, name: &str) -> Option<BindingId> {
+        self.lookup(name)
+            .filter(|id| self.resolution.binding(*id).kind == BindingKind::Variant)
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier(identifier) => self.use_identifier(&identifier.ident, expr.pos),
+            ExprKind::Literal(_) => {}
+            ExprKind::Call(call) => {
+                self.expr(&call.called);
+                call.args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Binary(binary) => {
+                self.expr(&binary.lhs);
+                self.expr(&binary.rhs);
+            }
+            ExprKind::Unary(unary) => self.expr(&unary.operand),
+            ExprKind::If(if_expr) => {
+                self.expr(&if_expr.condition);
+                self.expr(&if_expr.then);
+                if let Some(otherwise) = &if_expr.otherwise {
+                    self.expr(otherwise);
+                }
+            }
+            ExprKind::Block(stmts) => {
+                self.enter(ScopeKind::Block, expr.pos);
+                self.stmts(stmts);
+                self.exit();
+            }
+            ExprKind::List(items) => items.iter().for_each(|item| self.expr(&item.value)),
+            ExprKind::Index(index) => {
+                self.expr(&index.indexed);
+                self.expr(&index.index);
+            }
+            ExprKind::Record(record) => {
+                if let Some(base) = &record.base {
+                    self.expr(base);
+                }
+                record
+                    .fields
+                    .iter()
+                    .for_each(|field| self.expr(&field.value));
+            }
+            ExprKind::Field(field) => self.expr(&field.record),
+            ExprKind::Lambda(lambda) => self.function(expr.pos, &lambda.params, &lambda.body),
+            ExprKind::Match(match_expr) => {
+                self.expr(&match_expr.scrutinee);
+                for arm in match_expr.arms.iter() {
+                    self.enter(ScopeKind::Block, arm.pos);
+                    self.pattern(&arm.pattern, &mut HashMap::new());
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                    self.exit();
+                }
+            }
+            ExprKind::Assign(assign) => {
+                self.expr(&assign.target);
+                self.expr(&assign.value);
+                self.resolution.assigned.insert(assign.target.pos);
+                let Some(binding) = self.resolution.resolve(&assign.target.pos) else {
+                    return;
+                };
+                if binding.kind != BindingKind::State {
+                    let diagnostic = Diagnostic::error(
+                        "only `state` bindings can be assigned",
+                        assign.target.pos,
+                    );
+                    let diagnostic = match binding.kind {
+                        BindingKind::Host => diagnostic,
+                        _ => diagnostic.with_label("declared here", binding.pos),
+                    };
+                    self.resolution.diagnostics.push(diagnostic);
+                }
+            }
+            ExprKind::Element(element) => {
+                if let Some(tag) = element.tag.as_ref().filter(|tag| tag.is_component()) {
+                    self.use_identifier(&tag.name, tag.pos);
+                }
+                for attribute in element.attributes.iter() {
+                    attribute.value.iter().for_each(|value| self.expr(value));
+                }
+                for child in element.children.iter() {
+                    child.expr().iter().for_each(|value| self.expr(value));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use super::*;
+use crate::diagnostic::Severity;
+
+fn resolve(input: &str) -> Resolution {
+    resolve_program(&Parser::new(input).parse_program().unwrap())
+}
+
+// The span of the `n`th occurrence of `name` in `input`
+fn span_of(input: &str, name: &str, n: usize) -> Position {
+    let (start, _) = input.match_indices(name).nth(n).unwrap();
+    let start = input[..start].chars().count() as u32;
+    Position::new(start, start + name.chars().count() as u32)
+}
+
+#[test]
+fn resolve_let_uses() {
+    let input = "let a = 1;\nlet b = f(a);\nfn f(x) { x }";
+    let resolution = resolve(input);
+    assert_eq!(resolution.diagnostics, vec![]);
+
+    let a = resolution.resolve(&span_of(input, "a", 1)).unwrap();
+    assert_eq!(a.kind, BindingKind::Let);
+    assert_eq!(a.pos, Position::new(0, 10));
+
+    let x = resolution.resolve(&span_of(input, "x", 1)).unwrap();
+    assert_eq!(x.kind, BindingKind::Param);
+    assert_eq!(x.pos, span_of(input, "x", 0));
+    assert_eq!(resolution.scope(x.scope).kind, ScopeKind::Function);
+
+    // Functions are visible before their declaration
+    let f = resolution.resolve(&span_of(input, "f", 0)).unwrap();
+    assert_eq!(f.kind, BindingKind::Fn);
+    assert_eq!(resolution.references(f.id), vec![span_of(input, "f", 0)]);
+}
+
+#[test]
+fn resolve_unique_ids() {
+    let resolution = resolve("let a = 1; { let a = a; a }; a");
+    let ids: Vec<BindingId> = resolution.bindings.iter().map(|b| b.id).collect();
+    assert_eq!(ids, vec![BindingId(0), BindingId(1)]);
+
+    // The inner `a` refers to the outer one while it is being defined
+    assert_eq!(
+        resolution.uses.get(&Position::new(21, 22)),
+        Some(&BindingId(0))
+    );
+    assert_eq!(
+        resolution.uses.get(&Position::new(24, 25)),
+        Some(&BindingId(1))
+    );
+    assert_eq!(
+        resolution.uses.get(&Position::new(29, 30)),
+        Some(&BindingId(0))
+    );
+}
+
+#[test]
+fn resolve_recursion() {
+    let resolution = resolve("fn even(n) { odd(n) }\nfn odd(n) { even(n) }");
+    assert!(!resolution.has_errors());
+}
+
+#[test]
+fn report_undefined_variable() {
+    let input = "let a = { let b = 1; b };\nb";
+    let resolution = resolve(input);
+    assert_eq!(
+        resolution.diagnostics,
+        vec![Diagnostic::error(
+            "undefined variable `b`",
+            span_of(input, "b", 2)
+        )]
+    );
+    assert!(resolution.has_errors());
+}
+
+#[test]
+fn report_use_before_definition() {
+    let input = "fn f(x) { x }\nf(count);\nstate count = 0;";
+    let resolution = resolve(input);
+    assert_eq!(
+        resolution.diagnostics,
+        vec![Diagnostic::error(
+            "`count` is used before its definition",
+            span_of(input, "count", 0)
+        )
+        .with_label("defined here", Position::new(24, 40))],
+    );
+}
+
+#[test]
+fn report_shadowing() {
+    let input = "let x = 1;\nfn f(x) { let x = x; x }";
+    let resolution = resolve(input);
+    let warnings: Vec<&Diagnostic> = resolution
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+        .collect();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].pos, span_of(input, "x", 1));
+    assert_eq!(warnings[0].labels[0].pos, Position::new(0, 10));
+    assert_eq!(warnings[1].pos, Position::new(21, 31));
+    assert_eq!(warnings[1].labels[0].pos, span_of(input, "x", 1));
+    assert!(!resolution.has_errors());
+}
+
+#[test]
+fn find_lambda_captures() {
+    let input = "let g = 1;\nfn outer(a, b) {\n    let c = 2;\n    let f = fn(x) => fn(y) => x + a + c + y + g;\n    b\n}";
+    let resolution = resolve(input);
+    assert_eq!(resolution.diagnostics, vec![]);
+    let captures = find_captures(&resolution);
+
+    let id = |name: &str| {
+        resolution
+            .bindings
+            .iter()
+            .find(|binding| binding.name == name)
+            .unwrap()
+            .id
+    };
+    let start = input.find("fn(x)").unwrap() as u32;
+    let outer_lambda = Position::new(start, input.find(";\n    b").unwrap() as u32);
+    let inner_lambda = Position::new(input.find("fn(y)").unwrap() as u32, outer_lambda.end);
+
+    // `x` is captured from the outer lambda, which captures what the inner
+    // one needs from further out. Globals are never captured.
+    assert_eq!(captures.of(&inner_lambda), &[id("x"), id("a"), id("c")]);
+    assert_eq!(captures.of(&outer_lambda), &[id("a"), id("c")]);
+    assert_eq!(captures.of(&Position::new(11, input.len() as u32)), &[]);
+}
+
+#[test]
+fn find_nested_fn_captures() {
+    let input = "fn counter(start) {\n    fn next(n) { n + start }\n    fn again() { next(1) }\n    again\n}";
+    let resolution = resolve(input);
+    let captures = find_captures(&resolution);
+    let binding = |name: &str| {
+        resolution
+            .bindings
+            .iter()
+            .find(|binding| binding.name == name)
+            .unwrap()
+    };
+
+    assert_eq!(captures.of(&binding("next").pos), &[binding("start").id]);
+    // Local functions are captured like any other binding
+    assert_eq!(captures.of(&binding("again").pos), &[binding("next").id]);
+    assert_eq!(captures.of(&binding("counter").pos), &[]);
+}
+
+#[test]
+fn resolve_variants_and_patterns() {
+    let input = "let a = Some(1);\ntype Option = Some(Number) | None;\nmatch a { Some(x) => x, None => 0, other => 1 }";
+    let resolution = resolve(input);
+    assert_eq!(resolution.diagnostics, vec![]);
+
+    // Variants are visible before their declaration
+    let some = resolution.resolve(&span_of(input, "Some", 0)).unwrap();
+    assert_eq!(some.kind, BindingKind::Variant);
+    assert_eq!(some.pos, span_of(input, "Some(Number)", 0));
+
+    let x = resolution.resolve(&span_of(input, "x", 1)).unwrap();
+    assert_eq!(x.kind, BindingKind::Pattern);
+    assert_eq!(resolution.scope(x.scope).kind, ScopeKind::Block);
+
+    // A name that is a variant matches it instead of binding
+    let none = resolution.resolve(&span_of(input, "None", 1)).unwrap();
+    assert_eq!(none.kind, BindingKind::Variant);
+    let other = resolution.definition(&span_of(input, "other", 0)).unwrap();
+    assert_eq!(other.kind, BindingKind::Pattern);
+}
+
+#[test]
+fn resolve_pattern_errors() {
+    let input = "fn f() { 1 }\nmatch 1 { Pair(x, x) => x, f(y) => y }";
+    let resolution = resolve(input);
+    assert_eq!(
+        resolution.diagnostics,
+        vec![
+            Diagnostic::error("undefined variant `Pair`", span_of(input, "Pair(x, x)", 0)),
+            Diagnostic::error(
+                "`x` is bound more than once in this pattern",
+                span_of(input, "x", 1)
+            )
+            .with_label("first bound here", span_of(input, "x", 0)),
+            Diagnostic::error("undefined variant `f`", span_of(input, "f(y)", 0)),
+        ]
+    );
+}
+
+#[test]
+fn resolve_imports_and_exports() {
+    let input = "f(Button);\nimport { Button } from './button';\nexport fn f(x) { x }";
+    let resolution = resolve(input);
+    assert_eq!(resolution.diagnostics, vec![]);
+    let button = resolution.resolve(&span_of(input, "Button", 0)).unwrap();
+    assert_eq!(button.kind, BindingKind::Import);
+    assert_eq!(button.pos, span_of(input, "Button", 1));
+    let f = resolution.resolve(&span_of(input, "f", 0)).unwrap();
+    assert_eq!(f.kind, BindingKind::Fn);
+
+    let input = "fn g() {\n    import { a } from './a';\n    export let b = a;\n}";
+    let resolution = resolve(input);
+    assert_eq!(
+        resolution.diagnostics,
+        vec![
+            Diagnostic::error(
+                "imports are only allowed at the top level",
+                span_of(input, "import { a } from './a';", 0)
+            ),
+            Diagnostic::error(
+                "only top-level declarations can be exported",
+                span_of(input, "export let b = a;", 0)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn resolve_host_names() {
+    let input = "print(1);\nfn f() { let print = 2; print }";
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, &["print"]);
+    let print = resolution.resolve(&span_of(input, "print", 0)).unwrap();
+    assert_eq!(print.kind, BindingKind::Host);
+    assert_eq!(resolution.scope(print.scope).kind, ScopeKind::Program);
+    // Shadowing a host name has no earlier span to point at
+    assert_eq!(
+        resolution.diagnostics,
+        vec![Diagnostic::warning(
+            "`print` shadows an earlier binding",
+            span_of(input, "let print = 2;", 0)
+        )]
+    );
+}
+
+#[test]
+fn report_assigning_non_state() {
+    let input = "state a = 1;\nlet b = 2;\nfn f(c) { a = b; b = a; c = 3 }";
+    let resolution = resolve(input);
+    assert_eq!(
+        resolution.diagnostics,
+        vec![
+            Diagnostic::error(
+                "only `state` bindings can be assigned",
+                span_of(input, "b", 2)
+            )
+            .with_label("declared here", Position::new(13, 23)),
+            Diagnostic::error(
+                "only `state` bindings can be assigned",
+                span_of(input, "c", 1)
+            )
+            .with_label("declared here", span_of(input, "c", 0)),
+        ]
+    );
+}
+
+#[test]
+fn resolve_component_state() {
+    let input = "component Counter(start) {\n    state count = start;\n    let click = fn() {\n        state clicks = 0;\n        count = count + 1;\n    };\n    <button onclick={click}>{count}</button>\n}\nstate total = 0;\nlet app = <Counter start={total} />;";
+    let resolution = resolve(input);
+    assert_eq!(resolution.diagnostics, vec![]);
+
+    let counter = resolution.resolve(&span_of(input, "Counter", 1)).unwrap();
+    assert_eq!(counter.kind, BindingKind::Component);
+    let start = resolution.resolve(&span_of(input, "start", 1)).unwrap();
+    assert_eq!(start.kind, BindingKind::Param);
+
+    // Only the `state` of the component body belongs to its instances
+    let state = |name: &str| {
+        let binding = resolution.bindings.iter().find(|b| b.name == name);
+        binding.unwrap().id
+    };
+    assert!(resolution.instance_state.contains(&state("count")));
+    assert!(!resolution.instance_state.contains(&state("clicks")));
+    assert!(!resolution.instance_state.contains(&state("total")));
+}
@@ -0,0 +1,149 @@
+//! Exhaustiveness and reachability of `match` arms, by the usefulness
+//! algorithm from Maranget's "Warnings for pattern matching". A pattern is
+//! useful after some rows if it matches a value none of them do.
+
+use std::collections::HashMap;
+
+use super::Adt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Ctor {
+    Variant { adt: String, index: usize },
+    Bool(bool),
+    // Numbers and strings have too many values to list, so only a wildcard
+    // covers them
+    Number(u32),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+impl Pat {
+    pub(crate) fn display(&self, adts: &HashMap<String, Adt>) -> String {
+        match self {
+            Pat::Wild => "_".to_string(),
+            Pat::Ctor(Ctor::Variant { adt, index }, args) => {
+                let name = &adts[adt].variants[*index].name;
+                if args.is_empty() {
+                    return name.clone();
+                }
+                let args: Vec<String> = args.iter().map(|arg| arg.display(adts)).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            Pat::Ctor(Ctor::Bool(bool), _) => bool.to_string(),
+            Pat::Ctor(Ctor::Number(number), _) => number.to_string(),
+            Pat::Ctor(Ctor::String(string), _) => crate::print::quote(string),
+        }
+    }
+}
+
+/// A value matched by `q` but by none of `rows`, as one pattern per column,
+/// or `None` if `q` is useless.
+pub(crate) fn useful(
+    adts: &HashMap<String, Adt>,
+    rows: &[Vec<Pat>],
+    q: &[Pat],
+) -> Option<Vec<Pat>> {
+    let Some((head, tail)) = q.split_first() else {
+        return if rows.is_empty() { Some(vec![]) } else { None };
+    };
+
+    match head {
+        Pat::Ctor(ctor, args) => {
+            let mut q = args.clone();
+            q.extend_from_slice(tail);
+            let witness = useful(adts, &specialize(rows, ctor, args.len()), &q)?;
+            Some(rebuild(ctor, args.len(), witness))
+        }
+        Pat::Wild => {
+            let used: Vec<&Ctor> = rows
+                .iter()
+                .filter_map(|row| match &row[0] {
+                    Pat::Ctor(ctor, _) => Some(ctor),
+                    Pat::Wild => None,
+                })
+                .collect();
+            let signature = used.first().and_then(|ctor| signature(adts, ctor));
+
+            match signature {
+                // Every constructor appears, so a value is missed only if
+                // one of them misses it
+                Some(all) if all.iter().all(|(ctor, _)| used.contains(&ctor)) => {
+                    all.iter().find_map(|(ctor, arity)| {
+                        let mut q = vec![Pat::Wild; *arity];
+                        q.extend_from_slice(tail);
+                        let witness = useful(adts, &specialize(rows, ctor, *arity), &q)?;
+                        Some(rebuild(ctor, *arity, witness))
+                    })
+                }
+                // Otherwise the wildcard also stands for the missing ones
+                signature => {
+                    let rest: Vec<Vec<Pat>> = rows
+                        .iter()
+                        .filter(|row| row[0] == Pat::Wild)
+                        .map(|row| row[1..].to_vec())
+                        .collect();
+                    let mut witness = useful(adts, &rest, tail)?;
+                    let missing = signature
+                        .and_then(|all| all.into_iter().find(|(ctor, _)| !used.contains(&ctor)));
+                    let head = match missing {
+                        Some((ctor, arity)) => Pat::Ctor(ctor, vec![Pat::Wild; arity]),
+                        None => Pat::Wild,
+                    };
+                    witness.insert(0, head);
+                    Some(witness)
+                }
+            }
+        }
+    }
+}
+
+// Every constructor of the type `ctor` belongs to, with its arity, if there
+// are few enough to list
+fn signature(adts: &HashMap<String, Adt>, ctor: &Ctor) -> Option<Vec<(Ctor, usize)>> {
+    match ctor {
+        Ctor::Variant { adt, .. } => Some(
+            adts[adt]
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(index, variant)| {
+                    let ctor = Ctor::Variant {
+                        adt: adt.clone(),
+                        index,
+                    };
+                    (ctor, variant.fields.len())
+                })
+                .collect(),
+        ),
+        Ctor::Bool(_) => Some(vec![(Ctor::Bool(true), 0), (Ctor::Bool(false), 0)]),
+        Ctor::Number(_) | Ctor::String(_) => None,
+    }
+}
+
+// The rows that match `ctor`, with its arguments in place of the first column
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut specialized = match &row[0] {
+                Pat::Ctor(other, args) if other == ctor => args.clone(),
+                Pat::Ctor(..) => return None,
+                Pat::Wild => vec![Pat::Wild; arity],
+            };
+            specialized.extend_from_slice(&row[1..]);
+            Some(specialized)
+        })
+        .collect()
+}
+
+// Folds the first `arity` columns of a witness back into `ctor`
+fn rebuild(ctor: &Ctor, arity: usize, mut witness: Vec<Pat>) -> Vec<Pat> {
+    let rest = witness.split_off(arity);
+    let mut rebuilt = vec![Pat::Ctor(ctor.clone(), witness)];
+    rebuilt.extend(rest);
+    rebuilt
+}
@@ -0,0 +1,1261 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::exhaustive::{useful, Ctor, Pat};
+use super::*;
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+use crate::resolve::{BindingId, Resolution};
+
+/// The result of type checking: the type of every expression and pattern,
+/// keyed by its span, and the generalized type of every binding.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Typing {
+    pub types: HashMap<Position, Type>,
+    pub bindings: HashMap<BindingId, Scheme>,
+    // Types declared with `type`, by name
+    pub adts: HashMap<String, Adt>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Typing {
+    pub fn type_of(&self, pos: &Position) -> Option<&Type> {
+        self.types.get(pos)
+    }
+
+    pub fn binding_type(&self, id: BindingId) -> Option<&Scheme> {
+        self.bindings.get(&id)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Infers types with Hindley–Milner unification. `let` and `fn` bindings are
+/// generalized, so `fn id(x) { x }` can be used at several types; `state`
+/// cells are mutable and stay monomorphic.
+///
+/// `+` adds two numbers or joins two strings. It cannot stay generic, so
+/// operands that nothing else constrains default to `Number`, and
+/// `fn add(a, b) { a + b }` has the type `fn(Number, Number) -> Number`.
+pub fn check_program(program: &Program, resolution: &Resolution) -> Typing {
+    let mut checker = Checker::new(resolution);
+    checker.stmts(&program.stmts);
+    checker.finish()
+}
+
+enum UnifyError {
+    Mismatch,
+    Infinite,
+}
+
+struct VarState {
+    bound: Option<Type>,
+    // Depth of `let`/`fn` nesting the variable was created at. Only variables
+    // deeper than the binding being generalized become its type parameters.
+    level: u32,
+}
+
+struct Checker<'a> {
+    resolution: &'a Resolution,
+    vars: Vec<VarState>,
+    level: u32,
+    env: HashMap<BindingId, Scheme>,
+    types: HashMap<Position, Type>,
+    diagnostics: Vec<Diagnostic>,
+    // Operands of `+`, which must end up `Number` or `String`
+    addable: Vec<(Type, Position)>,
+    adts: HashMap<String, Adt>,
+    // Where each ADT is declared
+    adt_decls: HashMap<String, Position>,
+    // Variant constructor to its ADT and index in it
+    variants: HashMap<BindingId, (String, usize)>,
+    // Parameters of the `type` whose variants are being read
+    type_params: HashMap<String, Type>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(resolution: &'a Resolution) -> Self {
+        Self {
+            resolution,
+            vars: vec![],
+            level: 1,
+            env: HashMap::new(),
+            types: HashMap::new(),
+            diagnostics: vec![],
+            addable: vec![],
+            adts: HashMap::new(),
+            adt_decls: HashMap::new(),
+            variants: HashMap::new(),
+            type_params: HashMap::new(),
+        }
+    }
+
+    fn finish(mut self) -> Typing {
+        let addable = std::mem::take(&mut self.addable);
+        for (ty, pos) in addable {
+            self.check_addable(&ty, pos);
+        }
+
+        let types = self
+            .types
+            .iter()
+            .map(|(pos, ty)| (*pos, self.zonk(ty)))
+            .collect();
+        let bindings = self
+            .env
+            .iter()
+            .map(|(id, scheme)| (*id, Scheme::new(scheme.vars.clone(), self.zonk(&scheme.ty))))
+            .collect();
+        Typing {
+            types,
+            bindings,
+            adts: self.adts,
+            diagnostics: self.diagnostics,
+        }
+    }
+
+    fn fresh_var_at(&mut self, level: u32) -> TypeVar {
+        let var = TypeVar(self.vars.len() as u32);
+        self.vars.push(VarState { bound: None, level });
+        var
+    }
+
+    fn fresh_at(&mut self, level: u32) -> Type {
+        Type::Var(self.fresh_var_at(level))
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.fresh_at(self.level)
+    }
+
+    // Follows bound variables until a constructor or a free variable
+    fn shallow(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Var(var) = ty {
+            match &self.vars[var.0 as usize].bound {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    // Merges the fields of the records bound to a rest variable, so the
+    // rest is free or absent
+    fn row(
+        &self,
+        mut fields: BTreeMap<String, Type>,
+        mut rest: Option<TypeVar>,
+    ) -> (BTreeMap<String, Type>, Option<TypeVar>) {
+        while let Some(var) = rest {
+            match self.shallow(&Type::Var(var)) {
+                Type::Record(more, more_rest) => {
+                    fields.extend(more);
+                    rest = more_rest;
+                }
+                Type::Var(var) => return (fields, Some(var)),
+                _ => break,
+            }
+        }
+        (fields, rest)
+    }
+
+    /// Substitutes every bound variable in `ty`.
+    fn zonk(&self, ty: &Type) -> Type {
+        match self.shallow(ty) {
+            Type::Record(fields, rest) => {
+                let (fields, rest) = self.row(fields, rest);
+                let fields = fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), self.zonk(field)))
+                    .collect();
+                Type::Record(fields, rest)
+            }
+            Type::Fn(params, ret) => Type::func(
+                params.iter().map(|param| self.zonk(param)).collect(),
+                self.zonk(&ret),
+            ),
+            Type::Named(name, args) => {
+                Type::Named(name, args.iter().map(|arg| self.zonk(arg)).collect())
+            }
+            ty => ty,
+        }
+    }
+
+    // Fails if `var` occurs in `ty`, and lowers the levels of the variables
+    // in `ty` so they are not generalized sooner than `var` would be
+    fn occurs(&mut self, var: TypeVar, level: u32, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Type::Var(other) => {
+                let state = &mut self.vars[other.0 as usize];
+                state.level = state.level.min(level);
+                other == var
+            }
+            Type::Fn(params, ret) => {
+                params.iter().any(|param| self.occurs(var, level, param))
+                    || self.occurs(var, level, &ret)
+            }
+            Type::Named(_, args) => args.iter().any(|arg| self.occurs(var, level, arg)),
+            Type::Record(fields, rest) => {
+                let (fields, rest) = self.row(fields, rest);
+                if let Some(rest) = rest {
+                    let state = &mut self.vars[rest.0 as usize];
+                    state.level = state.level.min(level);
+                    if rest == var {
+                        return true;
+                    }
+                }
+                fields.values().any(|field| self.occurs(var, level, field))
+            }
+            Type::Number | Type::Bool | Type::String | Type::Unit => false,
+        }
+    }
+
+    fn unify_inner(&mut self, a: &Type, b: &Type) -> Result<(), UnifyError> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                let level = self.vars[var.0 as usize].level;
+                if self.occurs(var, level, &ty) {
+                    return Err(UnifyError::Infinite);
+                }
+                self.vars[var.0 as usize].bound = Some(ty);
+                Ok(())
+            }
+            (Type::Fn(a_params, a_ret), Type::Fn(b_params, b_ret)) => {
+                if a_params.len() != b_params.len() {
+                    return Err(UnifyError::Mismatch);
+                }
+                for (a, b) in a_params.iter().zip(b_params.iter()) {
+                    self.unify_inner(a, b)?;
+                }
+                self.unify_inner(&a_ret, &b_ret)
+            }
+            (Type::Named(a_name, a_args), Type::Named(b_name, b_args)) => {
+                if a_name != b_name || a_args.len() != b_args.len() {
+                    return Err(UnifyError::Mismatch);
+                }
+                for (a, b) in a_args.iter().zip(b_args.iter()) {
+                    self.unify_inner(a, b)?;
+                }
+                Ok(())
+            }
+            (Type::Record(a_fields, a_rest), Type::Record(b_fields, b_rest)) => {
+                let (a_fields, a_rest) = self.row(a_fields, a_rest);
+                let (b_fields, b_rest) = self.row(b_fields, b_rest);
+                for (name, a) in a_fields.iter() {
+                    if let Some(b) = b_fields.get(name) {
+                        self.unify_inner(a, b)?;
+                    }
+                }
+                let only = |fields: &BTreeMap<String, Type>, other: &BTreeMap<String, Type>| {
+                    fields
+                        .iter()
+                        .filter(|(name, _)| !other.contains_key(*name))
+                        .map(|(name, field)| (name.clone(), field.clone()))
+                        .collect::<BTreeMap<_, _>>()
+                };
+                let a_only = only(&a_fields, &b_fields);
+                let b_only = only(&b_fields, &a_fields);
+                match (a_rest, b_rest) {
+                    (None, None) if a_only.is_empty() && b_only.is_empty() => Ok(()),
+                    (Some(a_rest), None) if a_only.is_empty() => {
+                        self.bind_row(a_rest, Type::Record(b_only, None))
+                    }
+                    (None, Some(b_rest)) if b_only.is_empty() => {
+                        self.bind_row(b_rest, Type::Record(a_only, None))
+                    }
+                    (Some(a_rest), Some(b_rest)) if a_rest == b_rest => {
+                        if a_only.is_empty() && b_only.is_empty() {
+                            Ok(())
+                        } else {
+                            Err(UnifyError::Mismatch)
+                        }
+                    }
+                    (Some(a_rest), Some(b_rest)) => {
+                        // Both records get the fields only the other has,
+                        // and share whatever else either may have
+                        let level = self.vars[a_rest.0 as usize]
+                            .level
+                            .min(self.vars[b_rest.0 as usize].level);
+                        let rest = self.fresh_var_at(level);
+                        self.bind_row(a_rest, Type::Record(b_only, Some(rest)))?;
+                        self.bind_row(b_rest, Type::Record(a_only, Some(rest)))
+                    }
+                    _ => Err(UnifyError::Mismatch),
+                }
+            }
+            (a, b) if a == b => Ok(()),
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    fn bind_row(&mut self, rest: TypeVar, record: Type) -> Result<(), UnifyError> {
+        let level = self.vars[rest.0 as usize].level;
+        if self.occurs(rest, level, &record) {
+            return Err(UnifyError::Infinite);
+        }
+        self.vars[rest.0 as usize].bound = Some(record);
+        Ok(())
+    }
+
+    /// Unifies, reporting a mismatch at `pos` as "expected, found".
+    fn unify(&mut self, expected: &Type, found: &Type, pos: Position) -> bool {
+        match self.unify_inner(expected, found) {
+            Ok(()) => true,
+            Err(error) => {
+                let diagnostic = self.unify_error(error, expected, found, pos);
+                self.diagnostics.push(diagnostic);
+                false
+            }
+        }
+    }
+
+    fn unify_error(
+        &self,
+        error: UnifyError,
+        expected: &Type,
+        found: &Type,
+        pos: Position,
+    ) -> Diagnostic {
+        let expected = self.zonk(expected);
+        let found = self.zonk(found);
+        match error {
+            UnifyError::Mismatch => {
+                Diagnostic::error(format!("expected `{}`, found `{}`", expected, found), pos)
+            }
+            UnifyError::Infinite => Diagnostic::error(
+                format!(
+                    "cannot construct the infinite type `{}` = `{}`",
+                    expected, found
+                ),
+                pos,
+            ),
+        }
+    }
+
+    // Unifies a value with the annotation it was declared with
+    fn unify_annotated(
+        &mut self,
+        annotated: &Type,
+        found: &Type,
+        pos: Position,
+        annotation: Position,
+    ) {
+        if let Err(error) = self.unify_inner(annotated, found) {
+            let diagnostic = self
+                .unify_error(error, annotated, found, pos)
+                .with_label("expected because of this annotation", annotation);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// The type an annotation stands for.
+    fn annotation(&mut self, ty: &TypeExpr) -> Type {
+        match &ty.kind {
+            TypeExprKind::Named(named) => {
+                let name = named.name.as_str();
+                let mut args: Vec<Type> =
+                    named.args.iter().map(|arg| self.annotation(arg)).collect();
+                let arity = match name {
+                    _ if self.type_params.contains_key(name) => Some(0),
+                    "Number" | "Bool" | "String" | "Unit" | "Element" => Some(0),
+                    "List" => Some(1),
+                    _ => self.adts.get(name).map(|adt| adt.params.len()),
+                };
+                let message = match arity {
+                    Some(arity) if arity == args.len() => None,
+                    Some(0) => Some(format!("`{}` takes no type arguments", name)),
+                    Some(1) => Some(format!(
+                        "`{}` takes 1 type argument, found {}",
+                        name,
+                        args.len()
+                    )),
+                    Some(arity) => Some(format!(
+                        "`{}` takes {} type arguments, found {}",
+                        name,
+                        arity,
+                        args.len()
+                    )),
+                    None => Some(format!("unknown type `{}`", name)),
+                };
+                if let Some(message) = message {
+                    self.diagnostics.push(Diagnostic::error(message, ty.pos));
+                    return self.fresh();
+                }
+                match name {
+                    _ if self.type_params.contains_key(name) => self.type_params[name].clone(),
+                    "Number" => Type::Number,
+                    "Bool" => Type::Bool,
+                    "String" => Type::String,
+                    "Unit" => Type::Unit,
+                    "List" => Type::list(args.remove(0)),
+                    _ => Type::Named(name.to_string(), args),
+                }
+            }
+            TypeExprKind::Fn(fn_type) => {
+                let params = fn_type
+                    .params
+                    .iter()
+                    .map(|param| self.annotation(param))
+                    .collect();
+                let ret = self.annotation(&fn_type.ret);
+                Type::func(params, ret)
+            }
+        }
+    }
+
+    fn generalize(&mut self, ty: &Type) -> Scheme {
+        // `+` on a type that would become a parameter has no way to stay
+        // generic, so it defaults to `Number` first
+        let addable = std::mem::take(&mut self.addable);
+        for (addable_ty, pos) in addable {
+            match self.shallow(&addable_ty) {
+                Type::Var(var) if self.vars[var.0 as usize].level > self.level => {
+                    self.unify(&Type::Number, &addable_ty, pos);
+                }
+                Type::Var(_) => self.addable.push((addable_ty, pos)),
+                _ => self.check_addable(&addable_ty, pos),
+            }
+        }
+
+        let ty = self.zonk(ty);
+        let vars = ty
+            .vars()
+            .into_iter()
+            .filter(|var| self.vars[var.0 as usize].level > self.level)
+            .collect();
+        Scheme::new(vars, ty)
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let mapping: HashMap<TypeVar, Type> =
+            scheme.vars.iter().map(|var| (*var, self.fresh())).collect();
+        substitute(&scheme.ty, &mapping)
+    }
+
+    // Reports an operand of `+` that is neither `Number` nor `String`, and
+    // has one whose type is still open be `Number`
+    fn check_addable(&mut self, ty: &Type, pos: Position) {
+        match self.shallow(ty) {
+            Type::Number | Type::String => {}
+            Type::Var(_) => {
+                self.unify(&Type::Number, ty, pos);
+            }
+            ty => self.diagnostics.push(Diagnostic::error(
+                format!("`+` needs numbers or strings, found `{}`", self.zonk(&ty)),
+                pos,
+            )),
+        }
+    }
+
+    fn binding(&self, pos: &Position) -> Option<BindingId> {
+        self.resolution.definitions.get(pos).copied()
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Type {
+        // Types can be used anywhere in their scope, including by each other
+        for stmt in stmts.iter().map(Stmt::declaration) {
+            if let StmtKind::Type(type_stmt) = &stmt.kind {
+                self.declare_adt(type_stmt, stmt.pos);
+            }
+        }
+        for stmt in stmts.iter().map(Stmt::declaration) {
+            if let StmtKind::Type(type_stmt) = &stmt.kind {
+                self.define_adt(type_stmt);
+            }
+        }
+
+        let mut ty = Type::Unit;
+        for stmt in stmts.iter() {
+            ty = self.stmt(stmt);
+        }
+        ty
+    }
+
+    fn declare_adt(&mut self, type_stmt: &TypeStmt, pos: Position) {
+        let name = &type_stmt.name;
+        let builtin = matches!(
+            name.as_str(),
+            "Number" | "Bool" | "String" | "Unit" | "List" | "Element"
+        );
+        if builtin {
+            self.diagnostics.push(Diagnostic::error(
+                format!("`{}` is a built-in type", name),
+                pos,
+            ));
+            return;
+        }
+        if let Some(first) = self.adt_decls.get(name) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("type `{}` is defined twice", name), pos)
+                    .with_label("first defined here", *first),
+            );
+            return;
+        }
+        let params = type_stmt
+            .params
+            .iter()
+            .map(|_| self.fresh_var_at(self.level + 1))
+            .collect();
+        self.adt_decls.insert(name.clone(), pos);
+        self.adts.insert(
+            name.clone(),
+            Adt {
+                name: name.clone(),
+                params,
+                variants: vec![],
+            },
+        );
+    }
+
+    // Reads the fields of the variants, once every type of the scope is
+    // known, and gives each constructor its type
+    fn define_adt(&mut self, type_stmt: &TypeStmt) {
+        let Some(adt) = self.adts.get(&type_stmt.name) else {
+            return;
+        };
+        if !adt.variants.is_empty() {
+            // Already defined by the first declaration of the name
+            return;
+        }
+        let adt_params = adt.params.clone();
+        let adt_ty = adt.ty();
+        self.type_params = type_stmt
+            .params
+            .iter()
+            .cloned()
+            .zip(adt_params.iter().map(|param| Type::Var(*param)))
+            .collect();
+
+        let mut variants = vec![];
+        for (index, variant) in type_stmt.variants.iter().enumerate() {
+            let fields: Vec<Type> = variant
+                .fields
+                .iter()
+                .map(|field| self.annotation(field))
+                .collect();
+            let constructor = match fields.is_empty() {
+                true => adt_ty.clone(),
+                false => Type::func(fields.clone(), adt_ty.clone()),
+            };
+            if let Some(id) = self.binding(&variant.pos) {
+                self.env
+                    .insert(id, Scheme::new(adt_params.clone(), constructor));
+                self.variants.insert(id, (type_stmt.name.clone(), index));
+            }
+            variants.push(AdtVariant {
+                name: variant.name.clone(),
+                fields,
+            });
+        }
+        self.type_params.clear();
+        if let Some(adt) = self.adts.get_mut(&type_stmt.name) {
+            adt.variants = variants;
+        }
+    }
+
+    // The type of an expression statement, `Unit` for declarations
+    fn stmt(&mut self, stmt: &Stmt) -> Type {
+        match &stmt.kind {
+            StmtKind::Let(let_stmt) => {
+                self.level += 1;
+                let mut ty = self.expr(&let_stmt.value);
+                if let Some(annotation) = &let_stmt.ty {
+                    let annotated = self.annotation(annotation);
+                    self.unify_annotated(&annotated, &ty, let_stmt.value.pos, annotation.pos);
+                    ty = annotated;
+                }
+                self.level -= 1;
+                let scheme = self.generalize(&ty);
+                if let Some(id) = self.binding(&stmt.pos) {
+                    self.env.insert(id, scheme);
+                }
+                Type::Unit
+            }
+            StmtKind::State(state_stmt) => {
+                let mut ty = self.expr(&state_stmt.value);
+                if let Some(annotation) = &state_stmt.ty {
+                    let annotated = self.annotation(annotation);
+                    self.unify_annotated(&annotated, &ty, state_stmt.value.pos, annotation.pos);
+                    ty = annotated;
+                }
+                if let Some(id) = self.binding(&stmt.pos) {
+                    self.env.insert(id, Scheme::mono(ty));
+                }
+                Type::Unit
+            }
+            StmtKind::Fn(fn_stmt) => {
+                self.declaration(stmt.pos, |this| {
+                    this.function(&fn_stmt.params, &fn_stmt.ret, &fn_stmt.body)
+                });
+                Type::Unit
+            }
+            StmtKind::Component(component) => {
+                self.declaration(stmt.pos, |this| this.component(component));
+                Type::Unit
+            }
+            // Imported names are typed where they are used until modules
+            // are checked together
+            StmtKind::Type(_) | StmtKind::Import(_) => Type::Unit,
+            StmtKind::Export(exported) => self.stmt(exported),
+            StmtKind::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    // Types a function or component declared at `pos`, which is generalized
+    // once its body is checked
+    fn declaration(&mut self, pos: Position, infer: impl FnOnce(&mut Self) -> Type) {
+        let id = self.binding(&pos);
+        self.level += 1;
+        // A function used before its declaration already has a monomorphic
+        // placeholder
+        let fn_ty = match id.and_then(|id| self.env.get(&id)) {
+            Some(scheme) => scheme.ty.clone(),
+            None => self.fresh(),
+        };
+        if let Some(id) = id {
+            self.env.insert(id, Scheme::mono(fn_ty.clone()));
+        }
+
+        let ty = infer(self);
+        self.unify(&fn_ty, &ty, pos);
+        self.level -= 1;
+
+        let scheme = self.generalize(&fn_ty);
+        if let Some(id) = id {
+            self.env.insert(id, scheme);
+        }
+    }
+
+    // Brings the parameters into scope, annotated or not
+    fn params(&mut self, params: &[Param]) -> Vec<Type> {
+        let mut param_types = vec![];
+        for param in params.iter() {
+            let ty = match &param.ty {
+                Some(annotation) => self.annotation(annotation),
+                None => self.fresh(),
+            };
+            if let Some(id) = self.binding(&param.pos) {
+                self.env.insert(id, Scheme::mono(ty.clone()));
+            }
+            param_types.push(ty);
+        }
+        param_types
+    }
+
+    // A component is a function from a record of exactly its props, as
+    // elements pass them, to an element
+    fn component(&mut self, component: &ComponentStmt) -> Type {
+        let prop_types = self.params(&component.props);
+        let props = component
+            .props
+            .iter()
+            .map(|prop| prop.name.clone())
+            .zip(prop_types)
+            .collect();
+        let body = self.expr(&component.body);
+        self.unify(&Type::element(), &body, component.body.pos);
+        Type::func(vec![Type::Record(props, None)], Type::element())
+    }
+
+    // The type of a function or lambda, with its parameters in scope
+    fn function(&mut self, params: &[Param], ret: &Option<TypeExpr>, body: &Expr) -> Type {
+        let param_types = self.params(params);
+        let mut ret_ty = self.expr(body);
+        if let Some(annotation) = ret {
+            let annotated = self.annotation(annotation);
+            self.unify_annotated(&annotated, &ret_ty, body.pos, annotation.pos);
+            ret_ty = annotated;
+        }
+        Type::func(param_types, ret_ty)
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Type {
+        let ty = self.expr_inner(expr);
+        self.types.insert(expr.pos, ty.clone());
+        ty
+    }
+
+    fn expr_inner(&mut self, expr: &Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Identifier(_) => self.used(expr.pos),
+            ExprKind::Literal(LiteralExpr::Number(_)) => Type::Number,
+            ExprKind::Literal(LiteralExpr::Bool(_)) => Type::Bool,
+            ExprKind::Literal(LiteralExpr::String(_)) => Type::String,
+            ExprKind::Block(stmts) => self.stmts(stmts),
+            ExprKind::Call(call) => self.call(expr, call),
+            ExprKind::Binary(binary) => self.binary(expr, binary),
+            ExprKind::Unary(unary) => {
+                let operand = self.expr(&unary.operand);
+                let ty = match unary.op {
+                    UnaryOp::Neg => Type::Number,
+                    UnaryOp::Not => Type::Bool,
+                };
+                self.unify(&ty, &operand, unary.operand.pos);
+                ty
+            }
+            ExprKind::If(if_expr) => {
+                let condition = self.expr(&if_expr.condition);
+                self.unify(&Type::Bool, &condition, if_expr.condition.pos);
+                let then = self.expr(&if_expr.then);
+                match &if_expr.otherwise {
+                    Some(otherwise) => {
+                        let otherwise_ty = self.expr(otherwise);
+                        if self.unify_inner(&then, &otherwise_ty).is_err() {
+                            let then = self.zonk(&then);
+                            let otherwise_ty = self.zonk(&otherwise_ty);
+                            self.diagnostics.push(
+                                Diagnostic::error(
+                                    "`if` and `else` have different types",
+                                    otherwise.pos,
+                                )
+                                .with_label(format!("this is `{}`", then), if_expr.then.pos)
+                                .with_label(format!("this is `{}`", otherwise_ty), otherwise.pos),
+                            );
+                        }
+                        then
+                    }
+                    // The value of the branch is discarded
+                    None => Type::Unit,
+                }
+            }
+            ExprKind::List(items) => {
+                let item_ty = self.fresh();
+                for item in items {
+                    let value = self.expr(&item.value);
+                    if item.spread {
+                        self.unify(&Type::list(item_ty.clone()), &value, item.value.pos);
+                    } else {
+                        self.unify(&item_ty, &value, item.value.pos);
+                    }
+                }
+                Type::list(item_ty)
+            }
+            ExprKind::Index(index) => self.index(expr, index),
+            ExprKind::Record(record) => self.record(record),
+            ExprKind::Lambda(lambda) => self.function(&lambda.params, &lambda.ret, &lambda.body),
+            ExprKind::Match(match_expr) => self.match_expr(expr, match_expr),
+            ExprKind::Field(field) => {
+                let record = self.expr(&field.record);
+                self.field(&record, &field.field, expr.pos, field.record.pos)
+            }
+            ExprKind::Assign(assign) => {
+                let target = self.expr(&assign.target);
+                let value = self.expr(&assign.value);
+                self.unify(&target, &value, assign.value.pos);
+                Type::Unit
+            }
+            ExprKind::Element(element) => self.element(element),
+        }
+    }
+
+    // HTML tags take attributes and children of any type. A component is a
+    // function from its attributes, and `children` if it has any, to an
+    // element. `key` is left to renderers.
+    fn element(&mut self, element: &ElementExpr) -> Type {
+        // Each prop with where it is given, the tag for `children`
+        let mut props = BTreeMap::new();
+        for attribute in element.attributes.iter() {
+            let ty = match &attribute.value {
+                Some(value) => self.expr(value),
+                None => Type::Bool,
+            };
+            if attribute.name != "key" {
+                props.insert(attribute.name.clone(), (ty, attribute.pos));
+            }
+        }
+        for child in element.children.iter() {
+            if let Some(value) = child.expr() {
+                self.expr(value);
+            }
+        }
+        let Some(tag) = element.tag.as_ref().filter(|tag| tag.is_component()) else {
+            return Type::element();
+        };
+        if !element.children.is_empty() {
+            props.insert("children".to_string(), (Type::element(), tag.pos));
+        }
+        let component = self.used(tag.pos);
+        self.types.insert(tag.pos, component.clone());
+
+        // The props of a component are known, so each is checked where it
+        // is given, and those that are missing at the tag
+        if let Type::Fn(params, ret) = self.shallow(&component) {
+            if let [Type::Record(expected, None)] = params.as_slice() {
+                self.props(&tag.name, tag.pos, expected, &props);
+                self.unify(&Type::element(), &ret, tag.pos);
+                return Type::element();
+            }
+        }
+        let props = props.into_iter().map(|(name, (ty, _))| (name, ty));
+        let expected = Type::func(vec![Type::Record(props.collect(), None)], Type::element());
+        self.unify(&expected, &component, tag.pos);
+        Type::element()
+    }
+
+    fn props(
+        &mut self,
+        component: &str,
+        tag: Position,
+        expected: &BTreeMap<String, Type>,
+        given: &BTreeMap<String, (Type, Position)>,
+    ) {
+        for (name, (ty, pos)) in given.iter() {
+            let Some(prop) = expected.get(name) else {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("`{}` has no prop `{}`", component, name),
+                    *pos,
+                ));
+                continue;
+            };
+            match self.unify_inner(prop, ty) {
+                Ok(()) => {}
+                Err(UnifyError::Mismatch) => {
+                    let message = format!(
+                        "prop `{}` expects `{}`, found `{}`",
+                        name,
+                        self.zonk(prop),
+                        self.zonk(ty)
+                    );
+                    self.diagnostics.push(Diagnostic::error(message, *pos));
+                }
+                Err(error) => {
+                    let diagnostic = self.unify_error(error, prop, ty, *pos);
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        for name in expected.keys().filter(|name| !given.contains_key(*name)) {
+            self.diagnostics.push(Diagnostic::error(
+                format!("`{}` is missing the prop `{}`", component, name),
+                tag,
+            ));
+        }
+    }
+
+    // The type of the binding used at `pos`
+    fn used(&mut self, pos: Position) -> Type {
+        match self.resolution.uses.get(&pos) {
+            Some(id) => match self.env.get(id) {
+                Some(scheme) => {
+                    let scheme = scheme.clone();
+                    self.instantiate(&scheme)
+                }
+                // Used before its declaration, which only functions can be.
+                // Level 0 keeps the placeholder from being generalized by
+                // whatever uses it.
+                None => {
+                    let ty = self.fresh_at(0);
+                    self.env.insert(*id, Scheme::mono(ty.clone()));
+                    ty
+                }
+            },
+            // Already reported by the resolver
+            None => self.fresh(),
+        }
+    }
+
+    fn match_expr(&mut self, expr: &Expr, match_expr: &MatchExpr) -> Type {
+        let scrutinee = self.expr(&match_expr.scrutinee);
+        let mut patterns_ok = true;
+        let mut result: Option<(Type, Position)> = None;
+        for arm in match_expr.arms.iter() {
+            let errors = self.diagnostics.len();
+            self.pattern(&arm.pattern, &scrutinee);
+            patterns_ok &= self.diagnostics.len() == errors;
+            if let Some(guard) = &arm.guard {
+                let guard_ty = self.expr(guard);
+                self.unify(&Type::Bool, &guard_ty, guard.pos);
+            }
+            let body = self.expr(&arm.body);
+            match &result {
+                None => result = Some((body, arm.body.pos)),
+                Some((first, first_pos)) => {
+                    if self.unify_inner(first, &body).is_err() {
+                        let first = self.zonk(first);
+                        let body = self.zonk(&body);
+                        self.diagnostics.push(
+                            Diagnostic::error("`match` arms have different types", arm.body.pos)
+                                .with_label(format!("this is `{}`", first), *first_pos)
+                                .with_label(format!("this is `{}`", body), arm.body.pos),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Ill-typed patterns could mix constructors of different types
+        if patterns_ok {
+            self.check_arms(expr, match_expr);
+        }
+        match result {
+            Some((ty, _)) => ty,
+            None => self.fresh(),
+        }
+    }
+
+    fn check_arms(&mut self, expr: &Expr, match_expr: &MatchExpr) {
+        let mut rows = vec![];
+        for arm in match_expr.arms.iter() {
+            let pat = self.pat(&arm.pattern);
+            if useful(&self.adts, &rows, std::slice::from_ref(&pat)).is_none() {
+                self.diagnostics
+                    .push(Diagnostic::warning("unreachable pattern", arm.pattern.pos));
+            }
+            // A guard can fail, so its arm covers nothing for sure
+            if arm.guard.is_none() {
+                rows.push(vec![pat]);
+            }
+        }
+        if let Some(witness) = useful(&self.adts, &rows, &[Pat::Wild]) {
+            let scrutinee = self.zonk(&self.types[&match_expr.scrutinee.pos]);
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "non-exhaustive match, `{}` is not covered",
+                        witness[0].display(&self.adts)
+                    ),
+                    expr.pos,
+                )
+                .with_label(format!("this is `{}`", scrutinee), match_expr.scrutinee.pos),
+            );
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, expected: &Type) {
+        self.types.insert(pattern.pos, expected.clone());
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Identifier(_) => match self.resolution.uses.get(&pattern.pos) {
+                Some(id) => self.variant_pattern(pattern, *id, &[], expected),
+                None => {
+                    if let Some(id) = self.binding(&pattern.pos) {
+                        self.env.insert(id, Scheme::mono(expected.clone()));
+                    }
+                }
+            },
+            PatternKind::Literal(literal) => {
+                let ty = match literal {
+                    LiteralExpr::Number(_) => Type::Number,
+                    LiteralExpr::Bool(_) => Type::Bool,
+                    LiteralExpr::String(_) => Type::String,
+                };
+                self.unify(expected, &ty, pattern.pos);
+            }
+            PatternKind::Variant(_, patterns) => match self.resolution.uses.get(&pattern.pos) {
+                Some(id) => self.variant_pattern(pattern, *id, patterns, expected),
+                // Already reported by the resolver
+                None => {
+                    for pattern in patterns.iter() {
+                        let ty = self.fresh();
+                        self.pattern(pattern, &ty);
+                    }
+                }
+            },
+        }
+    }
+
+    fn variant_pattern(
+        &mut self,
+        pattern: &Pattern,
+        id: BindingId,
+        patterns: &[Pattern],
+        expected: &Type,
+    ) {
+        let Some((adt, index)) = self.variants.get(&id).cloned() else {
+            return;
+        };
+        let adt = &self.adts[&adt];
+        let variant = &adt.variants[index];
+        let name = variant.name.clone();
+        let params = adt.params.clone();
+        let fields = variant.fields.clone();
+        let adt_ty = adt.ty();
+
+        let mapping: HashMap<TypeVar, Type> =
+            params.iter().map(|param| (*param, self.fresh())).collect();
+        self.unify(expected, &substitute(&adt_ty, &mapping), pattern.pos);
+        if fields.len() != patterns.len() {
+            let fields = match fields.len() {
+                1 => "1 field".to_string(),
+                n => format!("{} fields", n),
+            };
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "`{}` has {}, but the pattern has {}",
+                    name,
+                    fields,
+                    patterns.len()
+                ),
+                pattern.pos,
+            ));
+            return;
+        }
+        for (pattern, field) in patterns.iter().zip(fields.iter()) {
+            let field = substitute(field, &mapping);
+            self.pattern(pattern, &field);
+        }
+    }
+
+    // The pattern as the exhaustiveness check sees it
+    fn pat(&self, pattern: &Pattern) -> Pat {
+        let variant = |id: Option<&BindingId>, patterns: &[Pattern]| match id
+            .and_then(|id| self.variants.get(id))
+        {
+            Some((adt, index)) => Pat::Ctor(
+                Ctor::Variant {
+                    adt: adt.clone(),
+                    index: *index,
+                },
+                patterns.iter().map(|pattern| self.pat(pattern)).collect(),
+            ),
+            None => Pat::Wild,
+        };
+        match &pattern.kind {
+            PatternKind::Wildcard => Pat::Wild,
+            PatternKind::Identifier(_) => variant(self.resolution.uses.get(&pattern.pos), &[]),
+            PatternKind::Literal(LiteralExpr::Bool(bool)) => Pat::Ctor(Ctor::Bool(*bool), vec![]),
+            PatternKind::Literal(LiteralExpr::Number(number)) => {
+                Pat::Ctor(Ctor::Number(*number), vec![])
+            }
+            PatternKind::Literal(LiteralExpr::String(string)) => {
+                Pat::Ctor(Ctor::String(string.clone()), vec![])
+            }
+            PatternKind::Variant(_, patterns) => {
+                variant(self.resolution.uses.get(&pattern.pos), patterns)
+            }
+        }
+    }
+
+    fn record(&mut self, record: &RecordExpr) -> Type {
+        let mut first: HashMap<&str, Position> = HashMap::new();
+        for field in record.fields.iter() {
+            if let Some(pos) = first.get(field.name.as_str()) {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!("field `{}` is defined twice", field.name),
+                        field.pos,
+                    )
+                    .with_label("first defined here", *pos),
+                );
+            } else {
+                first.insert(&field.name, field.pos);
+            }
+        }
+
+        match &record.base {
+            // An update keeps the types of the fields it replaces. Those a
+            // closed record does not have are added, while a record that is
+            // not known yet must have them, as its other fields can't be
+            // named.
+            Some(base) => {
+                let base_ty = self.expr(base);
+                let mut closed = match self.shallow(&base_ty) {
+                    Type::Record(fields, rest) => match self.row(fields, rest) {
+                        (fields, None) => Some(fields),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                for field in record.fields.iter() {
+                    let value = self.expr(&field.value);
+                    let field_ty = match closed.as_mut() {
+                        Some(fields) => match fields.get(&field.name) {
+                            Some(field_ty) => field_ty.clone(),
+                            None => {
+                                fields.insert(field.name.clone(), value);
+                                continue;
+                            }
+                        },
+                        None => self.field(&base_ty, &field.name, field.pos, base.pos),
+                    };
+                    self.unify(&field_ty, &value, field.value.pos);
+                }
+                match closed {
+                    Some(fields) => Type::Record(fields, None),
+                    None => base_ty,
+                }
+            }
+            None => {
+                let fields = record
+                    .fields
+                    .iter()
+                    .map(|field| (field.name.clone(), self.expr(&field.value)))
+                    .collect();
+                Type::Record(fields, None)
+            }
+        }
+    }
+
+    // The type of `name` on `record`, an open record if it is not known yet
+    fn field(&mut self, record: &Type, name: &str, pos: Position, record_pos: Position) -> Type {
+        let found = match self.shallow(record) {
+            Type::Record(fields, rest) => {
+                let (fields, rest) = self.row(fields, rest);
+                match fields.get(name) {
+                    Some(field) => return field.clone(),
+                    None => rest.is_some(),
+                }
+            }
+            Type::Var(_) => true,
+            _ => false,
+        };
+        if !found {
+            let record = self.zonk(record);
+            self.diagnostics.push(
+                Diagnostic::error(format!("no field `{}` on `{}`", name, record), pos)
+                    .with_label(format!("this is `{}`", record), record_pos),
+            );
+            return self.fresh();
+        }
+
+        let field = self.fresh();
+        let rest = self.fresh_var_at(self.level);
+        let mut fields = BTreeMap::new();
+        fields.insert(name.to_string(), field.clone());
+        self.unify(&Type::Record(fields, Some(rest)), record, record_pos);
+        field
+    }
+
+    fn index(&mut self, expr: &Expr, index: &IndexExpr) -> Type {
+        let indexed = self.expr(&index.indexed);
+        let index_ty = self.expr(&index.index);
+        self.unify(&Type::Number, &index_ty, index.index.pos);
+
+        match self.shallow(&indexed) {
+            Type::Named(name, mut args) if name == "List" && args.len() == 1 => args.remove(0),
+            Type::Var(_) => {
+                let item = self.fresh();
+                self.unify(&Type::list(item.clone()), &indexed, index.indexed.pos);
+                item
+            }
+            indexed => {
+                let indexed = self.zonk(&indexed);
+                self.diagnostics.push(
+                    Diagnostic::error(format!("`{}` cannot be indexed", indexed), expr.pos)
+                        .with_label(format!("this is `{}`", indexed), index.indexed.pos),
+                );
+                self.fresh()
+            }
+        }
+    }
+
+    fn call(&mut self, expr: &Expr, call: &CallExpr) -> Type {
+        let called = self.expr(&call.called);
+        let args: Vec<Type> = call.args.iter().map(|arg| self.expr(arg)).collect();
+
+        match self.shallow(&called) {
+            Type::Fn(params, ret) => {
+                if params.len() != args.len() {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!("expected {} arguments, found {}", params.len(), args.len()),
+                            expr.pos,
+                        )
+                        .with_label(format!("this is `{}`", self.zonk(&called)), call.called.pos),
+                    );
+                    return *ret;
+                }
+                for ((param, arg), arg_expr) in params.iter().zip(args.iter()).zip(call.args.iter())
+                {
+                    self.unify(param, arg, arg_expr.pos);
+                }
+                *ret
+            }
+            Type::Var(_) => {
+                let ret = self.fresh();
+                self.unify(&Type::func(args, ret.clone()), &called, call.called.pos);
+                ret
+            }
+            called => {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!("`{}` is not a function", self.zonk(&called)),
+                        expr.pos,
+                    )
+                    .with_label(format!("this is `{}`", self.zonk(&called)), call.called.pos),
+                );
+                self.fresh()
+            }
+        }
+    }
+
+    fn binary(&mut self, expr: &Expr, binary: &BinaryExpr) -> Type {
+        let lhs = self.expr(&binary.lhs);
+        let rhs = self.expr(&binary.rhs);
+
+        let both = |checker: &mut Self, ty: Type| {
+            checker.unify(&ty, &lhs, binary.lhs.pos);
+            checker.unify(&ty, &rhs, binary.rhs.pos);
+            ty
+        };
+
+        match binary.op {
+            BinaryOp::Add | BinaryOp::Eq | BinaryOp::Ne => {
+                if self.unify_inner(&lhs, &rhs).is_err() {
+                    let verb = if binary.op == BinaryOp::Add {
+                        "add"
+                    } else {
+                        "compare"
+                    };
+                    let lhs_ty = self.zonk(&lhs);
+                    let rhs_ty = self.zonk(&rhs);
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!("cannot {} `{}` and `{}`", verb, lhs_ty, rhs_ty),
+                            expr.pos,
+                        )
+                        .with_label(format!("this is `{}`", lhs_ty), binary.lhs.pos)
+                        .with_label(format!("this is `{}`", rhs_ty), binary.rhs.pos),
+                    );
+                }
+                if binary.op == BinaryOp::Add {
+                    self.addable.push((lhs.clone(), expr.pos));
+                    lhs
+                } else {
+                    Type::Bool
+                }
+            }
+            BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                both(self, Type::Number)
+            }
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                both(self, Type::Number);
+                Type::Bool
+            }
+            BinaryOp::And | BinaryOp::Or => both(self, Type::Bool),
+        }
+    }
+}
+
+fn substitute(ty: &Type, mapping: &HashMap<TypeVar, Type>) -> Type {
+    match ty {
+        Type::Var(var) => mapping.get(var).cloned().unwrap_or(Type::Var(*var)),
+        Type::Fn(params, ret) => Type::func(
+            params
+                .iter()
+                .map(|param| substitute(param, mapping))
+                .collect(),
+            substitute(ret, mapping),
+        ),
+        Type::Named(name, args) => Type::Named(
+            name.clone(),
+            args.iter().map(|arg| substitute(arg, mapping)).collect(),
+        ),
+        Type::Record(fields, rest) => {
+            let fields = fields
+                .iter()
+                .map(|(name, field)| (name.clone(), substitute(field, mapping)))
+                .collect();
+            let rest = rest.map(|rest| match mapping.get(&rest) {
+                Some(Type::Var(var)) => *var,
+                _ => rest,
+            });
+            Type::Record(fields, rest)
+        }
+        Type::Number | Type::Bool | Type::String | Type::Unit => ty.clone(),
+    }
+}
@@ -0,0 +1,202 @@
+mod exhaustive;
+pub mod infer;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+pub use self::infer::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeVar(pub u32);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Number,
+    Bool,
+    String,
+    Unit,
+    Var(TypeVar),
+    Fn(Vec<Type>, Box<Type>),
+    // A type constructor applied to its arguments, as in `List<Number>`
+    Named(String, Vec<Type>),
+    // Fields sorted by name. A record with a rest variable is open, and
+    // has at least the given fields.
+    Record(BTreeMap<String, Type>, Option<TypeVar>),
+}
+
+impl Type {
+    pub fn func(params: Vec<Type>, ret: Type) -> Type {
+        Type::Fn(params, Box::new(ret))
+    }
+
+    pub fn list(item: Type) -> Type {
+        Type::Named("List".to_string(), vec![item])
+    }
+
+    pub fn element() -> Type {
+        Type::Named("Element".to_string(), vec![])
+    }
+
+    /// Type variables in order of first appearance.
+    pub fn vars(&self) -> Vec<TypeVar> {
+        let mut vars = vec![];
+        self.collect_vars(&mut vars, true);
+        vars
+    }
+
+    // Rest variables of open records are only left out when naming the
+    // variables to display, as they are printed as `..`
+    fn collect_vars(&self, vars: &mut Vec<TypeVar>, rows: bool) {
+        match self {
+            Type::Var(var) => {
+                if !vars.contains(var) {
+                    vars.push(*var)
+                }
+            }
+            Type::Fn(params, ret) => {
+                params
+                    .iter()
+                    .for_each(|param| param.collect_vars(vars, rows));
+                ret.collect_vars(vars, rows);
+            }
+            Type::Named(_, args) => args.iter().for_each(|arg| arg.collect_vars(vars, rows)),
+            Type::Record(fields, rest) => {
+                fields
+                    .values()
+                    .for_each(|field| field.collect_vars(vars, rows));
+                match rest {
+                    Some(rest) if rows && !vars.contains(rest) => vars.push(*rest),
+                    _ => {}
+                }
+            }
+            Type::Number | Type::Bool | Type::String | Type::Unit => {}
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, names: &HashMap<TypeVar, String>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "Number"),
+            Type::Bool => write!(f, "Bool"),
+            Type::String => write!(f, "String"),
+            Type::Unit => write!(f, "Unit"),
+            Type::Var(var) => match names.get(var) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "'t{}", var.0),
+            },
+            Type::Fn(params, ret) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    param.write(f, names)?;
+                }
+                write!(f, ") -> ")?;
+                ret.write(f, names)
+            }
+            Type::Named(name, args) => {
+                write!(f, "{}", name)?;
+                if args.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "<")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    arg.write(f, names)?;
+                }
+                write!(f, ">")
+            }
+            Type::Record(fields, rest) => {
+                write!(f, "{{")?;
+                for (i, (name, field)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {}: ", name)?;
+                    field.write(f, names)?;
+                }
+                match (fields.is_empty(), rest) {
+                    (true, Some(_)) => write!(f, " .. }}"),
+                    (false, Some(_)) => write!(f, ", .. }}"),
+                    (true, None) => write!(f, "}}"),
+                    (false, None) => write!(f, " }}"),
+                }
+            }
+        }
+    }
+}
+
+// Names type variables `'a`, `'b`, ... in order of appearance
+fn var_names(vars: &[TypeVar]) -> HashMap<TypeVar, String> {
+    vars.iter()
+        .enumerate()
+        .map(|(i, var)| {
+            let letter = (b'a' + (i % 26) as u8) as char;
+            let name = match i / 26 {
+                0 => format!("'{}", letter),
+                n => format!("'{}{}", letter, n),
+            };
+            (*var, name)
+        })
+        .collect()
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut vars = vec![];
+        self.collect_vars(&mut vars, false);
+        self.write(f, &var_names(&vars))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdtVariant {
+    pub name: String,
+    pub fields: Vec<Type>,
+}
+
+/// A type declared with `type`. Its fields refer to the parameters as
+/// variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Adt {
+    pub name: String,
+    pub params: Vec<TypeVar>,
+    pub variants: Vec<AdtVariant>,
+}
+
+impl Adt {
+    /// The type of the ADT applied to its parameters.
+    pub fn ty(&self) -> Type {
+        Type::Named(
+            self.name.clone(),
+            self.params.iter().map(|param| Type::Var(*param)).collect(),
+        )
+    }
+}
+
+/// A type generalized over `vars`, as given to `let` and `fn` bindings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scheme {
+    pub vars: Vec<TypeVar>,
+    pub ty: Type,
+}
+
+impl Scheme {
+    pub fn new(vars: Vec<TypeVar>, ty: Type) -> Self {
+        Self { vars, ty }
+    }
+
+    pub fn mono(ty: Type) -> Self {
+        Self::new(vec![], ty)
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.ty.fmt(f)
+    }
+}
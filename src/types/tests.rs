@@ -0,0 +1,680 @@
+use super::*;
+use crate::ast::Parser;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Position;
+use crate::resolve::{resolve_program, Resolution};
+
+fn check(input: &str) -> (Resolution, Typing) {
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program(&program);
+    let typing = check_program(&program, &resolution);
+    (resolution, typing)
+}
+
+// The type of the binding declared by the statement starting at `prefix`
+fn binding_type(input: &str, prefix: &str) -> String {
+    let (resolution, typing) = check(input);
+    assert_eq!(typing.diagnostics, vec![], "in {}", input);
+    let start = input.find(prefix).unwrap() as u32;
+    let binding = resolution
+        .bindings
+        .iter()
+        .find(|binding| binding.pos.start == start)
+        .unwrap();
+    typing.binding_type(binding.id).unwrap().to_string()
+}
+
+fn errors(input: &str) -> Vec<Diagnostic> {
+    check(input).1.diagnostics
+}
+
+#[test]
+fn infer_literals_and_operators() {
+    assert_eq!(binding_type("let a = 1 + 2 * 3", "let a"), "Number");
+    assert_eq!(binding_type("let a = 'x' + 'y'", "let a"), "String");
+    assert_eq!(binding_type("let a = 1 < 2 && !false", "let a"), "Bool");
+    assert_eq!(binding_type("let a = 'x' == 'y'", "let a"), "Bool");
+    assert_eq!(binding_type("let a = {}", "let a"), "Unit");
+}
+
+#[test]
+fn infer_functions() {
+    let input = "fn add(a, b) { a + b }\nfn twice(f, x) { f(f(x)) }";
+    assert_eq!(
+        binding_type(input, "fn add"),
+        "fn(Number, Number) -> Number"
+    );
+    assert_eq!(
+        binding_type(input, "fn twice"),
+        "fn(fn('a) -> 'a, 'a) -> 'a"
+    );
+    assert_eq!(
+        binding_type(
+            "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }",
+            "fn"
+        ),
+        "fn(Number) -> Number"
+    );
+}
+
+#[test]
+fn infer_let_polymorphism() {
+    let input = "fn id(x) { x }\nlet a = id(1);\nlet b = id(true);\nlet f = id;";
+    assert_eq!(binding_type(input, "let a"), "Number");
+    assert_eq!(binding_type(input, "let b"), "Bool");
+    assert_eq!(binding_type(input, "let f"), "fn('a) -> 'a");
+}
+
+#[test]
+fn infer_state_is_monomorphic() {
+    let input = "fn id(x) { x }\nstate f = id;\nf(1);\nf(true);";
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "expected `Number`, found `Bool`",
+            Position::new(37, 41)
+        )]
+    );
+}
+
+#[test]
+fn check_assignments() {
+    let input = "state n = 0;\nlet r = n = 1;";
+    assert_eq!(binding_type(input, "let r"), "Unit");
+    assert_eq!(
+        errors("state n = 0;\nn = 'one'"),
+        vec![Diagnostic::error(
+            "expected `Number`, found `String`",
+            Position::new(17, 22)
+        )]
+    );
+}
+
+#[test]
+fn check_elements() {
+    let input =
+        "fn Button(props) { <button>{props.label}</button> }\nlet b = <Button label='Hi' />;";
+    assert_eq!(binding_type(input, "let b"), "Element");
+    assert_eq!(
+        binding_type(input, "fn Button"),
+        "fn({ label: 'a, .. }) -> Element"
+    );
+    let input =
+        "fn Card(props) -> Element { <div>{props.children}</div> }\nlet c = <Card>text</Card>;";
+    assert_eq!(binding_type(input, "let c"), "Element");
+    assert_eq!(
+        binding_type(input, "fn Card"),
+        "fn({ children: 'a, .. }) -> Element"
+    );
+
+    // A component is called with its attributes as props
+    let input = "fn Card(props) { let n = props.title + 1; <div /> }\n<Card title='Hi' />";
+    let start = input.rfind("Card").unwrap() as u32;
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "expected `fn({ title: String }) -> Element`, found `fn({ title: Number, .. }) -> Element`",
+            Position::new(start, start + 4)
+        )]
+    );
+}
+
+#[test]
+fn check_components() {
+    let input = "component Greeting(name: String, excited) {\n    state clicks = 0;\n    <p>{name}{if excited { '!' } else { '' }}</p>\n}\nlet g = <Greeting name='Ada' excited={true} />;";
+    assert_eq!(binding_type(input, "let g"), "Element");
+    assert_eq!(
+        binding_type(input, "component Greeting"),
+        "fn({ excited: Bool, name: String }) -> Element"
+    );
+
+    // Props are typed, each at its attribute, and every one of them has to
+    // be passed
+    let input = "component Greeting(name: String) { <p>{name}</p> }\n<Greeting name={1} />";
+    let start = input.find("name={1}").unwrap() as u32;
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "prop `name` expects `String`, found `Number`",
+            Position::new(start, start + 8)
+        )]
+    );
+    let input = "component Button(label: Number, wide: Bool) { <button /> }\n\
+                 <Button label='Hi' wide extra={1} />";
+    let label = input.find("label='Hi'").unwrap() as u32;
+    let extra = input.find("extra={1}").unwrap() as u32;
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error(
+                "`Button` has no prop `extra`",
+                Position::new(extra, extra + 9)
+            ),
+            Diagnostic::error(
+                "prop `label` expects `Number`, found `String`",
+                Position::new(label, label + 10)
+            ),
+        ]
+    );
+    let input = "component Greeting(name, excited) { <p>{name}</p> }\n<Greeting />";
+    let start = input.rfind("Greeting").unwrap() as u32;
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error(
+                "`Greeting` is missing the prop `excited`",
+                Position::new(start, start + 8)
+            ),
+            Diagnostic::error(
+                "`Greeting` is missing the prop `name`",
+                Position::new(start, start + 8)
+            ),
+        ]
+    );
+    // `key` is for renderers, not a prop
+    let input = "component Greeting(name) { <p>{name}</p> }\n<Greeting key={1} name='Ada' />";
+    assert_eq!(errors(input), vec![]);
+
+    // The body is markup
+    let input = "component Broken() { 1 }";
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "expected `Element`, found `Number`",
+            Position::new(19, 24)
+        )]
+    );
+}
+
+#[test]
+fn expose_expression_types() {
+    let input = "let a = f(1) + 2;\nfn f(x) { x }";
+    let (_, typing) = check(input);
+    assert_eq!(typing.type_of(&Position::new(8, 12)), Some(&Type::Number));
+    assert_eq!(typing.type_of(&Position::new(10, 11)), Some(&Type::Number));
+    assert_eq!(
+        typing.type_of(&Position::new(8, 9)),
+        Some(&Type::func(vec![Type::Number], Type::Number))
+    );
+}
+
+#[test]
+fn report_calling_a_bool() {
+    let input = "let flag = true;\nflag(1);";
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error("`Bool` is not a function", Position::new(17, 24))
+                .with_label("this is `Bool`", Position::new(17, 21))
+        ]
+    );
+}
+
+#[test]
+fn report_adding_string_to_number() {
+    let input = "let a = 'one' + 1;";
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error("cannot add `String` and `Number`", Position::new(8, 17))
+                .with_label("this is `String`", Position::new(8, 13))
+                .with_label("this is `Number`", Position::new(16, 17))
+        ]
+    );
+    assert_eq!(
+        errors("let a = true + false;"),
+        vec![Diagnostic::error(
+            "`+` needs numbers or strings, found `Bool`",
+            Position::new(8, 20)
+        )]
+    );
+}
+
+#[test]
+fn default_addable_to_number() {
+    // `+` has no way to stay generic, so `add` only takes numbers
+    let input = "fn add(a, b) { a + b }\nlet s = add('x', 'y');";
+    assert_eq!(
+        binding_type(input.lines().next().unwrap(), "fn add"),
+        "fn(Number, Number) -> Number"
+    );
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error("expected `Number`, found `String`", Position::new(35, 38)),
+            Diagnostic::error("expected `Number`, found `String`", Position::new(40, 43)),
+        ]
+    );
+}
+
+#[test]
+fn report_other_mismatches() {
+    assert_eq!(
+        errors("fn f(a, b) { a }\nf(1)"),
+        vec![
+            Diagnostic::error("expected 2 arguments, found 1", Position::new(17, 21))
+                .with_label("this is `fn('a, 'b) -> 'a`", Position::new(17, 18))
+        ]
+    );
+    assert_eq!(
+        errors("if 1 { 2 } else { 'three' }"),
+        vec![
+            Diagnostic::error("expected `Bool`, found `Number`", Position::new(3, 4)),
+            Diagnostic::error(
+                "`if` and `else` have different types",
+                Position::new(16, 27)
+            )
+            .with_label("this is `Number`", Position::new(5, 10))
+            .with_label("this is `String`", Position::new(16, 27)),
+        ]
+    );
+    assert_eq!(
+        errors("fn f(x) { x(x) }"),
+        vec![Diagnostic::error(
+            "cannot construct the infinite type `fn('a) -> 'b` = `'a`",
+            Position::new(10, 11)
+        )]
+    );
+}
+
+#[test]
+fn check_annotations() {
+    let input = "fn apply(f: fn(Number) -> Bool, x) -> Bool { f(x) }";
+    assert_eq!(
+        binding_type(input, "fn"),
+        "fn(fn(Number) -> Bool, Number) -> Bool"
+    );
+    assert_eq!(
+        binding_type(
+            "let f: fn(String) -> String = fn_id; fn fn_id(x) { x }",
+            "let"
+        ),
+        "fn(String) -> String"
+    );
+    assert_eq!(
+        binding_type("fn id(x) { x }\nlet f: fn(Bool) -> Bool = id;", "let"),
+        "fn(Bool) -> Bool"
+    );
+
+    assert_eq!(
+        errors("let a: Number = 'one';"),
+        vec![
+            Diagnostic::error("expected `Number`, found `String`", Position::new(16, 21))
+                .with_label("expected because of this annotation", Position::new(7, 13))
+        ]
+    );
+    assert_eq!(
+        errors("fn f(x: Number) -> String { x }"),
+        vec![
+            Diagnostic::error("expected `String`, found `Number`", Position::new(26, 31))
+                .with_label("expected because of this annotation", Position::new(19, 25))
+        ]
+    );
+    assert_eq!(
+        errors("state s: Strin = 'a';\nlet n: Number<Bool> = 1;"),
+        vec![
+            Diagnostic::error("unknown type `Strin`", Position::new(9, 14)),
+            Diagnostic::error("`Number` takes no type arguments", Position::new(29, 41)),
+        ]
+    );
+}
+
+#[test]
+fn infer_lists() {
+    assert_eq!(binding_type("let a = [1, 2, 3,]", "let a"), "List<Number>");
+    assert_eq!(binding_type("let a = []", "let a"), "List<'a>");
+    assert_eq!(
+        binding_type("let a = ['x']; let b = [..a, 'y', ..a]", "let b"),
+        "List<String>"
+    );
+    assert_eq!(binding_type("let a = [[true]][0][0]", "let a"), "Bool");
+    assert_eq!(
+        binding_type("fn first(xs) { xs[0] }", "fn"),
+        "fn(List<'a>) -> 'a"
+    );
+    assert_eq!(
+        binding_type("fn f(xs: List<Number>, i) { xs[i] }", "fn"),
+        "fn(List<Number>, Number) -> Number"
+    );
+
+    assert_eq!(
+        errors("let a = [1, 'two'];"),
+        vec![Diagnostic::error(
+            "expected `Number`, found `String`",
+            Position::new(12, 17)
+        )]
+    );
+    assert_eq!(
+        errors("let a = [..1];"),
+        vec![Diagnostic::error(
+            "expected `List<'a>`, found `Number`",
+            Position::new(11, 12)
+        )]
+    );
+    assert_eq!(
+        errors("let a = [1]['0'];\nlet b = 1[0];"),
+        vec![
+            Diagnostic::error("expected `Number`, found `String`", Position::new(12, 15)),
+            Diagnostic::error("`Number` cannot be indexed", Position::new(26, 30))
+                .with_label("this is `Number`", Position::new(26, 27)),
+        ]
+    );
+    assert_eq!(
+        errors("let a: List = [];"),
+        vec![Diagnostic::error(
+            "`List` takes 1 type argument, found 0",
+            Position::new(7, 11)
+        )]
+    );
+}
+
+#[test]
+fn infer_records() {
+    assert_eq!(
+        binding_type("let a = { name: 'x', count: 1 }", "let a"),
+        "{ count: Number, name: String }"
+    );
+    assert_eq!(
+        binding_type("let name = 'x'; let a = { name, b: [name] }.b", "let a"),
+        "List<String>"
+    );
+    assert_eq!(
+        binding_type("fn name(x) { x.name }", "fn"),
+        "fn({ name: 'a, .. }) -> 'a"
+    );
+    assert_eq!(
+        binding_type("fn area(r) { r.width * r.height }", "fn"),
+        "fn({ height: Number, width: Number, .. }) -> Number"
+    );
+    assert_eq!(
+        binding_type(
+            "fn name(x) { x.name }\nlet a = name({ name: true, other: 1 })",
+            "let a"
+        ),
+        "Bool"
+    );
+    assert_eq!(
+        binding_type("fn inc(x) { { ..x, count: x.count + 1 } }", "fn"),
+        "fn({ count: Number, .. }) -> { count: Number, .. }"
+    );
+    assert_eq!(
+        binding_type("let a = { ..{ name: 'x', count: 1 }, count: 2 }", "let a"),
+        "{ count: Number, name: String }"
+    );
+    let input = "let a = { name: 'x' };\nlet b = { ..a, count: 1 };\nlet c = b.count;";
+    assert_eq!(
+        binding_type(input, "let b"),
+        "{ count: Number, name: String }"
+    );
+    assert_eq!(binding_type(input, "let c"), "Number");
+}
+
+#[test]
+fn check_records() {
+    assert_eq!(
+        errors("let a = { name: 'x' }.count;"),
+        vec![Diagnostic::error(
+            "no field `count` on `{ name: String }`",
+            Position::new(8, 27)
+        )
+        .with_label("this is `{ name: String }`", Position::new(8, 21))]
+    );
+    assert_eq!(
+        errors("let a = { count: 1 };\nlet b = { ..a, count: 'x', name: 1 };"),
+        vec![Diagnostic::error(
+            "expected `Number`, found `String`",
+            Position::new(44, 47)
+        )]
+    );
+    // A record that is not known yet must have the fields an update gives
+    assert_eq!(
+        errors("fn f(r) { { ..r, y: 2 } }\nlet a = f({ x: 1 });"),
+        vec![Diagnostic::error(
+            "expected `{ y: Number, .. }`, found `{ x: Number }`",
+            Position::new(36, 44)
+        )]
+    );
+    assert_eq!(
+        errors("let a = { x: 1, x: 2 };"),
+        vec![
+            Diagnostic::error("field `x` is defined twice", Position::new(16, 20))
+                .with_label("first defined here", Position::new(10, 14))
+        ]
+    );
+    assert_eq!(
+        errors("fn f(r) { r.x + 1 }\nlet a = f({ y: 1 });"),
+        vec![Diagnostic::error(
+            "expected `{ x: Number, .. }`, found `{ y: Number }`",
+            Position::new(30, 38)
+        )]
+    );
+    assert_eq!(
+        errors("let a = 1.x;"),
+        vec![
+            Diagnostic::error("no field `x` on `Number`", Position::new(8, 11))
+                .with_label("this is `Number`", Position::new(8, 9))
+        ]
+    );
+}
+
+#[test]
+fn infer_lambdas() {
+    assert_eq!(
+        binding_type("let inc = fn(x) => x + 1", "let"),
+        "fn(Number) -> Number"
+    );
+    assert_eq!(binding_type("let id = fn(x) { x }", "let"), "fn('a) -> 'a");
+    assert_eq!(
+        binding_type("let id = fn(x) => x; let a = [id(1), id(2)]", "let a"),
+        "List<Number>"
+    );
+    assert_eq!(
+        binding_type(
+            "fn map(xs, f) { [f(xs[0])] }\nlet a = map([1], fn(n) => n > 0)",
+            "let a"
+        ),
+        "List<Bool>"
+    );
+    assert_eq!(
+        binding_type(
+            "let k = fn(x: String) -> fn(Bool) -> String => fn(y) => x",
+            "let"
+        ),
+        "fn(String) -> fn(Bool) -> String"
+    );
+
+    assert_eq!(
+        errors("let f = fn(x) -> Number { 'x' };"),
+        vec![
+            Diagnostic::error("expected `Number`, found `String`", Position::new(24, 31))
+                .with_label("expected because of this annotation", Position::new(17, 23))
+        ]
+    );
+}
+
+#[test]
+fn infer_adts() {
+    let shape = "type Shape = Circle(Number) | Rect(Number, Number) | Dot;\n";
+    assert_eq!(
+        binding_type(&format!("{}let a = Rect(1, 2)", shape), "let a"),
+        "Shape"
+    );
+    assert_eq!(
+        binding_type(&format!("{}let a = Dot", shape), "let a"),
+        "Shape"
+    );
+    assert_eq!(
+        binding_type(
+            &format!(
+                "{}fn area(s) {{ match s {{ Circle(r) => 3 * r * r, Rect(w, h) => w * h, Dot => 0 }} }}",
+                shape
+            ),
+            "fn"
+        ),
+        "fn(Shape) -> Number"
+    );
+
+    let option = "type Option<T> = Some(T) | None;\n";
+    assert_eq!(
+        binding_type(&format!("{}let a = Some", option), "let a"),
+        "fn('a) -> Option<'a>"
+    );
+    assert_eq!(
+        binding_type(
+            &format!(
+                "{}fn get(o, default) {{ match o {{ Some(x) => x, None => default }} }}",
+                option
+            ),
+            "fn"
+        ),
+        "fn(Option<'a>, 'a) -> 'a"
+    );
+    assert_eq!(
+        binding_type(
+            &format!("{}let a: Option<List<Bool>> = Some([true])", option),
+            "let a"
+        ),
+        "Option<List<Bool>>"
+    );
+    // Types can refer to themselves and each other
+    assert_eq!(
+        binding_type(
+            "type Tree = Leaf | Node(Tree, Item, Tree);\ntype Item = Item(String);\nlet a = Node(Leaf, Item('x'), Leaf)",
+            "let a"
+        ),
+        "Tree"
+    );
+}
+
+#[test]
+fn check_match_types() {
+    let option = "type Option<T> = Some(T) | None;\n";
+    let errors = |input: &str| errors(&format!("{}{}", option, input));
+    // The span of `text` in `input`, after the declaration
+    let at = |input: &str, text: &str| {
+        let start = (option.len() + input.find(text).unwrap()) as u32;
+        Position::new(start, start + text.len() as u32)
+    };
+
+    let input = "match Some(1) { Some('x') => 1, _ => 2 };";
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "expected `Number`, found `String`",
+            at(input, "'x'")
+        )]
+    );
+    let input = "match Some(1) { Some(x) => x + 1, None => 'none' };";
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error("`match` arms have different types", at(input, "'none'"))
+                .with_label("this is `Number`", at(input, "x + 1"))
+                .with_label("this is `String`", at(input, "'none'"))
+        ]
+    );
+    let input = "match None { Some(a, b) => 1, _ => 2 };";
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "`Some` has 1 field, but the pattern has 2",
+            at(input, "Some(a, b)")
+        )]
+    );
+    let input = "match Some(1) { Some(x) if x + 1 => 1, _ => 2 };";
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::error(
+            "expected `Bool`, found `Number`",
+            at(input, "x + 1")
+        )]
+    );
+    let input = "let a: Option = None; let b: Option<Number, Bool> = None;";
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error(
+                "`Option` takes 1 type argument, found 0",
+                at(input, "Option")
+            ),
+            Diagnostic::error(
+                "`Option` takes 1 type argument, found 2",
+                at(input, "Option<Number, Bool>")
+            ),
+        ]
+    );
+    let input = "type Option = A; type Number = B;";
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::error(
+                "type `Option` is defined twice",
+                at(input, "type Option = A;")
+            )
+            .with_label("first defined here", Position::new(0, 32)),
+            Diagnostic::error("`Number` is a built-in type", at(input, "type Number = B;")),
+        ]
+    );
+}
+
+#[test]
+fn check_exhaustiveness() {
+    let types = "type Shape = Circle(Number) | Rect(Number, Number) | Dot;\ntype Option<T> = Some(T) | None;\n";
+    let errors = |input: &str| errors(&format!("{}{}", types, input));
+    let at = |input: &str, text: &str| {
+        let start = (types.len() + input.find(text).unwrap()) as u32;
+        Position::new(start, start + text.len() as u32)
+    };
+    let missing = |input: &str, witness: &str, ty: &str| {
+        let scrutinee = input.split(' ').nth(1).unwrap();
+        vec![Diagnostic::error(
+            format!("non-exhaustive match, `{}` is not covered", witness),
+            at(input, input.trim_end_matches(';')),
+        )
+        .with_label(format!("this is `{}`", ty), at(input, scrutinee))]
+    };
+
+    let input = "match Dot { Circle(r) => r, Dot => 0 };";
+    assert_eq!(errors(input), missing(input, "Rect(_, _)", "Shape"));
+    let input = "match Dot { Circle(r) => r, Rect(w, 1) => w, Dot => 0 };";
+    assert_eq!(errors(input), missing(input, "Rect(_, _)", "Shape"));
+    let input = "match None { Some(true) => 1, None => 0 };";
+    assert_eq!(errors(input), missing(input, "Some(false)", "Option<Bool>"));
+    let input = "match Some(Dot) { Some(Circle(_)) => 1, Some(Rect(_, _)) => 2, None => 0 };";
+    assert_eq!(errors(input), missing(input, "Some(Dot)", "Option<Shape>"));
+    let input = "match 'x' { 'a' => 1, 'b' => 2 };";
+    assert_eq!(errors(input), missing(input, "_", "String"));
+    // A guard may fail, so its arm does not count
+    let input = "match true { true if 1 > 2 => 1, false => 0 };";
+    assert_eq!(errors(input), missing(input, "true", "Bool"));
+
+    // Covered, by variants, wildcards, bindings or both booleans
+    for input in [
+        "match Dot { Circle(_) => 1, Rect(_, _) => 2, Dot => 3 };",
+        "match Dot { Circle(1) => 1, _ => 2 };",
+        "match Some(1) { Some(1) => 1, Some(n) => n, None => 0 };",
+        "match Some(true) { Some(true) => 1, Some(false) => 2, None => 0 };",
+        "match None { Some(Some(x)) => x, Some(None) => 0, None => 0 };",
+    ] {
+        assert_eq!(errors(input), vec![], "in {}", input);
+    }
+
+    let input = "match Some(1) { Some(n) => n, None => 0, Some(2) => 2, _ => 3 };";
+    let wildcard = at(input, "_ => 3").start;
+    assert_eq!(
+        errors(input),
+        vec![
+            Diagnostic::warning("unreachable pattern", at(input, "Some(2)")),
+            Diagnostic::warning("unreachable pattern", Position::new(wildcard, wildcard + 1)),
+        ]
+    );
+    let input = "match true { true => 1, false => 0, true => 2 };";
+    let last = at(input, "true => 2").start;
+    assert_eq!(
+        errors(input),
+        vec![Diagnostic::warning(
+            "unreachable pattern",
+            Position::new(last, last + 4)
+        )]
+    );
+}
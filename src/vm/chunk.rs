@@ -0,0 +1,178 @@
+use std::rc::Rc;
+
+use crate::interp::Value;
+use crate::lexer::Position;
+
+/// One instruction. Operands index the constants, slots, cells, upvalues,
+/// globals or functions of the enclosing chunk, or are jump targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Constant(u32),
+    Unit,
+    True,
+    False,
+    Pop,
+    // Locals live in stack slots of the frame. `Set` instructions pop the value.
+    GetLocal(u32),
+    SetLocal(u32),
+    // Locals captured by a closure live in cells shared with the closure
+    GetCell(u32),
+    SetCell(u32),
+    GetUpvalue(u32),
+    SetUpvalue(u32),
+    GetGlobal(u32),
+    SetGlobal(u32),
+    // A name that did not resolve, with its constant
+    Unbound(u32),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Neg,
+    Not,
+    Jump(u32),
+    // Pops a `Bool`, and jumps if it is `false`
+    JumpIfFalse(u32),
+    // With the number of arguments, which are above the called value
+    Call(u32),
+    Return,
+    // Creates a closure of a function of the chunk
+    Closure(u32),
+    // Pushes an empty list, that `Push` and `Spread` add to
+    List,
+    Push,
+    Spread,
+    Index,
+    Record,
+    // With the constant of the field name
+    SetField(u32),
+    Field(u32),
+    // Whether the value is the variant of a binding, with as many fields
+    IsVariant(u32, u32),
+    VariantField(u32),
+    // Fails with the value of a slot, which no arm matched
+    NoMatch(u32),
+    // Push an empty element, that `SetAttribute` and `AddChild` add to: an
+    // HTML tag with the constant of its name, a fragment, or a component
+    // whose function is popped
+    Element(u32),
+    Fragment,
+    Component,
+    // With the constant of the attribute name
+    SetAttribute(u32),
+    AddChild,
+}
+
+/// The code of one function, with the span of each instruction.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    pub functions: Vec<Rc<Proto>>,
+    // Offset of the first instruction with each span, in order. Consecutive
+    // instructions of the same expression share an entry.
+    spans: Vec<(u32, Position)>,
+    // The spans of the operands of each binary operator, by its offset, for
+    // the errors about their types
+    operands: Vec<(u32, [Position; 2])>,
+}
+
+impl Chunk {
+    pub fn emit(&mut self, op: Op, pos: Position) -> u32 {
+        let offset = self.code.len() as u32;
+        if self.spans.last().map(|(_, last)| *last) != Some(pos) {
+            self.spans.push((offset, pos));
+        }
+        self.code.push(op);
+        offset
+    }
+
+    /// Emits a binary operator, with the spans of its operands.
+    pub fn emit_binary(&mut self, op: Op, pos: Position, operands: [Position; 2]) -> u32 {
+        let offset = self.emit(op, pos);
+        self.operands.push((offset, operands));
+        offset
+    }
+
+    /// Points the jump at `offset` to the next instruction.
+    pub fn patch(&mut self, offset: u32) {
+        let target = self.code.len() as u32;
+        match &mut self.code[offset as usize] {
+            Op::Jump(to) | Op::JumpIfFalse(to) => *to = target,
+            op => panic!("cannot patch {:?}", op),
+        }
+    }
+
+    pub fn constant(&mut self, value: Value) -> u32 {
+        let found = self
+            .constants
+            .iter()
+            .position(|constant| match (constant, &value) {
+                // Functions are told apart by identity only
+                (Value::Function(_), _) | (_, Value::Function(_)) => false,
+                (Value::Number(a), Value::Number(b)) => a.to_bits() == b.to_bits(),
+                (a, b) => a == b,
+            });
+        match found {
+            Some(index) => index as u32,
+            None => {
+                self.constants.push(value);
+                self.constants.len() as u32 - 1
+            }
+        }
+    }
+
+    /// The span of the instruction at `offset`.
+    pub fn span(&self, offset: u32) -> Position {
+        let index = self.spans.partition_point(|(start, _)| *start <= offset);
+        self.spans[index.saturating_sub(1)].1
+    }
+
+    /// The span of an operand of the binary operator at `offset`, the left
+    /// one first, or that of the operator if it has none.
+    pub fn operand_span(&self, offset: u32, operand: usize) -> Position {
+        match self.operands.binary_search_by_key(&offset, |(at, _)| *at) {
+            Ok(index) => self.operands[index].1[operand],
+            Err(_) => self.span(offset),
+        }
+    }
+
+    pub fn spans(&self) -> &[(u32, Position)] {
+        &self.spans
+    }
+}
+
+/// Where a closure gets a captured binding from when it is created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upvalue {
+    // A cell of the function creating the closure
+    Cell(u32),
+    // An upvalue of the function creating the closure
+    Upvalue(u32),
+}
+
+/// A compiled function, or the top level of a program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Proto {
+    pub name: Option<String>,
+    pub arity: u32,
+    // Stack slots, the parameters first
+    pub locals: u32,
+    pub cells: u32,
+    pub upvalues: Vec<Upvalue>,
+    pub chunk: Chunk,
+}
+
+/// A compiled program. Bindings of the program scope are globals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bytecode {
+    pub main: Rc<Proto>,
+    pub globals: Vec<String>,
+}
@@ -0,0 +1,689 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::chunk::*;
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::interp::{Constructor, Function, Value, VariantValue};
+use crate::lexer::Position;
+use crate::resolve::*;
+
+/// Compiles a resolved program to bytecode. The VM calls a component like a
+/// function, without an instance to keep its `state` in, so a component
+/// that declares `state` is rejected.
+pub fn compile_program(
+    program: &Program,
+    resolution: &Resolution,
+) -> Result<Bytecode, Vec<Diagnostic>> {
+    let mut compiler = Compiler::new(resolution);
+    compiler.functions.push(FunctionState::new(vec![], false));
+    let end = program.stmts.last().map_or(0, |stmt| stmt.pos.end);
+    compiler.stmts(&program.stmts, Position::new(end, end));
+    compiler.emit(Op::Return, Position::new(end, end));
+    if !compiler.diagnostics.is_empty() {
+        return Err(compiler.diagnostics);
+    }
+
+    let state = compiler.functions.pop().unwrap();
+    Ok(Bytecode {
+        main: Rc::new(state.finish(None, 0)),
+        globals: compiler.global_names,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Storage {
+    Global(u32),
+    Local(u32),
+    Cell(u32),
+}
+
+struct FunctionState {
+    chunk: Chunk,
+    bindings: HashMap<BindingId, Storage>,
+    // What the function captures, in the order of its upvalues
+    upvalues: Vec<BindingId>,
+    locals: u32,
+    cells: u32,
+    component: bool,
+}
+
+impl FunctionState {
+    fn new(upvalues: Vec<BindingId>, component: bool) -> Self {
+        Self {
+            chunk: Chunk::default(),
+            bindings: HashMap::new(),
+            upvalues,
+            locals: 0,
+            cells: 0,
+            component,
+        }
+    }
+
+    fn finish(self, name: Option<String>, arity: u32) -> Proto {
+        Proto {
+            name,
+            arity,
+            locals: self.locals,
+            cells: self.cells,
+            upvalues: vec![],
+            chunk: self.chunk,
+        }
+    }
+}
+
+struct Compiler<'a> {
+    resolution: &'a Resolution,
+    captures: Captures,
+    // Every binding some closure captures
+    captured: HashSet<BindingId>,
+    globals: HashMap<BindingId, u32>,
+    global_names: Vec<String>,
+    // Innermost last
+    functions: Vec<FunctionState>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(resolution: &'a Resolution) -> Self {
+        let captures = find_captures(resolution);
+        let captured = captures.captures.values().flatten().copied().collect();
+        Self {
+            resolution,
+            captures,
+            captured,
+            globals: HashMap::new(),
+            global_names: vec![],
+            functions: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, op: Op, pos: Position) -> u32 {
+        self.current().chunk.emit(op, pos)
+    }
+
+    fn patch(&mut self, offset: u32) {
+        self.current().chunk.patch(offset)
+    }
+
+    fn constant(&mut self, value: Value, pos: Position) {
+        let index = self.current().chunk.constant(value);
+        self.emit(Op::Constant(index), pos);
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        self.current().chunk.constant(Value::string(name))
+    }
+
+    fn slot(&mut self) -> u32 {
+        let state = self.current();
+        state.locals += 1;
+        state.locals - 1
+    }
+
+    fn global(&mut self, id: BindingId) -> u32 {
+        if let Some(index) = self.globals.get(&id) {
+            return *index;
+        }
+        let index = self.global_names.len() as u32;
+        self.global_names
+            .push(self.resolution.binding(id).name.clone());
+        self.globals.insert(id, index);
+        index
+    }
+
+    // Picks where a binding of the current function lives, once
+    fn declare(&mut self, id: BindingId) -> Storage {
+        let binding = self.resolution.binding(id);
+        if self.resolution.scope(binding.scope).kind == ScopeKind::Program {
+            return Storage::Global(self.global(id));
+        }
+        if let Some(storage) = self.current().bindings.get(&id) {
+            return *storage;
+        }
+        let storage = if self.captured.contains(&id) {
+            let state = self.current();
+            state.cells += 1;
+            Storage::Cell(state.cells - 1)
+        } else {
+            Storage::Local(self.slot())
+        };
+        self.current().bindings.insert(id, storage);
+        storage
+    }
+
+    // Pops the value on top of the stack into a binding
+    fn store(&mut self, storage: Storage, pos: Position) {
+        let op = match storage {
+            Storage::Global(index) => Op::SetGlobal(index),
+            Storage::Local(slot) => Op::SetLocal(slot),
+            Storage::Cell(cell) => Op::SetCell(cell),
+        };
+        self.emit(op, pos);
+    }
+
+    fn define(&mut self, pos: &Position) {
+        match self.resolution.definitions.get(pos) {
+            Some(id) => {
+                let storage = self.declare(*id);
+                self.store(storage, *pos);
+            }
+            None => {
+                self.emit(Op::Pop, *pos);
+            }
+        }
+    }
+
+    fn load(&mut self, name: &str, pos: Position) {
+        let op = match self.resolution.uses.get(&pos) {
+            Some(id) => self.load_op(*id),
+            None => None,
+        };
+        let op = match op {
+            Some(op) => op,
+            None => Op::Unbound(self.name(name)),
+        };
+        self.emit(op, pos);
+    }
+
+    fn load_op(&mut self, id: BindingId) -> Option<Op> {
+        let binding = self.resolution.binding(id);
+        if self.resolution.scope(binding.scope).kind == ScopeKind::Program {
+            return Some(Op::GetGlobal(self.global(id)));
+        }
+        let state = self.current();
+        match state.bindings.get(&id) {
+            Some(Storage::Local(slot)) => Some(Op::GetLocal(*slot)),
+            Some(Storage::Cell(cell)) => Some(Op::GetCell(*cell)),
+            Some(Storage::Global(index)) => Some(Op::GetGlobal(*index)),
+            None => state
+                .upvalues
+                .iter()
+                .position(|upvalue| *upvalue == id)
+                .map(|index| Op::GetUpvalue(index as u32)),
+        }
+    }
+
+    // Writes the value on top of the stack to a `state` binding
+    fn assign(&mut self, target: &Expr) {
+        let op = match self.resolution.uses.get(&target.pos) {
+            Some(id) => self.load_op(*id),
+            None => None,
+        };
+        let op = match op {
+            Some(Op::GetGlobal(index)) => Op::SetGlobal(index),
+            Some(Op::GetLocal(slot)) => Op::SetLocal(slot),
+            Some(Op::GetCell(cell)) => Op::SetCell(cell),
+            Some(Op::GetUpvalue(index)) => Op::SetUpvalue(index),
+            _ => {
+                let name = match &target.kind {
+                    ExprKind::Identifier(identifier) => identifier.ident.as_str(),
+                    _ => "",
+                };
+                Op::Unbound(self.name(name))
+            }
+        };
+        self.emit(op, target.pos);
+    }
+
+    // Leaves the value of the last statement on the stack, like a block
+    fn stmts(&mut self, stmts: &[Stmt], pos: Position) {
+        // Functions and variants can be used anywhere in their scope. They
+        // get their storage first, so that closures can capture each other.
+        for stmt in stmts.iter().map(Stmt::declaration) {
+            let ids = match &stmt.kind {
+                StmtKind::Fn(_) | StmtKind::Component(_) => {
+                    vec![self.resolution.definitions.get(&stmt.pos)]
+                }
+                StmtKind::Type(type_stmt) => type_stmt
+                    .variants
+                    .iter()
+                    .map(|variant| self.resolution.definitions.get(&variant.pos))
+                    .collect(),
+                _ => vec![],
+            };
+            for id in ids.into_iter().flatten() {
+                self.declare(*id);
+            }
+        }
+        for stmt in stmts.iter().map(Stmt::declaration) {
+            match &stmt.kind {
+                StmtKind::Fn(fn_stmt) => {
+                    self.function(
+                        stmt.pos,
+                        Some(&fn_stmt.name),
+                        &fn_stmt.params,
+                        &fn_stmt.body,
+                        false,
+                    );
+                    self.define(&stmt.pos);
+                }
+                // A function of its props, as the VM keeps no instances
+                StmtKind::Component(component) => {
+                    self.function(
+                        stmt.pos,
+                        Some(&component.name),
+                        &component.props,
+                        &component.body,
+                        true,
+                    );
+                    self.define(&stmt.pos);
+                }
+                StmtKind::Type(type_stmt) => self.variants(type_stmt),
+                _ => {}
+            }
+        }
+
+        if stmts.is_empty() {
+            self.emit(Op::Unit, pos);
+        }
+        for (i, stmt) in stmts.iter().enumerate() {
+            self.stmt(stmt, i + 1 == stmts.len());
+        }
+    }
+
+    fn variants(&mut self, type_stmt: &TypeStmt) {
+        for variant in type_stmt.variants.iter() {
+            let Some(id) = self.resolution.definitions.get(&variant.pos) else {
+                continue;
+            };
+            let value = if variant.fields.is_empty() {
+                Value::Variant(Rc::new(VariantValue {
+                    ty: type_stmt.name.clone(),
+                    name: variant.name.clone(),
+                    id: *id,
+                    fields: vec![],
+                }))
+            } else {
+                Value::Function(Rc::new(Function::Constructor(Constructor {
+                    ty: type_stmt.name.clone(),
+                    name: variant.name.clone(),
+                    id: *id,
+                    arity: variant.fields.len(),
+                })))
+            };
+            self.constant(value, variant.pos);
+            self.define(&variant.pos);
+        }
+    }
+
+    // With `keep`, pushes the value of an expression statement, or `Unit`
+    // for declarations
+    fn stmt(&mut self, stmt: &Stmt, keep: bool) {
+        match &stmt.kind {
+            StmtKind::Let(LetStmt { value, .. }) | StmtKind::State(StateStmt { value, .. }) => {
+                if matches!(stmt.kind, StmtKind::State(_)) && self.current().component {
+                    self.diagnostics.push(Diagnostic::error(
+                        "the VM keeps no component instances, so a component cannot have `state`",
+                        stmt.pos,
+                    ));
+                }
+                self.expr(value);
+                self.define(&stmt.pos);
+            }
+            StmtKind::Fn(_) | StmtKind::Component(_) | StmtKind::Type(_) | StmtKind::Import(_) => {}
+            StmtKind::Export(exported) => return self.stmt(exported, keep),
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+                if !keep {
+                    self.emit(Op::Pop, stmt.pos);
+                }
+                return;
+            }
+        }
+        if keep {
+            self.emit(Op::Unit, stmt.pos);
+        }
+    }
+
+    // Compiles a function and pushes a closure of it. A component takes a
+    // record of props, which are copied out of it by name.
+    fn function(
+        &mut self,
+        pos: Position,
+        name: Option<&str>,
+        params: &[Param],
+        body: &Expr,
+        component: bool,
+    ) {
+        let upvalues = self.captures.of(&pos).to_vec();
+        self.functions
+            .push(FunctionState::new(upvalues.clone(), component));
+
+        let arity = if component {
+            let props = self.slot();
+            for param in params.iter() {
+                self.emit(Op::GetLocal(props), param.pos);
+                let name = self.name(&param.name);
+                self.emit(Op::Field(name), param.pos);
+                self.define(&param.pos);
+            }
+            1
+        } else {
+            self.params(params);
+            params.len() as u32
+        };
+        self.expr(body);
+        self.emit(Op::Return, body.pos);
+
+        let state = self.functions.pop().unwrap();
+        let mut proto = state.finish(name.map(str::to_string), arity);
+        let enclosing = self.current();
+        proto.upvalues = upvalues
+            .iter()
+            .map(|id| match enclosing.bindings.get(id) {
+                Some(Storage::Cell(cell)) => Upvalue::Cell(*cell),
+                _ => {
+                    let index = enclosing
+                        .upvalues
+                        .iter()
+                        .position(|upvalue| upvalue == id)
+                        .expect("captured binding is not in scope");
+                    Upvalue::Upvalue(index as u32)
+                }
+            })
+            .collect();
+
+        let chunk = &mut enclosing.chunk;
+        chunk.functions.push(Rc::new(proto));
+        let index = chunk.functions.len() as u32 - 1;
+        self.emit(Op::Closure(index), pos);
+    }
+
+    // Gives each parameter a slot, in order
+    fn params(&mut self, params: &[Param]) {
+        let mut captured_params = vec![];
+        for param in params.iter() {
+            let slot = self.slot();
+            if let Some(id) = self.resolution.definitions.get(&param.pos) {
+                captured_params.push((slot, *id, param.pos));
+            }
+        }
+        // Captured parameters move from their slot to a cell
+        for (slot, id, pos) in captured_params {
+            if self.captured.contains(&id) {
+                self.emit(Op::GetLocal(slot), pos);
+                let storage = self.declare(id);
+                self.store(storage, pos);
+            } else {
+                self.current().bindings.insert(id, Storage::Local(slot));
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        let pos = expr.pos;
+        match &expr.kind {
+            ExprKind::Identifier(identifier) => self.load(&identifier.ident, pos),
+            ExprKind::Literal(LiteralExpr::Bool(true)) => {
+                self.emit(Op::True, pos);
+            }
+            ExprKind::Literal(LiteralExpr::Bool(false)) => {
+                self.emit(Op::False, pos);
+            }
+            ExprKind::Literal(LiteralExpr::Number(number)) => {
+                self.constant(Value::Number(*number as f64), pos)
+            }
+            ExprKind::Literal(LiteralExpr::String(string)) => {
+                self.constant(Value::string(string.as_str()), pos)
+            }
+            ExprKind::Call(call) => {
+                self.expr(&call.called);
+                call.args.iter().for_each(|arg| self.expr(arg));
+                self.emit(Op::Call(call.args.len() as u32), pos);
+            }
+            ExprKind::Binary(binary) => self.binary(expr, binary),
+            ExprKind::Unary(unary) => {
+                self.expr(&unary.operand);
+                let op = match unary.op {
+                    UnaryOp::Neg => Op::Neg,
+                    UnaryOp::Not => Op::Not,
+                };
+                self.emit(op, unary.operand.pos);
+            }
+            ExprKind::If(if_expr) => {
+                self.expr(&if_expr.condition);
+                let otherwise = self.emit(Op::JumpIfFalse(0), if_expr.condition.pos);
+                self.expr(&if_expr.then);
+                let end = self.emit(Op::Jump(0), pos);
+                self.patch(otherwise);
+                match &if_expr.otherwise {
+                    Some(otherwise) => self.expr(otherwise),
+                    None => {
+                        self.emit(Op::Unit, pos);
+                    }
+                }
+                self.patch(end);
+            }
+            ExprKind::Block(stmts) => self.stmts(stmts, pos),
+            ExprKind::List(items) => {
+                self.emit(Op::List, pos);
+                for item in items.iter() {
+                    self.expr(&item.value);
+                    let op = if item.spread { Op::Spread } else { Op::Push };
+                    self.emit(op, item.value.pos);
+                }
+            }
+            ExprKind::Index(index) => {
+                self.expr(&index.indexed);
+                self.expr(&index.index);
+                self.emit(Op::Index, index.index.pos);
+            }
+            ExprKind::Record(record) => {
+                match &record.base {
+                    Some(base) => self.expr(base),
+                    None => {
+                        self.emit(Op::Record, pos);
+                    }
+                }
+                for field in record.fields.iter() {
+                    self.expr(&field.value);
+                    let name = self.name(&field.name);
+                    self.emit(Op::SetField(name), field.pos);
+                }
+            }
+            ExprKind::Field(field) => {
+                self.expr(&field.record);
+                let name = self.name(&field.field);
+                self.emit(Op::Field(name), pos);
+            }
+            ExprKind::Lambda(lambda) => {
+                self.function(pos, None, &lambda.params, &lambda.body, false)
+            }
+            ExprKind::Match(match_expr) => self.match_expr(expr, match_expr),
+            ExprKind::Assign(assign) => {
+                self.expr(&assign.value);
+                self.assign(&assign.target);
+                self.emit(Op::Unit, pos);
+            }
+            ExprKind::Element(element) => self.element(element, pos),
+        }
+    }
+
+    fn element(&mut self, element: &ElementExpr, pos: Position) {
+        match &element.tag {
+            Some(tag) if tag.is_component() => {
+                self.load(&tag.name, tag.pos);
+                self.emit(Op::Component, tag.pos);
+            }
+            Some(tag) => {
+                let name = self.name(&tag.name);
+                self.emit(Op::Element(name), pos);
+            }
+            None => {
+                self.emit(Op::Fragment, pos);
+            }
+        }
+        for attribute in element.attributes.iter() {
+            match &attribute.value {
+                Some(value) => self.expr(value),
+                None => {
+                    self.emit(Op::True, attribute.pos);
+                }
+            }
+            let name = self.name(&attribute.name);
+            self.emit(Op::SetAttribute(name), attribute.pos);
+        }
+        for child in element.children.iter() {
+            match &child.kind {
+                ChildKind::Text(text) => self.constant(Value::string(text.as_str()), child.pos),
+                ChildKind::Expr(value) | ChildKind::Element(value) => self.expr(value),
+            }
+            self.emit(Op::AddChild, child.pos);
+        }
+    }
+
+    fn binary(&mut self, expr: &Expr, binary: &BinaryExpr) {
+        let pos = expr.pos;
+        // `&&` and `||` only evaluate their right side when needed
+        if let BinaryOp::And | BinaryOp::Or = binary.op {
+            self.expr(&binary.lhs);
+            let lhs_false = self.emit(Op::JumpIfFalse(0), binary.lhs.pos);
+            if binary.op == BinaryOp::Or {
+                self.emit(Op::True, pos);
+                let end = self.emit(Op::Jump(0), pos);
+                self.patch(lhs_false);
+                self.expr(&binary.rhs);
+                let rhs_false = self.emit(Op::JumpIfFalse(0), binary.rhs.pos);
+                self.emit(Op::True, pos);
+                let rhs_end = self.emit(Op::Jump(0), pos);
+                self.patch(rhs_false);
+                self.emit(Op::False, pos);
+                self.patch(end);
+                self.patch(rhs_end);
+            } else {
+                self.expr(&binary.rhs);
+                let rhs_false = self.emit(Op::JumpIfFalse(0), binary.rhs.pos);
+                self.emit(Op::True, pos);
+                let end = self.emit(Op::Jump(0), pos);
+                self.patch(lhs_false);
+                self.patch(rhs_false);
+                self.emit(Op::False, pos);
+                self.patch(end);
+            }
+            return;
+        }
+
+        self.expr(&binary.lhs);
+        self.expr(&binary.rhs);
+        let op = match binary.op {
+            BinaryOp::Add => Op::Add,
+            BinaryOp::Sub => Op::Sub,
+            BinaryOp::Mul => Op::Mul,
+            BinaryOp::Div => Op::Div,
+            BinaryOp::Rem => Op::Rem,
+            BinaryOp::Eq => Op::Eq,
+            BinaryOp::Ne => Op::Ne,
+            BinaryOp::Lt => Op::Lt,
+            BinaryOp::Le => Op::Le,
+            BinaryOp::Gt => Op::Gt,
+            BinaryOp::Ge => Op::Ge,
+            BinaryOp::And | BinaryOp::Or => unreachable!(),
+        };
+        self.current()
+            .chunk
+            .emit_binary(op, pos, [binary.lhs.pos, binary.rhs.pos]);
+    }
+
+    // The scrutinee is kept in a slot, and each arm tests it with its
+    // pattern and guard, jumping to the next arm when one fails
+    fn match_expr(&mut self, expr: &Expr, match_expr: &MatchExpr) {
+        self.expr(&match_expr.scrutinee);
+        let slot = self.slot();
+        self.emit(Op::SetLocal(slot), match_expr.scrutinee.pos);
+
+        let mut ends = vec![];
+        for arm in match_expr.arms.iter() {
+            let mut fails = vec![];
+            self.test_pattern(&arm.pattern, slot, &mut vec![], &mut fails);
+            self.bind_pattern(&arm.pattern, slot, &mut vec![]);
+            if let Some(guard) = &arm.guard {
+                self.expr(guard);
+                fails.push(self.emit(Op::JumpIfFalse(0), guard.pos));
+            }
+            self.expr(&arm.body);
+            ends.push(self.emit(Op::Jump(0), arm.pos));
+            for fail in fails {
+                self.patch(fail);
+            }
+        }
+        self.emit(Op::NoMatch(slot), expr.pos);
+        for end in ends {
+            self.patch(end);
+        }
+    }
+
+    // Pushes the part of the scrutinee at `path`, a list of field indices
+    fn load_path(&mut self, slot: u32, path: &[u32], pos: Position) {
+        self.emit(Op::GetLocal(slot), pos);
+        for index in path.iter() {
+            self.emit(Op::VariantField(*index), pos);
+        }
+    }
+
+    fn test_pattern(
+        &mut self,
+        pattern: &Pattern,
+        slot: u32,
+        path: &mut Vec<u32>,
+        fails: &mut Vec<u32>,
+    ) {
+        let pos = pattern.pos;
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Identifier(_) => {
+                if let Some(id) = self.resolution.uses.get(&pos) {
+                    self.load_path(slot, path, pos);
+                    self.emit(Op::IsVariant(id.0, 0), pos);
+                    fails.push(self.emit(Op::JumpIfFalse(0), pos));
+                }
+            }
+            PatternKind::Literal(literal) => {
+                self.load_path(slot, path, pos);
+                let literal = Expr::new(ExprKind::Literal(literal.clone()), pos);
+                self.expr(&literal);
+                self.emit(Op::Eq, pos);
+                fails.push(self.emit(Op::JumpIfFalse(0), pos));
+            }
+            PatternKind::Variant(_, patterns) => {
+                let Some(id) = self.resolution.uses.get(&pos) else {
+                    fails.push(self.emit(Op::Jump(0), pos));
+                    return;
+                };
+                self.load_path(slot, path, pos);
+                self.emit(Op::IsVariant(id.0, patterns.len() as u32), pos);
+                fails.push(self.emit(Op::JumpIfFalse(0), pos));
+                for (i, pattern) in patterns.iter().enumerate() {
+                    path.push(i as u32);
+                    self.test_pattern(pattern, slot, path, fails);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, slot: u32, path: &mut Vec<u32>) {
+        match &pattern.kind {
+            // Names of variants are uses rather than definitions
+            PatternKind::Identifier(_)
+                if self.resolution.definitions.contains_key(&pattern.pos) =>
+            {
+                self.load_path(slot, path, pattern.pos);
+                self.define(&pattern.pos);
+            }
+            PatternKind::Variant(_, patterns) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    path.push(i as u32);
+                    self.bind_pattern(pattern, slot, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
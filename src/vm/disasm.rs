@@ -0,0 +1,68 @@
+use std::fmt::Write;
+
+use super::chunk::*;
+
+/// A listing of the instructions of a program and each function in it, for
+/// debugging. Each line has the offset, the span when it changes, the
+/// instruction and what its operand refers to.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = String::new();
+    proto(&mut out, bytecode, &bytecode.main);
+    out
+}
+
+fn proto(out: &mut String, bytecode: &Bytecode, proto: &Proto) {
+    let name = match &proto.name {
+        Some(name) => format!("fn {}", name),
+        None if std::ptr::eq(proto, bytecode.main.as_ref()) => "main".to_string(),
+        None => "fn".to_string(),
+    };
+    writeln!(
+        out,
+        "== {} == arity {}, locals {}, cells {}, upvalues {}",
+        name,
+        proto.arity,
+        proto.locals,
+        proto.cells,
+        proto.upvalues.len()
+    )
+    .unwrap();
+
+    let chunk = &proto.chunk;
+    let mut spans = chunk.spans().iter().peekable();
+    for (offset, op) in chunk.code.iter().enumerate() {
+        let span = match spans.next_if(|(start, _)| *start as usize == offset) {
+            Some((_, pos)) => format!("{}..{}", pos.start, pos.end),
+            None => "|".to_string(),
+        };
+        write!(out, "{:04} {:>9}  {:?}", offset, span, op).unwrap();
+        if let Some(comment) = comment(bytecode, chunk, op) {
+            write!(out, "  ; {}", comment).unwrap();
+        }
+        out.push('\n');
+    }
+
+    for function in chunk.functions.iter() {
+        out.push('\n');
+        self::proto(out, bytecode, function);
+    }
+}
+
+fn comment(bytecode: &Bytecode, chunk: &Chunk, op: &Op) -> Option<String> {
+    match op {
+        Op::Constant(index)
+        | Op::SetField(index)
+        | Op::Field(index)
+        | Op::Unbound(index)
+        | Op::Element(index)
+        | Op::SetAttribute(index) => Some(chunk.constants[*index as usize].to_string()),
+        Op::GetGlobal(index) | Op::SetGlobal(index) => {
+            Some(bytecode.globals[*index as usize].clone())
+        }
+        Op::Closure(index) => {
+            let name = chunk.functions[*index as usize].name.as_deref();
+            Some(format!("fn {}", name.unwrap_or("<lambda>")))
+        }
+        _ => None,
+    }
+}
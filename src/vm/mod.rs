@@ -0,0 +1,584 @@
+pub mod chunk;
+pub mod compile;
+pub mod disasm;
+#[cfg(test)]
+mod tests;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+use crate::interp::*;
+use crate::lexer::Position;
+
+pub use self::chunk::*;
+pub use self::compile::*;
+pub use self::disasm::*;
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+/// A function compiled to bytecode, with the cells it captured.
+pub struct Closure {
+    pub proto: Rc<Proto>,
+    upvalues: Vec<Rc<RefCell<Value>>>,
+}
+
+// Captured cells can hold the closure itself
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("name", &self.proto.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Runs compiled bytecode, returning the value of its last statement.
+pub fn run_bytecode(bytecode: &Bytecode) -> Result<Value> {
+    Vm::new(bytecode).run()
+}
+
+struct Frame {
+    proto: Rc<Proto>,
+    upvalues: Vec<Rc<RefCell<Value>>>,
+    cells: Vec<Rc<RefCell<Value>>>,
+    ip: usize,
+    // Stack index of the first slot
+    base: usize,
+    // Where the stack is cut back to on return, dropping the called value
+    bottom: usize,
+}
+
+impl Frame {
+    // Whether a call entered the frame, rather than `run`
+    fn is_call(&self) -> bool {
+        self.bottom < self.base
+    }
+}
+
+/// A stack machine for `Bytecode`. Errors have the span of the instruction
+/// that failed, from the span table of its chunk.
+pub struct Vm<'a> {
+    bytecode: &'a Bytecode,
+    globals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    meter: Meter,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(bytecode: &'a Bytecode) -> Self {
+        Self::with_limits(bytecode, Limits::default())
+    }
+
+    pub fn with_limits(bytecode: &'a Bytecode, limits: Limits) -> Self {
+        Self {
+            bytecode,
+            globals: vec![None; bytecode.globals.len()],
+            stack: vec![],
+            frames: vec![],
+            meter: Meter::new(limits),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<Value> {
+        let depth = self.frames.len();
+        let proto = self.bytecode.main.clone();
+        self.enter(proto, vec![], self.stack.len(), self.stack.len());
+        self.execute(depth)
+    }
+
+    /// The value of a global, once `run` has reached its definition.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        let index = self
+            .bytecode
+            .globals
+            .iter()
+            .rposition(|global| global == name)?;
+        self.globals[index].clone()
+    }
+
+    /// Calls a function value. Errors that are not inside the function, like
+    /// a wrong number of arguments, are reported at `pos`.
+    pub fn call(&mut self, function: &Value, args: Vec<Value>, pos: Position) -> Result<Value> {
+        let depth = self.frames.len();
+        let bottom = self.stack.len();
+        let argc = args.len();
+        self.stack.push(function.clone());
+        self.stack.extend(args);
+        match self.call_value(argc, pos) {
+            Ok(true) => self.execute(depth),
+            Ok(false) => Ok(self.stack.pop().unwrap()),
+            Err(error) => {
+                self.stack.truncate(bottom);
+                Err(error)
+            }
+        }
+    }
+
+    /// Gives a value to a global, like one the host defined.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        if let Some(index) = self
+            .bytecode
+            .globals
+            .iter()
+            .rposition(|global| global == name)
+        {
+            self.globals[index] = Some(value);
+        }
+    }
+
+    fn enter(
+        &mut self,
+        proto: Rc<Proto>,
+        upvalues: Vec<Rc<RefCell<Value>>>,
+        base: usize,
+        bottom: usize,
+    ) {
+        let cells = (0..proto.cells)
+            .map(|_| Rc::new(RefCell::new(Value::Unit)))
+            .collect();
+        let params = proto.arity as usize;
+        self.stack
+            .resize(base + params.max(proto.locals as usize), Value::Unit);
+        self.frames.push(Frame {
+            proto,
+            upvalues,
+            cells,
+            ip: 0,
+            base,
+            bottom,
+        });
+    }
+
+    // Calls the value below the `argc` arguments on top of the stack. Returns
+    // whether a frame was entered; otherwise the result replaced them.
+    fn call_value(&mut self, argc: usize, pos: Position) -> Result<bool> {
+        let callee = self.stack.len() - argc - 1;
+        let function = match &self.stack[callee] {
+            Value::Function(function) => function.clone(),
+            value => {
+                let kind = RuntimeErrorKind::NotCallable(value.type_name().to_string());
+                return Err(RuntimeError::new(kind, pos));
+            }
+        };
+        if function.arity() != argc {
+            let kind = RuntimeErrorKind::Arity {
+                expected: function.arity(),
+                found: argc,
+            };
+            return Err(RuntimeError::new(kind, pos));
+        }
+
+        match function.as_ref() {
+            Function::Compiled(closure) => {
+                self.meter
+                    .enter()
+                    .map_err(|kind| RuntimeError::new(kind, pos))?;
+                self.enter(
+                    closure.proto.clone(),
+                    closure.upvalues.clone(),
+                    callee + 1,
+                    callee,
+                );
+                Ok(true)
+            }
+            Function::Constructor(constructor) => {
+                let fields = self.stack.split_off(callee + 1);
+                let value = Value::Variant(Rc::new(VariantValue {
+                    ty: constructor.ty.clone(),
+                    name: constructor.name.clone(),
+                    id: constructor.id,
+                    fields,
+                }));
+                self.meter
+                    .allocate(value.allocated_size())
+                    .map_err(|kind| RuntimeError::new(kind, pos))?;
+                self.stack[callee] = value;
+                Ok(false)
+            }
+            Function::Native(native) => {
+                let args = self.stack.split_off(callee + 1);
+                self.stack.pop();
+                let value = native.call(self, args, pos)?;
+                self.stack.push(value);
+                Ok(false)
+            }
+            // Closures of the interpreter need the AST to run
+            Function::Closure(_) => Err(RuntimeError::new(
+                RuntimeErrorKind::NotCallable("Function".to_string()),
+                pos,
+            )),
+        }
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn error(&self, kind: RuntimeErrorKind) -> RuntimeError {
+        let frame = self.frames.last().unwrap();
+        RuntimeError::new(kind, frame.proto.chunk.span(frame.ip as u32 - 1))
+    }
+
+    fn mismatch(&self, expected: &str, found: &Value) -> RuntimeError {
+        self.error(RuntimeErrorKind::Mismatch {
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+        })
+    }
+
+    // A mismatch of an operand of the current binary operator, at the
+    // operand, as the interpreter reports it
+    fn operand_mismatch(&self, operand: usize, expected: &str, found: &Value) -> RuntimeError {
+        let frame = self.frames.last().unwrap();
+        let mut error = self.mismatch(expected, found);
+        error.pos = frame.proto.chunk.operand_span(frame.ip as u32 - 1, operand);
+        error
+    }
+
+    fn allocate(&mut self, bytes: usize) -> Result<()> {
+        match self.meter.allocate(bytes) {
+            Ok(()) => Ok(()),
+            Err(kind) => Err(self.error(kind)),
+        }
+    }
+
+    fn push_element(&mut self, tag: ElementTag) -> Result<()> {
+        self.allocate(mem::size_of::<ElementValue>())?;
+        self.stack
+            .push(Value::Element(Rc::new(ElementValue::new(tag))));
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+
+    fn pop_number(&mut self) -> Result<f64> {
+        match self.pop() {
+            Value::Number(number) => Ok(number),
+            value => Err(self.mismatch("Number", &value)),
+        }
+    }
+
+    fn pop_numbers(&mut self) -> Result<(f64, f64)> {
+        let rhs = self.pop();
+        match (self.pop(), rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => Ok((lhs, rhs)),
+            (Value::Number(_), rhs) => Err(self.operand_mismatch(1, "Number", &rhs)),
+            (lhs, _) => Err(self.operand_mismatch(0, "Number", &lhs)),
+        }
+    }
+
+    fn constant(&self, index: u32) -> &Value {
+        &self.frames.last().unwrap().proto.chunk.constants[index as usize]
+    }
+
+    fn name(&self, index: u32) -> String {
+        match self.constant(index) {
+            Value::String(name) => name.to_string(),
+            value => value.to_string(),
+        }
+    }
+
+    // Runs until the frame count drops back to `depth`
+    fn execute(&mut self, depth: usize) -> Result<Value> {
+        let result = self.execute_inner(depth);
+        if result.is_err() {
+            // Unwind the frames the failed call entered
+            if let Some(frame) = self.frames.get(depth) {
+                let bottom = frame.bottom;
+                self.stack.truncate(bottom);
+            }
+            for frame in self.frames.drain(depth..) {
+                if frame.is_call() {
+                    self.meter.exit();
+                }
+            }
+        }
+        result
+    }
+
+    fn execute_inner(&mut self, depth: usize) -> Result<Value> {
+        loop {
+            let frame = self.frame();
+            let op = frame.proto.chunk.code[frame.ip];
+            frame.ip += 1;
+            if let Err(kind) = self.meter.tick() {
+                return Err(self.error(kind));
+            }
+
+            match op {
+                Op::Constant(index) => {
+                    let value = self.constant(index).clone();
+                    self.stack.push(value);
+                }
+                Op::Unit => self.stack.push(Value::Unit),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let index = self.frame().base + slot as usize;
+                    self.stack.push(self.stack[index].clone());
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.pop();
+                    let index = self.frame().base + slot as usize;
+                    self.stack[index] = value;
+                }
+                Op::GetCell(cell) => {
+                    let value = self.frame().cells[cell as usize].borrow().clone();
+                    self.stack.push(value);
+                }
+                Op::SetCell(cell) => {
+                    let value = self.pop();
+                    *self.frame().cells[cell as usize].borrow_mut() = value;
+                }
+                Op::GetUpvalue(index) => {
+                    let value = self.frame().upvalues[index as usize].borrow().clone();
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(index) => {
+                    let value = self.pop();
+                    *self.frame().upvalues[index as usize].borrow_mut() = value;
+                }
+                Op::GetGlobal(index) => match &self.globals[index as usize] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => {
+                        let name = self.bytecode.globals[index as usize].clone();
+                        return Err(self.error(RuntimeErrorKind::Unbound(name)));
+                    }
+                },
+                Op::SetGlobal(index) => {
+                    let value = self.pop();
+                    self.globals[index as usize] = Some(value);
+                }
+                Op::Unbound(name) => {
+                    return Err(self.error(RuntimeErrorKind::Unbound(self.name(name))));
+                }
+                Op::Add => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let value = match (&lhs, &rhs) {
+                        (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                        (Value::String(a), Value::String(b)) => {
+                            Value::string(format!("{}{}", a, b))
+                        }
+                        (Value::String(_), _) => {
+                            return Err(self.operand_mismatch(1, "String", &rhs))
+                        }
+                        (Value::Number(_), _) => {
+                            return Err(self.operand_mismatch(1, "Number", &rhs))
+                        }
+                        _ => return Err(self.operand_mismatch(0, "Number", &lhs)),
+                    };
+                    self.allocate(value.allocated_size())?;
+                    self.stack.push(value);
+                }
+                Op::Sub => {
+                    let (a, b) = self.pop_numbers()?;
+                    self.stack.push(Value::Number(a - b));
+                }
+                Op::Mul => {
+                    let (a, b) = self.pop_numbers()?;
+                    self.stack.push(Value::Number(a * b));
+                }
+                Op::Div | Op::Rem => {
+                    let (a, b) = self.pop_numbers()?;
+                    if b == 0.0 {
+                        return Err(self.error(RuntimeErrorKind::DivisionByZero));
+                    }
+                    let value = if op == Op::Div { a / b } else { a % b };
+                    self.stack.push(Value::Number(value));
+                }
+                Op::Eq | Op::Ne => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(Value::Bool((lhs == rhs) == (op == Op::Eq)));
+                }
+                Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+                    let (a, b) = self.pop_numbers()?;
+                    let value = match op {
+                        Op::Lt => a < b,
+                        Op::Le => a <= b,
+                        Op::Gt => a > b,
+                        _ => a >= b,
+                    };
+                    self.stack.push(Value::Bool(value));
+                }
+                Op::Neg => {
+                    let number = self.pop_number()?;
+                    self.stack.push(Value::Number(-number));
+                }
+                Op::Not => match self.pop() {
+                    Value::Bool(bool) => self.stack.push(Value::Bool(!bool)),
+                    value => return Err(self.mismatch("Bool", &value)),
+                },
+                Op::Jump(target) => self.frame().ip = target as usize,
+                Op::JumpIfFalse(target) => match self.pop() {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => self.frame().ip = target as usize,
+                    value => return Err(self.mismatch("Bool", &value)),
+                },
+                Op::Call(argc) => {
+                    let frame = self.frames.last().unwrap();
+                    let pos = frame.proto.chunk.span(frame.ip as u32 - 1);
+                    self.call_value(argc as usize, pos)?;
+                }
+                Op::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    if frame.is_call() {
+                        self.meter.exit();
+                    }
+                    self.stack.truncate(frame.bottom);
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+                Op::Closure(index) => {
+                    let frame = self.frames.last().unwrap();
+                    let proto = frame.proto.chunk.functions[index as usize].clone();
+                    let upvalues = proto
+                        .upvalues
+                        .iter()
+                        .map(|upvalue| match upvalue {
+                            Upvalue::Cell(cell) => frame.cells[*cell as usize].clone(),
+                            Upvalue::Upvalue(index) => frame.upvalues[*index as usize].clone(),
+                        })
+                        .collect();
+                    let closure =
+                        Value::Function(Rc::new(Function::Compiled(Closure { proto, upvalues })));
+                    self.allocate(closure.allocated_size())?;
+                    self.stack.push(closure);
+                }
+                Op::List => self.stack.push(Value::list(vec![])),
+                Op::Push => {
+                    self.allocate(mem::size_of::<Value>())?;
+                    let value = self.pop();
+                    if let Some(Value::List(items)) = self.stack.last_mut() {
+                        Rc::make_mut(items).push(value);
+                    }
+                }
+                Op::Spread => {
+                    let value = self.pop();
+                    let Value::List(spread) = value else {
+                        return Err(self.mismatch("List", &value));
+                    };
+                    self.allocate(spread.len() * mem::size_of::<Value>())?;
+                    if let Some(Value::List(items)) = self.stack.last_mut() {
+                        Rc::make_mut(items).extend(spread.iter().cloned());
+                    }
+                }
+                Op::Index => {
+                    let index = self.pop_number()?;
+                    let items = match self.pop() {
+                        Value::List(items) => items,
+                        value => return Err(self.mismatch("List", &value)),
+                    };
+                    if index < 0.0 || index.fract() != 0.0 || index as usize >= items.len() {
+                        return Err(self.error(RuntimeErrorKind::IndexOutOfBounds {
+                            index,
+                            len: items.len(),
+                        }));
+                    }
+                    self.stack.push(items[index as usize].clone());
+                }
+                Op::Element(name) => {
+                    let tag = ElementTag::Html(self.name(name));
+                    self.push_element(tag)?;
+                }
+                Op::Fragment => self.push_element(ElementTag::Fragment)?,
+                Op::Component => {
+                    let component = self.pop();
+                    self.push_element(ElementTag::Component(component))?;
+                }
+                Op::SetAttribute(name) => {
+                    let value = self.pop();
+                    let name = self.name(name);
+                    self.allocate(name.len() + mem::size_of::<Value>())?;
+                    if let Some(Value::Element(element)) = self.stack.last_mut() {
+                        Rc::make_mut(element).attributes.push((name, value));
+                    }
+                }
+                Op::AddChild => {
+                    let value = self.pop();
+                    let added = match &value {
+                        Value::List(items) => items.len(),
+                        _ => 1,
+                    };
+                    self.allocate(added * mem::size_of::<Value>())?;
+                    if let Some(Value::Element(element)) = self.stack.last_mut() {
+                        Rc::make_mut(element).push_child(value);
+                    }
+                }
+                Op::Record => self.stack.push(Value::record(Default::default())),
+                Op::SetField(name) => {
+                    let value = self.pop();
+                    let name = self.name(name);
+                    let bytes = match self.stack.last() {
+                        Some(record @ Value::Record(fields)) => {
+                            // A shared record is copied before it is changed
+                            let copy = match Rc::strong_count(fields) {
+                                1 => 0,
+                                _ => record.allocated_size(),
+                            };
+                            let field = match fields.contains_key(&name) {
+                                true => 0,
+                                false => name.len() + mem::size_of::<Value>(),
+                            };
+                            copy + field
+                        }
+                        _ => 0,
+                    };
+                    self.allocate(bytes)?;
+                    match self.stack.last_mut() {
+                        Some(Value::Record(fields)) => {
+                            Rc::make_mut(fields).insert(name, value);
+                        }
+                        _ => {
+                            let record = self.pop();
+                            return Err(self.mismatch("Record", &record));
+                        }
+                    }
+                }
+                Op::Field(name) => {
+                    let name = self.name(name);
+                    let value = match self.pop() {
+                        Value::Record(fields) => fields.get(&name).cloned(),
+                        value => return Err(self.mismatch("Record", &value)),
+                    };
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => return Err(self.error(RuntimeErrorKind::MissingField(name))),
+                    }
+                }
+                Op::IsVariant(id, arity) => {
+                    let matches = match self.pop() {
+                        Value::Variant(variant) => {
+                            variant.id.0 == id && variant.fields.len() == arity as usize
+                        }
+                        _ => false,
+                    };
+                    self.stack.push(Value::Bool(matches));
+                }
+                Op::VariantField(index) => {
+                    let field = match self.pop() {
+                        Value::Variant(variant) => variant.fields[index as usize].clone(),
+                        value => return Err(self.mismatch("variant", &value)),
+                    };
+                    self.stack.push(field);
+                }
+                Op::NoMatch(slot) => {
+                    let value = &self.stack[self.frames.last().unwrap().base + slot as usize];
+                    let kind = RuntimeErrorKind::NoMatchingArm(value.to_string());
+                    return Err(self.error(kind));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use super::*;
+use crate::ast::{Parser, Program};
+use crate::diagnostic::Diagnostic;
+use crate::resolve::{resolve_program, Resolution};
+
+fn parse(input: &str) -> (Program, Resolution) {
+    let program = Parser::new(input).parse_program().unwrap();
+    let resolution = resolve_program(&program);
+    assert!(!resolution.has_errors(), "in {}", input);
+    (program, resolution)
+}
+
+fn run(input: &str) -> Result<Value> {
+    let (program, resolution) = parse(input);
+    run_bytecode(&compile_program(&program, &resolution).unwrap())
+}
+
+// Runs `input` with both the VM and the interpreter, which must agree
+fn display(input: &str) -> String {
+    let (program, resolution) = parse(input);
+    let expected = run_program(&program, &resolution).unwrap().to_string();
+    let found = run_bytecode(&compile_program(&program, &resolution).unwrap())
+        .unwrap()
+        .to_string();
+    assert_eq!(found, expected, "in {}", input);
+    found
+}
+
+fn span(input: &str, text: &str) -> Position {
+    let start = input.find(text).unwrap() as u32;
+    Position::new(start, start + text.len() as u32)
+}
+
+#[test]
+fn run_operators_and_blocks() {
+    assert_eq!(display("1 + 2 * 3 - 4"), "3");
+    assert_eq!(display("[7 / 2, 7 % 4, -(2 - 5)]"), "[3.5, 3, 3]");
+    assert_eq!(display("1 < 2 && !(2 >= 3) || false"), "true");
+    assert_eq!(display("'ab' + \"cd\" == 'abcd'"), "true");
+    assert_eq!(display("false && 1 / 0 == 1"), "false");
+    assert_eq!(
+        display("let a = 1; let b = { let a = 2; a + 1 }; a + b"),
+        "4"
+    );
+    assert_eq!(
+        display("if 1 > 2 { 'a' } else if true { 'b' } else { 'c' }"),
+        "'b'"
+    );
+    assert_eq!(display("if false { 1 }"), "()");
+    assert_eq!(display("{}"), "()");
+    assert_eq!(display("let a = 1;"), "()");
+}
+
+#[test]
+fn run_functions_and_closures() {
+    assert_eq!(
+        display("fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }\nfib(15)"),
+        "610"
+    );
+    assert_eq!(
+        display("fn main() { is_even(10) }\nfn is_even(n) { if n == 0 { true } else { is_odd(n - 1) } }\nfn is_odd(n) { if n == 0 { false } else { is_even(n - 1) } }\nmain()"),
+        "true"
+    );
+    assert_eq!(
+        display(
+            "fn adder(n) { fn(x) => x + n }\nlet add_two = adder(2);\n[add_two(1), adder(10)(1)]"
+        ),
+        "[3, 11]"
+    );
+    // Captures through a closure that does not use them itself
+    assert_eq!(
+        display("fn outer(a) {\n    let b = a * 2;\n    fn(x) => fn(y) => a + b + x + y\n}\nouter(1)(10)(100)"),
+        "113"
+    );
+    // Local functions capturing each other
+    assert_eq!(
+        display("fn count(n) {\n    fn down(i) { if i == 0 { 0 } else { 1 + again(i - 1) } }\n    fn again(i) { down(i) + n * 0 }\n    down(n)\n}\ncount(5)"),
+        "5"
+    );
+    assert_eq!(
+        display("fn f() { 1 }\nlet g = fn(x) => x;\n[f, g]"),
+        "[<fn f>, <fn>]"
+    );
+}
+
+#[test]
+fn run_assignments() {
+    assert_eq!(
+        display("state total = 0;\nfn add(n) { total = total + n }\nadd(2);\nadd(3);\ntotal"),
+        "5"
+    );
+    // Closures share the state they capture
+    assert_eq!(
+        display("fn counter() {\n    state n = 0;\n    fn() => { n = n + 1; n }\n}\nlet next = counter();\nnext();\n[next(), counter()()]"),
+        "[2, 1]"
+    );
+    assert_eq!(display("fn f() { state a = 1; a = a * 10; a }\nf()"), "10");
+}
+
+#[test]
+fn run_elements() {
+    assert_eq!(
+        display("fn Item(props) { <li>{props.children}</li> }\nlet items = [1, 2];\n<ul id='list'>\n    <Item>{items}</Item>\n    done\n</ul>"),
+        "<ul id='list'><Item>{1}{2}</Item>done</ul>"
+    );
+}
+
+#[test]
+fn run_components() {
+    assert_eq!(
+        display("component Greeting(name, excited) {\n    let clicks = 1;\n    let shout = fn() => if excited { name + '!' } else { name };\n    <p title={name}>{shout()}{clicks}</p>\n}\nGreeting({ excited: true, name: 'Ada' })"),
+        "<p title='Ada'>Ada!{1}</p>"
+    );
+    // Without instances, `state` would start anew on every call
+    let input = "component Counter() {\n    state clicks = 0;\n    <p>{clicks}</p>\n}";
+    let (program, resolution) = parse(input);
+    assert_eq!(
+        compile_program(&program, &resolution).unwrap_err(),
+        vec![Diagnostic::error(
+            "the VM keeps no component instances, so a component cannot have `state`",
+            span(input, "state clicks = 0;")
+        )]
+    );
+    let input = "component Counter() {\n    let f = fn() { state n = 0; n };\n    <p>{f()}</p>\n}";
+    let (program, resolution) = parse(input);
+    assert!(compile_program(&program, &resolution).is_ok());
+    let input = "component Greeting(name) { <p>{name}</p> }\nGreeting({ other: 1 })";
+    assert_eq!(
+        run(input),
+        Err(RuntimeError::new(
+            RuntimeErrorKind::MissingField("name".to_string()),
+            span(input, "name")
+        ))
+    );
+}
+
+#[test]
+fn run_data_and_matches() {
+    assert_eq!(display("let xs = [1, 2];\n[0, ..xs, ..[], 3][2]"), "2");
+    assert_eq!(
+        display("let user = { name: 'ada', age: 36 };\nlet name = 'bob';\n{ ..user, name, admin: true }"),
+        "{ admin: true, age: 36, name: 'bob' }"
+    );
+    assert_eq!(
+        display("type List = Cons(Number, List) | Nil;\nfn sum(list) {\n    match list {\n        Cons(x, rest) if x > 0 => x + sum(rest),\n        Cons(_, rest) => sum(rest),\n        Nil => 0,\n    }\n}\nsum(Cons(1, Cons(-5, Cons(2, Nil))))"),
+        "3"
+    );
+    assert_eq!(
+        display("fn name(n) { match n { 0 => 'zero', 1 => 'one', other => other } }\n[name(0), name(1), name(7)]"),
+        "['zero', 'one', 7]"
+    );
+    assert_eq!(
+        display("type A = Same | Other;\nlet a = Same;\n{\n    type B = Same;\n    match a { Same => 'b', _ => 'a' }\n}"),
+        "'a'"
+    );
+}
+
+#[test]
+fn call_from_rust() {
+    let (program, resolution) = parse("fn greet(name) { 'hello ' + name }\nlet later = 1;");
+    let bytecode = compile_program(&program, &resolution).unwrap();
+    let mut vm = Vm::new(&bytecode);
+    assert_eq!(vm.global("later"), None);
+    vm.run().unwrap();
+    assert_eq!(vm.global("later"), Some(Value::Number(1.0)));
+
+    let greet = vm.global("greet").unwrap();
+    let pos = Position::new(0, 0);
+    assert_eq!(
+        vm.call(&greet, vec![Value::string("zope")], pos),
+        Ok(Value::string("hello zope"))
+    );
+    assert_eq!(
+        vm.call(&greet, vec![], pos).unwrap_err().kind,
+        RuntimeErrorKind::Arity {
+            expected: 1,
+            found: 0
+        }
+    );
+}
+
+#[test]
+fn report_runtime_errors() {
+    // The VM reports the same errors as the interpreter
+    let inputs = [
+        "fn f(x) { 10 / x }\nf(0)",
+        "let xs = [1, 2];\nxs[2]",
+        "let n = 1;\nn(2)",
+        "fn f(a, b) { a }\nf(1)",
+        "type T = A | B;\nmatch B { A => 1 }",
+        "fn get(r) { r.missing }\nget({ a: 1, b: 2 })",
+        "import { x } from './x';\nx + 1",
+        "if 1 { 2 }",
+        "fn f(x) { !x }\nf(1)",
+        "'a' + 1",
+        "[1][0] + 'a'",
+        "fn f(x) { x - 1 }\nf(true)",
+        "fn f(x) { 1 < x }\nf('a')",
+    ];
+    for input in inputs {
+        let (program, resolution) = parse(input);
+        let expected = run_program(&program, &resolution).unwrap_err();
+        assert_eq!(run(input).unwrap_err(), expected, "in {}", input);
+    }
+
+    let input = "fn f(x) { 10 % x }\nf(0)";
+    assert_eq!(
+        run(input).unwrap_err(),
+        RuntimeError::new(RuntimeErrorKind::DivisionByZero, span(input, "10 % x"))
+    );
+    // Operands are reported where they are, not at their operator
+    let input = "'a' + 1";
+    assert_eq!(run(input).unwrap_err().pos, span(input, "1"));
+}
+
+#[test]
+fn disassemble_programs() {
+    let (program, resolution) = parse("fn add(a, b) { a + b }\nadd(1, 2)");
+    let bytecode = compile_program(&program, &resolution).unwrap();
+    let expected = "\
+== main == arity 0, locals 0, cells 0, upvalues 0
+0000     0..22  Closure(0)  ; fn add
+0001         |  SetGlobal(0)  ; add
+0002    23..26  GetGlobal(0)  ; add
+0003    27..28  Constant(0)  ; 1
+0004    30..31  Constant(1)  ; 2
+0005    23..32  Call(2)
+0006    32..32  Return
+
+== fn add == arity 2, locals 2, cells 0, upvalues 0
+0000    15..16  GetLocal(0)
+0001    19..20  GetLocal(1)
+0002    15..20  Add
+0003    13..22  Return
+";
+    assert_eq!(disassemble(&bytecode), expected);
+}
+
+#[test]
+fn enforce_limits() {
+    let limited = |input: &str, limits: Limits| {
+        let (program, resolution) = parse(input);
+        let bytecode = compile_program(&program, &resolution).unwrap();
+        Vm::with_limits(&bytecode, limits).run().unwrap_err()
+    };
+
+    let input = "fn loop(n) { loop(n + 1) }\nloop(0)";
+    let limits = Limits {
+        fuel: Some(100),
+        ..Limits::default()
+    };
+    assert_eq!(limited(input, limits).kind, RuntimeErrorKind::OutOfFuel);
+    let limits = Limits {
+        max_depth: Some(50),
+        ..Limits::default()
+    };
+    assert_eq!(
+        limited(input, limits),
+        RuntimeError::new(
+            RuntimeErrorKind::StackOverflow(50),
+            span(input, "loop(n + 1)")
+        )
+    );
+
+    // The same programs allocate the same as with the interpreter
+    let input = "fn grow(xs) { grow([..xs, 1]) }\ngrow([])";
+    let limits = Limits {
+        max_bytes: Some(1000),
+        ..Limits::default()
+    };
+    let (program, resolution) = parse(input);
+    let expected = Interpreter::with_limits(&resolution, limits.clone())
+        .run(&program)
+        .unwrap_err();
+    assert_eq!(limited(input, limits), expected);
+
+    let cancel = CancelHandle::new();
+    let limits = Limits {
+        cancel: Some(cancel.clone()),
+        ..Limits::default()
+    };
+    let (program, resolution) = parse("fn f() { 1 }\nf");
+    let bytecode = compile_program(&program, &resolution).unwrap();
+    let mut vm = Vm::with_limits(&bytecode, limits);
+    let f = vm.run().unwrap();
+    cancel.cancel();
+    let pos = Position::new(0, 0);
+    assert_eq!(
+        vm.call(&f, vec![], pos),
+        Err(RuntimeError::new(
+            RuntimeErrorKind::Cancelled,
+            span("fn f() { 1 }", "1")
+        ))
+    );
+}
@@ -0,0 +1,181 @@
+use super::{BlockType, Instr, Module};
+
+pub const MAGIC: &[u8; 4] = b"\0asm";
+pub const VERSION: &[u8; 4] = &[1, 0, 0, 0];
+
+// Section ids
+pub const TYPE_SECTION: u8 = 1;
+pub const FUNCTION_SECTION: u8 = 3;
+pub const EXPORT_SECTION: u8 = 7;
+pub const CODE_SECTION: u8 = 10;
+
+const FUNC_TYPE: u8 = 0x60;
+const FUNC_EXPORT: u8 = 0x00;
+const EMPTY_BLOCK: u8 = 0x40;
+
+impl Module {
+    /// The module in the binary format.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(VERSION);
+
+        let mut types = vec![];
+        write_u32(&mut types, self.types.len() as u32);
+        for ty in self.types.iter() {
+            types.push(FUNC_TYPE);
+            write_u32(&mut types, ty.params.len() as u32);
+            types.extend(ty.params.iter().map(|ty| ty.byte()));
+            write_u32(&mut types, ty.results.len() as u32);
+            types.extend(ty.results.iter().map(|ty| ty.byte()));
+        }
+        section(&mut out, TYPE_SECTION, &types);
+
+        let mut functions = vec![];
+        write_u32(&mut functions, self.functions.len() as u32);
+        for function in self.functions.iter() {
+            write_u32(&mut functions, function.ty);
+        }
+        section(&mut out, FUNCTION_SECTION, &functions);
+
+        let mut exports = vec![];
+        write_u32(&mut exports, self.exports.len() as u32);
+        for export in self.exports.iter() {
+            write_name(&mut exports, &export.name);
+            exports.push(FUNC_EXPORT);
+            write_u32(&mut exports, export.function);
+        }
+        section(&mut out, EXPORT_SECTION, &exports);
+
+        let mut code = vec![];
+        write_u32(&mut code, self.functions.len() as u32);
+        for function in self.functions.iter() {
+            let mut body = vec![];
+            // Locals in runs of the same type
+            let mut runs: Vec<(u32, u8)> = vec![];
+            for (_, ty) in function.locals.iter() {
+                match runs.last_mut() {
+                    Some((count, byte)) if *byte == ty.byte() => *count += 1,
+                    _ => runs.push((1, ty.byte())),
+                }
+            }
+            write_u32(&mut body, runs.len() as u32);
+            for (count, byte) in runs {
+                write_u32(&mut body, count);
+                body.push(byte);
+            }
+            for instr in function.body.iter() {
+                write_instr(&mut body, instr);
+            }
+            write_instr(&mut body, &Instr::End);
+            write_u32(&mut code, body.len() as u32);
+            code.extend(body);
+        }
+        section(&mut out, CODE_SECTION, &code);
+        out
+    }
+}
+
+fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    write_u32(out, contents.len() as u32);
+    out.extend_from_slice(contents);
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+/// Appends `value` as an unsigned LEB128: seven bits at a time from the
+/// lowest, each byte but the last with its high bit set.
+pub fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Appends `value` as a signed LEB128, which ends once the rest of the value
+/// is all sign bits and the sign bit of the last byte agrees.
+pub fn write_i32(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let sign = byte & 0x40 != 0;
+        if (value == 0 && !sign) || (value == -1 && sign) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_block_type(out: &mut Vec<u8>, ty: BlockType) {
+    match ty {
+        BlockType::Empty => out.push(EMPTY_BLOCK),
+        BlockType::Value(ty) => out.push(ty.byte()),
+    }
+}
+
+pub fn write_instr(out: &mut Vec<u8>, instr: &Instr) {
+    match instr {
+        Instr::Unreachable => out.push(0x00),
+        Instr::Block(ty) => {
+            out.push(0x02);
+            write_block_type(out, *ty);
+        }
+        Instr::If(ty) => {
+            out.push(0x04);
+            write_block_type(out, *ty);
+        }
+        Instr::Else => out.push(0x05),
+        Instr::End => out.push(0x0B),
+        Instr::Br(depth) => {
+            out.push(0x0C);
+            write_u32(out, *depth);
+        }
+        Instr::Return => out.push(0x0F),
+        Instr::Call(function) => {
+            out.push(0x10);
+            write_u32(out, *function);
+        }
+        Instr::Drop => out.push(0x1A),
+        Instr::LocalGet(local) => {
+            out.push(0x20);
+            write_u32(out, *local);
+        }
+        Instr::LocalSet(local) => {
+            out.push(0x21);
+            write_u32(out, *local);
+        }
+        Instr::I32Const(value) => {
+            out.push(0x41);
+            write_i32(out, *value);
+        }
+        Instr::F64Const(value) => {
+            out.push(0x44);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        Instr::I32Eqz => out.push(0x45),
+        Instr::I32Eq => out.push(0x46),
+        Instr::I32Ne => out.push(0x47),
+        Instr::F64Eq => out.push(0x61),
+        Instr::F64Ne => out.push(0x62),
+        Instr::F64Lt => out.push(0x63),
+        Instr::F64Gt => out.push(0x64),
+        Instr::F64Le => out.push(0x65),
+        Instr::F64Ge => out.push(0x66),
+        Instr::F64Neg => out.push(0x9A),
+        Instr::F64Trunc => out.push(0x9D),
+        Instr::F64Add => out.push(0xA0),
+        Instr::F64Sub => out.push(0xA1),
+        Instr::F64Mul => out.push(0xA2),
+        Instr::F64Div => out.push(0xA3),
+    }
+}
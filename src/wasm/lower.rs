@@ -0,0 +1,695 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{FnStmt, Program, Stmt, StmtKind};
+use crate::diagnostic::Diagnostic;
+use crate::ir::{
+    self, BinaryOp, Block, BlockId, Callee, Const, Inst, InstKind, Terminator, UnaryOp, Value,
+    ValueType,
+};
+use crate::lexer::Position;
+use crate::resolve::Resolution;
+use crate::types::Typing;
+
+use super::{BlockType, Export, FuncType, Function, Instr, Module, ValType};
+
+/// Compiles the top-level functions of a checked program to a module that
+/// exports each of them by name. The functions are lowered to the IR and
+/// optimized first. Numbers are `f64` and bools `i32`, and `Unit` has no
+/// value. Dividing by zero and matching no arm trap.
+///
+/// The other top-level statements, like the calls that run a program, are
+/// left out. The errors are for the functions the IR cannot have, and for
+/// those that use strings or call the host.
+pub fn compile_program(
+    program: &Program,
+    resolution: &Resolution,
+    typing: &Typing,
+) -> Result<Module, Vec<Diagnostic>> {
+    let stmts: Vec<(Position, &FnStmt)> = program
+        .stmts
+        .iter()
+        .map(Stmt::declaration)
+        .filter_map(|stmt| match &stmt.kind {
+            StmtKind::Fn(fn_stmt) => Some((stmt.pos, fn_stmt)),
+            _ => None,
+        })
+        .collect();
+    let lowered = ir::lower_functions(program, resolution, typing);
+
+    let mut functions = vec![];
+    let mut diagnostics = vec![];
+    for ((pos, fn_stmt), function) in stmts.iter().zip(lowered) {
+        match function.and_then(|function| check(&function, *pos, fn_stmt).map(|()| function)) {
+            Ok(function) => functions.push(function),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    let mut lowered = ir::Module { functions };
+    if let Err(error) = ir::optimize(&mut lowered) {
+        panic!("the IR of a checked program is not well-formed {}", error);
+    }
+
+    let mut module = Module::default();
+    for (function, (_, fn_stmt)) in lowered.functions.iter().zip(stmts) {
+        let function = Builder::new(function, fn_stmt).lower(&mut module);
+        module.exports.push(Export {
+            name: fn_stmt.name.clone(),
+            function: module.functions.len() as u32,
+        });
+        module.functions.push(function);
+    }
+    Ok(module)
+}
+
+// Whether the function has only values WebAssembly has, and calls only
+// functions of the module. The errors for its parameters are at them, and
+// the others at the function.
+fn check(function: &ir::Function, pos: Position, fn_stmt: &FnStmt) -> Result<(), Diagnostic> {
+    for (param, value) in fn_stmt.params.iter().zip(function.params.iter()) {
+        match function.type_of(*value) {
+            ValueType::String => return Err(unsupported("values of type `String`", param.pos)),
+            ValueType::Unit => return Err(unsupported("parameters of type `Unit`", param.pos)),
+            ValueType::Number | ValueType::Bool => {}
+        }
+    }
+    if function.ret == ValueType::String {
+        return Err(unsupported("values of type `String`", pos));
+    }
+    for inst in function.insts() {
+        if function.type_of(inst.result) == ValueType::String {
+            return Err(unsupported("strings", pos));
+        }
+        if let InstKind::Call(Callee::Host(_), _) = inst.kind {
+            return Err(Diagnostic::error(
+                "only calls of top-level functions can be compiled to WebAssembly",
+                pos,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The WebAssembly type of values of type `ty`, `None` for `Unit`
+fn value_type(ty: ValueType) -> Option<ValType> {
+    match ty {
+        ValueType::Number => Some(ValType::F64),
+        ValueType::Bool => Some(ValType::I32),
+        ValueType::Unit => None,
+        ValueType::String => unreachable!("checked before lowering"),
+    }
+}
+
+fn unsupported(what: &str, pos: Position) -> Diagnostic {
+    Diagnostic::error(format!("{} cannot be compiled to WebAssembly", what), pos)
+}
+
+// Where a value is used: by an instruction of a block, at the end of a
+// block, by its terminator or the phis of the block it jumps to, or by the
+// phis of a block another one branches to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Use {
+    Inst(BlockId, usize),
+    End(BlockId),
+    Branch,
+}
+
+// What the condition of a branch is on the way to one of the blocks it
+// goes to, if `value` is the condition, as that of `&&` and `||` is
+fn known(function: &ir::Function, from: BlockId, to: BlockId, value: Value) -> Option<bool> {
+    match function.block(from)?.terminator {
+        Terminator::Branch {
+            condition,
+            then,
+            otherwise,
+        } if condition == value && then != otherwise => Some(to == then),
+        _ => None,
+    }
+}
+
+// The blocks that are on every path from each block to the return, itself
+// included, or `None` for blocks that reach no return. As those block every
+// path that goes through them, they are left out of the others'.
+fn post_dominators(function: &ir::Function) -> HashMap<BlockId, Option<HashSet<BlockId>>> {
+    fn visit(
+        function: &ir::Function,
+        id: BlockId,
+        known: &mut HashMap<BlockId, Option<HashSet<BlockId>>>,
+    ) -> Option<HashSet<BlockId>> {
+        if let Some(blocks) = known.get(&id) {
+            return blocks.clone();
+        }
+        let block = function.block(id).expect("verified");
+        let mut blocks = match &block.terminator {
+            Terminator::Return(_) => Some(HashSet::new()),
+            terminator => terminator
+                .successors()
+                .into_iter()
+                .map(|successor| visit(function, successor, known))
+                .fold(None, |all, blocks| match (all, blocks) {
+                    (Some(all), Some(blocks)) => Some(&all & &blocks),
+                    (all, blocks) => all.or(blocks),
+                }),
+        };
+        if let Some(blocks) = blocks.as_mut() {
+            blocks.insert(id);
+        }
+        known.insert(id, blocks.clone());
+        blocks
+    }
+
+    let mut known = HashMap::new();
+    for id in function.reachable() {
+        visit(function, id, &mut known);
+    }
+    known
+}
+
+// The code of one function. Control flow is structured from the blocks: a
+// branch is an `if` whose arms go on to the nearest block that is on every
+// path from the branch, which follows the `if`, and the first phi of that
+// block is the `if`'s value. A value used once, later in the block that
+// defines it, is computed where it is used, as long as what has effects
+// still runs in order, and the other values are kept in locals.
+struct Builder<'f> {
+    function: &'f ir::Function,
+    params: Vec<(String, ValType)>,
+    locals: Vec<(String, ValType)>,
+    // Local names in the text format, which must differ within a function
+    names: HashSet<String>,
+    // The local of each value that has one
+    slots: HashMap<Value, u32>,
+    defs: HashMap<Value, &'f Inst>,
+    uses: HashMap<Value, Vec<Use>>,
+    post_dominators: HashMap<BlockId, Option<HashSet<BlockId>>>,
+    body: Vec<Instr>,
+    // Two `f64` locals for the operands of `/` and `%`, once needed
+    scratch: Option<(u32, u32)>,
+    // The values of the block being lowered that are computed where they
+    // are used, not yet emitted
+    trees: HashSet<Value>,
+    // Those of them that no instruction has taken as an operand yet, in
+    // the order they are defined
+    pending: Vec<Value>,
+    // The value of the `if` before the block, while it is on the stack
+    stacked: Option<Value>,
+}
+
+impl<'f> Builder<'f> {
+    fn new(function: &'f ir::Function, fn_stmt: &FnStmt) -> Self {
+        let mut defs = HashMap::new();
+        let mut uses: HashMap<Value, Vec<Use>> = HashMap::new();
+        for block in function.blocks.iter() {
+            for (i, inst) in block.insts.iter().enumerate() {
+                defs.insert(inst.result, inst);
+                let InstKind::Phi(incoming) = &inst.kind else {
+                    for operand in inst.kind.operands() {
+                        uses.entry(operand)
+                            .or_default()
+                            .push(Use::Inst(block.id, i));
+                    }
+                    continue;
+                };
+                for (from, value) in incoming.iter() {
+                    if known(function, *from, block.id, *value).is_some() {
+                        continue;
+                    }
+                    let at = match function.block(*from).map(|from| &from.terminator) {
+                        Some(Terminator::Jump(_)) => Use::End(*from),
+                        _ => Use::Branch,
+                    };
+                    uses.entry(*value).or_default().push(at);
+                }
+            }
+            if let Some(operand) = block.terminator.operand() {
+                uses.entry(operand).or_default().push(Use::End(block.id));
+            }
+        }
+
+        let mut builder = Builder {
+            function,
+            params: vec![],
+            locals: vec![],
+            names: HashSet::new(),
+            slots: HashMap::new(),
+            defs,
+            uses,
+            post_dominators: post_dominators(function),
+            body: vec![],
+            scratch: None,
+            trees: HashSet::new(),
+            pending: vec![],
+            stacked: None,
+        };
+        for (param, value) in fn_stmt.params.iter().zip(function.params.iter()) {
+            let name = builder.name(&param.name);
+            let ty = value_type(function.type_of(*value)).expect("checked before lowering");
+            builder.slots.insert(*value, builder.params.len() as u32);
+            builder.params.push((name, ty));
+        }
+        builder
+    }
+
+    fn lower(mut self, module: &mut Module) -> Function {
+        if let Some(entry) = self.function.blocks.first() {
+            self.region(entry.id, None);
+        }
+        let ty = FuncType {
+            params: self.params.iter().map(|(_, ty)| *ty).collect(),
+            results: value_type(self.function.ret).into_iter().collect(),
+        };
+        Function {
+            name: self.function.name.clone(),
+            ty: module.type_index(ty),
+            params: self.params,
+            locals: self.locals,
+            body: self.body,
+        }
+    }
+
+    fn name(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut count = 0;
+        while !self.names.insert(unique.clone()) {
+            count += 1;
+            unique = format!("{}.{}", name, count);
+        }
+        unique
+    }
+
+    fn local(&mut self, name: &str, ty: ValType) -> u32 {
+        let name = self.name(name);
+        self.locals.push((name, ty));
+        (self.params.len() + self.locals.len() - 1) as u32
+    }
+
+    fn slot(&mut self, value: Value) -> u32 {
+        if let Some(slot) = self.slots.get(&value) {
+            return *slot;
+        }
+        let ty = self.value_type(value).expect("`Unit` has no local");
+        let slot = self.local(&format!("v{}", value.0), ty);
+        self.slots.insert(value, slot);
+        slot
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.body.push(instr);
+    }
+
+    fn value_type(&self, value: Value) -> Option<ValType> {
+        value_type(self.function.type_of(value))
+    }
+
+    // Emits the block and those after it, until the one `stop` is, which is
+    // where the arms of an `if` meet. Each of them leaves the value of the
+    // `if` on the stack.
+    fn region(&mut self, id: BlockId, stop: Option<BlockId>) {
+        let function = self.function;
+        let block = function.block(id).expect("verified");
+        self.insts(block);
+        match &block.terminator {
+            Terminator::Return(value) => {
+                self.operands(&[*value], false);
+                if stop.is_some() {
+                    self.emit(Instr::Return);
+                }
+            }
+            Terminator::Unreachable => {
+                self.begin(None);
+                self.emit(Instr::Unreachable);
+            }
+            Terminator::Jump(to) => {
+                self.edge(id, *to, stop == Some(*to));
+                match stop == Some(*to) {
+                    true => self.begin(None),
+                    false => self.region(*to, stop),
+                }
+            }
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.operands(&[*condition], false);
+                let merge = self.merge(id);
+                let value = merge.and_then(|merge| self.if_value(merge));
+                let ty = BlockType::of(value.and_then(|value| self.value_type(value)));
+                let start = self.body.len();
+                self.emit(Instr::If(ty));
+                self.arm(id, *then, merge);
+                // Without a value, an empty arm is left out
+                if ty == BlockType::Empty && self.body.len() == start + 1 {
+                    self.body.insert(start, Instr::I32Eqz);
+                    self.arm(id, *otherwise, merge);
+                } else {
+                    let start = self.body.len();
+                    self.emit(Instr::Else);
+                    self.arm(id, *otherwise, merge);
+                    if ty == BlockType::Empty && self.body.len() == start + 1 {
+                        self.body.pop();
+                    }
+                }
+                self.emit(Instr::End);
+                match merge {
+                    // Neither arm goes on
+                    None => self.emit(Instr::Unreachable),
+                    Some(merge) if Some(merge) == stop => {}
+                    Some(merge) => {
+                        if let Some(value) = value {
+                            match self.uses.get(&value).map_or(0, Vec::len) {
+                                1 => self.stacked = Some(value),
+                                _ => {
+                                    let slot = self.slot(value);
+                                    self.emit(Instr::LocalSet(slot));
+                                }
+                            }
+                        }
+                        self.region(merge, stop);
+                    }
+                }
+            }
+        }
+    }
+
+    fn arm(&mut self, from: BlockId, to: BlockId, merge: Option<BlockId>) {
+        self.edge(from, to, merge == Some(to));
+        if merge != Some(to) {
+            self.region(to, merge);
+        }
+    }
+
+    // The nearest block after a branch that is on every path from it that
+    // reaches the return, if any does
+    fn merge(&self, id: BlockId) -> Option<BlockId> {
+        let blocks = self.post_dominators[&id].as_ref()?;
+        blocks
+            .iter()
+            .filter(|block| **block != id)
+            .max_by_key(|block| {
+                self.post_dominators[*block]
+                    .as_ref()
+                    .map_or(0, HashSet::len)
+            })
+            .copied()
+    }
+
+    // The phi of a block that an `if` before it has as its value
+    fn if_value(&self, id: BlockId) -> Option<Value> {
+        let block = self.function.block(id)?;
+        block
+            .insts
+            .iter()
+            .filter(|inst| matches!(inst.kind, InstKind::Phi(_)))
+            .map(|inst| inst.result)
+            .find(|value| self.value_type(*value).is_some())
+    }
+
+    // Sets the phis of `to` to their values on the way from `from`, but
+    // leaves that of an `if` on the stack instead if `value`
+    fn edge(&mut self, from: BlockId, to: BlockId, value: bool) {
+        let function = self.function;
+        let block = function.block(to).expect("verified");
+        let stacked = match value {
+            true => self.if_value(to),
+            false => None,
+        };
+        let mut last = None;
+        for inst in block.insts.iter() {
+            let InstKind::Phi(incoming) = &inst.kind else {
+                continue;
+            };
+            let incoming = incoming.iter().find(|(block, _)| *block == from);
+            let Some((_, value)) = incoming.copied() else {
+                continue;
+            };
+            if Some(inst.result) == stacked {
+                last = Some(value);
+            } else if self.value_type(inst.result).is_some() {
+                self.value(from, to, value);
+                let slot = self.slot(inst.result);
+                self.emit(Instr::LocalSet(slot));
+            }
+        }
+        if let Some(value) = last {
+            self.value(from, to, value);
+        }
+    }
+
+    // Emits what a value is on the way from one block to another
+    fn value(&mut self, from: BlockId, to: BlockId, value: Value) {
+        match known(self.function, from, to, value) {
+            Some(bool) => {
+                self.begin(None);
+                self.emit(Instr::I32Const(bool as i32));
+            }
+            None => self.operands(&[value], false),
+        }
+    }
+
+    fn insts(&mut self, block: &'f Block) {
+        for (i, inst) in block.insts.iter().enumerate() {
+            // Constants are emitted where they are used, however many times
+            if let InstKind::Phi(_) | InstKind::Const(_) = inst.kind {
+                continue;
+            }
+            let operands = inst.kind.operands();
+            if self.is_tree(block.id, i, inst.result) {
+                self.take(&operands);
+                self.trees.insert(inst.result);
+                self.pending.push(inst.result);
+                continue;
+            }
+            self.operands(&operands, inst.kind.has_effects());
+            self.op(&inst.kind);
+            if self.value_type(inst.result).is_some() {
+                match self.uses.contains_key(&inst.result) {
+                    true => {
+                        let slot = self.slot(inst.result);
+                        self.emit(Instr::LocalSet(slot));
+                    }
+                    false => self.emit(Instr::Drop),
+                }
+            }
+        }
+    }
+
+    // Whether the value of the instruction at `i` of the block is computed
+    // where it is used
+    fn is_tree(&self, block: BlockId, i: usize, value: Value) -> bool {
+        if self.value_type(value).is_none() {
+            return false;
+        }
+        match self.uses.get(&value).map(Vec::as_slice) {
+            Some([Use::Inst(used, j)]) => *used == block && *j > i,
+            Some([Use::End(used)]) => *used == block,
+            _ => false,
+        }
+    }
+
+    // Has an instruction take the pending values among its operands. They
+    // are only computed where it is if they are the last ones pending, and in
+    // order, and otherwise go to their locals now.
+    fn take(&mut self, operands: &[Value]) {
+        let taken: Vec<Value> = operands
+            .iter()
+            .copied()
+            .filter(|value| self.pending.contains(value))
+            .collect();
+        if self.pending.ends_with(&taken) {
+            self.pending.truncate(self.pending.len() - taken.len());
+        } else {
+            for value in taken {
+                self.materialize(value);
+            }
+        }
+    }
+
+    // Emits the operands of what comes next, after what was pending that
+    // must run first
+    fn operands(&mut self, operands: &[Value], effects: bool) {
+        self.take(operands);
+        let effects = effects
+            || operands
+                .iter()
+                .any(|value| self.trees.contains(value) && self.has_effects(*value));
+        if effects {
+            self.flush(self.pending.len());
+        }
+        self.begin(self.first(operands));
+        for operand in operands {
+            self.push(*operand);
+        }
+    }
+
+    // Whether computing the value where it is used runs anything with effects
+    fn has_effects(&self, value: Value) -> bool {
+        let inst = self.defs[&value];
+        inst.kind.has_effects()
+            || inst
+                .kind
+                .operands()
+                .into_iter()
+                .any(|operand| self.trees.contains(&operand) && self.has_effects(operand))
+    }
+
+    // Computes those of the first `count` pending values that have effects
+    // into their locals, in order
+    fn flush(&mut self, count: usize) {
+        let effects: Vec<Value> = self.pending[..count]
+            .iter()
+            .copied()
+            .filter(|value| self.has_effects(*value))
+            .collect();
+        for value in effects {
+            self.materialize(value);
+        }
+    }
+
+    // Computes a pending value into its local now
+    fn materialize(&mut self, value: Value) {
+        let Some(index) = self.pending.iter().position(|pending| *pending == value) else {
+            return;
+        };
+        if self.has_effects(value) {
+            self.flush(index);
+        }
+        self.pending.retain(|pending| *pending != value);
+        self.trees.remove(&value);
+        let inst: &'f Inst = self.defs[&value];
+        let kind = &inst.kind;
+        let operands = kind.operands();
+        self.begin(self.first(&operands));
+        for operand in operands {
+            self.push(operand);
+        }
+        self.op(kind);
+        let slot = self.slot(value);
+        self.emit(Instr::LocalSet(slot));
+    }
+
+    // The value that the first instruction computing the values gets, if it
+    // is one that has been computed before
+    fn first(&self, values: &[Value]) -> Option<Value> {
+        let value = values
+            .iter()
+            .find(|value| self.value_type(**value).is_some())?;
+        match self.defs.get(value).map(|inst| &inst.kind) {
+            Some(InstKind::Const(_)) => None,
+            Some(kind) if self.trees.contains(value) => self.first(&kind.operands()),
+            _ => Some(*value),
+        }
+    }
+
+    // Moves the value of the `if` before to its local, unless it is `first`,
+    // as it is under what is computed next otherwise
+    fn begin(&mut self, first: Option<Value>) {
+        if let Some(value) = self.stacked.filter(|value| first != Some(*value)) {
+            self.stacked = None;
+            let slot = self.slot(value);
+            self.emit(Instr::LocalSet(slot));
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        let def = self.defs.get(&value).copied();
+        if let Some(Inst {
+            kind: kind @ InstKind::Const(_),
+            ..
+        }) = def
+        {
+            self.op(kind);
+        } else if self.trees.remove(&value) {
+            let inst: &'f Inst = self.defs[&value];
+            let kind = &inst.kind;
+            for operand in kind.operands() {
+                self.push(operand);
+            }
+            self.op(kind);
+        } else if self.stacked == Some(value) {
+            self.stacked = None;
+        } else if self.value_type(value).is_some() {
+            let slot = self.slot(value);
+            self.emit(Instr::LocalGet(slot));
+        }
+    }
+
+    // The instructions for what an instruction does, with its operands on
+    // the stack
+    fn op(&mut self, kind: &InstKind) {
+        let instr = match kind {
+            InstKind::Const(Const::Number(number)) => Instr::F64Const(*number),
+            InstKind::Const(Const::Bool(bool)) => Instr::I32Const(*bool as i32),
+            InstKind::Const(Const::Unit) | InstKind::Copy(_) => return,
+            InstKind::Unary(UnaryOp::Neg, _) => Instr::F64Neg,
+            InstKind::Unary(UnaryOp::Not, _) => Instr::I32Eqz,
+            InstKind::Binary(op, lhs, _) => return self.binary(*op, self.function.type_of(*lhs)),
+            InstKind::Call(Callee::Function(function), _) => Instr::Call(*function),
+            InstKind::Const(Const::String(_)) | InstKind::Call(Callee::Host(_), _) => {
+                unreachable!("checked before lowering")
+            }
+            InstKind::Phi(_) => unreachable!("phis are set by the blocks before"),
+        };
+        self.emit(instr);
+    }
+
+    fn binary(&mut self, op: BinaryOp, operands: ValueType) {
+        let instr = match (op, operands) {
+            (BinaryOp::Eq | BinaryOp::Ne, ValueType::Unit) => {
+                Instr::I32Const((op == BinaryOp::Eq) as i32)
+            }
+            (BinaryOp::Eq, ValueType::Bool) => Instr::I32Eq,
+            (BinaryOp::Ne, ValueType::Bool) => Instr::I32Ne,
+            (BinaryOp::Eq, _) => Instr::F64Eq,
+            (BinaryOp::Ne, _) => Instr::F64Ne,
+            (BinaryOp::Lt, _) => Instr::F64Lt,
+            (BinaryOp::Le, _) => Instr::F64Le,
+            (BinaryOp::Gt, _) => Instr::F64Gt,
+            (BinaryOp::Ge, _) => Instr::F64Ge,
+            (BinaryOp::Add, _) => Instr::F64Add,
+            (BinaryOp::Sub, _) => Instr::F64Sub,
+            (BinaryOp::Mul, _) => Instr::F64Mul,
+            (BinaryOp::Div | BinaryOp::Rem, _) => return self.division(op),
+            (BinaryOp::Concat, _) => unreachable!("checked before lowering"),
+        };
+        self.emit(instr);
+    }
+
+    // The operands are on the stack. The remainder has the sign of the
+    // dividend, as `a - b * trunc(a / b)`.
+    fn division(&mut self, op: BinaryOp) {
+        let (a, b) = match self.scratch {
+            Some(scratch) => scratch,
+            None => {
+                let scratch = (
+                    self.local("lhs", ValType::F64),
+                    self.local("rhs", ValType::F64),
+                );
+                self.scratch = Some(scratch);
+                scratch
+            }
+        };
+        self.emit(Instr::LocalSet(b));
+        self.emit(Instr::LocalSet(a));
+        self.emit(Instr::LocalGet(b));
+        self.emit(Instr::F64Const(0.0));
+        self.emit(Instr::F64Eq);
+        self.emit(Instr::If(BlockType::Empty));
+        self.emit(Instr::Unreachable);
+        self.emit(Instr::End);
+        self.emit(Instr::LocalGet(a));
+        if op == BinaryOp::Rem {
+            self.emit(Instr::LocalGet(b));
+            self.emit(Instr::LocalGet(a));
+        }
+        self.emit(Instr::LocalGet(b));
+        self.emit(Instr::F64Div);
+        if op == BinaryOp::Rem {
+            self.emit(Instr::F64Trunc);
+            self.emit(Instr::F64Mul);
+            self.emit(Instr::F64Sub);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+pub mod encode;
+pub mod lower;
+#[cfg(test)]
+mod tests;
+pub mod validate;
+mod wat;
+
+use std::fmt;
+
+pub use self::encode::*;
+pub use self::lower::*;
+pub use self::validate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValType {
+    pub fn byte(&self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+            ValType::F32 => 0x7D,
+            ValType::F64 => 0x7C,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<ValType> {
+        match byte {
+            0x7F => Some(ValType::I32),
+            0x7E => Some(ValType::I64),
+            0x7D => Some(ValType::F32),
+            0x7C => Some(ValType::F64),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F32 => "f32",
+            ValType::F64 => "f64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// What a `block`, `loop` or `if` leaves on the stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockType {
+    Empty,
+    Value(ValType),
+}
+
+impl BlockType {
+    pub fn of(ty: Option<ValType>) -> BlockType {
+        ty.map_or(BlockType::Empty, BlockType::Value)
+    }
+}
+
+/// The instructions the backend emits. Structured ones are flat, with their
+/// `else` and `end` as instructions of their own, as in the binary format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instr {
+    Unreachable,
+    Block(BlockType),
+    If(BlockType),
+    Else,
+    End,
+    Br(u32),
+    Return,
+    Call(u32),
+    Drop,
+    LocalGet(u32),
+    LocalSet(u32),
+    I32Const(i32),
+    F64Const(f64),
+    I32Eqz,
+    I32Eq,
+    I32Ne,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    F64Neg,
+    F64Trunc,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    // Index into the types of the module
+    pub ty: u32,
+    // Named for the text format, and numbered after the parameters
+    pub params: Vec<(String, ValType)>,
+    pub locals: Vec<(String, ValType)>,
+    // Without the final `end`
+    pub body: Vec<Instr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub function: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Module {
+    pub types: Vec<FuncType>,
+    pub functions: Vec<Function>,
+    pub exports: Vec<Export>,
+}
+
+impl Module {
+    /// The index of `ty`, added to the types if it is new.
+    pub fn type_index(&mut self, ty: FuncType) -> u32 {
+        match self.types.iter().position(|known| *known == ty) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(ty);
+                (self.types.len() - 1) as u32
+            }
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::*;
+use crate::ast::Parser;
+use crate::resolve::resolve_program_with;
+use crate::types::check_program;
+
+// Names the golden programs call without defining
+const HOST: &[&str] = &["print"];
+
+fn lower(source: &str) -> std::result::Result<Module, Vec<String>> {
+    let program = Parser::new(source).parse_program().unwrap();
+    let resolution = resolve_program_with(&program, HOST);
+    assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+    let typing = check_program(&program, &resolution);
+    assert!(!typing.has_errors(), "{:?}", typing.diagnostics);
+    compile_program(&program, &resolution, &typing).map_err(|diagnostics| {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    })
+}
+
+fn ty(params: &[ValType], results: &[ValType]) -> FuncType {
+    FuncType {
+        params: params.to_vec(),
+        results: results.to_vec(),
+    }
+}
+
+// The programs in `tests/golden/wasm`, each with the text format it
+// compiles to next to it. Run with `UPDATE_GOLDEN=1` to write them anew.
+#[test]
+fn compile_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/wasm");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".zp"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+
+    let mut changed = vec![];
+    for name in names.iter() {
+        let source = fs::read_to_string(dir.join(name)).unwrap();
+        let module = lower(&source).unwrap();
+        let valid =
+            validate(&module.to_binary()).unwrap_or_else(|error| panic!("{}: {}", name, error));
+        assert_eq!(valid.exports.len(), module.functions.len());
+
+        let file = format!("{}.wat", name.trim_end_matches(".zp"));
+        let path = dir.join(&file);
+        let actual = module.to_wat();
+        if update {
+            fs::write(&path, &actual).unwrap();
+        } else if fs::read_to_string(&path).ok().as_deref() != Some(actual.as_str()) {
+            changed.push(file);
+        }
+    }
+    assert!(
+        changed.is_empty(),
+        "differ from their golden files: {:?}",
+        changed
+    );
+}
+
+#[test]
+fn export_functions_with_their_types() {
+    let module = lower(
+        "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }\n\
+         fn even(n) { n % 2 == 0 }\n\
+         fn both(a, b) { a && b }\n\
+         fn skip(n) { let m = n + 1; }",
+    )
+    .unwrap();
+    let valid = validate(&module.to_binary()).unwrap();
+    use ValType::{F64, I32};
+    assert_eq!(valid.export_type("fib"), Some(&ty(&[F64], &[F64])));
+    assert_eq!(valid.export_type("even"), Some(&ty(&[F64], &[I32])));
+    assert_eq!(valid.export_type("both"), Some(&ty(&[I32, I32], &[I32])));
+    assert_eq!(valid.export_type("skip"), Some(&ty(&[F64], &[])));
+    assert_eq!(valid.export_type("missing"), None);
+}
+
+#[test]
+fn encode_a_module() {
+    let module = lower("fn add(a, b) { a + b }").unwrap();
+    #[rustfmt::skip]
+    let expected: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00,
+        // (type (func (param f64 f64) (result f64)))
+        0x01, 0x07, 0x01, 0x60, 0x02, 0x7C, 0x7C, 0x01, 0x7C,
+        // (func (type 0))
+        0x03, 0x02, 0x01, 0x00,
+        // (export "add" (func 0))
+        0x07, 0x07, 0x01, 0x03, b'a', b'd', b'd', 0x00, 0x00,
+        // local.get 0, local.get 1, f64.add, end
+        0x0A, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0xA0, 0x0B,
+    ];
+    assert_eq!(module.to_binary(), expected);
+    assert_eq!(
+        module.to_wat(),
+        "(module\n  (type (;0;) (func (param f64 f64) (result f64)))\n  \
+         (func $add (type 0) (param $a f64) (param $b f64) (result f64)\n    \
+         local.get $a\n    local.get $b\n    f64.add\n  )\n  \
+         (export \"add\" (func $add))\n)\n"
+    );
+}
+
+#[test]
+fn encode_leb128() {
+    let unsigned = |value| {
+        let mut out = vec![];
+        write_u32(&mut out, value);
+        out
+    };
+    let signed = |value| {
+        let mut out = vec![];
+        write_i32(&mut out, value);
+        out
+    };
+    assert_eq!(unsigned(0), [0x00]);
+    assert_eq!(unsigned(127), [0x7F]);
+    assert_eq!(unsigned(128), [0x80, 0x01]);
+    assert_eq!(unsigned(624485), [0xE5, 0x8E, 0x26]);
+    assert_eq!(unsigned(u32::MAX), [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    assert_eq!(signed(0), [0x00]);
+    assert_eq!(signed(-1), [0x7F]);
+    assert_eq!(signed(63), [0x3F]);
+    assert_eq!(signed(64), [0xC0, 0x00]);
+    assert_eq!(signed(-64), [0x40]);
+    assert_eq!(signed(-123456), [0xC0, 0xBB, 0x78]);
+    assert_eq!(signed(i32::MIN), [0x80, 0x80, 0x80, 0x80, 0x78]);
+}
+
+// A module with one function of type `[] -> [i32]` and the given body,
+// without its locals or the final `end`
+fn module_with_body(body: &[u8]) -> Vec<u8> {
+    let mut code = vec![0x00];
+    code.extend_from_slice(body);
+    code.push(0x0B);
+    let mut bytes = vec![
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F,
+        0x03, 0x02, 0x01, 0x00,
+    ];
+    bytes.push(0x0A);
+    bytes.push(code.len() as u8 + 2);
+    bytes.push(0x01);
+    bytes.push(code.len() as u8);
+    bytes.extend(code);
+    bytes
+}
+
+fn error(bytes: &[u8]) -> String {
+    validate(bytes).unwrap_err().message
+}
+
+#[test]
+fn validate_instructions() {
+    // i32.const 1
+    assert!(validate(&module_with_body(&[0x41, 0x01])).is_ok());
+    // if (result i32) i32.const 1 else i32.const 2 end, under i32.const 0
+    assert!(validate(&module_with_body(&[
+        0x41, 0x00, 0x04, 0x7F, 0x41, 0x01, 0x05, 0x41, 0x02, 0x0B
+    ]))
+    .is_ok());
+    // Anything goes after `unreachable`
+    assert!(validate(&module_with_body(&[0x00, 0xA0, 0x1A])).is_ok());
+
+    // Nothing left for the result
+    assert!(error(&module_with_body(&[])).contains("empty"));
+    // f64.const 1 where an i32 is returned
+    let mut body = vec![0x44];
+    body.extend_from_slice(&1f64.to_le_bytes());
+    assert!(error(&module_with_body(&body)).contains("expected i32"));
+    // i32.add is fine, but i32.const 1, f64.add is not
+    assert!(error(&module_with_body(&[0x41, 0x01, 0x41, 0x01, 0xA0])).contains("expected f64"));
+    assert!(error(&module_with_body(&[0x20, 0x00])).contains("local"));
+    assert!(error(&module_with_body(&[0x10, 0x05])).contains("function"));
+    assert!(error(&module_with_body(&[0x41, 0x01, 0x0C, 0x03])).contains("label"));
+    // if (result i32) without an else
+    assert!(error(&module_with_body(&[
+        0x41, 0x00, 0x04, 0x7F, 0x41, 0x01, 0x0B
+    ]))
+    .contains("else"));
+    // A block that the `end` of the function closes, leaving it open
+    assert!(validate(&module_with_body(&[0x02, 0x7F, 0x41, 0x01])).is_err());
+    assert!(error(&module_with_body(&[0xFC])).contains("unsupported"));
+}
+
+#[test]
+fn validate_sections() {
+    let valid = module_with_body(&[0x41, 0x01]);
+    assert!(validate(&valid).is_ok());
+
+    assert_eq!(
+        validate(b"\0wasm\x01\0\0\0").unwrap_err(),
+        ValidationError {
+            offset: 0,
+            message: "not a WebAssembly module".to_string()
+        }
+    );
+    assert_eq!(validate(b"\0asm\x02\0\0\0").unwrap_err().offset, 4);
+
+    // Cut off within the code
+    assert!(validate(&valid[..valid.len() - 2]).is_err());
+    // The function section claiming a byte more than it has
+    let mut longer = valid.clone();
+    longer[16] = 0x03;
+    assert!(validate(&longer).is_err());
+    // A second type section after the code
+    let mut repeated = valid.clone();
+    repeated.extend_from_slice(&valid[8..15]);
+    assert_eq!(error(&repeated), "section 1 is out of order");
+    // The function section after the code, which then has a body too many
+    let mut swapped = valid[..15].to_vec();
+    swapped.extend_from_slice(&valid[19..]);
+    swapped.extend_from_slice(&valid[15..19]);
+    assert_eq!(error(&swapped), "1 function bodies for 0 functions");
+    // No code for the function
+    assert!(error(&valid[..19]).contains("code"));
+    // A custom section anywhere is skipped
+    let mut custom = valid.clone();
+    custom.extend_from_slice(&[0x00, 0x03, 0x01, b'x', 0xFF]);
+    assert!(validate(&custom).is_ok());
+
+    let mut exports = valid[..19].to_vec();
+    exports.extend_from_slice(&[
+        0x07, 0x09, 0x02, 0x01, b'f', 0x00, 0x00, 0x01, b'f', 0x00, 0x00,
+    ]);
+    exports.extend_from_slice(&valid[19..]);
+    assert!(error(&exports).contains("exported twice"));
+}
+
+#[test]
+fn reject_what_has_no_lowering() {
+    let errors = |source| lower(source).unwrap_err();
+    assert_eq!(
+        errors("fn greet(n) { n + 1; 'hi' }"),
+        vec!["values of type `String` cannot be compiled to WebAssembly"]
+    );
+    assert_eq!(
+        errors("fn f(n) { let s = 'a'; n + 1 }"),
+        vec!["strings cannot be compiled to WebAssembly"]
+    );
+    assert_eq!(
+        errors("fn f(n) { let g = fn(x) => x + 1; g(n) }"),
+        vec!["lambdas cannot be lowered to the IR"]
+    );
+    assert_eq!(
+        errors("fn d(n) { n * 2 }\nfn f(n) { let g = d; g(n) }"),
+        vec!["functions as values cannot be lowered to the IR"]
+    );
+    assert_eq!(errors("fn id(x) { x }").len(), 1);
+    // Other functions are still lowered, and errors are for each function
+    assert_eq!(
+        errors("fn a(n) { [n] }\nfn b(n) { n + 1 }\nfn c(n) { n + 1; 'c' }\nlet x = a(1);\nlet y = c(1);")
+            .len(),
+        2
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Val {
+    I32(i32),
+    F64(f64),
+}
+
+// The index of the `else` or `end` that closes the block opened before
+// `start`, at its depth
+fn closing(body: &[Instr], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, instr) in body.iter().enumerate().skip(start) {
+        match instr {
+            Instr::Block(_) | Instr::If(_) => depth += 1,
+            Instr::Else if depth == 0 => return i,
+            Instr::End if depth == 0 => return i,
+            Instr::End => depth -= 1,
+            _ => {}
+        }
+    }
+    unreachable!("validated")
+}
+
+// What a function of the module returns for the arguments, or `None` if it
+// traps. Only what the lowering emits is run.
+fn run(module: &Module, function: u32, args: &[Val]) -> Option<Option<Val>> {
+    let function = &module.functions[function as usize];
+    let mut locals = args.to_vec();
+    locals.extend(function.locals.iter().map(|(_, ty)| match ty {
+        ValType::I32 => Val::I32(0),
+        _ => Val::F64(0.0),
+    }));
+    let body = &function.body;
+    let mut stack = vec![];
+    let mut pc = 0;
+    while pc < body.len() {
+        let f64s = |stack: &mut Vec<Val>| match (stack.pop(), stack.pop()) {
+            (Some(Val::F64(b)), Some(Val::F64(a))) => (a, b),
+            _ => unreachable!("validated"),
+        };
+        let value = match body[pc] {
+            Instr::Unreachable => return None,
+            Instr::If(_) => {
+                if stack.pop() == Some(Val::I32(0)) {
+                    pc = closing(body, pc + 1);
+                }
+                None
+            }
+            Instr::Else => {
+                pc = closing(body, pc + 1);
+                None
+            }
+            Instr::End => None,
+            Instr::Return => break,
+            Instr::Call(callee) => {
+                let count = module.types[module.functions[callee as usize].ty as usize]
+                    .params
+                    .len();
+                let args = stack.split_off(stack.len() - count);
+                run(module, callee, &args)?
+            }
+            Instr::Drop => {
+                stack.pop();
+                None
+            }
+            Instr::LocalGet(local) => Some(locals[local as usize]),
+            Instr::LocalSet(local) => {
+                locals[local as usize] = stack.pop().unwrap();
+                None
+            }
+            Instr::I32Const(value) => Some(Val::I32(value)),
+            Instr::F64Const(value) => Some(Val::F64(value)),
+            Instr::I32Eqz => match stack.pop() {
+                Some(Val::I32(value)) => Some(Val::I32((value == 0) as i32)),
+                _ => unreachable!("validated"),
+            },
+            Instr::I32Eq | Instr::I32Ne => match (stack.pop(), stack.pop()) {
+                (Some(b), Some(a)) => {
+                    Some(Val::I32(((a == b) == (body[pc] == Instr::I32Eq)) as i32))
+                }
+                _ => unreachable!("validated"),
+            },
+            Instr::F64Neg | Instr::F64Trunc => match stack.pop() {
+                Some(Val::F64(value)) if body[pc] == Instr::F64Neg => Some(Val::F64(-value)),
+                Some(Val::F64(value)) => Some(Val::F64(value.trunc())),
+                _ => unreachable!("validated"),
+            },
+            instr => {
+                let (a, b) = f64s(&mut stack);
+                Some(match instr {
+                    Instr::F64Eq => Val::I32((a == b) as i32),
+                    Instr::F64Ne => Val::I32((a != b) as i32),
+                    Instr::F64Lt => Val::I32((a < b) as i32),
+                    Instr::F64Gt => Val::I32((a > b) as i32),
+                    Instr::F64Le => Val::I32((a <= b) as i32),
+                    Instr::F64Ge => Val::I32((a >= b) as i32),
+                    Instr::F64Add => Val::F64(a + b),
+                    Instr::F64Sub => Val::F64(a - b),
+                    Instr::F64Mul => Val::F64(a * b),
+                    Instr::F64Div => Val::F64(a / b),
+                    instr => unreachable!("not emitted: {:?}", instr),
+                })
+            }
+        };
+        stack.extend(value);
+        pc += 1;
+    }
+    Some(stack.pop())
+}
+
+#[test]
+fn run_golden_functions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/wasm");
+    let module = |name| lower(&fs::read_to_string(dir.join(name)).unwrap()).unwrap();
+    let call = |module: &Module, name: &str, args: &[Val]| {
+        let export = module.exports.iter().find(|export| export.name == name);
+        run(module, export.unwrap().function, args).unwrap()
+    };
+    use Val::{F64, I32};
+
+    let arith = module("arith.zp");
+    assert_eq!(call(&arith, "fib", &[F64(10.0)]), Some(F64(55.0)));
+    assert_eq!(call(&arith, "gcd", &[F64(12.0), F64(18.0)]), Some(F64(6.0)));
+    assert_eq!(call(&arith, "gcd", &[F64(-7.0), F64(0.0)]), Some(F64(-7.0)));
+    assert_eq!(call(&arith, "mean", &[F64(3.0), F64(4.0)]), Some(F64(3.5)));
+    assert_eq!(call(&arith, "negate", &[F64(2.0)]), Some(F64(-2.0)));
+
+    let control = module("control.zp");
+    for (n, class) in [(0.0, 0.0), (-5.0, -1.0), (5.0, 1.0)] {
+        assert_eq!(call(&control, "classify", &[F64(n)]), Some(F64(class)));
+        assert_eq!(call(&control, "twice", &[F64(n)]), Some(F64(class * 2.0)));
+    }
+    assert_eq!(call(&control, "count", &[F64(3.0)]), Some(F64(1.0)));
+    assert_eq!(call(&control, "count", &[F64(6.0)]), Some(F64(13.0)));
+    assert_eq!(call(&control, "flag", &[I32(1)]), Some(F64(1.0)));
+    assert_eq!(call(&control, "flag", &[I32(0)]), Some(F64(0.0)));
+    assert_eq!(call(&control, "nothing", &[F64(1.0)]), Some(F64(2.0)));
+
+    let logic = module("logic.zp");
+    let (x, low, high) = (F64(5.0), F64(1.0), F64(10.0));
+    assert_eq!(call(&logic, "between", &[x, low, high]), Some(I32(1)));
+    assert_eq!(call(&logic, "outside", &[x, low, high]), Some(I32(0)));
+    assert_eq!(
+        call(&logic, "outside", &[F64(11.0), low, high]),
+        Some(I32(1))
+    );
+    for (a, b) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+        let (or, same) = ((a | b), (a == b) as i32);
+        assert_eq!(call(&logic, "either", &[I32(a), I32(b)]), Some(I32(or)));
+        assert_eq!(call(&logic, "same", &[I32(a), I32(b)]), Some(I32(same)));
+    }
+
+    let module = lower("fn f(a) { 1 / a }").unwrap();
+    assert_eq!(run(&module, 0, &[F64(0.0)]), None);
+    assert_eq!(run(&module, 0, &[F64(4.0)]), Some(Some(F64(0.25))));
+}
@@ -0,0 +1,546 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::encode::{CODE_SECTION, EXPORT_SECTION, FUNCTION_SECTION, MAGIC, TYPE_SECTION, VERSION};
+use super::{FuncType, ValType};
+
+const CUSTOM_SECTION: u8 = 0;
+
+// Past which a function is taken to declare locals without end
+const MAX_LOCALS: u64 = 50_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    // Of the byte at which the module stops being valid
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+type Result<T> = std::result::Result<T, ValidationError>;
+
+/// What a valid module declares.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidModule {
+    pub types: Vec<FuncType>,
+    // The type index of each function
+    pub functions: Vec<u32>,
+    pub exports: Vec<(String, u32)>,
+}
+
+impl ValidModule {
+    /// The type of the function exported as `name`.
+    pub fn export_type(&self, name: &str) -> Option<&FuncType> {
+        let (_, function) = self.exports.iter().find(|(export, _)| export == name)?;
+        Some(&self.types[self.functions[*function as usize] as usize])
+    }
+}
+
+/// Checks that `bytes` are a module of the binary format with the sections
+/// and instructions this backend uses: its sections are in order and of the
+/// size they claim, indices refer to what exists, and every function body
+/// type checks, with blocks that are closed and leave the values they
+/// declare.
+pub fn validate(bytes: &[u8]) -> Result<ValidModule> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err(reader.error_at(0, "not a WebAssembly module"));
+    }
+    if reader.take(4)? != VERSION {
+        return Err(reader.error_at(4, "unsupported version"));
+    }
+
+    let mut module = ValidModule::default();
+    let mut last = 0;
+    let mut bodies = None;
+    while !reader.at_end() {
+        let start = reader.offset;
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let end = reader.offset + size;
+        if end > bytes.len() {
+            return Err(reader.error("section goes past the end of the module"));
+        }
+        if id != CUSTOM_SECTION {
+            if id <= last {
+                return Err(reader.error_at(start, format!("section {} is out of order", id)));
+            }
+            last = id;
+        }
+        let mut section = Reader {
+            bytes: &bytes[..end],
+            offset: reader.offset,
+        };
+        match id {
+            CUSTOM_SECTION => {
+                section.name()?;
+                section.offset = end;
+            }
+            TYPE_SECTION => module.types = section.types()?,
+            FUNCTION_SECTION => {
+                for _ in 0..section.u32()? {
+                    let index_at = section.offset;
+                    let index = section.u32()?;
+                    if index as usize >= module.types.len() {
+                        return Err(section.error_at(index_at, "unknown type"));
+                    }
+                    module.functions.push(index);
+                }
+            }
+            EXPORT_SECTION => module.exports = section.exports(module.functions.len())?,
+            CODE_SECTION => {
+                let count_at = section.offset;
+                let count = section.u32()? as usize;
+                if count != module.functions.len() {
+                    return Err(section.error_at(
+                        count_at,
+                        format!(
+                            "{} function bodies for {} functions",
+                            count,
+                            module.functions.len()
+                        ),
+                    ));
+                }
+                for function in 0..count {
+                    section.body(&module, function)?;
+                }
+                bodies = Some(count);
+            }
+            id => return Err(reader.error_at(start, format!("unsupported section {}", id))),
+        }
+        if !section.at_end() {
+            return Err(section.error("section is longer than its contents"));
+        }
+        reader.offset = end;
+    }
+    if bodies.is_none() && !module.functions.is_empty() {
+        return Err(reader.error("functions without a code section"));
+    }
+    Ok(module)
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    offset: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn error(&self, message: impl Into<String>) -> ValidationError {
+        self.error_at(self.offset, message)
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> ValidationError {
+        ValidationError {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or_else(|| self.error("unexpected end"))?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'b [u8]> {
+        if self.offset + count > self.bytes.len() {
+            return Err(self.error("unexpected end"));
+        }
+        self.offset += count;
+        Ok(&self.bytes[self.offset - count..self.offset])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let start = self.offset;
+        let mut value: u64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value).map_err(|_| self.error_at(start, "integer too large"));
+            }
+        }
+        Err(self.error_at(start, "integer representation too long"))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let start = self.offset;
+        let mut value: i64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as i64) << shift;
+            if byte & 0x80 == 0 {
+                if byte & 0x40 != 0 {
+                    value |= -1i64 << (shift + 7);
+                }
+                return i32::try_from(value).map_err(|_| self.error_at(start, "integer too large"));
+            }
+        }
+        Err(self.error_at(start, "integer representation too long"))
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let length = self.u32()? as usize;
+        let start = self.offset;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.error_at(start, "name is not UTF-8"))
+    }
+
+    fn val_type(&mut self) -> Result<ValType> {
+        let byte = self.byte()?;
+        ValType::from_byte(byte)
+            .ok_or_else(|| self.error_at(self.offset - 1, format!("unknown type {:#04x}", byte)))
+    }
+
+    fn types(&mut self) -> Result<Vec<FuncType>> {
+        let mut types = vec![];
+        for _ in 0..self.u32()? {
+            if self.byte()? != 0x60 {
+                return Err(self.error_at(self.offset - 1, "expected a function type"));
+            }
+            let mut ty = FuncType::default();
+            for _ in 0..self.u32()? {
+                ty.params.push(self.val_type()?);
+            }
+            for _ in 0..self.u32()? {
+                ty.results.push(self.val_type()?);
+            }
+            types.push(ty);
+        }
+        Ok(types)
+    }
+
+    fn exports(&mut self, functions: usize) -> Result<Vec<(String, u32)>> {
+        let mut exports = vec![];
+        let mut names = HashSet::new();
+        for _ in 0..self.u32()? {
+            let name_at = self.offset;
+            let name = self.name()?;
+            if !names.insert(name.clone()) {
+                return Err(self.error_at(name_at, format!("`{}` is exported twice", name)));
+            }
+            if self.byte()? != 0x00 {
+                return Err(self.error_at(self.offset - 1, "only functions can be exported"));
+            }
+            let index_at = self.offset;
+            let index = self.u32()?;
+            if index as usize >= functions {
+                return Err(self.error_at(index_at, "unknown function"));
+            }
+            exports.push((name, index));
+        }
+        Ok(exports)
+    }
+
+    fn body(&mut self, module: &ValidModule, function: usize) -> Result<()> {
+        let size = self.u32()? as usize;
+        let end = self.offset + size;
+        if end > self.bytes.len() {
+            return Err(self.error("function body goes past the end of its section"));
+        }
+        let ty = &module.types[module.functions[function] as usize];
+        let mut locals = ty.params.clone();
+        let mut count: u64 = 0;
+        for _ in 0..self.u32()? {
+            let run = self.u32()?;
+            count += run as u64;
+            if count > MAX_LOCALS {
+                return Err(self.error("too many locals"));
+            }
+            let ty = self.val_type()?;
+            locals.extend(std::iter::repeat_n(ty, run as usize));
+        }
+
+        let mut body = Reader {
+            bytes: &self.bytes[..end],
+            offset: self.offset,
+        };
+        let mut checker = Checker {
+            module,
+            locals,
+            operands: vec![],
+            frames: vec![Frame {
+                kind: FrameKind::Function,
+                results: ty.results.clone(),
+                height: 0,
+                unreachable: false,
+            }],
+        };
+        while !checker.frames.is_empty() {
+            let at = body.offset;
+            checker
+                .instr(&mut body)
+                .map_err(|message| body.error_at(at, message))?;
+        }
+        if !body.at_end() {
+            return Err(body.error("instructions after the end of the function"));
+        }
+        self.offset = end;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+// A block being checked
+struct Frame {
+    kind: FrameKind,
+    results: Vec<ValType>,
+    // Of the operand stack when the block started
+    height: usize,
+    // After an instruction that never falls through, below which the
+    // operands are of any type
+    unreachable: bool,
+}
+
+// Checks the types of instructions as in the validation algorithm of the
+// specification. An operand of `None` type is one of an unreachable part,
+// which stands in for any type.
+struct Checker<'m> {
+    module: &'m ValidModule,
+    locals: Vec<ValType>,
+    operands: Vec<Option<ValType>>,
+    frames: Vec<Frame>,
+}
+
+type Check<T> = std::result::Result<T, String>;
+
+impl Checker<'_> {
+    fn push(&mut self, ty: ValType) {
+        self.operands.push(Some(ty));
+    }
+
+    fn pop(&mut self, expected: Option<ValType>) -> Check<Option<ValType>> {
+        let frame = self.frames.last().unwrap();
+        if self.operands.len() == frame.height {
+            return match frame.unreachable {
+                true => Ok(expected),
+                false => Err("operand stack is empty".to_string()),
+            };
+        }
+        let actual = self.operands.pop().unwrap();
+        match (actual, expected) {
+            (Some(actual), Some(expected)) if actual != expected => Err(format!(
+                "type mismatch: expected {}, found {}",
+                expected, actual
+            )),
+            (Some(actual), _) => Ok(Some(actual)),
+            (None, expected) => Ok(expected),
+        }
+    }
+
+    fn pop_all(&mut self, types: &[ValType]) -> Check<()> {
+        for ty in types.iter().rev() {
+            self.pop(Some(*ty))?;
+        }
+        Ok(())
+    }
+
+    fn unreachable(&mut self) {
+        let frame = self.frames.last_mut().unwrap();
+        self.operands.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    // Of the label `depth` blocks out, which a branch leaves
+    fn label(&self, depth: u32) -> Check<Vec<ValType>> {
+        let index = self
+            .frames
+            .len()
+            .checked_sub(depth as usize + 1)
+            .ok_or_else(|| format!("unknown label {}", depth))?;
+        let frame = &self.frames[index];
+        Ok(match frame.kind {
+            FrameKind::Loop => vec![],
+            _ => frame.results.clone(),
+        })
+    }
+
+    fn block_type(&self, reader: &mut Reader) -> Check<Vec<ValType>> {
+        match reader.byte().map_err(|error| error.message)? {
+            0x40 => Ok(vec![]),
+            byte => match ValType::from_byte(byte) {
+                Some(ty) => Ok(vec![ty]),
+                None => Err(format!("unsupported block type {:#04x}", byte)),
+            },
+        }
+    }
+
+    fn enter(&mut self, kind: FrameKind, results: Vec<ValType>) {
+        self.frames.push(Frame {
+            kind,
+            results,
+            height: self.operands.len(),
+            unreachable: false,
+        });
+    }
+
+    // Checks that the block left exactly its results
+    fn exit(&mut self) -> Check<Frame> {
+        let results = self.frames.last().unwrap().results.clone();
+        self.pop_all(&results)?;
+        let frame = self.frames.pop().unwrap();
+        if self.operands.len() != frame.height {
+            return Err("values left on the stack at the end of a block".to_string());
+        }
+        Ok(frame)
+    }
+
+    fn instr(&mut self, reader: &mut Reader) -> Check<()> {
+        use ValType::{F64, I32};
+
+        let read = |result: Result<u32>| result.map_err(|error| error.message);
+        let opcode = reader.byte().map_err(|error| error.message)?;
+        match opcode {
+            // unreachable, nop
+            0x00 => self.unreachable(),
+            0x01 => {}
+            // block, loop
+            0x02 | 0x03 => {
+                let results = self.block_type(reader)?;
+                let kind = if opcode == 0x02 {
+                    FrameKind::Block
+                } else {
+                    FrameKind::Loop
+                };
+                self.enter(kind, results);
+            }
+            // if
+            0x04 => {
+                let results = self.block_type(reader)?;
+                self.pop(Some(I32))?;
+                self.enter(FrameKind::If, results);
+            }
+            // else
+            0x05 => {
+                if self.frames.last().unwrap().kind != FrameKind::If {
+                    return Err("`else` outside of an `if`".to_string());
+                }
+                let frame = self.exit()?;
+                self.enter(FrameKind::Else, frame.results);
+            }
+            // end
+            0x0B => {
+                let frame = self.exit()?;
+                if frame.kind == FrameKind::If && !frame.results.is_empty() {
+                    return Err("`if` without `else` cannot have results".to_string());
+                }
+                self.operands
+                    .extend(frame.results.iter().map(|ty| Some(*ty)));
+            }
+            // br, br_if
+            0x0C | 0x0D => {
+                let depth = read(reader.u32())?;
+                if opcode == 0x0D {
+                    self.pop(Some(I32))?;
+                }
+                let label = self.label(depth)?;
+                self.pop_all(&label)?;
+                if opcode == 0x0C {
+                    self.unreachable();
+                } else {
+                    self.operands.extend(label.iter().map(|ty| Some(*ty)));
+                }
+            }
+            // return
+            0x0F => {
+                let results = self.frames[0].results.clone();
+                self.pop_all(&results)?;
+                self.unreachable();
+            }
+            // call
+            0x10 => {
+                let function = read(reader.u32())? as usize;
+                let ty = self
+                    .module
+                    .functions
+                    .get(function)
+                    .map(|ty| self.module.types[*ty as usize].clone())
+                    .ok_or_else(|| format!("unknown function {}", function))?;
+                self.pop_all(&ty.params)?;
+                ty.results.iter().for_each(|ty| self.push(*ty));
+            }
+            // drop
+            0x1A => {
+                self.pop(None)?;
+            }
+            // select
+            0x1B => {
+                self.pop(Some(I32))?;
+                let first = self.pop(None)?;
+                let second = self.pop(first)?;
+                self.operands.push(first.or(second));
+            }
+            // local.get, local.set, local.tee
+            0x20..=0x22 => {
+                let local = read(reader.u32())? as usize;
+                let ty = *self
+                    .locals
+                    .get(local)
+                    .ok_or_else(|| format!("unknown local {}", local))?;
+                if opcode != 0x20 {
+                    self.pop(Some(ty))?;
+                }
+                if opcode != 0x21 {
+                    self.push(ty);
+                }
+            }
+            // i32.const, f64.const
+            0x41 => {
+                reader.i32().map_err(|error| error.message)?;
+                self.push(I32);
+            }
+            0x44 => {
+                reader.take(8).map_err(|error| error.message)?;
+                self.push(F64);
+            }
+            // i32.eqz
+            0x45 => self.operator(&[I32], I32)?,
+            // i32 comparisons
+            0x46..=0x4F => self.operator(&[I32, I32], I32)?,
+            // f64 comparisons
+            0x61..=0x66 => self.operator(&[F64, F64], I32)?,
+            // i32.clz, i32.ctz, i32.popcnt
+            0x67..=0x69 => self.operator(&[I32], I32)?,
+            // i32 arithmetic and bitwise operators
+            0x6A..=0x78 => self.operator(&[I32, I32], I32)?,
+            // f64 abs, neg, ceil, floor, trunc, nearest, sqrt
+            0x99..=0x9F => self.operator(&[F64], F64)?,
+            // f64 add, sub, mul, div, min, max, copysign
+            0xA0..=0xA6 => self.operator(&[F64, F64], F64)?,
+            // i32.trunc_f64_s, i32.trunc_f64_u
+            0xAA | 0xAB => self.operator(&[F64], I32)?,
+            // f64.convert_i32_s, f64.convert_i32_u
+            0xB7 | 0xB8 => self.operator(&[I32], F64)?,
+            opcode => return Err(format!("unsupported instruction {:#04x}", opcode)),
+        }
+        Ok(())
+    }
+
+    fn operator(&mut self, params: &[ValType], result: ValType) -> Check<()> {
+        self.pop_all(params)?;
+        self.push(result);
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::{BlockType, FuncType, Function, Instr, Module, ValType};
+
+const INDENT: &str = "  ";
+
+impl Module {
+    /// The module in the text format, with functions and locals referred to
+    /// by name and the instructions of blocks indented.
+    pub fn to_wat(&self) -> String {
+        // Names must differ, and functions may have been declared twice
+        let mut taken = HashSet::new();
+        let names: Vec<String> = self
+            .functions
+            .iter()
+            .map(|function| {
+                let mut name = function.name.clone();
+                let mut count = 0;
+                while !taken.insert(name.clone()) {
+                    count += 1;
+                    name = format!("{}.{}", function.name, count);
+                }
+                name
+            })
+            .collect();
+
+        let mut out = String::from("(module\n");
+        for (i, ty) in self.types.iter().enumerate() {
+            writeln!(
+                out,
+                "{}(type (;{};) (func{}))",
+                INDENT,
+                i,
+                signature(ty, None)
+            )
+            .unwrap();
+        }
+        for (function, name) in self.functions.iter().zip(names.iter()) {
+            self.function(function, name, &names, &mut out);
+        }
+        for export in self.exports.iter() {
+            writeln!(
+                out,
+                "{}(export {} (func ${}))",
+                INDENT,
+                quote(&export.name),
+                names[export.function as usize]
+            )
+            .unwrap();
+        }
+        out.push_str(")\n");
+        out
+    }
+
+    fn function(&self, function: &Function, name: &str, names: &[String], out: &mut String) {
+        let ty = &self.types[function.ty as usize];
+        writeln!(
+            out,
+            "{}(func ${} (type {}){}",
+            INDENT,
+            name,
+            function.ty,
+            signature(ty, Some(&function.params))
+        )
+        .unwrap();
+        for (name, ty) in function.locals.iter() {
+            writeln!(out, "{0}{0}(local ${1} {2})", INDENT, name, ty).unwrap();
+        }
+        let locals: Vec<&str> = function
+            .params
+            .iter()
+            .chain(function.locals.iter())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let mut depth = 2;
+        for instr in function.body.iter() {
+            if let Instr::Else | Instr::End = instr {
+                depth -= 1;
+            }
+            out.push_str(&INDENT.repeat(depth));
+            match instr {
+                Instr::Block(ty) => write!(out, "block{}", block_type(*ty)),
+                Instr::If(ty) => write!(out, "if{}", block_type(*ty)),
+                Instr::Br(depth) => write!(out, "br {}", depth),
+                Instr::Call(function) => write!(out, "call ${}", names[*function as usize]),
+                Instr::LocalGet(local) => write!(out, "local.get ${}", locals[*local as usize]),
+                Instr::LocalSet(local) => write!(out, "local.set ${}", locals[*local as usize]),
+                Instr::I32Const(value) => write!(out, "i32.const {}", value),
+                Instr::F64Const(value) => write!(out, "f64.const {}", value),
+                instr => write!(out, "{}", mnemonic(instr)),
+            }
+            .unwrap();
+            out.push('\n');
+            if let Instr::Block(_) | Instr::If(_) | Instr::Else = instr {
+                depth += 1;
+            }
+        }
+        writeln!(out, "{})", INDENT).unwrap();
+    }
+}
+
+// ` (param $a f64) (result f64)`, or with unnamed parameters
+// ` (param f64 f64) (result f64)`
+fn signature(ty: &FuncType, params: Option<&[(String, ValType)]>) -> String {
+    let mut out = String::new();
+    match params {
+        Some(params) => {
+            for (name, ty) in params {
+                write!(out, " (param ${} {})", name, ty).unwrap();
+            }
+        }
+        None if !ty.params.is_empty() => {
+            let params: Vec<String> = ty.params.iter().map(|ty| ty.to_string()).collect();
+            write!(out, " (param {})", params.join(" ")).unwrap();
+        }
+        None => {}
+    }
+    if !ty.results.is_empty() {
+        let results: Vec<String> = ty.results.iter().map(|ty| ty.to_string()).collect();
+        write!(out, " (result {})", results.join(" ")).unwrap();
+    }
+    out
+}
+
+fn block_type(ty: BlockType) -> String {
+    match ty {
+        BlockType::Empty => String::new(),
+        BlockType::Value(ty) => format!(" (result {})", ty),
+    }
+}
+
+// The name of an instruction without immediates
+fn mnemonic(instr: &Instr) -> &'static str {
+    match instr {
+        Instr::Unreachable => "unreachable",
+        Instr::Else => "else",
+        Instr::End => "end",
+        Instr::Return => "return",
+        Instr::Drop => "drop",
+        Instr::I32Eqz => "i32.eqz",
+        Instr::I32Eq => "i32.eq",
+        Instr::I32Ne => "i32.ne",
+        Instr::F64Eq => "f64.eq",
+        Instr::F64Ne => "f64.ne",
+        Instr::F64Lt => "f64.lt",
+        Instr::F64Gt => "f64.gt",
+        Instr::F64Le => "f64.le",
+        Instr::F64Ge => "f64.ge",
+        Instr::F64Neg => "f64.neg",
+        Instr::F64Trunc => "f64.trunc",
+        Instr::F64Add => "f64.add",
+        Instr::F64Sub => "f64.sub",
+        Instr::F64Mul => "f64.mul",
+        Instr::F64Div => "f64.div",
+        Instr::Block(_)
+        | Instr::If(_)
+        | Instr::Br(_)
+        | Instr::Call(_)
+        | Instr::LocalGet(_)
+        | Instr::LocalSet(_)
+        | Instr::I32Const(_)
+        | Instr::F64Const(_) => unreachable!("has immediates"),
+    }
+}
+
+// A string of the text format, with `"`, `\` and bytes outside of printable
+// ASCII escaped as hex
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' => write!(quoted, "\\{}", byte as char).unwrap(),
+            0x20..=0x7E => quoted.push(byte as char),
+            byte => write!(quoted, "\\{:02x}", byte).unwrap(),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,150 @@
+#include "zope.h"
+
+static bool z_note(double n);
+static double z_sign(double n);
+static double z_classify(double n);
+static double z_sum(double a, double b);
+static zp_unit z_nothing(double n);
+static double z_calls;
+static double z_double;
+static double z_int;
+static bool z_both;
+static bool z_either;
+static double z__ue9;
+
+#line 4 "control.zp"
+static bool z_note(double n) {
+    zp_print_number(n);
+    z_calls = z_calls + 1.0;
+    return n > 0.0;
+}
+
+static double z_sign(double n) {
+    if (n < 0.0) {
+#line 11 "control.zp"
+        return -1.0;
+    } else if (n == 0.0) {
+#line 11 "control.zp"
+        return 0.0;
+    } else {
+#line 11 "control.zp"
+        return 1.0;
+    }
+}
+
+#line 14 "control.zp"
+static double z_classify(double n) {
+    double zp_t1 = n;
+    if (zp_t1 == 0.0) {
+#line 16 "control.zp"
+        return 0.0;
+    } else {
+        bool zp_t3;
+        {
+#line 17 "control.zp"
+            double limit = 10.0;
+#line 17 "control.zp"
+            zp_t3 = zp_t1 < limit;
+        }
+        bool zp_t2 = zp_t3;
+        if (zp_t2) {
+#line 17 "control.zp"
+            return 1.0;
+        } else if (zp_t1 > 1000.0 && z_note(zp_t1)) {
+#line 18 "control.zp"
+            return 3.0;
+        } else {
+#line 19 "control.zp"
+            return 2.0;
+        }
+    }
+}
+
+#line 23 "control.zp"
+static double z_sum(double a, double b) {
+    return a * 10.0 + b;
+}
+
+static zp_unit z_nothing(double n) {
+    return zp_print_number(n * 2.0);
+}
+
+int main(void) {
+#line 2 "control.zp"
+    z_calls = 0.0;
+#line 31 "control.zp"
+    z_double = 2.0;
+    {
+#line 32 "control.zp"
+        double double_ = z_double * 3.0;
+#line 32 "control.zp"
+        z_int = double_ + 1.0;
+    }
+#line 33 "control.zp"
+    z_both = z_note(1.0) && z_note(0.0 - 1.0) && z_note(2.0);
+    z_either = z_note(0.0) || z_note(3.0);
+    zp_print_number(z_int);
+    double zp_t1;
+    bool zp_t2 = z_note(4.0);
+    if (zp_t2 == z_note(5.0)) {
+#line 36 "control.zp"
+        zp_t1 = 1.0;
+    } else {
+#line 36 "control.zp"
+        zp_t1 = 0.0;
+    }
+    double zp_t3 = zp_t1;
+    double zp_t4;
+    {
+#line 36 "control.zp"
+        z_calls = z_calls + 10.0;
+#line 36 "control.zp"
+        zp_t4 = z_calls;
+    }
+    zp_print_number(z_sum(zp_t3, zp_t4));
+#line 37 "control.zp"
+    double zp_t5 = z_sign(0.0 - 3.0);
+    double zp_t6 = z_sum(zp_t5, z_sign(0.0));
+    zp_print_number(zp_t6 + z_sign(8.0));
+#line 38 "control.zp"
+    double zp_t7 = z_classify(0.0);
+    double zp_t8 = zp_t7 + z_classify(5.0) * 10.0;
+    double zp_t9 = zp_t8 + z_classify(50.0) * 100.0;
+    zp_print_number(zp_t9 + z_classify(5000.0) * 1000.0);
+#line 39 "control.zp"
+    double zp_t10;
+    if (z_both || !z_either) {
+#line 39 "control.zp"
+        zp_t10 = 1.0;
+    } else {
+#line 39 "control.zp"
+        zp_t10 = 0.0;
+    }
+    zp_print_number(zp_t10);
+#line 40 "control.zp"
+    double zp_t11;
+    z_nothing(1.0);
+    z_nothing(2.0);
+    if (true) {
+#line 40 "control.zp"
+        zp_t11 = z_calls;
+    } else {
+#line 40 "control.zp"
+        zp_t11 = 0.0;
+    }
+    zp_print_number(zp_t11);
+#line 41 "control.zp"
+    bool zp_t12 = z_double > 1.0;
+    if (zp_t12) {
+#line 41 "control.zp"
+        z__ue9 = 7.0;
+    } else if (!zp_t12) {
+#line 41 "control.zp"
+        z__ue9 = 8.0;
+    } else {
+        zp_no_match(__FILE__, 41, zp_bool_string(zp_t12));
+    }
+#line 42 "control.zp"
+    zp_print_number(z__ue9);
+    return 0;
+}
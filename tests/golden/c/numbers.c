@@ -0,0 +1,61 @@
+#include "zope.h"
+
+static double z_fib(double n);
+static double z_gcd(double a, double b);
+static double z_power(double base, double exponent);
+
+#line 2 "numbers.zp"
+static double z_fib(double n) {
+    if (n < 2.0) {
+#line 3 "numbers.zp"
+        return n;
+    } else {
+#line 3 "numbers.zp"
+        double zp_t1 = z_fib(n - 1.0);
+        return zp_t1 + z_fib(n - 2.0);
+    }
+}
+
+#line 6 "numbers.zp"
+static double z_gcd(double a, double b) {
+    if (b == 0.0) {
+#line 7 "numbers.zp"
+        return a;
+    } else {
+#line 7 "numbers.zp"
+        double zp_t1 = b;
+        return z_gcd(zp_t1, zp_rem(a, b, __FILE__, 7));
+    }
+}
+
+#line 10 "numbers.zp"
+static double z_power(double base, double exponent) {
+    double zp_t1 = exponent;
+    if (zp_t1 == 0.0) {
+#line 12 "numbers.zp"
+        return 1.0;
+    } else {
+#line 13 "numbers.zp"
+        double zp_t2 = base;
+        return zp_t2 * z_power(base, exponent - 1.0);
+    }
+}
+
+int main(void) {
+#line 17 "numbers.zp"
+    zp_print_number(z_fib(20.0));
+    zp_print_number(z_gcd(1071.0, 462.0));
+    zp_print_number(zp_div(1.0, 3.0, __FILE__, 19));
+    zp_print_number(zp_div(2.0, 3.0, __FILE__, 20) * 3.0);
+    zp_print_number(zp_div(1.0 + 2.0, 10.0, __FILE__, 21));
+    zp_print_number(0.0 - zp_rem(7.0, 3.0, __FILE__, 22));
+    zp_print_number(zp_rem(zp_div(15.0, 2.0, __FILE__, 23), 4.0, __FILE__, 23));
+    zp_print_number(z_power(10.0, 21.0));
+    double zp_t1 = z_power(2.0, 35.0);
+    zp_print_number(zp_t1 * z_power(2.0, 35.0));
+#line 26 "numbers.zp"
+    zp_print_number(zp_div(1.0, z_power(2.0, 30.0), __FILE__, 26));
+    zp_print_number(0.0 * -1.0);
+    zp_print_number(-(-5.0));
+    return 0;
+}
@@ -0,0 +1,49 @@
+#include "zope.h"
+
+static zp_string z_greet(zp_string name);
+static zp_string z_describe(zp_string word);
+static zp_string z_quoted;
+
+#line 2 "strings.zp"
+static zp_string z_greet(zp_string name) {
+    return zp_concat(zp_concat(ZP_STRING("Hello, "), name), ZP_STRING("!"));
+}
+
+static zp_string z_describe(zp_string word) {
+    zp_string zp_t1 = word;
+    if (zp_string_eq(zp_t1, ZP_STRING("zope"))) {
+#line 8 "strings.zp"
+        return ZP_STRING("a language");
+    } else if (zp_string_eq(zp_t1, ZP_STRING("it's"))) {
+#line 9 "strings.zp"
+        return ZP_STRING("a contraction");
+    } else if (zp_string_eq(zp_t1, ZP_STRING(""))) {
+#line 10 "strings.zp"
+        return ZP_STRING("nothing");
+    } else {
+#line 11 "strings.zp"
+        return zp_concat(ZP_STRING("just "), zp_t1);
+    }
+}
+
+int main(void) {
+#line 15 "strings.zp"
+    z_quoted = ZP_STRING("'single' \?\?= \\back\\slash");
+    zp_print_string(z_greet(ZP_STRING("world")));
+    zp_print_string(z_describe(ZP_STRING("zope")));
+    zp_print_string(z_describe(ZP_STRING("it's")));
+    zp_print_string(z_describe(ZP_STRING("")));
+    zp_print_string(z_describe(ZP_STRING("caf\303\251 \342\230\225")));
+    zp_print_string(z_quoted);
+    zp_string zp_t1;
+    zp_string zp_t2 = z_greet(ZP_STRING("a"));
+    if (!zp_string_eq(zp_t2, z_greet(ZP_STRING("b")))) {
+#line 22 "strings.zp"
+        zp_t1 = ZP_STRING("differ");
+    } else {
+#line 22 "strings.zp"
+        zp_t1 = ZP_STRING("same");
+    }
+    zp_print_string(zp_t1);
+    return 0;
+}
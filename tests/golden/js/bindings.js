@@ -0,0 +1,21 @@
+const total = 1;
+let count = 0;
+const total$1 = total + 1;
+
+function $class($new, $this) {
+  const $new$1 = $new + $this;
+  return $new$1 * 2;
+}
+
+function inc() {
+  count = count + 1;
+}
+
+let result;
+{
+  const total$2 = $class(total$1, 3);
+  result = total$2;
+}
+inc();
+print(result + count);
+//# sourceMappingURL=bindings.js.map
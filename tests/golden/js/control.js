@@ -0,0 +1,85 @@
+function Circle(...fields) {
+  return { tag: "Circle", fields };
+}
+function Rect(...fields) {
+  return { tag: "Rect", fields };
+}
+const Dot = { tag: "Dot", fields: [] };
+
+function area(shape) {
+  if (shape.tag === "Circle") {
+    const r = shape.fields[0];
+    return 3 * r * r;
+  }
+  if (shape.tag === "Rect") {
+    const w = shape.fields[0];
+    const h = shape.fields[1];
+    if (w === h) {
+      return w * w;
+    }
+  }
+  if (shape.tag === "Rect") {
+    const w = shape.fields[0];
+    const h = shape.fields[1];
+    return w * h;
+  }
+  if (shape.tag === "Dot") {
+    return 0;
+  }
+  throw new Error("no arm matches");
+}
+
+function sign(n) {
+  if (n < 0) {
+    return -1;
+  } else if (n === 0) {
+    return 0;
+  } else {
+    return 1;
+  }
+}
+
+let size;
+{
+  const a = area(Rect(2, 3));
+  const b = area(Circle(1));
+  size = a + b;
+}
+
+let label;
+$match1: {
+  const $subject1 = size > 10;
+  if ($subject1 === true) {
+    label = "big";
+    break $match1;
+  }
+  if ($subject1 === false) {
+    label = "small";
+    break $match1;
+  }
+  throw new Error("no arm matches");
+}
+
+function describe(n) {
+  let kind;
+  if (n > 100) {
+    const huge = n / 100;
+    kind = huge;
+  } else {
+    kind = n;
+  }
+  $match2: {
+    const $subject2 = sign(n);
+    if ($subject2 === 1) {
+      print("positive");
+      break $match2;
+    }
+    print("not positive");
+    break $match2;
+  }
+  return kind;
+}
+
+print(label);
+const total = describe(size) + area(Dot);
+//# sourceMappingURL=control.js.map
@@ -0,0 +1,43 @@
+function Some(...fields) {
+  return { tag: "Some", fields };
+}
+const None = { tag: "None", fields: [] };
+
+const numbers = [1, 2, 3];
+const more = [0, ...numbers, 4];
+const counter = { name: "counter", count: 0 };
+const next = { ...counter, count: counter.count + 1 };
+const name = next.name;
+const shorthand = { name, first: more[0] };
+
+const inc = (x) => x + 1;
+const pair = (x) => ({ left: x, right: inc(x) });
+const twice = (f) => (x) => f(f(x));
+
+const same = $eq(numbers, [1, 2, 3]) && !$eq(counter, next);
+const found = $eq(Some(2), Some(inc(1)));
+const text = "say \"hi\"\\n" + name;
+const all = same && found && -(-2) === twice(inc)(0);
+print(text);
+
+// Structural equality of lists, records and variants
+function $eq(a, b) {
+  if (a === b) {
+    return true;
+  }
+  if (typeof a !== "object" || typeof b !== "object" || a === null || b === null) {
+    return false;
+  }
+  for (const key in a) {
+    if (!(key in b) || !$eq(a[key], b[key])) {
+      return false;
+    }
+  }
+  for (const key in b) {
+    if (!(key in a)) {
+      return false;
+    }
+  }
+  return true;
+}
+//# sourceMappingURL=data.js.map
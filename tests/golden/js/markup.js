@@ -0,0 +1,31 @@
+function Counter({ label, step }) {
+  let count = 0;
+  const click = () => {
+    count = count + step;
+  };
+  return $h("button", { onclick: click, "aria-label": label }, label, ": ", count);
+}
+
+function Panel({ title, children }) {
+  return $h("section", { class: "panel" },
+    $h("h2", null, title),
+    children
+  );
+}
+
+export function App() {
+  return $h(Panel, { title: "Counters" },
+    $h(Counter, { label: "By one", step: 1 }),
+    $h(Counter, { label: "By ten", step: 10 }),
+    $h(null, null,
+      $h("hr", null),
+      "Done"
+    )
+  );
+}
+
+// An element, with the lists among its children flattened
+function $h(tag, props, ...children) {
+  return { tag, props, children: children.flat(Infinity) };
+}
+//# sourceMappingURL=markup.js.map
@@ -0,0 +1,16 @@
+import { Button, default as $default } from "./ui/button.js";
+import "./setup.js";
+export const Small = { tag: "Small", fields: [] };
+export const Large = { tag: "Large", fields: [] };
+
+export const size = Large;
+
+export function label(text) {
+  return text;
+}
+
+export let count = 0;
+
+const $default$1 = Button;
+export { $default$1 as default };
+//# sourceMappingURL=modules.js.map
@@ -0,0 +1,80 @@
+(module
+  (type (;0;) (func (param f64) (result f64)))
+  (type (;1;) (func (param f64 f64) (result f64)))
+  (func $fib (type 0) (param $n f64) (result f64)
+    local.get $n
+    f64.const 2
+    f64.lt
+    if (result f64)
+      local.get $n
+    else
+      local.get $n
+      f64.const 1
+      f64.sub
+      call $fib
+      local.get $n
+      f64.const 2
+      f64.sub
+      call $fib
+      f64.add
+    end
+  )
+  (func $gcd (type 1) (param $a f64) (param $b f64) (result f64)
+    (local $lhs f64)
+    (local $rhs f64)
+    local.get $b
+    f64.const 0
+    f64.eq
+    if (result f64)
+      local.get $a
+    else
+      local.get $b
+      local.get $a
+      local.get $b
+      local.set $rhs
+      local.set $lhs
+      local.get $rhs
+      f64.const 0
+      f64.eq
+      if
+        unreachable
+      end
+      local.get $lhs
+      local.get $rhs
+      local.get $lhs
+      local.get $rhs
+      f64.div
+      f64.trunc
+      f64.mul
+      f64.sub
+      call $gcd
+    end
+  )
+  (func $mean (type 1) (param $a f64) (param $b f64) (result f64)
+    (local $lhs f64)
+    (local $rhs f64)
+    local.get $a
+    local.get $b
+    f64.add
+    f64.const 2
+    local.set $rhs
+    local.set $lhs
+    local.get $rhs
+    f64.const 0
+    f64.eq
+    if
+      unreachable
+    end
+    local.get $lhs
+    local.get $rhs
+    f64.div
+  )
+  (func $negate (type 0) (param $x f64) (result f64)
+    local.get $x
+    f64.neg
+  )
+  (export "fib" (func $fib))
+  (export "gcd" (func $gcd))
+  (export "mean" (func $mean))
+  (export "negate" (func $negate))
+)
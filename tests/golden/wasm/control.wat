@@ -0,0 +1,104 @@
+(module
+  (type (;0;) (func (param f64) (result f64)))
+  (type (;1;) (func (param i32) (result f64)))
+  (func $classify (type 0) (param $n f64) (result f64)
+    local.get $n
+    f64.const 0
+    f64.eq
+    if (result f64)
+      f64.const 0
+    else
+      local.get $n
+      f64.const 0
+      f64.lt
+      if (result f64)
+        f64.const -1
+      else
+        f64.const 1
+      end
+    end
+  )
+  (func $count (type 0) (param $n f64) (result f64)
+    (local $v3 f64)
+    local.get $n
+    f64.const 2
+    f64.mul
+    local.set $v3
+    local.get $v3
+    f64.const 10
+    f64.gt
+    if (result f64)
+      local.get $v3
+    else
+      f64.const 0
+    end
+    f64.const 1
+    f64.add
+  )
+  (func $flag (type 1) (param $b i32) (result f64)
+    local.get $b
+    i32.const 1
+    i32.eq
+    if (result f64)
+      f64.const 1
+    else
+      local.get $b
+      i32.const 0
+      i32.eq
+      i32.eqz
+      if
+        unreachable
+      end
+      f64.const 0
+    end
+  )
+  (func $nothing (type 0) (param $n f64) (result f64)
+    local.get $n
+    f64.const 1
+    f64.add
+  )
+  (func $twice (type 0) (param $n f64) (result f64)
+    (local $v13 f64)
+    (local $v23 f64)
+    local.get $n
+    f64.const 0
+    f64.eq
+    if (result f64)
+      f64.const 0
+    else
+      local.get $n
+      f64.const 0
+      f64.lt
+      if (result f64)
+        f64.const -1
+      else
+        f64.const 1
+      end
+    end
+    local.set $v13
+    local.get $n
+    f64.const 0
+    f64.eq
+    if (result f64)
+      f64.const 0
+    else
+      local.get $n
+      f64.const 0
+      f64.lt
+      if (result f64)
+        f64.const -1
+      else
+        f64.const 1
+      end
+    end
+    local.set $v23
+    local.get $v13
+    local.get $v23
+    f64.add
+  )
+  (export "classify" (func $classify))
+  (export "count" (func $count))
+  (export "flag" (func $flag))
+  (export "nothing" (func $nothing))
+  (export "twice" (func $twice))
+)
@@ -0,0 +1,54 @@
+(module
+  (type (;0;) (func (param f64 f64 f64) (result i32)))
+  (type (;1;) (func (param i32 i32) (result i32)))
+  (func $between (type 0) (param $x f64) (param $low f64) (param $high f64) (result i32)
+    local.get $x
+    local.get $low
+    f64.ge
+    if (result i32)
+      local.get $x
+      local.get $high
+      f64.le
+    else
+      i32.const 0
+    end
+  )
+  (func $outside (type 0) (param $x f64) (param $low f64) (param $high f64) (result i32)
+    local.get $x
+    local.get $low
+    f64.ge
+    if (result i32)
+      local.get $x
+      local.get $high
+      f64.le
+    else
+      i32.const 0
+    end
+    i32.eqz
+  )
+  (func $either (type 1) (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    if (result i32)
+      i32.const 1
+    else
+      local.get $b
+    end
+  )
+  (func $same (type 1) (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    local.get $b
+    i32.eq
+    if (result i32)
+      local.get $a
+      local.get $b
+      i32.eqz
+      i32.ne
+    else
+      i32.const 0
+    end
+  )
+  (export "between" (func $between))
+  (export "outside" (func $outside))
+  (export "either" (func $either))
+  (export "same" (func $same))
+)